@@ -1,6 +1,9 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::hint::black_box;
-use photon_core::{encode_data, decode_data};
+use photon_core::{encode_data, decode_data, encode_with_scheme, decode_with_scheme, encode_data_with_config, CodecConfig, Quantizer};
+use photon_core::{simulate_crosstalk, simulate_crosstalk_with_config, simulate_psf, Connectivity, CrosstalkConfig, PhotonicVoxel, PsfKernel, VoxelLattice};
+use photon_core::{run_ber_simulation_with_trials, SimulationConfig};
+use rand::{rngs::StdRng, SeedableRng};
 
 pub fn benchmark_encoding(c: &mut Criterion) {
     let data = vec![0xAB; 1000]; // 1KB of data
@@ -19,5 +22,79 @@ pub fn benchmark_decoding_with_noise(c: &mut Criterion) {
     c.bench_function("decode_1kb_noise", |b| b.iter(|| decode_data(black_box(&voxels), true)));
 }
 
-criterion_group!(benches, benchmark_encoding, benchmark_decoding, benchmark_decoding_with_noise);
+/// 1MB inputs: `encode_data`/`decode_data` use rayon when built with
+/// `--features parallel`, the `*_serial` variants always run on one thread.
+pub fn benchmark_large_inputs(c: &mut Criterion) {
+    let config = CodecConfig::default();
+    let quantizer = Quantizer::new(&config);
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| i as u8).collect();
+    let voxels = encode_data(&data);
+
+    c.bench_function("encode_1mb", |b| b.iter(|| encode_data(black_box(&data))));
+    c.bench_function("encode_1mb_serial", |b| b.iter(|| encode_with_scheme(black_box(&data), &config)));
+    c.bench_function("decode_1mb", |b| b.iter(|| decode_data(black_box(&voxels), false)));
+    c.bench_function("decode_1mb_serial", |b| b.iter(|| decode_with_scheme(black_box(&voxels), &quantizer)));
+}
+
+/// Nearest-level search (`CodecConfig::demodulate`) against the precomputed
+/// decision tables of `Quantizer`, on the default config and on a dense
+/// Gray-coded one (16 levels, 16 wavelengths).
+pub fn benchmark_quantizer(c: &mut Criterion) {
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| (i * 31) as u8).collect();
+    let dense = CodecConfig {
+        intensity_levels: 16,
+        polarization_levels: 16,
+        phase_levels: 16,
+        wavelengths: (0..16).map(|i| 450.0 + 20.0 * i as f32).collect(),
+        gray_code: true,
+    };
+
+    for (name, config) in [("default", CodecConfig::default()), ("dense", dense)] {
+        let voxels = encode_data_with_config(&data, &config);
+        let quantizer = Quantizer::new(&config);
+        c.bench_function(&format!("quantize_linear_{}", name), |b| b.iter(|| decode_with_scheme(black_box(&voxels), &config)));
+        c.bench_function(&format!("quantize_lut_{}", name), |b| b.iter(|| decode_with_scheme(black_box(&voxels), &quantizer)));
+    }
+}
+
+/// Point spread function blur of a 64x64x16 lattice: direct convolution
+/// against the FFT path (`--features fft`), for the 6-neighbour kernel and a
+/// wide Gaussian spot (7x7x13 voxels).
+pub fn benchmark_psf(c: &mut Criterion) {
+    let data: Vec<u8> = (0..64 * 64 * 16u32).map(|i| (i * 31) as u8).collect();
+    let lattice = VoxelLattice::from_voxels_padded(64, 64, encode_data(&data), PhotonicVoxel::new(0.0, 0.0, 0.0, 650.0)).unwrap();
+
+    for (name, kernel) in [("six_neighbor", PsfKernel::six_neighbor(0.01)), ("gaussian", PsfKernel::gaussian(1.0, 2.0))] {
+        c.bench_function(&format!("psf_direct_{}", name), |b| b.iter(|| simulate_psf(black_box(&lattice), &kernel)));
+        #[cfg(feature = "fft")]
+        c.bench_function(&format!("psf_fft_{}", name), |b| b.iter(|| photon_core::simulate_psf_fft(black_box(&lattice), &kernel)));
+    }
+}
+
+/// Crosstalk of a 256x256x16 lattice (10^6 voxels), processed in z-slabs on
+/// the rayon pool with `--features parallel`, with 6 and 26 neighbours.
+pub fn benchmark_crosstalk(c: &mut Criterion) {
+    let data: Vec<u8> = (0..256 * 256 * 16u32).map(|i| (i * 31) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(256, 256, encode_data(&data)).unwrap();
+    let corners = CrosstalkConfig { connectivity: Connectivity::TwentySix, ..CrosstalkConfig::new(0.01) };
+
+    let mut group = c.benchmark_group("crosstalk");
+    group.sample_size(10);
+    group.bench_function("six_neighbor_1m", |b| b.iter(|| simulate_crosstalk(black_box(&lattice), 0.01)));
+    group.bench_function("twenty_six_neighbor_1m", |b| b.iter(|| simulate_crosstalk_with_config(black_box(&lattice), &corners)));
+    group.finish();
+}
+
+pub fn benchmark_ber_campaign(c: &mut Criterion) {
+    let config = SimulationConfig { trials: 10, confidence: 0.95 };
+
+    let mut group = c.benchmark_group("ber_campaign");
+    group.sample_size(10);
+    group.bench_function("21_levels_10_trials_10kb", |b| {
+        b.iter(|| run_ber_simulation_with_trials(10_000, 20, 0.2, &CodecConfig::default(), black_box(&config), &mut StdRng::seed_from_u64(1)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_encoding, benchmark_decoding, benchmark_decoding_with_noise, benchmark_large_inputs, benchmark_quantizer, benchmark_psf, benchmark_crosstalk, benchmark_ber_campaign);
 criterion_main!(benches);
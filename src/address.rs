@@ -0,0 +1,88 @@
+use crate::codec::CodecConfig;
+use std::ops::Range;
+
+/// Physical position of a voxel in a lattice: layer `z`, row `y`, column `x`
+/// (see [`VoxelLattice`](crate::VoxelLattice)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoxelAddress {
+    pub layer: usize,
+    pub row: usize,
+    pub column: usize,
+}
+
+/// Layout of an encoded stream in a lattice: layers of `width` x `height`
+/// voxels, each voxel carrying `bits_per_voxel` bits of the stream (least
+/// significant bits first, as written by the codec), starting at voxel 0.
+///
+/// Maps byte offsets of the data to the voxels that hold them and back, e.g.
+/// to damage or read only the voxels of a given file region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatticeGeometry {
+    pub width: usize,
+    pub height: usize,
+    pub bits_per_voxel: usize,
+}
+
+impl LatticeGeometry {
+    /// Geometry of data encoded with `codec` in layers of `width` x `height` voxels.
+    pub fn for_codec(width: usize, height: usize, codec: &CodecConfig) -> Self {
+        Self { width, height, bits_per_voxel: codec.bits_per_voxel() }
+    }
+
+    /// Checks that layers and voxels are not empty.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("Layers of {}x{} voxels are empty", self.width, self.height));
+        }
+        if self.bits_per_voxel == 0 {
+            return Err("Voxels must carry at least one bit".to_string());
+        }
+        Ok(())
+    }
+
+    /// Address of the voxel at `index` in the stream.
+    pub fn address(&self, index: usize) -> VoxelAddress {
+        let layer_size = self.width * self.height;
+        VoxelAddress {
+            layer: index / layer_size,
+            row: index % layer_size / self.width,
+            column: index % self.width,
+        }
+    }
+
+    /// Index in the stream of the voxel at `address`, or `None` if the row or
+    /// column is outside a layer.
+    pub fn index(&self, address: &VoxelAddress) -> Option<usize> {
+        (address.row < self.height && address.column < self.width)
+            .then(|| (address.layer * self.height + address.row) * self.width + address.column)
+    }
+
+    /// Indices of the voxels holding (at least one bit of) the bytes in `bytes`.
+    pub fn voxels_for_bytes(&self, bytes: Range<usize>) -> Range<usize> {
+        if bytes.is_empty() {
+            return 0..0;
+        }
+        let first = bytes.start * 8 / self.bits_per_voxel;
+        let last = (bytes.end * 8 - 1) / self.bits_per_voxel;
+        first..last + 1
+    }
+
+    /// Addresses of the voxels holding the bytes in `bytes`, in stream order.
+    pub fn addresses_for_bytes(&self, bytes: Range<usize>) -> impl Iterator<Item = VoxelAddress> + '_ {
+        self.voxels_for_bytes(bytes).map(|index| self.address(index))
+    }
+
+    /// Byte offsets with at least one bit stored in the voxel at `index`.
+    pub fn bytes_for_voxel(&self, index: usize) -> Range<usize> {
+        let first_bit = index * self.bits_per_voxel;
+        first_bit / 8..(first_bit + self.bits_per_voxel).div_ceil(8)
+    }
+
+    /// Byte offsets with at least one bit stored at `address`, or `None` if the
+    /// address is outside a layer.
+    pub fn bytes_at(&self, address: &VoxelAddress) -> Option<Range<usize>> {
+        self.index(address).map(|index| self.bytes_for_voxel(index))
+    }
+}
@@ -0,0 +1,232 @@
+use crate::codec::CodecConfig;
+use crate::container::{self, Container};
+use crate::ecc::EccConfig;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// First bytes of the data of an archive container.
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"PARC";
+
+/// Version of the archive layout written by this crate. Readers refuse
+/// archives from newer versions.
+pub const ARCHIVE_VERSION: u16 = 1;
+
+/// Magic, version, entry count and table length.
+const PREAMBLE_LEN: usize = 4 + 2 + 4 + 4;
+
+/// A file listed in the table of contents of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArchiveEntry {
+    /// Path of the file in the archive: relative, `/`-separated.
+    pub name: String,
+    /// Offset of the file in the archive data.
+    pub offset: u64,
+    /// Length of the file in bytes.
+    pub len: u64,
+}
+
+/// Several named files stored in one container, like a small read-only
+/// filesystem on the crystal.
+///
+/// The container data starts with the table of contents: magic, version
+/// (`u16`), entry count and table length (`u32` each, little-endian), then for
+/// every entry its name length (`u16`), UTF-8 name, offset and length (`u64`
+/// each). The files follow back to back. [`list`] and [`extract`] read only the
+/// table and the wanted file, through [`container::read_range`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Archive {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Archive {
+    /// Empty archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file under `name`.
+    ///
+    /// Fails if `name` is not a relative path without `.` or `..` components,
+    /// or is already taken.
+    pub fn add(&mut self, name: impl Into<String>, data: Vec<u8>) -> Result<(), String> {
+        let name = name.into();
+        validate_name(&name)?;
+        if self.get(&name).is_some() {
+            return Err(format!("duplicate archive entry {:?}", name));
+        }
+        self.files.push((name, data));
+        Ok(())
+    }
+
+    /// Contents of the file `name`, if the archive has one.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.files.iter().find(|(entry, _)| entry == name).map(|(_, data)| data.as_slice())
+    }
+
+    /// Names and contents of the files, in the order they were added.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files.iter().map(|(name, data)| (name.as_str(), data.as_slice()))
+    }
+
+    /// Number of files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// True if the archive holds no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Table of contents, as [`to_bytes`](Self::to_bytes) lays it out.
+    pub fn entries(&self) -> Vec<ArchiveEntry> {
+        let table_len: usize = self.files.iter().map(|(name, _)| entry_len(name)).sum();
+        let mut offset = (PREAMBLE_LEN + table_len) as u64;
+        self.files
+            .iter()
+            .map(|(name, data)| {
+                let entry = ArchiveEntry { name: name.clone(), offset, len: data.len() as u64 };
+                offset += entry.len;
+                entry
+            })
+            .collect()
+    }
+
+    /// Serializes the table of contents and the files.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let entries = self.entries();
+        let table_len: usize = entries.iter().map(|entry| entry_len(&entry.name)).sum();
+
+        let mut bytes = Vec::with_capacity(PREAMBLE_LEN + table_len + self.files.iter().map(|(_, data)| data.len()).sum::<usize>());
+        bytes.extend_from_slice(&ARCHIVE_MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(table_len as u32).to_le_bytes());
+        for entry in &entries {
+            bytes.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(entry.name.as_bytes());
+            bytes.extend_from_slice(&entry.offset.to_le_bytes());
+            bytes.extend_from_slice(&entry.len.to_le_bytes());
+        }
+        for (_, data) in &self.files {
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    /// Inverse of [`to_bytes`](Self::to_bytes). Fails on a bad magic, a newer
+    /// version, an invalid table or a file past the end of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let (count, table_len) = parse_preamble(bytes.get(..PREAMBLE_LEN).ok_or("truncated archive header")?)?;
+        let table = bytes.get(PREAMBLE_LEN..PREAMBLE_LEN + table_len).ok_or("truncated archive table of contents")?;
+
+        let mut archive = Self::new();
+        for entry in parse_table(table, count)? {
+            let data = usize::try_from(entry.offset)
+                .ok()
+                .zip(usize::try_from(entry.len).ok())
+                .and_then(|(offset, len)| bytes.get(offset..offset.checked_add(len)?))
+                .ok_or_else(|| format!("archive entry {:?} is past the end of the archive", entry.name))?;
+            archive.add(entry.name, data.to_vec())?;
+        }
+        Ok(archive)
+    }
+
+    /// Container of the archive, encoded with `codec` and the optional ECC.
+    ///
+    /// Panics if `codec` or `ecc` is invalid.
+    pub fn to_container(&self, codec: &CodecConfig, ecc: Option<EccConfig>) -> Container {
+        Container::encode_with_ecc(&self.to_bytes(), codec, ecc)
+    }
+
+    /// Decodes the whole archive from `container`.
+    pub fn from_container(container: &Container, simulate_noise: bool) -> Result<Self, String> {
+        Self::from_bytes(&container.decode(simulate_noise)?)
+    }
+}
+
+/// Reads the table of contents of an archive container, decoding only its
+/// first bytes.
+///
+/// Fails with `InvalidData` if the container data is not an archive.
+pub fn list<R: Read + Seek>(mut reader: R) -> io::Result<Vec<ArchiveEntry>> {
+    let start = reader.stream_position()?;
+    let preamble = container::read_range(&mut reader, 0, PREAMBLE_LEN)?;
+    let (count, table_len) = parse_preamble(&preamble).map_err(invalid_data)?;
+
+    reader.seek(SeekFrom::Start(start))?;
+    let table = container::read_range(&mut reader, PREAMBLE_LEN as u64, table_len)?;
+    parse_table(&table, count).map_err(invalid_data)
+}
+
+/// Reads the file `name` of an archive container, decoding only the table of
+/// contents and that file.
+///
+/// Fails with `NotFound` if the archive has no such file.
+pub fn extract<R: Read + Seek>(mut reader: R, name: &str) -> io::Result<Vec<u8>> {
+    let start = reader.stream_position()?;
+    let entry = list(&mut reader)?
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no entry {:?} in the archive", name)))?;
+    let len = usize::try_from(entry.len).map_err(|_| invalid_data(format!("archive entry {:?} is too large", name)))?;
+
+    reader.seek(SeekFrom::Start(start))?;
+    container::read_range(&mut reader, entry.offset, len)
+}
+
+/// Checks that `name` can be stored and later extracted below a directory.
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.len() > u16::MAX as usize {
+        return Err(format!("archive entry name is {} bytes long (at most {})", name.len(), u16::MAX));
+    }
+    if name.contains('\\') || name.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return Err(format!("archive entry name {:?} is not a relative path", name));
+    }
+    Ok(())
+}
+
+/// Bytes of one table entry.
+fn entry_len(name: &str) -> usize {
+    2 + name.len() + 8 + 8
+}
+
+/// Entry count and table length from the first [`PREAMBLE_LEN`] bytes.
+fn parse_preamble(preamble: &[u8]) -> Result<(usize, usize), String> {
+    if preamble[..4] != ARCHIVE_MAGIC {
+        return Err("not an archive (bad magic)".to_string());
+    }
+    let version = u16::from_le_bytes([preamble[4], preamble[5]]);
+    if version == 0 || version > ARCHIVE_VERSION {
+        return Err(format!("unsupported archive version {} (this build reads up to {})", version, ARCHIVE_VERSION));
+    }
+    let count = u32::from_le_bytes(preamble[6..10].try_into().expect("4 bytes")) as usize;
+    let table_len = u32::from_le_bytes(preamble[10..14].try_into().expect("4 bytes")) as usize;
+    Ok((count, table_len))
+}
+
+fn parse_table(mut table: &[u8], count: usize) -> Result<Vec<ArchiveEntry>, String> {
+    let mut take = |len: usize| -> Result<&[u8], String> {
+        if table.len() < len {
+            return Err("truncated archive table of contents".to_string());
+        }
+        let (field, rest) = table.split_at(len);
+        table = rest;
+        Ok(field)
+    };
+
+    let mut entries: Vec<ArchiveEntry> = Vec::new();
+    for _ in 0..count {
+        let name_len = u16::from_le_bytes(take(2)?.try_into().expect("2 bytes")) as usize;
+        let name = String::from_utf8(take(name_len)?.to_vec()).map_err(|_| "archive entry name is not UTF-8".to_string())?;
+        validate_name(&name)?;
+        let offset = u64::from_le_bytes(take(8)?.try_into().expect("8 bytes"));
+        let len = u64::from_le_bytes(take(8)?.try_into().expect("8 bytes"));
+        entries.push(ArchiveEntry { name, offset, len });
+    }
+    Ok(entries)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,153 @@
+use crate::codec::{gray_decode, gray_encode, level_bits, pack_symbols, unpack_symbols, circular_distance};
+use crate::jones::{JonesMatrix, JonesVoxel};
+use std::f32::consts::PI;
+
+/// A voxel as written by femtosecond-laser nanogratings in fused silica: a
+/// tiny birefringent element described by the orientation of its slow axis and
+/// its retardance strength.
+///
+/// With the three spatial coordinates these are the "5 dimensions" of the
+/// published 5D storage. Reading probes the voxel with polarized light
+/// (see [`readout`](Self::readout)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BirefringentVoxel {
+    /// Slow-axis orientation in radians, in [0, PI).
+    pub slow_axis: f32,
+    /// Retardance (optical path difference between slow and fast axis) in nm.
+    pub retardance_nm: f32,
+}
+
+impl BirefringentVoxel {
+    pub fn new(slow_axis: f32, retardance_nm: f32) -> Self {
+        Self { slow_axis, retardance_nm }
+    }
+
+    /// Phase retardance in radians at `wavelength_nm`: `2 PI * retardance / wavelength`.
+    pub fn phase_retardance(&self, wavelength_nm: f32) -> f32 {
+        2.0 * PI * self.retardance_nm / wavelength_nm
+    }
+
+    /// The voxel as an optical element at `wavelength_nm`.
+    pub fn jones_matrix(&self, wavelength_nm: f32) -> JonesMatrix {
+        JonesMatrix::retarder(self.slow_axis, self.phase_retardance(wavelength_nm))
+    }
+
+    /// Polarization state of the `probe` light after passing through the voxel.
+    pub fn readout(&self, probe: &JonesVoxel) -> JonesVoxel {
+        probe.apply(&self.jones_matrix(probe.wavelength))
+    }
+}
+
+/// Modulation parameters for [`BirefringentVoxel`]s: bits are split between
+/// the slow-axis orientation (low bits) and the retardance.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BirefringentConfig {
+    /// Slow-axis orientations, evenly spaced over [0, PI). Power of two.
+    pub axis_levels: usize,
+    /// Retardance levels, evenly spaced up to `max_retardance_nm` and never
+    /// zero (an unwritten voxel has no retardance). Power of two.
+    pub retardance_levels: usize,
+    /// Retardance of the strongest level, in nm.
+    pub max_retardance_nm: f32,
+    /// Map bits to levels with Gray code, so neighbouring levels differ in one bit.
+    pub gray_code: bool,
+}
+
+impl Default for BirefringentConfig {
+    /// 4 orientations and 4 retardance levels up to 120 nm: 4 bits per voxel.
+    fn default() -> Self {
+        Self { axis_levels: 4, retardance_levels: 4, max_retardance_nm: 120.0, gray_code: false }
+    }
+}
+
+impl BirefringentConfig {
+    /// Checks that level counts are powers of two, the maximum retardance is
+    /// positive and a voxel carries between 1 and 32 bits.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, levels) in [("axis_levels", self.axis_levels), ("retardance_levels", self.retardance_levels)] {
+            if !levels.is_power_of_two() {
+                return Err(format!("{} must be a non-zero power of two (got {})", name, levels));
+            }
+        }
+        if !self.max_retardance_nm.is_finite() || self.max_retardance_nm <= 0.0 {
+            return Err(format!("max_retardance_nm must be positive and finite (got {})", self.max_retardance_nm));
+        }
+        let bits = self.bits_per_voxel();
+        if bits == 0 || bits > 32 {
+            return Err(format!("bits per voxel must be in 1..=32 (got {})", bits));
+        }
+        Ok(())
+    }
+
+    /// Number of data bits stored in a single voxel.
+    pub fn bits_per_voxel(&self) -> usize {
+        level_bits(self.axis_levels) + level_bits(self.retardance_levels)
+    }
+
+    /// Slow-axis orientation of level `idx`.
+    pub fn axis_level(&self, idx: usize) -> f32 {
+        idx as f32 * PI / self.axis_levels as f32
+    }
+
+    /// Retardance of level `idx`, in nm.
+    pub fn retardance_level(&self, idx: usize) -> f32 {
+        (idx as f32 + 1.0) * self.max_retardance_nm / self.retardance_levels as f32
+    }
+
+    /// Voxel carrying `symbol`.
+    pub fn modulate(&self, symbol: u32) -> BirefringentVoxel {
+        let axis_width = level_bits(self.axis_levels);
+        let axis_bits = (symbol as usize) & ((1 << axis_width) - 1);
+        let retardance_bits = (symbol as usize) >> axis_width;
+        BirefringentVoxel::new(
+            self.axis_level(self.bits_to_level(axis_bits)),
+            self.retardance_level(self.bits_to_level(retardance_bits)),
+        )
+    }
+
+    /// Symbol of the nearest orientation and retardance levels.
+    pub fn demodulate(&self, voxel: &BirefringentVoxel) -> u32 {
+        let axis = (0..self.axis_levels)
+            .min_by(|&a, &b| {
+                circular_distance(voxel.slow_axis, self.axis_level(a), PI).total_cmp(&circular_distance(voxel.slow_axis, self.axis_level(b), PI))
+            })
+            .unwrap_or(0);
+        let step = self.max_retardance_nm / self.retardance_levels as f32;
+        let retardance = ((voxel.retardance_nm / step - 1.0).round().max(0.0) as usize).min(self.retardance_levels - 1);
+
+        let axis_width = level_bits(self.axis_levels);
+        (self.level_to_bits(retardance) << axis_width | self.level_to_bits(axis)) as u32
+    }
+
+    fn bits_to_level(&self, bits: usize) -> usize {
+        if self.gray_code { gray_decode(bits) } else { bits }
+    }
+
+    fn level_to_bits(&self, level: usize) -> usize {
+        if self.gray_code { gray_encode(level) } else { level }
+    }
+}
+
+/// Encodes bytes into birefringent voxels, `config.bits_per_voxel()` bits per voxel.
+///
+/// Panics if `config` is invalid.
+pub fn encode_birefringent(data: &[u8], config: &BirefringentConfig) -> Vec<BirefringentVoxel> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid birefringent config: {}", e);
+    }
+    pack_symbols(data, config.bits_per_voxel()).into_iter().map(|symbol| config.modulate(symbol)).collect()
+}
+
+/// Decodes birefringent voxels back into bytes. When the bits per voxel do not
+/// divide evenly into bytes, the output may end with a zero padding byte.
+///
+/// Panics if `config` is invalid.
+pub fn decode_birefringent(voxels: &[BirefringentVoxel], config: &BirefringentConfig) -> Vec<u8> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid birefringent config: {}", e);
+    }
+    let symbols: Vec<u32> = voxels.iter().map(|voxel| config.demodulate(voxel)).collect();
+    unpack_symbols(&symbols, config.bits_per_voxel())
+}
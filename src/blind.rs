@@ -0,0 +1,163 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{circular_distance, decode_data_with_config, CodecConfig};
+use std::f32::consts::PI;
+
+// Lloyd iterations are stopped once no centroid moves by more than this
+// fraction of the level spacing (angles) or of the value range (linear).
+const CONVERGENCE: f32 = 1e-4;
+const MAX_ITERATIONS: usize = 50;
+
+/// Level positions learned from the received voxels themselves, by 1-D k-means
+/// in every dimension.
+///
+/// Centroids are indexed like the ideal levels of the codec: `intensity[i]` is
+/// where level `i` actually landed, `wavelength[i]` where palette entry `i`
+/// did. This is a blind alternative to pilots: it needs no training voxels but
+/// assumes all levels are used roughly equally often (random or scrambled
+/// data), and angles can only be recovered for rotations below half a level
+/// spacing.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LearnedLevels {
+    pub intensity: Vec<f32>,
+    pub polarization: Vec<f32>,
+    pub phase: Vec<f32>,
+    pub wavelength: Vec<f32>,
+}
+
+impl LearnedLevels {
+    /// Clusters the received values of every dimension around the levels of `config`.
+    pub fn estimate(voxels: &[PhotonicVoxel], config: &CodecConfig) -> Self {
+        let values = |f: fn(&PhotonicVoxel) -> f32| voxels.iter().map(f).collect::<Vec<f32>>();
+
+        // Linear dimensions are seeded from quantiles: the k-th fraction of the
+        // sorted values belongs to the k-th level when levels are equally likely.
+        let intensity = linear_centroids(&values(|v| v.intensity), config.intensity_levels);
+
+        let mut by_rank: Vec<usize> = (0..config.data_wavelengths()).collect();
+        by_rank.sort_by(|&a, &b| config.wavelengths[a].total_cmp(&config.wavelengths[b]));
+        let sorted = linear_centroids(&values(|v| v.wavelength), by_rank.len());
+        let mut wavelength = vec![0.0; by_rank.len()];
+        for (rank, &idx) in by_rank.iter().enumerate() {
+            wavelength[idx] = sorted[rank];
+        }
+
+        Self {
+            intensity,
+            polarization: circular_centroids(&values(|v| v.polarization), config.polarization_levels, PI),
+            phase: circular_centroids(&values(|v| v.phase), config.phase_levels, 2.0 * PI),
+            wavelength,
+        }
+    }
+
+    /// Snaps a received voxel to the ideal levels of `config`, choosing in each
+    /// dimension the level whose learned centroid is nearest.
+    pub fn remap(&self, voxel: &PhotonicVoxel, config: &CodecConfig) -> PhotonicVoxel {
+        let nearest = |centroids: &[f32], distance: &dyn Fn(f32) -> f32| {
+            (0..centroids.len())
+                .min_by(|&a, &b| distance(centroids[a]).total_cmp(&distance(centroids[b])))
+                .unwrap_or(0)
+        };
+
+        let i = nearest(&self.intensity, &|c| (voxel.intensity - c).abs());
+        let p = nearest(&self.polarization, &|c| circular_distance(voxel.polarization, c, PI));
+        let ph = nearest(&self.phase, &|c| circular_distance(voxel.phase, c, 2.0 * PI));
+        let w = nearest(&self.wavelength, &|c| (voxel.wavelength - c).abs());
+
+        PhotonicVoxel::new(
+            config.intensity_level(i),
+            config.polarization_level(p),
+            config.phase_level(ph),
+            config.wavelengths[w],
+        )
+    }
+}
+
+/// Decoder mode for streams with unknown systematic offsets: learns the actual
+/// level positions with [`LearnedLevels::estimate`] and quantizes against them
+/// instead of the ideal levels.
+pub fn decode_blind(voxels: &[PhotonicVoxel], config: &CodecConfig) -> Vec<u8> {
+    let levels = LearnedLevels::estimate(voxels, config);
+    let remapped: Vec<PhotonicVoxel> = voxels.iter().map(|v| levels.remap(v, config)).collect();
+    decode_data_with_config(&remapped, false, config)
+}
+
+/// 1-D k-means over `values`, seeded at the midpoint quantile of each level.
+/// Returns `k` centroids in increasing order.
+fn linear_centroids(values: &[f32], k: usize) -> Vec<f32> {
+    if values.is_empty() || k <= 1 {
+        let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+        return vec![mean; k];
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let n = sorted.len();
+    let mut centroids: Vec<f32> = (0..k).map(|j| sorted[((2 * j + 1) * n / (2 * k)).min(n - 1)]).collect();
+    let tolerance = CONVERGENCE * (sorted[sorted.len() - 1] - sorted[0]).max(f32::EPSILON);
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut sums = vec![(0.0f32, 0usize); k];
+        for &v in &sorted {
+            // Centroids stay sorted, so the nearest one is found by partition
+            let j = centroids.partition_point(|&c| c < v);
+            let j = if j == k || (j > 0 && v - centroids[j - 1] < centroids[j] - v) { j - 1 } else { j };
+            sums[j].0 += v;
+            sums[j].1 += 1;
+        }
+        let mut moved = 0.0f32;
+        for (c, (sum, n)) in centroids.iter_mut().zip(sums) {
+            if n > 0 {
+                let updated = sum / n as f32;
+                moved = moved.max((updated - *c).abs());
+                *c = updated;
+            }
+        }
+        if moved < tolerance {
+            break;
+        }
+    }
+
+    centroids
+}
+
+/// 1-D k-means on a circle of circumference `period`, for `k` levels ideally at
+/// `i * period / k`. Returns the centroid of every level index.
+fn circular_centroids(values: &[f32], k: usize, period: f32) -> Vec<f32> {
+    let spacing = period / k as f32;
+    if values.is_empty() || k <= 1 {
+        return (0..k).map(|i| i as f32 * spacing).collect();
+    }
+
+    // Common rotation of the whole constellation: raising the angles to the
+    // k-th power folds all levels onto one point (as in M-PSK carrier recovery).
+    let scale = 2.0 * PI / spacing;
+    let (sin, cos) = values.iter().fold((0.0f32, 0.0f32), |(s, c), v| (s + (v * scale).sin(), c + (v * scale).cos()));
+    let rotation = sin.atan2(cos) / scale;
+    let mut centroids: Vec<f32> = (0..k).map(|i| i as f32 * spacing + rotation).collect();
+
+    let wrapped = |d: f32| (d + period / 2.0).rem_euclid(period) - period / 2.0;
+    for _ in 0..MAX_ITERATIONS {
+        let mut sums = vec![(0.0f32, 0usize); k];
+        for &v in values {
+            let j = (0..k)
+                .min_by(|&a, &b| circular_distance(v, centroids[a], period).total_cmp(&circular_distance(v, centroids[b], period)))
+                .unwrap_or(0);
+            sums[j].0 += wrapped(v - centroids[j]);
+            sums[j].1 += 1;
+        }
+        let mut moved = 0.0f32;
+        for (c, (sum, n)) in centroids.iter_mut().zip(sums) {
+            if n > 0 {
+                let shift = sum / n as f32;
+                moved = moved.max(shift.abs());
+                *c = (*c + shift).rem_euclid(period);
+            }
+        }
+        if moved < CONVERGENCE * spacing {
+            break;
+        }
+    }
+
+    centroids
+}
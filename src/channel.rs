@@ -0,0 +1,200 @@
+use crate::defects::DefectMap;
+use crate::detector::{DetectorModel, PhotonBudget};
+use crate::noise::{apply_noise_with_rng, NoiseProfile};
+use crate::physics::{
+    simulate_aberration, simulate_aging, simulate_attenuation, simulate_crosstalk_with_config, simulate_focus_error, simulate_polarization_drift_with_rng, simulate_position_jitter_with_rng, simulate_psf, simulate_rewrite_cycles_with_rng,
+    simulate_scattering, simulate_sensor_response, simulate_write_with_rng, AberratedPsf, AgingParams, BirefringenceDrift, CrosstalkConfig, FatigueModel, FocusError, PositionJitter, PsfKernel, SensorResponse, WriteParams,
+};
+use crate::structs::{PhotonicVoxel, VoxelLattice};
+use rand::RngCore;
+
+/// One effect of the channel between the written data and the decoder,
+/// turning the voxels of a lattice into what the next stage (or the reader)
+/// sees. Chained in a [`ChannelPipeline`].
+///
+/// The physics models, noise, aging and defects implement it through their
+/// parameter types, e.g. a [`CrosstalkConfig`] applies
+/// [`simulate_crosstalk_with_config`]. Random stages draw from `rng`, so a
+/// seeded pipeline is reproducible.
+pub trait ChannelStage: std::fmt::Debug {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice;
+}
+
+/// Channel stages applied in order, from writing to readout, e.g. a
+/// [`WriteParams`], then a [`CrosstalkConfig`], an [`Attenuation`] and a
+/// [`NoiseProfile`]. A pipeline is itself a stage, so channels nest.
+#[derive(Debug, Default)]
+pub struct ChannelPipeline {
+    stages: Vec<Box<dyn ChannelStage>>,
+}
+
+impl ChannelPipeline {
+    /// A channel that changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pipeline with `stage` appended.
+    pub fn stage(mut self, stage: impl ChannelStage + 'static) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Appends `stage`.
+    pub fn push(&mut self, stage: impl ChannelStage + 'static) {
+        self.stages.push(Box::new(stage));
+    }
+
+    /// Number of stages.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// `lattice` after every stage.
+    pub fn apply(&self, lattice: &VoxelLattice) -> VoxelLattice {
+        self.apply_with_rng(lattice, &mut rand::rng())
+    }
+
+    /// [`ChannelPipeline::apply`] drawing from `rng`, for reproducible runs.
+    pub fn apply_with_rng<R: RngCore>(&self, lattice: &VoxelLattice, rng: &mut R) -> VoxelLattice {
+        ChannelStage::apply(self, lattice, rng)
+    }
+}
+
+impl ChannelStage for ChannelPipeline {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        self.stages.iter().fold(lattice.clone(), |lattice, stage| stage.apply(&lattice, rng))
+    }
+}
+
+/// Depth attenuation stage ([`simulate_attenuation`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attenuation {
+    pub absorption_coefficient: f32,
+}
+
+/// Volumetric scattering stage ([`simulate_scattering`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scattering {
+    pub scattering_coefficient: f32,
+}
+
+/// Rewrite fatigue stage: the data is written after `cycles` erase/rewrite
+/// cycles of `model` ([`simulate_rewrite_cycles_with_rng`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RewriteCycles {
+    pub cycles: usize,
+    pub model: FatigueModel,
+}
+
+/// `lattice` with its voxels replaced by `voxels`, in storage order.
+fn with_voxels(lattice: &VoxelLattice, voxels: Vec<PhotonicVoxel>) -> VoxelLattice {
+    let mut output = lattice.clone();
+    for (slot, voxel) in output.iter_mut().zip(voxels) {
+        *slot = voxel;
+    }
+    output
+}
+
+impl ChannelStage for WriteParams {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, simulate_write_with_rng(lattice.voxels(), self, rng))
+    }
+}
+
+impl ChannelStage for RewriteCycles {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, simulate_rewrite_cycles_with_rng(lattice.voxels(), self.cycles, &self.model, rng))
+    }
+}
+
+impl ChannelStage for DefectMap {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, DefectMap::apply(self, lattice.voxels()))
+    }
+}
+
+impl ChannelStage for AgingParams {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_aging(lattice, self)
+    }
+}
+
+impl ChannelStage for BirefringenceDrift {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, simulate_polarization_drift_with_rng(lattice.voxels(), self, rng))
+    }
+}
+
+impl ChannelStage for CrosstalkConfig {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_crosstalk_with_config(lattice, self)
+    }
+}
+
+impl ChannelStage for PsfKernel {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_psf(lattice, self)
+    }
+}
+
+impl ChannelStage for AberratedPsf {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_aberration(lattice, self)
+    }
+}
+
+impl ChannelStage for FocusError {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_focus_error(lattice, self)
+    }
+}
+
+impl ChannelStage for PositionJitter {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_position_jitter_with_rng(lattice, self, rng)
+    }
+}
+
+impl ChannelStage for Attenuation {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_attenuation(lattice, self.absorption_coefficient)
+    }
+}
+
+impl ChannelStage for Scattering {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_scattering(lattice, self.scattering_coefficient)
+    }
+}
+
+impl ChannelStage for SensorResponse {
+    fn apply(&self, lattice: &VoxelLattice, _rng: &mut dyn RngCore) -> VoxelLattice {
+        simulate_sensor_response(lattice, self)
+    }
+}
+
+impl ChannelStage for NoiseProfile {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, apply_noise_with_rng(lattice.voxels(), *self, rng))
+    }
+}
+
+impl ChannelStage for DetectorModel {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, self.read_voxels_with_rng(lattice.voxels(), rng))
+    }
+}
+
+impl ChannelStage for PhotonBudget {
+    fn apply(&self, lattice: &VoxelLattice, rng: &mut dyn RngCore) -> VoxelLattice {
+        with_voxels(lattice, self.read_voxels_with_rng(lattice.voxels(), rng))
+    }
+}
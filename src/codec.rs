@@ -1,6 +1,9 @@
 use crate::structs::PhotonicVoxel;
-use std::f32::consts::PI;
+use crate::noise::{apply_noise, apply_noise_with_rng, NoiseProfile};
+use crate::quantizer::Quantizer;
 use rand::Rng;
+use std::f32::consts::PI;
+use std::io::{self, Read};
 
 // Constants for encoding
 const INTENSITY_LEVELS: usize = 4;
@@ -14,6 +17,402 @@ const PHASE_LEVELS: usize = 4;
 // 3: IR (800 nm) - Just an example
 const WAVELENGTHS: [f32; 4] = [532.0, 650.0, 450.0, 800.0];
 
+// Wavelength band spanned by adaptive palettes (min/max of the default palette)
+const WAVELENGTH_BAND: (f32, f32) = (450.0, 800.0);
+
+// Upper bound on the bits a single dimension receives from bit-loading
+const MAX_BITS_PER_DIMENSION: usize = 8;
+
+// Inputs smaller than this (in voxels) are processed serially even with the
+// `parallel` feature: below it, rayon's scheduling overhead outweighs the gain.
+#[cfg(feature = "parallel")]
+pub(crate) const PARALLEL_THRESHOLD: usize = 16 * 1024;
+
+/// Modulation parameters for the voxel codec.
+///
+/// Every level count must be a power of two, so that each dimension carries a
+/// whole number of bits: `log2(levels)` bits per dimension, `bits_per_voxel()`
+/// bits in total. The wavelength palette may have any length; see `wavelengths`.
+///
+/// The default configuration is the original 8-bit scheme
+/// (4 intensity levels, 4 polarization angles, 4 phases, 4 wavelengths)
+/// with natural binary bit mapping.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodecConfig {
+    /// Number of intensity levels, evenly spaced in (0.0, 1.0].
+    pub intensity_levels: usize,
+    /// Number of polarization angles, evenly spaced in [0, PI).
+    pub polarization_levels: usize,
+    /// Number of phase shifts, evenly spaced in [0, 2*PI).
+    pub phase_levels: usize,
+    /// Wavelength palette in nanometers, in any order. The index in this list is
+    /// the encoded symbol. Palettes of arbitrary length are accepted: the first
+    /// `2^floor(log2(n))` entries carry data and any further entries are unused.
+    /// Decision boundaries are the midpoints between neighbouring wavelengths.
+    pub wavelengths: Vec<f32>,
+    /// Map bits to levels with a Gray code, so that neighbouring levels differ in
+    /// exactly one bit and a nearest-level misread costs a single bit error.
+    /// Wavelengths are Gray-coded in order of increasing wavelength.
+    pub gray_code: bool,
+}
+
+impl Default for CodecConfig {
+    fn default() -> Self {
+        Self {
+            intensity_levels: INTENSITY_LEVELS,
+            polarization_levels: POLARIZATION_LEVELS,
+            phase_levels: PHASE_LEVELS,
+            wavelengths: WAVELENGTHS.to_vec(),
+            gray_code: false,
+        }
+    }
+}
+
+impl CodecConfig {
+    /// Checks that every dimension has a power-of-two number of levels, that the
+    /// wavelength palette holds distinct positive values and that a voxel carries
+    /// between 1 and 32 bits.
+    pub fn validate(&self) -> Result<(), String> {
+        let dimensions = [
+            ("intensity_levels", self.intensity_levels),
+            ("polarization_levels", self.polarization_levels),
+            ("phase_levels", self.phase_levels),
+        ];
+        for (name, levels) in dimensions {
+            if !levels.is_power_of_two() {
+                return Err(format!("{} must be a non-zero power of two (got {})", name, levels));
+            }
+        }
+
+        if self.wavelengths.is_empty() {
+            return Err("wavelength palette must not be empty".to_string());
+        }
+        for (i, &w) in self.wavelengths.iter().enumerate() {
+            if !w.is_finite() || w <= 0.0 {
+                return Err(format!("wavelength {} must be positive and finite (got {})", i, w));
+            }
+            if self.wavelengths[..i].contains(&w) {
+                return Err(format!("wavelength {} nm appears twice in the palette", w));
+            }
+        }
+
+        let bits = self.bits_per_voxel();
+        if bits == 0 || bits > 32 {
+            return Err(format!("bits per voxel must be in 1..=32 (got {})", bits));
+        }
+
+        Ok(())
+    }
+
+    /// Builds a configuration by bit-loading: distributes `total_bits` over the
+    /// four dimensions according to their measured noise, giving more levels to
+    /// quiet dimensions and fewer to noisy ones (as in multi-carrier bit-loading).
+    ///
+    /// `variances` are the per-dimension noise variances from a calibration run,
+    /// in the order intensity, polarization (rad²), phase (rad²), wavelength (nm²).
+    ///
+    /// Bits are assigned greedily, one at a time, to the dimension whose decision
+    /// margin (half the level spacing, in noise standard deviations) stays the
+    /// largest after adding the bit. This maximizes the worst-case margin.
+    /// Wavelength levels are spread evenly over 450-800 nm. At most 8 bits go to
+    /// a single dimension.
+    pub fn from_noise_variances(variances: [f32; 4], total_bits: usize) -> Result<Self, String> {
+        if total_bits == 0 || total_bits > 4 * MAX_BITS_PER_DIMENSION {
+            return Err(format!("total_bits must be in 1..={} (got {})", 4 * MAX_BITS_PER_DIMENSION, total_bits));
+        }
+
+        // Noise-free dimensions still need a finite sigma to compare margins
+        let sigmas = variances.map(|v| v.max(f32::EPSILON).sqrt());
+        let margin = |dim: usize, bits: usize| level_spacing(dim, 1 << bits) / (2.0 * sigmas[dim]);
+
+        let mut bits = [0usize; 4];
+        for _ in 0..total_bits {
+            let best = (0..4)
+                .filter(|&dim| bits[dim] < MAX_BITS_PER_DIMENSION)
+                .max_by(|&a, &b| margin(a, bits[a] + 1).total_cmp(&margin(b, bits[b] + 1)))
+                .expect("total_bits is bounded by the per-dimension cap");
+            bits[best] += 1;
+        }
+
+        let wavelength_levels = 1usize << bits[3];
+        let wavelengths = if wavelength_levels == 1 {
+            vec![WAVELENGTHS[0]]
+        } else {
+            let (low, high) = WAVELENGTH_BAND;
+            let step = (high - low) / (wavelength_levels - 1) as f32;
+            (0..wavelength_levels).map(|i| low + step * i as f32).collect()
+        };
+
+        Ok(Self {
+            intensity_levels: 1 << bits[0],
+            polarization_levels: 1 << bits[1],
+            phase_levels: 1 << bits[2],
+            wavelengths,
+            gray_code: false,
+        })
+    }
+
+    /// [`CodecConfig::from_noise_variances`] for a measured [`NoiseProfile`].
+    pub fn from_noise_profile(profile: &NoiseProfile, total_bits: usize) -> Result<Self, String> {
+        Self::from_noise_variances(profile.variances(), total_bits)
+    }
+
+    /// Number of data bits stored in a single voxel.
+    pub fn bits_per_voxel(&self) -> usize {
+        level_bits(self.intensity_levels)
+            + level_bits(self.polarization_levels)
+            + level_bits(self.phase_levels)
+            + level_bits(self.wavelengths.len())
+    }
+
+    /// Number of palette entries that carry data (a power of two).
+    pub(crate) fn data_wavelengths(&self) -> usize {
+        1 << level_bits(self.wavelengths.len())
+    }
+
+    /// Decision boundaries between the data-carrying wavelengths, in increasing
+    /// order: the midpoints between neighbouring wavelengths of the palette.
+    /// A received wavelength is decoded as the palette entry between the two
+    /// boundaries that enclose it.
+    pub fn wavelength_decision_boundaries(&self) -> Vec<f32> {
+        let mut sorted: Vec<f32> = self.wavelengths[..self.data_wavelengths()].to_vec();
+        sorted.sort_by(f32::total_cmp);
+        sorted.windows(2).map(|pair| (pair[0] + pair[1]) / 2.0).collect()
+    }
+
+    /// Intensity of level `idx`: [0.25, 0.5, 0.75, 1.0] for 4 levels.
+    pub(crate) fn intensity_level(&self, idx: usize) -> f32 {
+        (idx as f32 + 1.0) / self.intensity_levels as f32
+    }
+
+    /// Polarization angle of level `idx`: [0, PI/4, PI/2, 3PI/4] for 4 levels.
+    pub(crate) fn polarization_level(&self, idx: usize) -> f32 {
+        idx as f32 * (PI / self.polarization_levels as f32)
+    }
+
+    /// Phase of level `idx`: [0, PI/2, PI, 3PI/2] for 4 levels.
+    pub(crate) fn phase_level(&self, idx: usize) -> f32 {
+        idx as f32 * (2.0 * PI / self.phase_levels as f32)
+    }
+
+    /// Maps the bits of a dimension to the level index that carries them.
+    fn bits_to_level(&self, bits: usize) -> usize {
+        if self.gray_code { gray_decode(bits) } else { bits }
+    }
+
+    /// Maps a level index back to the bits it carries.
+    pub(crate) fn level_to_bits(&self, level: usize) -> usize {
+        if self.gray_code { gray_encode(level) } else { level }
+    }
+
+    /// Data-carrying palette indices ordered by increasing wavelength.
+    pub(crate) fn wavelengths_by_rank(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.data_wavelengths()).collect();
+        order.sort_by(|&a, &b| self.wavelengths[a].total_cmp(&self.wavelengths[b]));
+        order
+    }
+
+    /// Palette index carrying the wavelength bits `bits`.
+    fn bits_to_wavelength_idx(&self, bits: usize) -> usize {
+        if self.gray_code {
+            self.wavelengths_by_rank()[gray_decode(bits)]
+        } else {
+            bits
+        }
+    }
+
+    /// Wavelength bits carried by palette index `idx`.
+    pub(crate) fn wavelength_idx_to_bits(&self, idx: usize) -> usize {
+        if self.gray_code {
+            let rank = self.wavelengths_by_rank().iter().position(|&i| i == idx).unwrap_or(0);
+            gray_encode(rank)
+        } else {
+            idx
+        }
+    }
+}
+
+impl CodecConfig {
+    /// Nearest-level decision in every dimension
+    /// (intensity, polarization, phase, wavelength).
+    pub(crate) fn decide(&self, voxel: &PhotonicVoxel) -> [LevelDecision; 4] {
+        // Decode Intensity
+        let intensity = nearest_level(self.intensity_levels, |i| {
+            (voxel.intensity - self.intensity_level(i)).abs()
+        });
+
+        // Decode Polarization (angles wrap around every PI)
+        let polarization = nearest_level(self.polarization_levels, |i| {
+            circular_distance(voxel.polarization, self.polarization_level(i), PI)
+        });
+
+        // Decode Phase (wraps around every 2*PI)
+        let phase = nearest_level(self.phase_levels, |i| {
+            circular_distance(voxel.phase, self.phase_level(i), 2.0 * PI)
+        });
+
+        // Decode Wavelength
+        let wavelength = nearest_level(self.data_wavelengths(), |i| {
+            (voxel.wavelength - self.wavelengths[i]).abs()
+        });
+
+        [intensity, polarization, phase, wavelength]
+    }
+
+    /// Builds the symbol carried by the given level indices
+    /// (intensity, polarization, phase, palette index).
+    fn assemble_symbol(&self, levels: [usize; 4]) -> u32 {
+        let i_width = level_bits(self.intensity_levels);
+        let p_width = level_bits(self.polarization_levels);
+        let ph_width = level_bits(self.phase_levels);
+
+        let i_bits = self.level_to_bits(levels[0]) as u32;
+        let p_bits = self.level_to_bits(levels[1]) as u32;
+        let ph_bits = self.level_to_bits(levels[2]) as u32;
+        let w_bits = self.wavelength_idx_to_bits(levels[3]) as u32;
+
+        // Reassemble: wavelength | phase | polarization | intensity (low bits)
+        w_bits << (i_width + p_width + ph_width) | ph_bits << (i_width + p_width) | p_bits << i_width | i_bits
+    }
+}
+
+/// Spacing between neighbouring levels of dimension `dim` (0: intensity,
+/// 1: polarization, 2: phase, 3: wavelength) when it has `levels` levels, as laid
+/// out by [`CodecConfig`] and [`CodecConfig::from_noise_variances`].
+fn level_spacing(dim: usize, levels: usize) -> f32 {
+    let n = levels as f32;
+    match dim {
+        0 => 1.0 / n,
+        1 => PI / n,
+        2 => 2.0 * PI / n,
+        _ if levels == 1 => f32::INFINITY,
+        _ => (WAVELENGTH_BAND.1 - WAVELENGTH_BAND.0) / (n - 1.0),
+    }
+}
+
+/// Binary-reflected Gray code of `value`.
+pub(crate) fn gray_encode(value: usize) -> usize {
+    value ^ (value >> 1)
+}
+
+/// Inverse of [`gray_encode`].
+pub(crate) fn gray_decode(mut gray: usize) -> usize {
+    let mut value = 0;
+    while gray != 0 {
+        value ^= gray;
+        gray >>= 1;
+    }
+    value
+}
+
+/// Number of bits encoded by a dimension with `levels` levels: `floor(log2(levels))`.
+pub(crate) fn level_bits(levels: usize) -> usize {
+    levels.checked_ilog2().unwrap_or(0) as usize
+}
+
+/// A mapping between fixed-width bit symbols and physical voxel states.
+///
+/// Implement this trait to plug a custom constellation into the codec without
+/// forking the crate, then use [`encode_with_scheme`] / [`decode_with_scheme`].
+/// [`CodecConfig`] is the built-in implementation (per-dimension levels); its
+/// default is the original 8-bit scheme. Schemes are shared by the threads
+/// of parallel BER sweeps, hence `Sync`.
+pub trait ModulationScheme: Sync {
+    /// Number of data bits carried by one voxel. Must be in 1..=32.
+    fn bits_per_voxel(&self) -> usize;
+
+    /// Maps a symbol (the low `bits_per_voxel()` bits of `symbol`) to a voxel.
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel;
+
+    /// Maps a received voxel to the most likely symbol.
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32;
+
+    /// Appends `bits_per_voxel()` log-likelihood ratios (least significant bit
+    /// first, positive favours 0) for a received voxel to `llrs`.
+    ///
+    /// The default implementation has no reliability information and reports
+    /// the hard decision with a confidence of 1.0 per bit.
+    fn demodulate_soft(&self, voxel: &PhotonicVoxel, llrs: &mut Vec<f32>) {
+        let symbol = self.demodulate(voxel);
+        llrs.extend((0..self.bits_per_voxel()).map(|bit| if (symbol >> bit) & 1 == 0 { 1.0 } else { -1.0 }));
+    }
+}
+
+impl ModulationScheme for CodecConfig {
+    fn bits_per_voxel(&self) -> usize {
+        CodecConfig::bits_per_voxel(self)
+    }
+
+    /// Lowest bits: Intensity, then Polarization, then Phase, highest bits: Wavelength.
+    /// With the default config this is bits 0-1, 2-3, 4-5 and 6-7 of a byte.
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        let i_width = level_bits(self.intensity_levels);
+        let p_width = level_bits(self.polarization_levels);
+        let ph_width = level_bits(self.phase_levels);
+        let w_width = level_bits(self.wavelengths.len());
+
+        let intensity_bits = take_bits(symbol, 0, i_width);
+        let polarization_bits = take_bits(symbol, i_width, p_width);
+        let phase_bits = take_bits(symbol, i_width + p_width, ph_width);
+        let wavelength_bits = take_bits(symbol, i_width + p_width + ph_width, w_width);
+
+        let intensity = self.intensity_level(self.bits_to_level(intensity_bits));
+        let polarization = self.polarization_level(self.bits_to_level(polarization_bits));
+        let phase = self.phase_level(self.bits_to_level(phase_bits));
+        let wavelength = self.wavelengths[self.bits_to_wavelength_idx(wavelength_bits)];
+
+        PhotonicVoxel::new(intensity, polarization, phase, wavelength)
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        self.assemble_symbol(self.decide(voxel).map(|d| d.level))
+    }
+
+    /// Max-log LLRs of a Gaussian channel whose standard deviation per dimension
+    /// matches the simulated readout noise ([`NoiseProfile::default`]):
+    /// `LLR = (min d²(bit = 1) - min d²(bit = 0)) / (2σ²)`, where `d` is the
+    /// distance from the received value to the levels carrying each bit value.
+    fn demodulate_soft(&self, voxel: &PhotonicVoxel, llrs: &mut Vec<f32>) {
+        self.demodulate_soft_with_noise(voxel, &NoiseProfile::default(), llrs);
+    }
+}
+
+impl CodecConfig {
+    /// [`demodulate_soft`](ModulationScheme::demodulate_soft) for a channel
+    /// with the standard deviations of `noise`.
+    fn demodulate_soft_with_noise(&self, voxel: &PhotonicVoxel, noise: &NoiseProfile, llrs: &mut Vec<f32>) {
+        dimension_llrs(
+            self.intensity_levels,
+            |i| (voxel.intensity - self.intensity_level(i)).abs(),
+            |i| self.level_to_bits(i),
+            noise.intensity_sigma_at(voxel.intensity),
+            llrs,
+        );
+        dimension_llrs(
+            self.polarization_levels,
+            |i| circular_distance(voxel.polarization, self.polarization_level(i), PI),
+            |i| self.level_to_bits(i),
+            noise.polarization,
+            llrs,
+        );
+        dimension_llrs(
+            self.phase_levels,
+            |i| circular_distance(voxel.phase, self.phase_level(i), 2.0 * PI),
+            |i| self.level_to_bits(i),
+            noise.phase,
+            llrs,
+        );
+        dimension_llrs(
+            self.data_wavelengths(),
+            |i| (voxel.wavelength - self.wavelengths[i]).abs(),
+            |i| self.wavelength_idx_to_bits(i),
+            noise.wavelength_nm,
+            llrs,
+        );
+    }
+}
+
 /// Encodes a byte array into a vector of PhotonicVoxels using 8-bit encoding per voxel.
 ///
 /// We are encoding 4 chunks of 2 bits each into one voxel:
@@ -26,135 +425,703 @@ const WAVELENGTHS: [f32; 4] = [532.0, 650.0, 450.0, 800.0];
 ///
 /// This simplifies the encoding logic significantly: 1 byte -> 1 voxel.
 pub fn encode_data(data: &[u8]) -> Vec<PhotonicVoxel> {
-    let mut voxels = Vec::with_capacity(data.len());
+    encode_data_with_config(data, &CodecConfig::default())
+}
 
-    for &byte in data {
-        voxels.push(encode_byte_to_voxel(byte));
+/// Encodes a byte array using the modulation levels described by `config`.
+///
+/// The input is treated as a bit stream (least significant bit of each byte first)
+/// and cut into `config.bits_per_voxel()`-bit symbols. The last symbol is padded
+/// with zero bits.
+///
+/// With the `parallel` feature, large inputs are modulated on the rayon pool.
+///
+/// Panics if `config` is invalid (see [`CodecConfig::validate`]).
+pub fn encode_data_with_config(data: &[u8], config: &CodecConfig) -> Vec<PhotonicVoxel> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
     }
 
-    voxels
+    let symbols = pack_symbols(data, config.bits_per_voxel());
+
+    #[cfg(feature = "parallel")]
+    if symbols.len() >= PARALLEL_THRESHOLD {
+        use rayon::prelude::*;
+        return symbols.par_iter().map(|&symbol| config.modulate(symbol)).collect();
+    }
+
+    symbols.iter().map(|&symbol| config.modulate(symbol)).collect()
 }
 
-/// Encodes a full byte into a single PhotonicVoxel.
-/// Bits 0-1: Intensity
-/// Bits 2-3: Polarization
-/// Bits 4-5: Phase
-/// Bits 6-7: Wavelength
-fn encode_byte_to_voxel(byte: u8) -> PhotonicVoxel {
-    let intensity_bits = byte & 0b0011;
-    let polarization_bits = (byte >> 2) & 0b0011;
-    let phase_bits = (byte >> 4) & 0b0011;
-    let wavelength_bits = (byte >> 6) & 0b0011;
+/// Encodes a byte array with an arbitrary [`ModulationScheme`].
+///
+/// Panics if the scheme reports a symbol width outside 1..=32 bits.
+pub fn encode_with_scheme<S: ModulationScheme + ?Sized>(data: &[u8], scheme: &S) -> Vec<PhotonicVoxel> {
+    let bits = checked_symbol_width(scheme);
 
-    // Intensity: [0.25, 0.5, 0.75, 1.0]
-    let intensity = (intensity_bits as f32 + 1.0) * 0.25;
+    pack_symbols(data, bits)
+        .into_iter()
+        .map(|symbol| scheme.modulate(symbol))
+        .collect()
+}
 
-    // Polarization: [0, 45, 90, 135] (deg) -> [0, PI/4, PI/2, 3PI/4]
-    let polarization = (polarization_bits as f32) * (PI / 4.0);
+/// Decodes a vector of PhotonicVoxels back into bytes.
+///
+/// Streams written with [`encode_with_header`](crate::header::encode_with_header)
+/// are decoded with the scheme recorded in their header; others with the default
+/// 8-bit codec. Simulates readout noise if `simulate_noise` is true.
+///
+/// A header this version cannot read (damaged, or from a newer version) is
+/// decoded as data, and the data after a header whose ECC check fails is
+/// returned as demodulated; [`decode_auto`](crate::header::decode_auto)
+/// reports both as errors instead.
+pub fn decode_data(voxels: &[PhotonicVoxel], simulate_noise: bool) -> Vec<u8> {
+    match crate::header::read_header(voxels, simulate_noise) {
+        Ok(Some((header, len))) => {
+            let received = header.scheme.decode(&voxels[len..], simulate_noise);
+            header.finish_data(received.clone()).unwrap_or(received)
+        }
+        Ok(None) | Err(_) => decode_data_with_config(voxels, simulate_noise, &CodecConfig::default()),
+    }
+}
 
-    // Phase: [0, 90, 180, 270] (deg) -> [0, PI/2, PI, 3PI/2]
-    let phase = (phase_bits as f32) * (PI / 2.0);
+/// Decodes voxels after adding readout noise with explicit amplitudes (a
+/// [`NoiseProfile`] or uniform [`NoiseParams`](crate::NoiseParams)), instead of
+/// the fixed ranges used by `decode_data(voxels, true)`.
+pub fn decode_data_noisy(voxels: &[PhotonicVoxel], noise: impl Into<NoiseProfile>) -> Vec<u8> {
+    decode_data(&apply_noise(voxels, noise), false)
+}
 
-    // Wavelength
-    let wavelength = WAVELENGTHS[wavelength_bits as usize];
+/// `decode_data(voxels, true)` with the readout noise drawn from `rng`, so a
+/// run can be replayed bit-for-bit from a seeded generator.
+pub fn decode_data_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], rng: &mut R) -> Vec<u8> {
+    decode_data(&apply_noise_with_rng(voxels, NoiseProfile::default(), rng), false)
+}
 
-    PhotonicVoxel::new(intensity, polarization, phase, wavelength)
+/// [`decode_data_noisy`] drawing the noise from `rng`.
+pub fn decode_data_noisy_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], noise: impl Into<NoiseProfile>, rng: &mut R) -> Vec<u8> {
+    decode_data(&apply_noise_with_rng(voxels, noise, rng), false)
 }
 
-/// Decodes a vector of PhotonicVoxels back into bytes.
+/// [`decode_data_noisy`] for voxels written with [`encode_data_with_config`].
+pub fn decode_data_noisy_with_config(voxels: &[PhotonicVoxel], noise: impl Into<NoiseProfile>, config: &CodecConfig) -> Vec<u8> {
+    decode_data_with_config(&apply_noise(voxels, noise), false, config)
+}
+
+/// Decodes voxels written with [`encode_data_with_config`] back into bytes.
 ///
-/// Simulates readout noise if `simulate_noise` is true.
-pub fn decode_data(voxels: &[PhotonicVoxel], simulate_noise: bool) -> Vec<u8> {
-    let mut data = Vec::with_capacity(voxels.len());
+/// `config` must match the one used for encoding. When the bits per voxel do not
+/// divide evenly into bytes, the output may end with a zero padding byte.
+///
+/// With the `parallel` feature, large inputs are demodulated on the rayon pool.
+///
+/// Panics if `config` is invalid (see [`CodecConfig::validate`]).
+pub fn decode_data_with_config(voxels: &[PhotonicVoxel], simulate_noise: bool, config: &CodecConfig) -> Vec<u8> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+
+    let quantizer = Quantizer::new(config);
+    let decode_voxel = |&voxel: &PhotonicVoxel| {
+        if simulate_noise {
+            quantizer.demodulate(&add_readout_noise(voxel))
+        } else {
+            quantizer.demodulate(&voxel)
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    if voxels.len() >= PARALLEL_THRESHOLD {
+        use rayon::prelude::*;
+        let symbols: Vec<u32> = voxels.par_iter().map(decode_voxel).collect();
+        return unpack_symbols(&symbols, config.bits_per_voxel());
+    }
 
+    let symbols: Vec<u32> = voxels.iter().map(decode_voxel).collect();
+    unpack_symbols(&symbols, config.bits_per_voxel())
+}
+
+/// Decodes voxels (taken as received, no noise added) with an arbitrary
+/// [`ModulationScheme`]. The output may end with a zero padding byte when the
+/// symbol width does not divide evenly into bytes.
+pub fn decode_with_scheme<S: ModulationScheme + ?Sized>(voxels: &[PhotonicVoxel], scheme: &S) -> Vec<u8> {
+    let bits = checked_symbol_width(scheme);
+
+    let symbols: Vec<u32> = voxels.iter().map(|voxel| scheme.demodulate(voxel)).collect();
+
+    unpack_symbols(&symbols, bits)
+}
+
+/// Per-voxel decision margins: in each dimension, how much farther the received
+/// value is from the second-closest level than from the chosen one.
+///
+/// Margins are in each dimension's own units (intensity, radians, radians, nm)
+/// and are infinite for dimensions with a single level. A margin of zero means
+/// the value sits exactly on a decision boundary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoxelConfidence {
+    pub intensity: f32,
+    pub polarization: f32,
+    pub phase: f32,
+    pub wavelength: f32,
+    /// Smallest margin across dimensions, relative to the distance between the
+    /// two competing levels: 0.0 on a decision boundary, 1.0 exactly on a level.
+    pub min_relative: f32,
+}
+
+impl VoxelConfidence {
+    fn from_decisions(decisions: &[LevelDecision; 4]) -> Self {
+        let margin = |d: &LevelDecision| d.runner_up - d.nearest;
+        let min_relative = decisions.iter().map(LevelDecision::relative_margin).fold(1.0f32, f32::min);
+
+        Self {
+            intensity: margin(&decisions[0]),
+            polarization: margin(&decisions[1]),
+            phase: margin(&decisions[2]),
+            wavelength: margin(&decisions[3]),
+            min_relative,
+        }
+    }
+
+    /// True if any dimension's relative margin is below `threshold` (in [0, 1]).
+    /// Such voxels are good candidates for being treated as erasures by ECC.
+    pub fn is_uncertain(&self, threshold: f32) -> bool {
+        self.min_relative < threshold
+    }
+}
+
+/// Decodes voxels like [`decode_data_with_config`] and also returns one
+/// [`VoxelConfidence`] per voxel, so uncertain voxels can be flagged.
+///
+/// Panics if `config` is invalid (see [`CodecConfig::validate`]).
+pub fn decode_data_with_confidence(voxels: &[PhotonicVoxel], simulate_noise: bool, config: &CodecConfig) -> (Vec<u8>, Vec<VoxelConfidence>) {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+
+    let mut symbols = Vec::with_capacity(voxels.len());
+    let mut confidence = Vec::with_capacity(voxels.len());
     for &voxel in voxels {
-        data.push(decode_voxel(voxel, simulate_noise));
+        let received = if simulate_noise { add_readout_noise(voxel) } else { voxel };
+        let decisions = config.decide(&received);
+        symbols.push(config.assemble_symbol(decisions.map(|d| d.level)));
+        confidence.push(VoxelConfidence::from_decisions(&decisions));
     }
 
-    data
+    (unpack_symbols(&symbols, config.bits_per_voxel()), confidence)
 }
 
-/// Decodes a single voxel into a byte.
-fn decode_voxel(voxel: PhotonicVoxel, noise: bool) -> u8 {
-    let mut intensity = voxel.intensity;
-    let mut polarization = voxel.polarization;
-    let mut phase = voxel.phase;
-    let mut wavelength = voxel.wavelength;
+/// Number of bins of the margin histograms in a [`DecodeReport`].
+pub const MARGIN_HISTOGRAM_BINS: usize = 10;
 
-    if noise {
-        let mut rng = rand::rng();
-        // Add Gaussian-like noise
-        let i_noise: f32 = rng.random_range(-0.05..0.05);
-        let p_noise: f32 = rng.random_range(-0.08..0.08);
-        let ph_noise: f32 = rng.random_range(-0.1..0.1);
-        let w_noise: f32 = rng.random_range(-10.0..10.0); // +/- 10nm noise
+/// Received values and decision margins of one dimension over a decoded batch.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DimensionReport {
+    /// Smallest received value (infinite if nothing was decoded).
+    pub min: f32,
+    /// Largest received value (negative infinite if nothing was decoded).
+    pub max: f32,
+    /// Histogram of relative decision margins (as in
+    /// [`VoxelConfidence::min_relative`]): bin `b` counts margins in
+    /// `[b, b + 1) / MARGIN_HISTOGRAM_BINS`, the last bin includes 1.0.
+    /// Dimensions with a single level always count as 1.0.
+    pub margin_histogram: [usize; MARGIN_HISTOGRAM_BINS],
+    /// Voxels whose relative margin in this dimension is below the threshold
+    /// of the report.
+    pub near_boundary: usize,
+}
 
-        intensity += i_noise;
-        polarization += p_noise;
-        phase += ph_noise;
-        wavelength += w_noise;
+impl DimensionReport {
+    fn new() -> Self {
+        Self { min: f32::INFINITY, max: f32::NEG_INFINITY, margin_histogram: [0; MARGIN_HISTOGRAM_BINS], near_boundary: 0 }
     }
 
-    // Decode Intensity
-    let mut best_i_idx = 0;
-    let mut best_i_dist = f32::MAX;
-    for i in 0..INTENSITY_LEVELS {
-        let level = (i as f32 + 1.0) * 0.25;
-        let dist = (intensity - level).abs();
-        if dist < best_i_dist {
-            best_i_dist = dist;
-            best_i_idx = i;
+    /// Records one received value; returns true if it is near a boundary.
+    fn record(&mut self, value: f32, decision: &LevelDecision, threshold: f32) -> bool {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        let relative = decision.relative_margin();
+        let bin = ((relative * MARGIN_HISTOGRAM_BINS as f32) as usize).min(MARGIN_HISTOGRAM_BINS - 1);
+        self.margin_histogram[bin] += 1;
+
+        let near = relative < threshold;
+        if near {
+            self.near_boundary += 1;
         }
+        near
     }
+}
+
+/// Diagnostics gathered while decoding a batch of voxels with
+/// [`decode_data_with_report`]: value ranges, margin histograms and
+/// near-boundary counts for every dimension.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeReport {
+    /// Number of voxels decoded.
+    pub voxels: usize,
+    /// Relative margin below which a decision counts as near a boundary.
+    pub threshold: f32,
+    pub intensity: DimensionReport,
+    pub polarization: DimensionReport,
+    pub phase: DimensionReport,
+    pub wavelength: DimensionReport,
+    /// Voxels near a boundary in at least one dimension.
+    pub near_boundary: usize,
+}
 
-    // Decode Polarization
-    let mut best_p_idx = 0;
-    let mut best_p_dist = f32::MAX;
-    for i in 0..POLARIZATION_LEVELS {
-        let angle = (i as f32) * (PI / 4.0);
-        let mut dist = (polarization - angle).abs();
-        if dist > PI / 2.0 {
-             dist = PI - dist;
+impl DecodeReport {
+    /// Empty report counting margins below `threshold` (in [0, 1]) as near a boundary.
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            voxels: 0,
+            threshold,
+            intensity: DimensionReport::new(),
+            polarization: DimensionReport::new(),
+            phase: DimensionReport::new(),
+            wavelength: DimensionReport::new(),
+            near_boundary: 0,
         }
+    }
+
+    /// Fraction of the decoded voxels that were near a boundary in at least
+    /// one dimension (0.0 for an empty batch).
+    pub fn near_boundary_fraction(&self) -> f64 {
+        self.near_boundary as f64 / self.voxels.max(1) as f64
+    }
 
-        if dist < best_p_dist {
-            best_p_dist = dist;
-            best_p_idx = i;
+    fn record(&mut self, voxel: &PhotonicVoxel, decisions: &[LevelDecision; 4]) {
+        let threshold = self.threshold;
+        // Record every dimension, do not short-circuit
+        let near = [
+            self.intensity.record(voxel.intensity, &decisions[0], threshold),
+            self.polarization.record(voxel.polarization, &decisions[1], threshold),
+            self.phase.record(voxel.phase, &decisions[2], threshold),
+            self.wavelength.record(voxel.wavelength, &decisions[3], threshold),
+        ];
+
+        self.voxels += 1;
+        if near.contains(&true) {
+            self.near_boundary += 1;
         }
     }
+}
 
-    // Decode Phase
-    let mut best_ph_idx = 0;
-    let mut best_ph_dist = f32::MAX;
-    for i in 0..PHASE_LEVELS {
-        let angle = (i as f32) * (PI / 2.0);
-        let mut dist = (phase - angle).abs();
-        if dist > PI {
-            dist = (2.0 * PI) - dist;
+/// Decodes voxels like [`decode_data_with_config`] and also returns a
+/// [`DecodeReport`] on the batch, counting decisions with a relative margin
+/// below `threshold` as near a decision boundary.
+///
+/// Panics if `config` is invalid (see [`CodecConfig::validate`]).
+pub fn decode_data_with_report(voxels: &[PhotonicVoxel], simulate_noise: bool, config: &CodecConfig, threshold: f32) -> (Vec<u8>, DecodeReport) {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+
+    let mut symbols = Vec::with_capacity(voxels.len());
+    let mut report = DecodeReport::new(threshold);
+    for &voxel in voxels {
+        let received = if simulate_noise { add_readout_noise(voxel) } else { voxel };
+        let decisions = config.decide(&received);
+        symbols.push(config.assemble_symbol(decisions.map(|d| d.level)));
+        report.record(&received, &decisions);
+    }
+
+    (unpack_symbols(&symbols, config.bits_per_voxel()), report)
+}
+
+/// Adds the simulated readout noise to a single voxel.
+fn add_readout_noise(voxel: PhotonicVoxel) -> PhotonicVoxel {
+    NoiseProfile::default().perturb(&voxel)
+}
+
+/// Returns the scheme's symbol width, panicking if it is outside 1..=32 bits.
+fn checked_symbol_width<S: ModulationScheme + ?Sized>(scheme: &S) -> usize {
+    let bits = scheme.bits_per_voxel();
+    if bits == 0 || bits > 32 {
+        panic!("Modulation scheme must carry 1..=32 bits per voxel (got {})", bits);
+    }
+    bits
+}
+
+/// Streaming encoder: reads bytes from `R` and yields voxels one at a time.
+///
+/// Produces exactly the same voxels as [`encode_with_scheme`] on the whole input,
+/// but only keeps a small read buffer in memory, so arbitrarily large inputs can
+/// be encoded. Reads are buffered internally, so `R` does not need to be a
+/// `BufReader`.
+pub struct VoxelEncoder<R: Read, S: ModulationScheme = CodecConfig> {
+    reader: R,
+    scheme: S,
+    bits: usize,
+    buffer: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    acc: u64,
+    acc_bits: usize,
+    eof: bool,
+}
+
+impl<R: Read> VoxelEncoder<R> {
+    /// Encoder using the default 8-bit scheme.
+    pub fn new(reader: R) -> Self {
+        Self::with_scheme(reader, CodecConfig::default())
+    }
+
+    /// Encoder using `config`. Panics if `config` is invalid.
+    pub fn with_config(reader: R, config: CodecConfig) -> Self {
+        if let Err(e) = config.validate() {
+            panic!("Invalid codec config: {}", e);
+        }
+        Self::with_scheme(reader, config)
+    }
+}
+
+impl<R: Read, S: ModulationScheme> VoxelEncoder<R, S> {
+    /// Encoder using an arbitrary modulation scheme.
+    pub fn with_scheme(reader: R, scheme: S) -> Self {
+        let bits = checked_symbol_width(&scheme);
+        Self {
+            reader,
+            scheme,
+            bits,
+            buffer: vec![0; 8192],
+            pos: 0,
+            filled: 0,
+            acc: 0,
+            acc_bits: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read, S: ModulationScheme> Iterator for VoxelEncoder<R, S> {
+    type Item = io::Result<PhotonicVoxel>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.acc_bits >= self.bits {
+                let symbol = (self.acc & ((1u64 << self.bits) - 1)) as u32;
+                self.acc >>= self.bits;
+                self.acc_bits -= self.bits;
+                return Some(Ok(self.scheme.modulate(symbol)));
+            }
+
+            if self.eof {
+                if self.acc_bits == 0 {
+                    return None;
+                }
+                // Zero-padded final symbol
+                let symbol = self.acc as u32;
+                self.acc = 0;
+                self.acc_bits = 0;
+                return Some(Ok(self.scheme.modulate(symbol)));
+            }
+
+            if self.pos == self.filled {
+                match self.reader.read(&mut self.buffer) {
+                    Ok(0) => self.eof = true,
+                    Ok(n) => {
+                        self.pos = 0;
+                        self.filled = n;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Some(Err(e)),
+                }
+                continue;
+            }
+
+            self.acc |= (self.buffer[self.pos] as u64) << self.acc_bits;
+            self.acc_bits += 8;
+            self.pos += 1;
         }
+    }
+}
 
-        if dist < best_ph_dist {
-            best_ph_dist = dist;
-            best_ph_idx = i;
+/// Streaming decoder: consumes voxels from any iterator and yields decoded bytes.
+///
+/// The streaming counterpart of [`decode_data_with_config`]; trailing bits that
+/// do not fill a whole byte are dropped.
+pub struct VoxelDecoder<I: Iterator<Item = PhotonicVoxel>, S: ModulationScheme = Quantizer> {
+    voxels: I,
+    scheme: S,
+    bits: usize,
+    simulate_noise: bool,
+    acc: u64,
+    acc_bits: usize,
+}
+
+impl<I: Iterator<Item = PhotonicVoxel>> VoxelDecoder<I> {
+    /// Decoder for the default 8-bit scheme. Simulates readout noise if
+    /// `simulate_noise` is true.
+    pub fn new(voxels: impl IntoIterator<IntoIter = I>, simulate_noise: bool) -> Self {
+        Self::with_config(voxels, CodecConfig::default(), simulate_noise)
+    }
+
+    /// Decoder for voxels written with `config`. Panics if `config` is invalid.
+    pub fn with_config(voxels: impl IntoIterator<IntoIter = I>, config: CodecConfig, simulate_noise: bool) -> Self {
+        if let Err(e) = config.validate() {
+            panic!("Invalid codec config: {}", e);
         }
+        Self::with_scheme(voxels, Quantizer::new(&config), simulate_noise)
     }
+}
 
-    // Decode Wavelength
-    let mut best_w_idx = 0;
-    let mut best_w_dist = f32::MAX;
-    for (i, &target) in WAVELENGTHS.iter().enumerate() {
-        let dist = (wavelength - target).abs();
-        if dist < best_w_dist {
-            best_w_dist = dist;
-            best_w_idx = i;
+impl<I: Iterator<Item = PhotonicVoxel>, S: ModulationScheme> VoxelDecoder<I, S> {
+    /// Decoder for an arbitrary modulation scheme.
+    pub fn with_scheme(voxels: impl IntoIterator<IntoIter = I>, scheme: S, simulate_noise: bool) -> Self {
+        let bits = checked_symbol_width(&scheme);
+        Self {
+            voxels: voxels.into_iter(),
+            scheme,
+            bits,
+            simulate_noise,
+            acc: 0,
+            acc_bits: 0,
         }
     }
+}
+
+impl<I: Iterator<Item = PhotonicVoxel>, S: ModulationScheme> Iterator for VoxelDecoder<I, S> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.acc_bits < 8 {
+            let mut voxel = self.voxels.next()?;
+            if self.simulate_noise {
+                voxel = add_readout_noise(voxel);
+            }
+            self.acc |= (self.scheme.demodulate(&voxel) as u64) << self.acc_bits;
+            self.acc_bits += self.bits;
+        }
 
-    let i_bits = best_i_idx as u8;
-    let p_bits = best_p_idx as u8;
-    let ph_bits = best_ph_idx as u8;
-    let w_bits = best_w_idx as u8;
+        let byte = self.acc as u8;
+        self.acc >>= 8;
+        self.acc_bits -= 8;
+        Some(byte)
+    }
+}
 
-    // Reassemble: w_bits (6,7) | ph_bits (4,5) | p_bits (2,3) | i_bits (0,1)
-    (w_bits << 6) | (ph_bits << 4) | (p_bits << 2) | i_bits
+/// Soft decision for one decoded byte.
+///
+/// Each bit carries a log-likelihood ratio `ln(P(bit = 0) / P(bit = 1))`:
+/// positive values favour 0, negative values favour 1, and the magnitude is the
+/// confidence of the decision. Values near zero sit on a decision boundary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftByte {
+    /// Per-bit LLRs, index 0 is the least significant bit.
+    pub llrs: [f32; 8],
+}
+
+impl SoftByte {
+    /// The byte obtained by hard-slicing every LLR.
+    pub fn hard_decision(&self) -> u8 {
+        self.llrs
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (bit, &llr)| if llr < 0.0 { byte | (1 << bit) } else { byte })
+    }
+
+    /// Confidence of the least reliable bit (smallest `|LLR|`).
+    pub fn min_confidence(&self) -> f32 {
+        self.llrs.iter().fold(f32::MAX, |min, llr| min.min(llr.abs()))
+    }
+}
+
+/// Decodes voxels into per-bit log-likelihood ratios instead of hard bytes.
+///
+/// Intended for soft-input FEC decoders and for studying decision margins near
+/// the noise threshold. See [`decode_soft_with_config`].
+pub fn decode_soft(voxels: &[PhotonicVoxel]) -> Vec<SoftByte> {
+    decode_soft_with_config(voxels, &CodecConfig::default())
+}
+
+/// Soft-decision counterpart of [`decode_data_with_config`].
+///
+/// See [`CodecConfig`]'s `demodulate_soft` for the LLR model.
+/// No noise is added; the voxels are taken as received.
+pub fn decode_soft_with_config(voxels: &[PhotonicVoxel], config: &CodecConfig) -> Vec<SoftByte> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+
+    decode_soft_with_scheme(voxels, config)
+}
+
+/// [`decode_soft_with_config`] with LLRs scaled for a channel with the noise
+/// of `profile` (e.g. measured during calibration) instead of the default
+/// readout noise, so that confidences match the actual channel.
+pub fn decode_soft_with_profile(voxels: &[PhotonicVoxel], config: &CodecConfig, profile: &NoiseProfile) -> Vec<SoftByte> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+
+    decode_soft_with_scheme(voxels, &NoisyChannel { config, noise: profile })
+}
+
+/// A codec whose soft decisions assume the noise of a given profile.
+struct NoisyChannel<'a> {
+    config: &'a CodecConfig,
+    noise: &'a NoiseProfile,
+}
+
+impl ModulationScheme for NoisyChannel<'_> {
+    fn bits_per_voxel(&self) -> usize {
+        self.config.bits_per_voxel()
+    }
+
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        self.config.modulate(symbol)
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        self.config.demodulate(voxel)
+    }
+
+    fn demodulate_soft(&self, voxel: &PhotonicVoxel, llrs: &mut Vec<f32>) {
+        self.config.demodulate_soft_with_noise(voxel, self.noise, llrs);
+    }
+}
+
+/// Soft-decision decoding with an arbitrary [`ModulationScheme`].
+pub fn decode_soft_with_scheme<S: ModulationScheme + ?Sized>(voxels: &[PhotonicVoxel], scheme: &S) -> Vec<SoftByte> {
+    let bits = checked_symbol_width(scheme);
+
+    let mut bit_llrs = Vec::with_capacity(voxels.len() * bits);
+    for voxel in voxels {
+        scheme.demodulate_soft(voxel, &mut bit_llrs);
+    }
+
+    // Regroup the bit stream into bytes, dropping trailing padding bits
+    bit_llrs
+        .chunks_exact(8)
+        .map(|chunk| {
+            let mut llrs = [0.0; 8];
+            llrs.copy_from_slice(chunk);
+            SoftByte { llrs }
+        })
+        .collect()
+}
+
+/// Appends the max-log LLRs of the `log2(levels)` bits of one dimension to `out`.
+///
+/// `distance(i)` is the distance from the received value to level `i` and
+/// `bits(i)` the bit pattern carried by level `i`.
+fn dimension_llrs(levels: usize, distance: impl Fn(usize) -> f32, bits: impl Fn(usize) -> usize, sigma: f32, out: &mut Vec<f32>) {
+    let width = level_bits(levels);
+    let mut min_d0 = [f32::MAX; 32];
+    let mut min_d1 = [f32::MAX; 32];
+
+    for i in 0..levels {
+        let d = distance(i);
+        let d2 = d * d;
+        let pattern = bits(i);
+        for bit in 0..width {
+            if (pattern >> bit) & 1 == 0 {
+                min_d0[bit] = min_d0[bit].min(d2);
+            } else {
+                min_d1[bit] = min_d1[bit].min(d2);
+            }
+        }
+    }
+
+    let scale = 1.0 / (2.0 * sigma * sigma);
+    out.extend((0..width).map(|bit| (min_d1[bit] - min_d0[bit]) * scale));
+}
+
+/// Outcome of a nearest-level search in one dimension.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LevelDecision {
+    /// Index of the closest level.
+    pub(crate) level: usize,
+    /// Distance to the closest level.
+    nearest: f32,
+    /// Distance to the second-closest level (infinite with a single level).
+    runner_up: f32,
+}
+
+impl LevelDecision {
+    /// Margin relative to the distance between the two competing levels: 0.0
+    /// on a decision boundary, 1.0 exactly on a level or with a single level.
+    fn relative_margin(&self) -> f32 {
+        if self.runner_up.is_finite() {
+            (self.runner_up - self.nearest) / (self.runner_up + self.nearest).max(f32::MIN_POSITIVE)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Returns the index in `0..levels` with the smallest `distance`, along with
+/// the distances to the closest and second-closest levels.
+fn nearest_level(levels: usize, distance: impl Fn(usize) -> f32) -> LevelDecision {
+    let mut decision = LevelDecision { level: 0, nearest: f32::INFINITY, runner_up: f32::INFINITY };
+    for i in 0..levels {
+        let dist = distance(i);
+        if dist < decision.nearest {
+            decision.runner_up = decision.nearest;
+            decision.nearest = dist;
+            decision.level = i;
+        } else if dist < decision.runner_up {
+            decision.runner_up = dist;
+        }
+    }
+    decision
+}
+
+/// Distance between two angles on a circle of circumference `period`.
+pub(crate) fn circular_distance(value: f32, target: f32, period: f32) -> f32 {
+    let dist = (value - target).rem_euclid(period);
+    dist.min(period - dist)
+}
+
+/// Extracts `width` bits of `symbol` starting at bit `offset`.
+fn take_bits(symbol: u32, offset: usize, width: usize) -> usize {
+    ((symbol >> offset) & ((1u32 << width) - 1)) as usize
+}
+
+/// Cuts a byte stream into `bits`-wide symbols, least significant bit first.
+pub(crate) fn pack_symbols(data: &[u8], bits: usize) -> Vec<u32> {
+    let total_bits = data.len() * 8;
+    let mut symbols = Vec::with_capacity(total_bits.div_ceil(bits));
+
+    let mut acc: u64 = 0;
+    let mut acc_bits = 0;
+    for &byte in data {
+        acc |= (byte as u64) << acc_bits;
+        acc_bits += 8;
+        while acc_bits >= bits {
+            symbols.push((acc & ((1u64 << bits) - 1)) as u32);
+            acc >>= bits;
+            acc_bits -= bits;
+        }
+    }
+    if acc_bits > 0 {
+        // Zero-padded final symbol
+        symbols.push(acc as u32);
+    }
+
+    symbols
+}
+
+/// Reassembles `bits`-wide symbols into bytes. Trailing bits that do not fill a
+/// whole byte are dropped.
+pub(crate) fn unpack_symbols(symbols: &[u32], bits: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(symbols.len() * bits / 8);
+
+    let mut acc: u64 = 0;
+    let mut acc_bits = 0;
+    for &symbol in symbols {
+        acc |= (symbol as u64) << acc_bits;
+        acc_bits += bits;
+        while acc_bits >= 8 {
+            data.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    }
+
+    data
 }
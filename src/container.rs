@@ -0,0 +1,1085 @@
+use crate::structs::{LatticeShape, PhotonicVoxel, VoxelLattice};
+use crate::codec::{decode_data_with_config, encode_data_with_config, unpack_symbols, CodecConfig, ModulationScheme};
+use crate::ecc::{add_error_correction_with, correct_errors_and_erasures_with, recover_error_correction_with, EccConfig, EccScheme};
+use crate::scrambler::{descramble, scramble};
+use crate::serialization::{voxel_from_bytes, voxel_to_bytes, voxels_from_bytes, voxels_to_bytes};
+use crate::serialization::VOXEL_RECORD_LEN;
+use crate::stream::read_voxel;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First bytes of every `.pvox` container.
+pub const CONTAINER_MAGIC: [u8; 4] = *b"PVOX";
+
+/// Version of the container layout written by this crate. Readers refuse
+/// containers from newer versions; version 1 containers (one session, no
+/// session table), version 2 containers (no file metadata), version 3
+/// containers (no sparse payloads), version 4 containers (ECC over the
+/// whole session, no block size) and version 5 containers (no scrambler) are
+/// still read.
+pub const CONTAINER_VERSION: u16 = 6;
+
+/// Voxels per checksummed chunk of a new container.
+pub const DEFAULT_CHUNK_VOXELS: usize = 4096;
+
+/// Length of the BLAKE3 digest at the end of a container.
+pub const DIGEST_LEN: usize = 32;
+
+const FLAG_GRAY_CODE: u8 = 1;
+const FLAG_ZSTD: u8 = 2;
+const FLAG_METADATA: u8 = 4;
+const FLAG_SPARSE: u8 = 8;
+const FLAG_SCRAMBLED: u8 = 16;
+
+/// ECC scheme id in the header for no ECC; the others are [`EccScheme`] ids.
+const ECC_NONE: u8 = 0;
+
+/// zstd level used for compressed payloads.
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Everything a reader needs to decode the payload of a container.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContainerHeader {
+    /// Layout version the container was written with.
+    pub version: u16,
+    /// Codec of the payload voxels.
+    pub codec: CodecConfig,
+    /// Error correction applied before modulation, if any.
+    pub ecc: Option<EccConfig>,
+    /// Shape of the lattice the payload voxels fill, if they are laid out as
+    /// one (see [`Container::with_geometry`]).
+    pub geometry: Option<LatticeShape>,
+    /// Length of the original data in bytes, before ECC and symbol padding.
+    pub data_len: u64,
+    /// Voxels per checksummed chunk of the payload.
+    pub chunk_voxels: usize,
+    /// Store the voxel records zstd-compressed. Writing and reading such
+    /// containers needs the `zstd` feature.
+    pub compressed: bool,
+    /// The file the data came from, if it came from one.
+    pub metadata: Option<FileMetadata>,
+    /// Store runs of this voxel as run lengths instead of records, for
+    /// mostly blank payloads (e.g. lattices with a few written layers).
+    pub sparse: Option<PhotonicVoxel>,
+    /// Seed of the [scrambler](crate::scrambler) that whitened the encoded
+    /// bytes of every session (after ECC, before modulation), if any.
+    pub scramble: Option<u32>,
+}
+
+impl ContainerHeader {
+    /// Header for `data_len` bytes written by this version with `codec`.
+    pub fn new(codec: &CodecConfig, data_len: usize) -> Self {
+        Self { version: CONTAINER_VERSION, codec: codec.clone(), ecc: None, geometry: None, data_len: data_len as u64, chunk_voxels: DEFAULT_CHUNK_VOXELS, compressed: false, metadata: None, sparse: None, scramble: None }
+    }
+}
+
+/// Description of the file a container was encoded from, so it can be
+/// restored under its own name and described without decoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMetadata {
+    /// File name, without directories.
+    pub name: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Last modification time, if known and not before the Unix epoch.
+    pub modified: Option<SystemTime>,
+    /// MIME type of the contents, if known.
+    pub mime: Option<String>,
+}
+
+impl FileMetadata {
+    /// Name, size and modification time of the file at `path`.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let metadata = path.metadata()?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} has no file name", path)))?
+            .to_string_lossy()
+            .into_owned();
+        let modified = metadata.modified().ok().filter(|time| *time >= UNIX_EPOCH);
+        Ok(Self { name, size: metadata.len(), modified, mime: None })
+    }
+}
+
+/// One write session of a container: data encoded in one go, with its own
+/// ECC codewords and chunks (see [`Container::append`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Session {
+    /// Length of the data written in the session, in bytes.
+    pub data_len: u64,
+    /// Voxels of the session, padding included.
+    pub voxel_count: usize,
+}
+
+/// A `.pvox` voxel container: a versioned header, the payload voxels split
+/// into checksummed chunks, and a BLAKE3 digest of the whole file.
+///
+/// Layout (all integers little-endian): magic, version (`u16`), the log2 of
+/// the intensity, polarization and phase level counts (`u8` each), flags
+/// (`u8`: Gray code, zstd, metadata, sparse, scrambled), palette length (`u16`) and palette (`f32`s), ECC
+/// scheme (`u8`: 0 none, 1 Reed-Solomon, 2 SECDED), data and parity shards (`u8` each),
+/// bytes per shard of an ECC block (`u32`, zero for one block), lattice width, height
+/// and depth (`u32` each, zero for none), data length (`u64`), voxels per chunk (`u32`),
+/// voxel count (`u64`), the session table (session count `u32`, then the data
+/// length and voxel count of every session, `u64` each), the file metadata if
+/// flagged (name length `u16` and UTF-8 name, size `u64`, a `u8` that is 1 if
+/// the modification time is known, its Unix seconds `u64` and nanoseconds
+/// `u32`, MIME type length `u8` and MIME type, empty for none), the blank
+/// voxel record of a sparse payload, the scrambler seed (`u32`) if flagged,
+/// one CRC-32 per chunk
+/// (`u32` each), the voxel records ([`crate::serialization`]) and finally the
+/// BLAKE3 digest of everything before it. Sparse containers store the records
+/// as segments of a blank run length and a literal count (`u32` each)
+/// followed by the literal records; compressed containers store the records
+/// (or segments) as one zstd frame. Both are preceded by the stored length
+/// (`u64`), and chunk checksums always cover the plain records.
+///
+/// Data appended with [`append`](Self::append) forms a new session that
+/// leaves the voxels and chunks of the earlier ones untouched: chunks never
+/// straddle two sessions, and ECC codewords are computed per session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Container {
+    pub header: ContainerHeader,
+    pub voxels: Vec<PhotonicVoxel>,
+    sessions: Vec<Session>,
+    chunk_checksums: Vec<u32>,
+    digest_ok: bool,
+}
+
+impl Container {
+    /// Container of `voxels`, written in one session of `header.data_len`
+    /// bytes, with fresh chunk checksums.
+    ///
+    /// Panics if `header.chunk_voxels` is zero.
+    pub fn new(header: ContainerHeader, voxels: Vec<PhotonicVoxel>) -> Self {
+        assert!(header.chunk_voxels > 0, "Chunks must hold at least one voxel");
+        let chunk_checksums = voxels.chunks(header.chunk_voxels).map(chunk_checksum).collect();
+        let sessions = vec![Session { data_len: header.data_len, voxel_count: voxels.len() }];
+        Self { header, voxels, sessions, chunk_checksums, digest_ok: true }
+    }
+
+    /// Encodes `data` with `codec`, after adding Reed-Solomon parity in the
+    /// default 10+4 layout if `ecc` is true.
+    ///
+    /// Panics if `codec` is invalid.
+    pub fn encode(data: &[u8], codec: &CodecConfig, ecc: bool) -> Self {
+        Self::encode_with_ecc(data, codec, ecc.then(EccConfig::default))
+    }
+
+    /// Like [`encode`](Self::encode), with any Reed-Solomon layout.
+    ///
+    /// Panics if `codec` or `ecc` is invalid.
+    pub fn encode_with_ecc(data: &[u8], codec: &CodecConfig, ecc: Option<EccConfig>) -> Self {
+        let mut header = ContainerHeader::new(codec, data.len());
+        header.ecc = ecc;
+        Self::encode_with_header(data, header)
+    }
+
+    /// Encodes `data` with the codec, ECC and scrambler of `header`, whose
+    /// `data_len` is set to the length of `data`.
+    ///
+    /// Panics if the codec or ECC of `header` is invalid.
+    pub fn encode_with_header(data: &[u8], mut header: ContainerHeader) -> Self {
+        header.data_len = data.len() as u64;
+        let voxels = encode_session(data, &header);
+        Self::new(header, voxels)
+    }
+
+    /// Appends `data` as a new session, encoded with the codec, ECC and
+    /// scrambler of the header: the voxels already written are kept as they are, and the new
+    /// ones get chunks and checksums of their own. With a recorded geometry
+    /// the session is padded to whole layers, which are added to the lattice.
+    pub fn append(&mut self, data: &[u8]) {
+        let mut voxels = encode_session(data, &self.header);
+        if let Some(shape) = &mut self.header.geometry {
+            let layer = shape.width * shape.height;
+            let layers = voxels.len().div_ceil(layer);
+            voxels.resize(layers * layer, self.header.codec.modulate(0));
+            shape.depth += layers;
+        }
+        self.chunk_checksums.extend(voxels.chunks(self.header.chunk_voxels).map(chunk_checksum));
+        self.sessions.push(Session { data_len: data.len() as u64, voxel_count: voxels.len() });
+        self.header.data_len += data.len() as u64;
+        self.voxels.extend(voxels);
+    }
+
+    /// Decodes the payload with the recorded codec, descrambles it if the
+    /// header records a seed, checks and removes the parity described in it, and returns the original `data_len`
+    /// bytes of every session, in order. Simulates readout noise if
+    /// `simulate_noise` is true.
+    ///
+    /// Fails if the ECC check of a session fails.
+    pub fn decode(&self, simulate_noise: bool) -> Result<Vec<u8>, String> {
+        let mut data = Vec::with_capacity(self.header.data_len as usize);
+        for (index, voxels) in self.session_voxels().enumerate() {
+            let mut session = decode_data_with_config(&self.voxels[voxels], simulate_noise, &self.header.codec);
+            let data_len = self.sessions[index].data_len as usize;
+            if let Some(seed) = self.header.scramble {
+                session = descramble(&session, seed);
+            }
+            if let Some(ecc) = self.header.ecc {
+                session = ecc.recover(&session, data_len).map_err(|e| format!("session {}: {}", index, e))?;
+            }
+            session.truncate(data_len);
+            data.extend(session);
+        }
+        Ok(data)
+    }
+
+    /// Lays the payload out in layers of `width` x `height` voxels: pads the
+    /// last layer with the voxel of symbol 0 (the decoder stops at the data
+    /// length, so padding is never decoded) and records the lattice shape.
+    ///
+    /// Panics if the layers are empty.
+    pub fn with_geometry(self, width: usize, height: usize) -> Self {
+        let filler = self.header.codec.modulate(0);
+        let lattice = VoxelLattice::from_voxels_padded(width, height, self.voxels, filler).unwrap_or_else(|e| panic!("{}", e));
+        Self::from_lattice(self.header, lattice)
+    }
+
+    /// Container of the voxels of `lattice`, recording its shape in the header.
+    pub fn from_lattice(mut header: ContainerHeader, lattice: VoxelLattice) -> Self {
+        header.geometry = Some(lattice.shape());
+        Self::new(header, lattice.into_voxels())
+    }
+
+    /// The payload arranged as the recorded lattice, e.g. for
+    /// [`simulate_crosstalk`](crate::simulate_crosstalk). `None` if the
+    /// container records no geometry.
+    pub fn lattice(&self) -> Option<VoxelLattice> {
+        let shape = self.header.geometry?;
+        VoxelLattice::from_voxels(shape.width, shape.height, self.voxels.clone()).ok().filter(|lattice| lattice.shape() == shape)
+    }
+
+    /// Write sessions, in order. A container that was never appended to has one.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// Indices of the voxels of every session, in order.
+    fn session_voxels(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let len = self.voxels.len();
+        session_ranges(&self.sessions).map(move |voxels| voxels.start.min(len)..voxels.end.min(len))
+    }
+
+    /// Number of payload chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_checksums.len()
+    }
+
+    /// Indices of the voxels in chunk `chunk`.
+    pub fn chunk_voxels(&self, chunk: usize) -> Range<usize> {
+        let len = self.voxels.len();
+        chunk_ranges(&self.sessions, self.header.chunk_voxels).nth(chunk).map_or(len..len, |voxels| voxels.start.min(len)..voxels.end.min(len))
+    }
+
+    /// CRC-32 of every chunk, as recorded in the container.
+    pub fn chunk_checksums(&self) -> &[u32] {
+        &self.chunk_checksums
+    }
+
+    /// Chunks whose voxels no longer match their recorded CRC-32.
+    pub fn corrupted_chunks(&self) -> Vec<usize> {
+        let len = self.voxels.len();
+        chunk_ranges(&self.sessions, self.header.chunk_voxels)
+            .zip(&self.chunk_checksums)
+            .enumerate()
+            .filter(|(_, (voxels, &checksum))| chunk_checksum(&self.voxels[voxels.start.min(len)..voxels.end.min(len)]) != checksum)
+            .map(|(chunk, _)| chunk)
+            .collect()
+    }
+
+    /// False if the container was read from a file whose BLAKE3 digest did not
+    /// match, i.e. some part of the file (header included) was altered.
+    pub fn digest_ok(&self) -> bool {
+        self.digest_ok
+    }
+
+    /// True if the file digest and every chunk checksum match.
+    pub fn verify(&self) -> bool {
+        self.digest_ok && self.corrupted_chunks().is_empty()
+    }
+
+    /// Rebuilds the voxels of corrupted chunks: decodes every session with a
+    /// corrupted chunk, corrects it with the recorded ECC (the bytes of the
+    /// corrupted chunks as erasures), re-encodes it and takes the new voxels
+    /// of every corrupted chunk that matches its recorded CRC-32 again.
+    /// Chunks that do not are left as they are and reported as unrecoverable.
+    ///
+    /// Without ECC, only chunks whose damage did not change any decoded symbol
+    /// can be rebuilt. Voxels not written by the codec (e.g. physics
+    /// simulations stored with [`from_lattice`](Self::from_lattice)) never
+    /// match their checksums once re-encoded.
+    pub fn repair(&mut self) -> RepairReport {
+        let corrupted = self.corrupted_chunks();
+        let chunks: Vec<Range<usize>> = chunk_ranges(&self.sessions, self.header.chunk_voxels).collect();
+        let sessions: Vec<(Range<usize>, usize)> = session_ranges(&self.sessions).zip(&self.sessions).map(|(voxels, session)| (voxels, session.data_len as usize)).collect();
+        let mut report = RepairReport { chunks: chunks.len(), repaired_chunks: Vec::new(), unrecoverable_chunks: Vec::new(), repaired_voxels: 0, corrected_bytes: 0 };
+
+        for (voxels, data_len) in sessions {
+            let damaged: Vec<usize> = corrupted.iter().copied().filter(|&chunk| voxels.contains(&chunks[chunk].start)).collect();
+            if damaged.is_empty() {
+                continue;
+            }
+            let Some((rebuilt, corrected_bytes)) = self.rebuild_session(voxels.clone(), data_len, damaged.iter().map(|&chunk| chunks[chunk].clone())) else {
+                report.unrecoverable_chunks.extend(damaged);
+                continue;
+            };
+            report.corrected_bytes += corrected_bytes;
+            for chunk in damaged {
+                let range = chunks[chunk].clone();
+                let new = &rebuilt[range.start - voxels.start..range.end - voxels.start];
+                if chunk_checksum(new) != self.chunk_checksums[chunk] {
+                    report.unrecoverable_chunks.push(chunk);
+                    continue;
+                }
+                report.repaired_voxels += self.voxels[range.clone()].iter().zip(new).filter(|(old, new)| old != new).count();
+                self.voxels[range].copy_from_slice(new);
+                report.repaired_chunks.push(chunk);
+            }
+        }
+        report.unrecoverable_chunks.sort_unstable();
+        report
+    }
+
+    /// Voxels of session `voxels` re-encoded from its decoded data, corrected
+    /// with the bytes of the `damaged` chunks flagged as erasures, and the
+    /// number of bytes the ECC changed. `None` if the session is cut short.
+    fn rebuild_session(&self, voxels: Range<usize>, data_len: usize, damaged: impl Iterator<Item = Range<usize>>) -> Option<(Vec<PhotonicVoxel>, usize)> {
+        let codec = &self.header.codec;
+        let bits = codec.bits_per_voxel();
+        let encoded_len = self.header.ecc.map_or(data_len, |ecc| ecc.encoded_len(data_len));
+        let mut bytes = decode_data_with_config(self.voxels.get(voxels.clone())?, false, codec);
+        if bytes.len() < encoded_len {
+            return None;
+        }
+        bytes.truncate(encoded_len);
+        if let Some(seed) = self.header.scramble {
+            bytes = descramble(&bytes, seed);
+        }
+
+        let mut corrected_bytes = 0;
+        if let Some(ecc) = self.header.ecc {
+            // A scrambled bit also flips the two descrambled bits 18 and 23
+            // bits later, up to 3 bytes past the damage
+            let spread = if self.header.scramble.is_some() { 3 } else { 0 };
+            let mut erasures = vec![false; encoded_len];
+            for chunk in damaged {
+                let end = (((chunk.end - voxels.start) * bits).div_ceil(8) + spread).min(encoded_len);
+                erasures[((chunk.start - voxels.start) * bits / 8).min(end)..end].fill(true);
+            }
+            let received = bytes.clone();
+            // Codewords beyond repair keep their bytes: the chunks holding
+            // them then fail their checksums
+            let _ = correct_errors_and_erasures_with(&mut bytes, &erasures, &ecc);
+            corrected_bytes = bytes.iter().zip(&received).filter(|(byte, received)| byte != received).count();
+        }
+
+        if let Some(seed) = self.header.scramble {
+            bytes = scramble(&bytes, seed);
+        }
+        let mut rebuilt = encode_data_with_config(&bytes, codec);
+        rebuilt.resize(voxels.len(), codec.modulate(0));
+        Some((rebuilt, corrected_bytes))
+    }
+}
+
+/// Outcome of [`Container::repair`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Number of payload chunks.
+    pub chunks: usize,
+    /// Corrupted chunks rebuilt to match their CRC-32 again.
+    pub repaired_chunks: Vec<usize>,
+    /// Corrupted chunks that could not be rebuilt.
+    pub unrecoverable_chunks: Vec<usize>,
+    /// Voxels that changed in the repaired chunks.
+    pub repaired_voxels: usize,
+    /// Bytes of the encoded data corrected by the ECC.
+    pub corrected_bytes: usize,
+}
+
+impl RepairReport {
+    /// True if no corrupted chunk is left.
+    pub fn is_complete(&self) -> bool {
+        self.unrecoverable_chunks.is_empty()
+    }
+}
+
+/// Outcome of [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Number of payload chunks.
+    pub chunks: usize,
+    /// Voxels per chunk.
+    pub chunk_voxels: usize,
+    /// Chunks whose CRC-32 did not match.
+    pub corrupted_chunks: Vec<usize>,
+    /// True if the BLAKE3 digest of the file matched.
+    pub digest_ok: bool,
+}
+
+impl IntegrityReport {
+    /// True if nothing in the file was altered.
+    pub fn is_intact(&self) -> bool {
+        self.digest_ok && self.corrupted_chunks.is_empty()
+    }
+}
+
+/// Writes `container` (header, chunk checksums, payload and digest) to `writer`.
+///
+/// Fails with `InvalidInput` if the codec palette, ECC, geometry or chunk size
+/// do not fit the header fields, and with `Unsupported` for a compressed
+/// container without the `zstd` feature.
+pub fn write<W: Write>(mut writer: W, container: &Container) -> io::Result<()> {
+    let header = &container.header;
+    let codec = &header.codec;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&CONTAINER_MAGIC);
+    bytes.extend_from_slice(&CONTAINER_VERSION.to_le_bytes());
+    for levels in [codec.intensity_levels, codec.polarization_levels, codec.phase_levels] {
+        bytes.push(levels.trailing_zeros() as u8);
+    }
+    let mut flags = 0;
+    if codec.gray_code {
+        flags |= FLAG_GRAY_CODE;
+    }
+    if header.compressed {
+        flags |= FLAG_ZSTD;
+    }
+    if header.metadata.is_some() {
+        flags |= FLAG_METADATA;
+    }
+    if header.sparse.is_some() {
+        flags |= FLAG_SPARSE;
+    }
+    if header.scramble.is_some() {
+        flags |= FLAG_SCRAMBLED;
+    }
+    bytes.push(flags);
+    let palette_len = u16::try_from(codec.wavelengths.len()).map_err(|_| invalid("palette too large for the container header"))?;
+    bytes.extend_from_slice(&palette_len.to_le_bytes());
+    for wavelength in &codec.wavelengths {
+        bytes.extend_from_slice(&wavelength.to_le_bytes());
+    }
+
+    let (scheme, data_shards, parity_shards) = match header.ecc {
+        Some(ecc) => (ecc.scheme.id(), ecc.data_shards, ecc.parity_shards),
+        None => (ECC_NONE, 0, 0),
+    };
+    bytes.push(scheme);
+    for shards in [data_shards, parity_shards] {
+        bytes.push(u8::try_from(shards).map_err(|_| invalid("too many ECC shards for the container header"))?);
+    }
+    let block_shard_len = header.ecc.and_then(|ecc| ecc.block_shard_len).unwrap_or(0);
+    let block_shard_len = u32::try_from(block_shard_len).map_err(|_| invalid("ECC blocks too large for the container header"))?;
+    bytes.extend_from_slice(&block_shard_len.to_le_bytes());
+    let shape = header.geometry.unwrap_or(LatticeShape { width: 0, height: 0, depth: 0 });
+    for side in [shape.width, shape.height, shape.depth] {
+        let side = u32::try_from(side).map_err(|_| invalid("lattice too large for the container header"))?;
+        bytes.extend_from_slice(&side.to_le_bytes());
+    }
+    bytes.extend_from_slice(&header.data_len.to_le_bytes());
+    let chunk_voxels = u32::try_from(header.chunk_voxels).map_err(|_| invalid("chunks too large for the container header"))?;
+    bytes.extend_from_slice(&chunk_voxels.to_le_bytes());
+    bytes.extend_from_slice(&(container.voxels.len() as u64).to_le_bytes());
+    let sessions = &container.sessions;
+    if sessions.iter().map(|session| session.voxel_count).sum::<usize>() != container.voxels.len()
+        || sessions.iter().map(|session| session.data_len).sum::<u64>() != header.data_len
+    {
+        return Err(invalid("session table does not match the payload"));
+    }
+    let session_count = u32::try_from(sessions.len()).map_err(|_| invalid("too many sessions for the container header"))?;
+    bytes.extend_from_slice(&session_count.to_le_bytes());
+    for session in sessions {
+        bytes.extend_from_slice(&session.data_len.to_le_bytes());
+        bytes.extend_from_slice(&(session.voxel_count as u64).to_le_bytes());
+    }
+    if let Some(metadata) = &header.metadata {
+        let name_len = u16::try_from(metadata.name.len()).map_err(|_| invalid("file name too long for the container header"))?;
+        bytes.extend_from_slice(&name_len.to_le_bytes());
+        bytes.extend_from_slice(metadata.name.as_bytes());
+        bytes.extend_from_slice(&metadata.size.to_le_bytes());
+        let since_epoch = metadata.modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        bytes.push(since_epoch.is_some() as u8);
+        let since_epoch = since_epoch.unwrap_or_default();
+        bytes.extend_from_slice(&since_epoch.as_secs().to_le_bytes());
+        bytes.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+        let mime = metadata.mime.as_deref().unwrap_or("");
+        bytes.push(u8::try_from(mime.len()).map_err(|_| invalid("MIME type too long for the container header"))?);
+        bytes.extend_from_slice(mime.as_bytes());
+    }
+    if let Some(blank) = &header.sparse {
+        bytes.extend_from_slice(&voxel_to_bytes(blank));
+    }
+    if let Some(seed) = header.scramble {
+        bytes.extend_from_slice(&seed.to_le_bytes());
+    }
+    for checksum in &container.chunk_checksums {
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+    }
+    let mut payload = match &header.sparse {
+        Some(blank) => sparse_segments(&container.voxels, blank),
+        None => voxels_to_bytes(&container.voxels),
+    };
+    if header.compressed {
+        payload = compress(&payload)?;
+    }
+    if header.compressed || header.sparse.is_some() {
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&bytes);
+    hasher.update(&payload);
+    writer.write_all(&bytes)?;
+    writer.write_all(&payload)?;
+    writer.write_all(hasher.finalize().as_bytes())?;
+    writer.flush()
+}
+
+/// Reads a container written by [`write`].
+///
+/// Fails with `InvalidData` on a bad magic, a version newer than
+/// [`CONTAINER_VERSION`], an invalid header or a truncated file. Checksums do
+/// not make it fail: see [`Container::corrupted_chunks`] and [`Container::digest_ok`].
+pub fn read<R: Read>(reader: R) -> io::Result<Container> {
+    let mut reader = HashingReader::new(reader);
+    let layout = read_layout(&mut reader)?;
+
+    let mut voxels = Vec::new();
+    with_payload(&mut reader, &layout, |payload| {
+        for _ in 0..layout.voxel_count {
+            match read_voxel(payload)? {
+                Some(voxel) => voxels.push(voxel),
+                None => return Err(invalid_data(format!("truncated payload ({} of {} voxels)", voxels.len(), layout.voxel_count))),
+            }
+        }
+        Ok(())
+    })?;
+    let digest_ok = reader.finish()?;
+
+    Ok(Container { header: layout.header, voxels, sessions: layout.sessions, chunk_checksums: layout.chunk_checksums, digest_ok })
+}
+
+/// Checks the chunk checksums and the file digest of a container without
+/// decoding its voxels, streaming through the file once (and through the
+/// decompressed records of a compressed container).
+///
+/// Fails like [`read`] if the file cannot be parsed at all.
+pub fn verify<R: Read>(reader: R) -> io::Result<IntegrityReport> {
+    let mut reader = HashingReader::new(reader);
+    let layout = read_layout(&mut reader)?;
+    let chunk_voxels = layout.header.chunk_voxels;
+
+    let mut corrupted_chunks = Vec::new();
+    with_payload(&mut reader, &layout, |payload| {
+        let mut chunk = Vec::with_capacity(chunk_voxels * VOXEL_RECORD_LEN);
+        for (index, (voxels, &checksum)) in chunk_ranges(&layout.sessions, chunk_voxels).zip(&layout.chunk_checksums).enumerate() {
+            chunk.resize(voxels.len() * VOXEL_RECORD_LEN, 0);
+            payload.read_exact(&mut chunk)?;
+            if crc32fast::hash(&chunk) != checksum {
+                corrupted_chunks.push(index);
+            }
+        }
+        Ok(())
+    })?;
+    let digest_ok = reader.finish()?;
+
+    Ok(IntegrityReport { chunks: layout.chunk_checksums.len(), chunk_voxels, corrupted_chunks, digest_ok })
+}
+
+/// Header of a container as found by [`inspect`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerInfo {
+    pub header: ContainerHeader,
+    /// Number of payload voxels.
+    pub voxel_count: usize,
+    /// Write sessions, in order.
+    pub sessions: Vec<Session>,
+    /// Number of checksummed chunks.
+    pub chunks: usize,
+}
+
+/// Reads what the header of a container says about its contents (codec,
+/// ECC, geometry, sessions, file metadata) without reading the payload.
+///
+/// Fails like [`read`] if the header cannot be parsed.
+pub fn inspect<R: Read>(mut reader: R) -> io::Result<ContainerInfo> {
+    let layout = read_layout(&mut reader)?;
+    Ok(ContainerInfo { header: layout.header, voxel_count: layout.voxel_count, sessions: layout.sessions, chunks: layout.chunk_checksums.len() })
+}
+
+/// Reads `len` bytes of the original data starting at `byte_offset`, decoding
+/// only the voxels that hold them (and, with ECC, the parity bytes of the same
+/// Reed-Solomon columns, which are checked). Compressed, sparse and scrambled
+/// containers cannot be entered in the middle and are decoded in full.
+///
+/// Fails with `InvalidInput` if the range goes past the end of the data and
+/// with `InvalidData` if the ECC check of the range fails.
+pub fn read_range<R: Read + Seek>(mut reader: R, byte_offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let start = reader.stream_position()?;
+    let layout = read_layout(&mut reader)?;
+    let header = &layout.header;
+
+    let end = byte_offset + len as u64;
+    if end > header.data_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("bytes {}..{} are past the end of the data ({} bytes)", byte_offset, end, header.data_len),
+        ));
+    }
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+
+    if header.compressed || header.sparse.is_some() || header.scramble.is_some() {
+        reader.seek(SeekFrom::Start(start))?;
+        let data = read(reader)?.decode(false).map_err(invalid_data)?;
+        return Ok(data[byte_offset as usize..end as usize].to_vec());
+    }
+
+    // Each session is encoded on its own: read its share of the range
+    let payload_start = reader.stream_position()?;
+    let mut data = Vec::with_capacity(len);
+    let mut session_start = 0;
+    for (session, voxels) in layout.sessions.iter().zip(session_ranges(&layout.sessions)) {
+        let session_end = session_start + session.data_len;
+        let range = byte_offset.max(session_start) - session_start..end.min(session_end).saturating_sub(session_start);
+        if range.start < range.end {
+            let start = payload_start + (voxels.start * VOXEL_RECORD_LEN) as u64;
+            let mut payload = PayloadReader { reader: &mut reader, start, codec: &header.codec, voxel_count: voxels.len() };
+            data.extend(match header.ecc {
+                None => payload.read_bytes(range)?,
+                Some(ecc) => read_ecc_range(payload, ecc, session.data_len, range)?,
+            });
+        }
+        session_start = session_end;
+    }
+    Ok(data)
+}
+
+/// True if `bytes` start with the container magic.
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(&CONTAINER_MAGIC)
+}
+
+/// Everything in front of the voxel records.
+struct Layout {
+    header: ContainerHeader,
+    voxel_count: usize,
+    sessions: Vec<Session>,
+    chunk_checksums: Vec<u32>,
+    /// Length of the stored payload of a compressed or sparse container.
+    stored_len: Option<u64>,
+}
+
+/// Runs `body` on a reader of the uncompressed voxel records that follow the
+/// layout, then skips whatever of the stored payload `body` left unread.
+fn with_payload<R: Read>(reader: &mut R, layout: &Layout, body: impl FnOnce(&mut dyn Read) -> io::Result<()>) -> io::Result<()> {
+    let stored_len = match layout.stored_len {
+        Some(len) => len,
+        None => layout.voxel_count.checked_mul(VOXEL_RECORD_LEN).ok_or_else(|| invalid_data("voxel count in container header overflows".to_string()))? as u64,
+    };
+    let mut stored = reader.take(stored_len);
+    {
+        let mut payload: Box<dyn Read + '_> = Box::new(&mut stored);
+        #[cfg(feature = "zstd")]
+        if layout.header.compressed {
+            payload = Box::new(zstd::stream::read::Decoder::new(payload)?);
+        }
+        if let Some(blank) = &layout.header.sparse {
+            payload = Box::new(SparseReader { inner: payload, blank: voxel_to_bytes(blank), blank_bytes: 0, literal_bytes: 0 });
+        }
+        body(&mut payload)?;
+    }
+    io::copy(&mut stored, &mut io::sink())?;
+    if stored.limit() > 0 {
+        return Err(invalid_data("truncated payload".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn compress(records: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::stream::encode_all(records, ZSTD_LEVEL)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress(_records: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "compressed containers need the `zstd` feature"))
+}
+
+fn read_layout<R: Read>(reader: &mut R) -> io::Result<Layout> {
+    let magic: [u8; 4] = read_array(reader)?;
+    if magic != CONTAINER_MAGIC {
+        return Err(invalid_data("not a .pvox container (bad magic)".to_string()));
+    }
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version == 0 || version > CONTAINER_VERSION {
+        return Err(invalid_data(format!("unsupported container version {} (this build reads up to {})", version, CONTAINER_VERSION)));
+    }
+
+    let [intensity, polarization, phase, flags]: [u8; 4] = read_array(reader)?;
+    let compressed = flags & FLAG_ZSTD != 0;
+    if compressed && !cfg!(feature = "zstd") {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "compressed container: rebuild with the `zstd` feature"));
+    }
+    if [intensity, polarization, phase].iter().any(|&bits| bits as u32 >= usize::BITS) {
+        return Err(invalid_data("invalid level count in container header".to_string()));
+    }
+    let palette_len = u16::from_le_bytes(read_array(reader)?) as usize;
+    let wavelengths = (0..palette_len)
+        .map(|_| read_array(reader).map(f32::from_le_bytes))
+        .collect::<io::Result<Vec<f32>>>()?;
+    let codec = CodecConfig {
+        intensity_levels: 1 << intensity,
+        polarization_levels: 1 << polarization,
+        phase_levels: 1 << phase,
+        wavelengths,
+        gray_code: flags & FLAG_GRAY_CODE != 0,
+    };
+    codec.validate().map_err(|e| invalid_data(format!("invalid codec config in container header: {}", e)))?;
+
+    let [scheme, data_shards, parity_shards]: [u8; 3] = read_array(reader)?;
+    // Before version 5 the ECC of a session was one block
+    let block_shard_len = if version >= 5 { u32::from_le_bytes(read_array(reader)?) as usize } else { 0 };
+    let ecc = match scheme {
+        ECC_NONE => None,
+        id => {
+            let scheme = EccScheme::from_id(id).ok_or_else(|| invalid_data(format!("unknown ECC scheme id {} in container header", id)))?;
+            let config = EccConfig { scheme, data_shards: data_shards as usize, parity_shards: parity_shards as usize, block_shard_len: (block_shard_len > 0).then_some(block_shard_len) };
+            config.validate().map_err(|e| invalid_data(format!("invalid ECC parameters in container header: {}", e)))?;
+            Some(config)
+        }
+    };
+    let mut side = || read_array(reader).map(|side| u32::from_le_bytes(side) as usize);
+    let shape = LatticeShape { width: side()?, height: side()?, depth: side()? };
+    let geometry = (!shape.is_empty()).then_some(shape);
+    let data_len = u64::from_le_bytes(read_array(reader)?);
+    let chunk_voxels = u32::from_le_bytes(read_array(reader)?) as usize;
+    if chunk_voxels == 0 {
+        return Err(invalid_data("zero voxels per chunk in container header".to_string()));
+    }
+    let voxel_count = u64::from_le_bytes(read_array(reader)?) as usize;
+    if let Some(shape) = geometry.filter(|shape| shape.checked_len() != Some(voxel_count)) {
+        return Err(invalid_data(format!(
+            "{}x{}x{} lattice in container header does not hold its {} voxels",
+            shape.width, shape.height, shape.depth, voxel_count
+        )));
+    }
+    let sessions = if version == 1 {
+        vec![Session { data_len, voxel_count }]
+    } else {
+        let session_count = u32::from_le_bytes(read_array(reader)?);
+        let sessions = (0..session_count)
+            .map(|_| {
+                let data_len = u64::from_le_bytes(read_array(reader)?);
+                let voxel_count = u64::from_le_bytes(read_array(reader)?) as usize;
+                Ok(Session { data_len, voxel_count })
+            })
+            .collect::<io::Result<Vec<Session>>>()?;
+        let voxels = sessions.iter().try_fold(0usize, |sum, session| sum.checked_add(session.voxel_count));
+        let bytes = sessions.iter().try_fold(0u64, |sum, session| sum.checked_add(session.data_len));
+        if voxels != Some(voxel_count) || bytes != Some(data_len) {
+            return Err(invalid_data("session table in container header does not add up to the payload".to_string()));
+        }
+        sessions
+    };
+    // A corrupted length must not size any buffer beyond what the voxels carry
+    for session in &sessions {
+        let capacity = session.voxel_count.checked_mul(codec.bits_per_voxel()).map(|bits| bits / 8);
+        if capacity.is_none_or(|capacity| session.data_len > capacity as u64) {
+            return Err(invalid_data(format!("{} bytes of data in container header do not fit in {} voxels", session.data_len, session.voxel_count)));
+        }
+    }
+    let metadata = if version >= 3 && flags & FLAG_METADATA != 0 { Some(read_metadata(reader)?) } else { None };
+    let sparse = if version >= 4 && flags & FLAG_SPARSE != 0 { Some(voxel_from_bytes(&read_array(reader)?)) } else { None };
+    let scramble = if version >= 6 && flags & FLAG_SCRAMBLED != 0 { Some(u32::from_le_bytes(read_array(reader)?)) } else { None };
+    let chunk_checksums = (0..chunk_ranges(&sessions, chunk_voxels).count())
+        .map(|_| read_array(reader).map(u32::from_le_bytes))
+        .collect::<io::Result<Vec<u32>>>()?;
+
+    let stored_len = if compressed || sparse.is_some() { Some(u64::from_le_bytes(read_array(reader)?)) } else { None };
+
+    let header = ContainerHeader { version, codec, ecc, geometry, data_len, chunk_voxels, compressed, metadata, sparse, scramble };
+    Ok(Layout { header, voxel_count, sessions, chunk_checksums, stored_len })
+}
+
+fn read_metadata<R: Read>(reader: &mut R) -> io::Result<FileMetadata> {
+    fn read_string<R: Read>(reader: &mut R, len: usize, what: &str) -> io::Result<String> {
+        let mut bytes = vec![0u8; len];
+        reader.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| invalid_data(format!("{} in container header is not UTF-8", what)))
+    }
+    let name_len = u16::from_le_bytes(read_array(reader)?) as usize;
+    let name = read_string(reader, name_len, "file name")?;
+    let size = u64::from_le_bytes(read_array(reader)?);
+    let [has_modified]: [u8; 1] = read_array(reader)?;
+    let since_epoch = Duration::new(u64::from_le_bytes(read_array(reader)?), u32::from_le_bytes(read_array(reader)?));
+    let modified = (has_modified != 0).then(|| UNIX_EPOCH.checked_add(since_epoch)).flatten();
+    let [mime_len]: [u8; 1] = read_array(reader)?;
+    let mime = Some(read_string(reader, mime_len as usize, "MIME type")?).filter(|mime| !mime.is_empty());
+    Ok(FileMetadata { name, size, modified, mime })
+}
+
+/// Sparse payload of `voxels`: segments of a run of `blank` voxels and a run
+/// of literal records.
+fn sparse_segments(voxels: &[PhotonicVoxel], blank: &PhotonicVoxel) -> Vec<u8> {
+    // Records are compared bit for bit, so e.g. -0.0 is not taken for 0.0
+    let blank = voxel_to_bytes(blank);
+    let records: Vec<[u8; VOXEL_RECORD_LEN]> = voxels.iter().map(voxel_to_bytes).collect();
+    let run = |records: &[[u8; VOXEL_RECORD_LEN]], is_blank: bool| {
+        records.iter().take(u32::MAX as usize).take_while(|record| (**record == blank) == is_blank).count()
+    };
+
+    let mut segments = Vec::new();
+    let mut rest = &records[..];
+    while !rest.is_empty() {
+        let blanks = run(rest, true);
+        let literals = run(&rest[blanks..], false);
+        segments.extend_from_slice(&(blanks as u32).to_le_bytes());
+        segments.extend_from_slice(&(literals as u32).to_le_bytes());
+        for record in &rest[blanks..blanks + literals] {
+            segments.extend_from_slice(record);
+        }
+        rest = &rest[blanks + literals..];
+    }
+    segments
+}
+
+/// Expands the segments of a sparse payload back into voxel records.
+struct SparseReader<R> {
+    inner: R,
+    blank: [u8; VOXEL_RECORD_LEN],
+    /// Bytes left in the current blank run and literal run.
+    blank_bytes: u64,
+    literal_bytes: u64,
+}
+
+impl<R: Read> Read for SparseReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.blank_bytes == 0 && self.literal_bytes == 0 {
+            let mut segment = [0u8; 8];
+            // The payload ends at a segment boundary
+            let n = self.inner.read(&mut segment)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.inner.read_exact(&mut segment[n..])?;
+            self.blank_bytes = u32::from_le_bytes(segment[..4].try_into().expect("4 bytes")) as u64 * VOXEL_RECORD_LEN as u64;
+            self.literal_bytes = u32::from_le_bytes(segment[4..].try_into().expect("4 bytes")) as u64 * VOXEL_RECORD_LEN as u64;
+        }
+
+        if self.blank_bytes > 0 {
+            let n = buf.len().min(self.blank_bytes.try_into().unwrap_or(usize::MAX));
+            // Runs end on a record boundary, so the remaining length tells where in the record we are
+            let offset = (VOXEL_RECORD_LEN - (self.blank_bytes % VOXEL_RECORD_LEN as u64) as usize) % VOXEL_RECORD_LEN;
+            for (i, byte) in buf[..n].iter_mut().enumerate() {
+                *byte = self.blank[(offset + i) % VOXEL_RECORD_LEN];
+            }
+            self.blank_bytes -= n as u64;
+            return Ok(n);
+        }
+        let n = buf.len().min(self.literal_bytes.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..n])?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated sparse payload"));
+        }
+        self.literal_bytes -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Encodes the data of one session with the codec, ECC and scrambler of `header`.
+fn encode_session(data: &[u8], header: &ContainerHeader) -> Vec<PhotonicVoxel> {
+    let encoded = match &header.ecc {
+        Some(config) => add_error_correction_with(data, config),
+        None => data.to_vec(),
+    };
+    match header.scramble {
+        Some(seed) => encode_data_with_config(&scramble(&encoded, seed), &header.codec),
+        None => encode_data_with_config(&encoded, &header.codec),
+    }
+}
+
+/// Indices of the voxels of every session.
+fn session_ranges(sessions: &[Session]) -> impl Iterator<Item = Range<usize>> + '_ {
+    sessions.iter().scan(0, |start, session| {
+        let voxels = *start..*start + session.voxel_count;
+        *start = voxels.end;
+        Some(voxels)
+    })
+}
+
+/// Indices of the voxels of every chunk: each session is split into chunks of
+/// `chunk_voxels` of its own.
+fn chunk_ranges(sessions: &[Session], chunk_voxels: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+    session_ranges(sessions).flat_map(move |voxels| voxels.clone().step_by(chunk_voxels).map(move |start| start..(start + chunk_voxels).min(voxels.end)))
+}
+
+/// Random access to the encoded byte stream of an uncompressed payload.
+struct PayloadReader<'a, R> {
+    reader: R,
+    /// Offset of the first voxel record.
+    start: u64,
+    codec: &'a CodecConfig,
+    voxel_count: usize,
+}
+
+impl<R: Read + Seek> PayloadReader<'_, R> {
+    /// Reads and decodes the voxels holding the encoded bytes `bytes`.
+    fn read_bytes(&mut self, bytes: Range<u64>) -> io::Result<Vec<u8>> {
+        let bits = self.codec.bits_per_voxel() as u64;
+        let first = bytes.start * 8 / bits;
+        let last = (bytes.end * 8 - 1) / bits;
+        if last >= self.voxel_count as u64 {
+            return Err(invalid_data("payload shorter than its data length".to_string()));
+        }
+
+        self.reader.seek(SeekFrom::Start(self.start + first * VOXEL_RECORD_LEN as u64))?;
+        let mut records = vec![0u8; ((last - first + 1) as usize) * VOXEL_RECORD_LEN];
+        self.reader.read_exact(&mut records)?;
+        let mut symbols: Vec<u32> = voxels_from_bytes(&records).map_err(invalid_data)?.iter().map(|v| self.codec.demodulate(v)).collect();
+        // A zero symbol flushes the bits of the last voxel that do not fill a byte
+        symbols.push(0);
+        let stream = unpack_symbols(&symbols, bits as usize);
+
+        // The first voxel need not start on a byte boundary
+        let skip = (bytes.start * 8 - first * bits) as usize;
+        Ok((0..(bytes.end - bytes.start) as usize).map(|i| bit_aligned_byte(&stream, skip + 8 * i)).collect())
+    }
+}
+
+/// Reads the data bytes `range` of an ECC-protected payload, block by block.
+fn read_ecc_range<R: Read + Seek>(mut payload: PayloadReader<R>, ecc: EccConfig, data_len: u64, range: Range<u64>) -> io::Result<Vec<u8>> {
+    let block_len = ecc.block_len().map_or(data_len, |len| len as u64).max(1);
+    let encoded_block_len = ecc.encoded_len(block_len as usize) as u64;
+
+    let mut data = Vec::with_capacity((range.end - range.start) as usize);
+    let mut start = range.start;
+    while start < range.end {
+        let block_start = start / block_len * block_len;
+        let block_data_len = block_len.min(data_len - block_start);
+        let end = range.end.min(block_start + block_data_len);
+        let encoded_start = start / block_len * encoded_block_len;
+        data.extend(read_ecc_block_range(&mut payload, ecc, encoded_start, block_data_len, start - block_start..end - block_start)?);
+        start = end;
+    }
+    Ok(data)
+}
+
+/// Reads the bytes `range` of the ECC block of `data_len` bytes whose encoded
+/// bytes start at `encoded_start`. The parity of [`add_error_correction_with`]
+/// runs across shards, so the columns (offsets within a shard) holding the
+/// range form a smaller code of their own: only those columns are
+/// read from each of the shards and checked.
+fn read_ecc_block_range<R: Read + Seek>(payload: &mut PayloadReader<R>, ecc: EccConfig, encoded_start: u64, data_len: u64, range: Range<u64>) -> io::Result<Vec<u8>> {
+    let shard_len = data_len.div_ceil(ecc.data_shards as u64);
+
+    // Columns touched by the range, one segment per data shard it crosses, merged
+    let mut columns: Vec<Range<u64>> = (range.start / shard_len..=(range.end - 1) / shard_len)
+        .map(|shard| {
+            let shard_start = shard * shard_len;
+            range.start.max(shard_start) - shard_start..range.end.min(shard_start + shard_len) - shard_start
+        })
+        .collect();
+    columns.sort_by_key(|segment| segment.start);
+    let mut merged: Vec<Range<u64>> = Vec::new();
+    for segment in columns {
+        match merged.last_mut() {
+            Some(last) if segment.start <= last.end => last.end = last.end.max(segment.end),
+            _ => merged.push(segment),
+        }
+    }
+    let width: u64 = merged.iter().map(|segment| segment.end - segment.start).sum();
+
+    let mut shards = Vec::with_capacity((width as usize) * (ecc.data_shards + ecc.parity_shards));
+    for shard in 0..(ecc.data_shards + ecc.parity_shards) as u64 {
+        for segment in &merged {
+            let shard_start = encoded_start + shard * shard_len;
+            shards.extend(payload.read_bytes(shard_start + segment.start..shard_start + segment.end)?);
+        }
+    }
+    // The columns read form one block of their own
+    let columns = EccConfig { block_shard_len: None, ..ecc };
+    let data_shards = recover_error_correction_with(&shards, &columns).map_err(invalid_data)?;
+
+    // Position of original byte `offset` in the recovered column subset
+    let locate = |offset: u64| {
+        let (shard, column) = (offset / shard_len, offset % shard_len);
+        let mut position = shard * width;
+        for segment in &merged {
+            if segment.contains(&column) {
+                return (position + column - segment.start) as usize;
+            }
+            position += segment.end - segment.start;
+        }
+        unreachable!("column {} is outside the columns read", column)
+    };
+    Ok(range.map(|offset| data_shards[locate(offset)]).collect())
+}
+
+/// Byte starting at bit `bit` of an LSB-first bit stream.
+fn bit_aligned_byte(stream: &[u8], bit: usize) -> u8 {
+    let (index, shift) = (bit / 8, bit % 8);
+    if shift == 0 {
+        return stream[index];
+    }
+    (stream[index] >> shift) | (stream[index + 1] << (8 - shift))
+}
+
+/// Reader that hashes everything read through it, to check the file digest.
+struct HashingReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: blake3::Hasher::new() }
+    }
+
+    /// Reads the digest trailer and compares it with the bytes read so far.
+    fn finish(mut self) -> io::Result<bool> {
+        let digest: [u8; DIGEST_LEN] = read_array(&mut self.inner)?;
+        Ok(self.hasher.finalize() == blake3::Hash::from_bytes(digest))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+fn read_array<R: Read + ?Sized, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn chunk_checksum(voxels: &[PhotonicVoxel]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for voxel in voxels {
+        hasher.update(&voxel_to_bytes(voxel));
+    }
+    hasher.finalize()
+}
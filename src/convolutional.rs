@@ -0,0 +1,141 @@
+use crate::codec::SoftByte;
+
+/// Rate-1/2 feedforward convolutional code, decoded with the Viterbi algorithm.
+///
+/// Every input bit shifts into a register of the last `constraint_length`
+/// bits and produces two coded bits, the parities of the register masked by
+/// each generator (bit 0 of a generator taps the newest input bit). The input
+/// is followed by `constraint_length - 1` zero tail bits, so every message
+/// ends in the all-zero state. Unlike block codes it has no block boundaries:
+/// random bit errors anywhere are corrected as long as they are sparse within a
+/// few constraint lengths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConvolutionalCode {
+    /// Input bits each coded bit depends on, the current one included.
+    pub constraint_length: usize,
+    /// Tap masks of the two coded bits.
+    pub generators: [u32; 2],
+}
+
+impl Default for ConvolutionalCode {
+    /// The K = 7 code with generators 171 and 133 (octal) of Voyager, DVB and
+    /// 802.11: free distance 10.
+    fn default() -> Self {
+        Self { constraint_length: 7, generators: [0o171, 0o133] }
+    }
+}
+
+impl ConvolutionalCode {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2..=16).contains(&self.constraint_length) {
+            return Err(format!("Constraint length must be in 2..=16 (got {})", self.constraint_length));
+        }
+        let taps = 1u32 << self.constraint_length;
+        if self.generators.iter().any(|&generator| generator == 0 || generator >= taps) {
+            return Err(format!("Generators must be nonzero and tap at most {} bits (got {:o}, {:o})", self.constraint_length, self.generators[0], self.generators[1]));
+        }
+        Ok(())
+    }
+
+    /// Length of `data_len` bytes once encoded: two coded bits per data and
+    /// tail bit, the last byte zero-padded.
+    pub fn encoded_len(&self, data_len: usize) -> usize {
+        (2 * (8 * data_len + self.constraint_length - 1)).div_ceil(8)
+    }
+
+    /// Encodes `data` (bits least significant first) followed by the tail.
+    ///
+    /// Panics if the code is invalid.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if let Err(e) = self.validate() {
+            panic!("Invalid convolutional code: {}", e);
+        }
+        let tail = std::iter::repeat_n(0, self.constraint_length - 1);
+        let input = data.iter().flat_map(|&byte| (0..8).map(move |bit| (byte >> bit) as u32 & 1)).chain(tail);
+
+        let mut encoded = vec![0u8; self.encoded_len(data.len())];
+        let mut register = 0;
+        for (index, bit) in input.enumerate() {
+            register = (register << 1 | bit) & ((1 << self.constraint_length) - 1);
+            for (output, generator) in self.generators.iter().enumerate() {
+                let coded = 2 * index + output;
+                encoded[coded / 8] |= (((register & generator).count_ones() % 2) as u8) << (coded % 8);
+            }
+        }
+        encoded
+    }
+
+    /// Hard-decision Viterbi decoding of `data_len` bytes from the coded bytes
+    /// `received`.
+    pub fn decode(&self, received: &[u8], data_len: usize) -> Vec<u8> {
+        let soft: Vec<SoftByte> = received
+            .iter()
+            .map(|&byte| SoftByte { llrs: std::array::from_fn(|bit| if (byte >> bit) & 1 == 0 { 1.0 } else { -1.0 }) })
+            .collect();
+        self.decode_soft(&soft, data_len)
+    }
+
+    /// Soft-decision Viterbi decoding of `data_len` bytes from the demodulator
+    /// LLRs of the coded bytes ([`decode_soft_with_scheme`](crate::decode_soft_with_scheme)):
+    /// the most likely message, weighing every coded bit by its confidence.
+    ///
+    /// Coded bits missing at the end of `soft` are treated as erased.
+    ///
+    /// Panics if the code is invalid.
+    pub fn decode_soft(&self, soft: &[SoftByte], data_len: usize) -> Vec<u8> {
+        if let Err(e) = self.validate() {
+            panic!("Invalid convolutional code: {}", e);
+        }
+        let memory = self.constraint_length - 1;
+        let states = 1usize << memory;
+        let steps = 8 * data_len + memory;
+        let llr = |coded: usize| soft.get(coded / 8).map_or(0.0, |byte| byte.llrs[coded % 8]);
+
+        // Path metrics are correlations with the LLRs (higher is likelier);
+        // paths start in the zero state
+        let mut metrics = vec![f32::NEG_INFINITY; states];
+        metrics[0] = 0.0;
+        let mut next = vec![0.0f32; states];
+        // One bit per state and step: which predecessor survived
+        let words = states.div_ceil(64);
+        let mut decisions = vec![0u64; steps * words];
+
+        for step in 0..steps {
+            let received = [llr(2 * step), llr(2 * step + 1)];
+            for state in 0..states {
+                let input = state as u32 & 1;
+                let mut best = (f32::NEG_INFINITY, 0);
+                for oldest in 0..2 {
+                    let previous = state >> 1 | oldest << (memory - 1);
+                    let register = (previous as u32) << 1 | input;
+                    let branch: f32 = self
+                        .generators
+                        .iter()
+                        .zip(received)
+                        .map(|(&generator, llr)| if (register & generator).count_ones().is_multiple_of(2) { llr } else { -llr })
+                        .sum();
+                    let metric = metrics[previous] + branch;
+                    if metric > best.0 {
+                        best = (metric, oldest);
+                    }
+                }
+                next[state] = best.0;
+                decisions[step * words + state / 64] |= (best.1 as u64) << (state % 64);
+            }
+            std::mem::swap(&mut metrics, &mut next);
+        }
+
+        // Trace back from the zero state the tail leads to
+        let mut data = vec![0u8; data_len];
+        let mut state = 0;
+        for step in (0..steps).rev() {
+            if step < 8 * data_len {
+                data[step / 8] |= (state as u8 & 1) << (step % 8);
+            }
+            let oldest = (decisions[step * words + state / 64] >> (state % 64)) as usize & 1;
+            state = state >> 1 | oldest << (memory - 1);
+        }
+        data
+    }
+}
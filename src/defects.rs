@@ -0,0 +1,162 @@
+use crate::address::LatticeGeometry;
+use crate::structs::{LatticeShape, PhotonicVoxel};
+use rand::Rng;
+use std::collections::BTreeMap;
+
+/// A flawed voxel of the medium.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Defect {
+    /// The laser cannot modify the glass here: the voxel reads back with no
+    /// intensity, polarization or phase, whatever was written.
+    Dead,
+    /// The voxel always reads back as this voxel, e.g. a scattering inclusion.
+    Stuck(PhotonicVoxel),
+}
+
+impl Defect {
+    /// What a voxel written as `written` reads back as.
+    pub fn read(&self, written: &PhotonicVoxel) -> PhotonicVoxel {
+        match self {
+            Defect::Dead => PhotonicVoxel { intensity: 0.0, polarization: 0.0, phase: 0.0, ..*written },
+            Defect::Stuck(voxel) => *voxel,
+        }
+    }
+}
+
+/// Random defects of [`DefectMap::random`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DefectConfig {
+    /// Fraction of the voxels that are defective, in [0, 1].
+    pub density: f64,
+    /// Defects come in clusters of the voxels within this distance of a
+    /// random centre, in voxel pitches: 0 scatters isolated voxels, larger
+    /// radii model inclusions and cracks spanning neighbouring voxels.
+    pub cluster_radius: f32,
+    /// Fraction of the clusters that are stuck rather than dead.
+    pub stuck_fraction: f64,
+    /// What stuck voxels read back as.
+    pub stuck_voxel: PhotonicVoxel,
+}
+
+impl Default for DefectConfig {
+    /// One voxel in ten thousand defective, in isolated dead voxels.
+    fn default() -> Self {
+        Self { density: 1e-4, cluster_radius: 0.0, stuck_fraction: 0.0, stuck_voxel: PhotonicVoxel::new(1.0, 0.0, 0.0, 532.0) }
+    }
+}
+
+impl DefectConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.density) {
+            return Err(format!("Defect density must be in [0, 1] (got {})", self.density));
+        }
+        if !(self.cluster_radius.is_finite() && self.cluster_radius >= 0.0) {
+            return Err(format!("Cluster radius must not be negative (got {})", self.cluster_radius));
+        }
+        if !(0.0..=1.0).contains(&self.stuck_fraction) {
+            return Err(format!("Stuck fraction must be in [0, 1] (got {})", self.stuck_fraction));
+        }
+        Ok(())
+    }
+}
+
+/// Dead and stuck voxels of a medium, by index in the voxel stream (the flat
+/// index of a [`VoxelLattice`](crate::VoxelLattice), see
+/// [`LatticeGeometry::index`] for addresses).
+///
+/// [`apply`](Self::apply) reads written voxels through the defects, and
+/// [`erasures`](Self::erasures) flags the bytes they hold, so a known defect
+/// map costs the ECC erasures instead of errors.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DefectMap {
+    defects: BTreeMap<usize, Defect>,
+}
+
+impl DefectMap {
+    /// A flawless medium.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Random defects in a lattice of `shape`, placed as clusters of `config`
+    /// until about `config.density` of the voxels are defective.
+    ///
+    /// Panics if `config` is invalid.
+    pub fn random<R: Rng + ?Sized>(shape: LatticeShape, config: &DefectConfig, rng: &mut R) -> Self {
+        if let Err(e) = config.validate() {
+            panic!("Invalid defect config: {}", e);
+        }
+        let mut map = Self::new();
+        let target = (config.density * shape.len() as f64).round() as usize;
+        let reach = config.cluster_radius.floor() as isize;
+        let radius_squared = (config.cluster_radius * config.cluster_radius) as isize;
+
+        while map.len() < target {
+            let (x, y, z) = (rng.random_range(0..shape.width), rng.random_range(0..shape.height), rng.random_range(0..shape.depth));
+            let defect = if rng.random_bool(config.stuck_fraction) { Defect::Stuck(config.stuck_voxel) } else { Defect::Dead };
+            for dz in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let (cx, cy, cz) = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy), z.wrapping_add_signed(dz));
+                        if dx * dx + dy * dy + dz * dz <= radius_squared && cx < shape.width && cy < shape.height && cz < shape.depth {
+                            map.insert((cz * shape.height + cy) * shape.width + cx, defect);
+                        }
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    /// Marks the voxel at `index` defective, replacing any defect recorded there.
+    pub fn insert(&mut self, index: usize, defect: Defect) {
+        self.defects.insert(index, defect);
+    }
+
+    /// Defect of the voxel at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&Defect> {
+        self.defects.get(&index)
+    }
+
+    /// Number of defective voxels.
+    pub fn len(&self) -> usize {
+        self.defects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defects.is_empty()
+    }
+
+    /// Defective voxels in index order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Defect)> {
+        self.defects.iter().map(|(&index, defect)| (index, defect))
+    }
+
+    /// `voxels` as written into and read back from the medium. Defects past
+    /// the end of `voxels` are ignored.
+    pub fn apply(&self, voxels: &[PhotonicVoxel]) -> Vec<PhotonicVoxel> {
+        let mut read = voxels.to_vec();
+        for (&index, defect) in self.defects.range(..voxels.len()) {
+            read[index] = defect.read(&voxels[index]);
+        }
+        read
+    }
+
+    /// Flags of the `len` bytes of a stream laid out as `geometry` with at
+    /// least one bit in a defective voxel, as taken by
+    /// [`correct_errors_and_erasures_with`](crate::correct_errors_and_erasures_with).
+    pub fn erasures(&self, geometry: &LatticeGeometry, len: usize) -> Vec<bool> {
+        let mut erasures = vec![false; len];
+        for &index in self.defects.keys() {
+            let bytes = geometry.bytes_for_voxel(index);
+            if bytes.start >= len {
+                break;
+            }
+            erasures[bytes.start..bytes.end.min(len)].fill(true);
+        }
+        erasures
+    }
+}
@@ -0,0 +1,224 @@
+use crate::structs::PhotonicVoxel;
+use rand::Rng;
+use rand_distr::{Distribution, Normal, Poisson};
+use std::f64::consts::PI;
+
+/// Camera or photodiode readout chain measuring voxel intensities: photon and
+/// dark current shot noise, read noise, and an ADC of finite resolution.
+///
+/// A voxel of intensity `I` yields a Poisson number of photoelectrons around
+/// `I * photons_per_voxel`, plus Poisson dark electrons around
+/// `dark_current`, plus Gaussian read noise of `read_noise` electrons. The
+/// ADC counts `gain` electrons per step, clipping at zero and at its full
+/// scale of `2^adc_bits - 1` counts. The count is calibrated back to
+/// intensity by subtracting the mean dark signal, so the result is an
+/// unbiased but quantized and noisy intensity.
+///
+/// Only the intensity goes through the chain; polarization, phase and
+/// wavelength pass through unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectorModel {
+    /// Photoelectrons collected from a voxel of intensity 1.0.
+    pub photons_per_voxel: f32,
+    /// Mean dark electrons per readout.
+    pub dark_current: f32,
+    /// Standard deviation of the read noise, in electrons.
+    pub read_noise: f32,
+    /// ADC resolution.
+    pub adc_bits: u32,
+    /// Electrons per ADC count.
+    pub gain: f32,
+}
+
+impl Default for DetectorModel {
+    /// A 12-bit scientific camera: 10,000 photoelectrons at full intensity,
+    /// 5 dark electrons, 3 electrons of read noise and 4 electrons per count,
+    /// which leaves headroom up to intensity 1.6.
+    fn default() -> Self {
+        Self { photons_per_voxel: 10_000.0, dark_current: 5.0, read_noise: 3.0, adc_bits: 12, gain: 4.0 }
+    }
+}
+
+impl DetectorModel {
+    /// A nearly ideal detector: no dark current or read noise, and enough
+    /// light and ADC resolution that shot noise and quantization stay around
+    /// 1e-6 of full intensity.
+    pub fn ideal() -> Self {
+        Self { photons_per_voxel: 1e12, dark_current: 0.0, read_noise: 0.0, adc_bits: 32, gain: 1000.0 }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.photons_per_voxel.is_finite() && self.photons_per_voxel > 0.0) {
+            return Err(format!("Photons per voxel must be positive (got {})", self.photons_per_voxel));
+        }
+        if !(self.dark_current >= 0.0 && self.read_noise >= 0.0) {
+            return Err(format!("Dark current and read noise must not be negative (got {}, {})", self.dark_current, self.read_noise));
+        }
+        if !(1..=32).contains(&self.adc_bits) {
+            return Err(format!("ADC resolution must be 1 to 32 bits (got {})", self.adc_bits));
+        }
+        if !(self.gain.is_finite() && self.gain > 0.0) {
+            return Err(format!("Gain must be positive (got {} electrons per count)", self.gain));
+        }
+        Ok(())
+    }
+
+    /// Highest ADC count.
+    pub fn full_scale(&self) -> u32 {
+        (((1u64 << self.adc_bits) - 1) as u32).max(1)
+    }
+
+    /// Highest intensity the detector reads before the ADC clips.
+    pub fn max_intensity(&self) -> f32 {
+        (self.full_scale() as f32 * self.gain - self.dark_current) / self.photons_per_voxel
+    }
+
+    /// Intensity step of one ADC count.
+    pub fn intensity_step(&self) -> f32 {
+        self.gain / self.photons_per_voxel
+    }
+
+    /// ADC count of one readout of `intensity`.
+    ///
+    /// Panics if the model is invalid.
+    pub fn count<R: Rng + ?Sized>(&self, intensity: f32, rng: &mut R) -> u32 {
+        if let Err(e) = self.validate() {
+            panic!("Invalid detector model: {}", e);
+        }
+        // Poisson rejects a zero mean: no light, no electrons
+        let mut poisson = |mean: f64| Poisson::new(mean).map_or(0.0, |poisson| poisson.sample(rng));
+        let signal = poisson(intensity.max(0.0) as f64 * self.photons_per_voxel as f64);
+        let dark = poisson(self.dark_current as f64);
+        let read = Normal::new(0.0, self.read_noise as f64).map_or(0.0, |normal| normal.sample(rng));
+        ((signal + dark + read) / self.gain as f64).round().clamp(0.0, self.full_scale() as f64) as u32
+    }
+
+    /// Intensity calibrated from an ADC `count`.
+    pub fn intensity_from_count(&self, count: u32) -> f32 {
+        ((count as f64 * self.gain as f64 - self.dark_current as f64) / self.photons_per_voxel as f64) as f32
+    }
+
+    /// `voxel` as read through the detector.
+    pub fn read_voxel(&self, voxel: &PhotonicVoxel) -> PhotonicVoxel {
+        self.read_voxel_with_rng(voxel, &mut rand::rng())
+    }
+
+    /// [`DetectorModel::read_voxel`] drawing from `rng`, for reproducible runs.
+    pub fn read_voxel_with_rng<R: Rng + ?Sized>(&self, voxel: &PhotonicVoxel, rng: &mut R) -> PhotonicVoxel {
+        PhotonicVoxel { intensity: self.intensity_from_count(self.count(voxel.intensity, rng)), ..*voxel }
+    }
+
+    /// [`DetectorModel::read_voxel_with_rng`] over every voxel, e.g. after
+    /// [`apply_noise_with_rng`](crate::apply_noise_with_rng) to feed detected
+    /// readouts to the decoder.
+    pub fn read_voxels_with_rng<R: Rng + ?Sized>(&self, voxels: &[PhotonicVoxel], rng: &mut R) -> Vec<PhotonicVoxel> {
+        voxels.iter().map(|v| self.read_voxel_with_rng(v, rng)).collect()
+    }
+}
+
+/// Photon-counting readout for the low-light regime: instead of adding
+/// Gaussian noise to exact values, every dimension of a voxel is measured
+/// from a Poisson number of detected photons, so fast or low-power reading
+/// can be characterized by its statistics.
+///
+/// Each measurement collects `photons_per_measurement * I` photons on
+/// average from a voxel of intensity `I`, plus `dark_counts` per channel:
+/// - intensity: one channel, dark-subtracted and scaled back to intensity;
+/// - polarization: four analyzers at 0°, 45°, 90° and 135°, each passing
+///   `(1 + cos 2(θ - α)) / 4` of the light, giving `θ` from the Stokes
+///   parameters `S1 = N0 - N90`, `S2 = N45 - N135`;
+/// - phase: four-step interferometry against a reference beam of intensity
+///   `reference_intensity`, the channel at step `k` passing
+///   `(I + R + 2 sqrt(I R) cos(φ - kπ/2)) / 4`, giving `φ = atan2(N1 - N3, N0 - N2)`;
+/// - wavelength: the mean wavelength of the detected photons, drawn from a
+///   spectral line of standard deviation `linewidth_nm`.
+///
+/// A measurement that detects no photon reads as zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhotonBudget {
+    /// Photons detected per measurement of a voxel of intensity 1.0.
+    pub photons_per_measurement: f64,
+    /// Mean dark counts per channel and measurement.
+    pub dark_counts: f64,
+    /// Intensity of the phase reference beam.
+    pub reference_intensity: f64,
+    /// Standard deviation of the spectral line, in nm.
+    pub linewidth_nm: f64,
+}
+
+impl Default for PhotonBudget {
+    /// 1,000 photons per measurement, 0.1 dark counts per channel, a
+    /// reference as bright as a full voxel and 5 nm spectral lines.
+    fn default() -> Self {
+        Self::new(1000.0)
+    }
+}
+
+impl PhotonBudget {
+    /// `photons_per_measurement` photons per measurement, the rest as in the default.
+    pub fn new(photons_per_measurement: f64) -> Self {
+        Self { photons_per_measurement, dark_counts: 0.1, reference_intensity: 1.0, linewidth_nm: 5.0 }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.photons_per_measurement.is_finite() && self.photons_per_measurement > 0.0) {
+            return Err(format!("Photons per measurement must be positive (got {})", self.photons_per_measurement));
+        }
+        for (name, value) in [("Dark counts", self.dark_counts), ("Reference intensity", self.reference_intensity), ("Linewidth", self.linewidth_nm)] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{} must not be negative (got {})", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// `voxel` as measured photon by photon.
+    pub fn read_voxel(&self, voxel: &PhotonicVoxel) -> PhotonicVoxel {
+        self.read_voxel_with_rng(voxel, &mut rand::rng())
+    }
+
+    /// [`PhotonBudget::read_voxel`] drawing from `rng`, for reproducible runs.
+    ///
+    /// Panics if the budget is invalid.
+    pub fn read_voxel_with_rng<R: Rng + ?Sized>(&self, voxel: &PhotonicVoxel, rng: &mut R) -> PhotonicVoxel {
+        if let Err(e) = self.validate() {
+            panic!("Invalid photon budget: {}", e);
+        }
+        let n = self.photons_per_measurement;
+        let intensity = voxel.intensity.max(0.0) as f64;
+        // Poisson rejects a zero mean: no light, no photons
+        let mut count = |mean: f64| Poisson::new(mean + self.dark_counts).map_or(0.0, |poisson| poisson.sample(rng));
+
+        let photons = count(n * intensity);
+        let measured_intensity = (photons - self.dark_counts) / n;
+
+        let theta = voxel.polarization as f64;
+        let [n0, n45, n90, n135] = [0.0, 1.0, 2.0, 3.0].map(|k| count(n * intensity * (1.0 + (2.0 * (theta - k * PI / 4.0)).cos()) / 4.0));
+        let polarization = ((n45 - n135).atan2(n0 - n90) / 2.0).rem_euclid(PI);
+
+        let (phi, reference) = (voxel.phase as f64, self.reference_intensity);
+        let [p0, p1, p2, p3] = [0.0, 1.0, 2.0, 3.0].map(|k| count(n * (intensity + reference + 2.0 * (intensity * reference).sqrt() * (phi - k * PI / 2.0).cos()) / 4.0));
+        let phase = (p1 - p3).atan2(p0 - p2).rem_euclid(2.0 * PI);
+
+        let spectral = Poisson::new(n * intensity).map_or(0.0, |poisson| poisson.sample(rng));
+        let wavelength = if spectral > 0.0 {
+            Normal::new(voxel.wavelength as f64, self.linewidth_nm / spectral.sqrt()).map_or(voxel.wavelength as f64, |normal| normal.sample(rng))
+        } else {
+            0.0
+        };
+
+        PhotonicVoxel {
+            intensity: measured_intensity as f32,
+            polarization: if polarization >= PI { 0.0 } else { polarization as f32 },
+            phase: phase as f32,
+            wavelength: wavelength as f32,
+        }
+    }
+
+    /// [`PhotonBudget::read_voxel_with_rng`] over every voxel.
+    pub fn read_voxels_with_rng<R: Rng + ?Sized>(&self, voxels: &[PhotonicVoxel], rng: &mut R) -> Vec<PhotonicVoxel> {
+        voxels.iter().map(|v| self.read_voxel_with_rng(v, rng)).collect()
+    }
+}
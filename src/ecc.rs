@@ -1,127 +1,1070 @@
-use reed_solomon_erasure::galois_8::ReedSolomon;
+use reed_solomon_erasure::galois_8::{add, div, exp, mul, ReedSolomon};
+use crate::analysis::{estimate_channel, predicted_residual_ber};
+use crate::codec::{CodecConfig, SoftByte, VoxelConfidence};
+use crate::convolutional::ConvolutionalCode;
+use crate::interleave::Interleaver;
+use crate::noise::NoiseProfile;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+/// Bytes of each shard in a block of [`EccConfig::default`]: 4 KiB, so a
+/// 10+4 block holds 40 KiB of data.
+pub const DEFAULT_BLOCK_SHARD_LEN: usize = 4096;
+
+/// Code computing the parity shards of an [`EccConfig`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EccScheme {
+    /// Reed-Solomon over GF(2^8): corrects up to `parity_shards / 2` corrupted
+    /// bytes per codeword.
+    #[default]
+    ReedSolomon,
+    /// Extended Hamming (72,64) code, single error correction and double
+    /// error detection: 8 data shards and 1 check shard, so 12.5% overhead
+    /// for correcting one flipped bit per codeword.
+    Secded,
+}
+
+impl EccScheme {
+    /// Name for messages and reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EccScheme::ReedSolomon => "Reed-Solomon",
+            EccScheme::Secded => "SECDED",
+        }
+    }
+
+    /// Id recorded in stream and container headers (0 means no ECC).
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            EccScheme::ReedSolomon => 1,
+            EccScheme::Secded => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(EccScheme::ReedSolomon),
+            2 => Some(EccScheme::Secded),
+            _ => None,
+        }
+    }
+}
+
+/// ECC layout: the data is cut into blocks of `data_shards * block_shard_len`
+/// bytes, each split into `data_shards` equal shards (zero-padded) followed by
+/// `parity_shards` parity shards computed with `scheme`. Each byte offset
+/// within the shards of a block is one codeword across all of them, so damage
+/// stays within its block and blocks are encoded and checked one at a time.
+///
+/// Recorded in stream and container headers so decoders do not have to guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EccConfig {
+    pub scheme: EccScheme,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// Bytes of each shard in a block (the last block may be shorter); 1 gives
+    /// classic contiguous codewords, e.g. 255-byte ones with 223+32 shards.
+    /// `None` makes the whole data one block, the layout of files written
+    /// before blocks.
+    pub block_shard_len: Option<usize>,
+}
+
+impl Default for EccConfig {
+    /// 10 data + 4 parity shards in 40 KiB blocks, the layout of
+    /// [`add_error_correction`].
+    fn default() -> Self {
+        Self::new(10, 4)
+    }
+}
+
+impl EccConfig {
+    /// Reed-Solomon with `data_shards` + `parity_shards` in blocks of
+    /// [`DEFAULT_BLOCK_SHARD_LEN`] bytes per shard.
+    pub fn new(data_shards: usize, parity_shards: usize) -> Self {
+        Self { scheme: EccScheme::ReedSolomon, data_shards, parity_shards, block_shard_len: Some(DEFAULT_BLOCK_SHARD_LEN) }
+    }
+
+    /// [`EccScheme::Secded`] (8 data + 1 check shard) in blocks of
+    /// [`DEFAULT_BLOCK_SHARD_LEN`] bytes per shard.
+    pub fn secded() -> Self {
+        Self { scheme: EccScheme::Secded, ..Self::new(8, 1) }
+    }
+
+    /// Checks that there is at least one data and one parity shard, at most
+    /// 255 shards in total (the GF(2^8) limit for locating errors) and
+    /// non-empty blocks.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data_shards == 0 || self.parity_shards == 0 {
+            return Err(format!("Need at least one data and one parity shard (got {}+{})", self.data_shards, self.parity_shards));
+        }
+        if self.total_shards() > MAX_SHARDS {
+            return Err(format!("At most {} shards in total (got {})", MAX_SHARDS, self.total_shards()));
+        }
+        if self.block_shard_len == Some(0) {
+            return Err("ECC blocks need at least one byte per shard".to_string());
+        }
+        if self.scheme == EccScheme::Secded && (self.data_shards, self.parity_shards) != (8, 1) {
+            return Err(format!("SECDED codewords hold 8 data and 1 check byte (got {}+{})", self.data_shards, self.parity_shards));
+        }
+        Ok(())
+    }
+
+    pub fn total_shards(&self) -> usize {
+        self.data_shards + self.parity_shards
+    }
+
+    /// Corrupted bytes per codeword that can be corrected at unknown
+    /// locations: half the parity shards for Reed-Solomon, one (with a single
+    /// flipped bit) for SECDED.
+    pub fn correctable_errors(&self) -> usize {
+        match self.scheme {
+            EccScheme::ReedSolomon => self.parity_shards / 2,
+            EccScheme::Secded => 1,
+        }
+    }
+
+    /// Fraction of the encoded bytes spent on parity, e.g. 0.286 for 10+4.
+    pub fn overhead_ratio(&self) -> f64 {
+        self.parity_shards as f64 / self.total_shards() as f64
+    }
+
+    /// Most bytes of data that fit in `bytes` of storage once encoded: the
+    /// inverse of [`encoded_len`](Self::encoded_len).
+    pub fn effective_capacity(&self, bytes: usize) -> usize {
+        match self.encoded_block_len() {
+            Some(block_len) => bytes / block_len * self.block_len().unwrap_or(0) + bytes % block_len / self.total_shards() * self.data_shards,
+            None => bytes / self.total_shards() * self.data_shards,
+        }
+    }
+
+    /// Bytes of data in a full block, `None` if the data is one block.
+    pub fn block_len(&self) -> Option<usize> {
+        self.block_shard_len.map(|len| len * self.data_shards)
+    }
+
+    /// Bytes in each shard of the first block, for `data_len` bytes of data.
+    /// All blocks but the last have the same shards.
+    pub fn shard_len(&self, data_len: usize) -> usize {
+        let len = data_len.div_ceil(self.data_shards);
+        self.block_shard_len.map_or(len, |block| len.min(block))
+    }
+
+    /// Length of the data with parity, for `data_len` bytes of data.
+    pub fn encoded_len(&self, data_len: usize) -> usize {
+        match self.block_len() {
+            Some(block_len) => (data_len / block_len) * block_len / self.data_shards * self.total_shards() + (data_len % block_len).div_ceil(self.data_shards) * self.total_shards(),
+            None => data_len.div_ceil(self.data_shards) * self.total_shards(),
+        }
+    }
+
+    /// Checks and strips the parity of `encoded`, which may carry trailing
+    /// bytes (e.g. symbol padding from the codec), and returns the first
+    /// `data_len` bytes of data.
+    pub fn recover(&self, encoded: &[u8], data_len: usize) -> Result<Vec<u8>, String> {
+        let encoded_len = self.encoded_len(data_len);
+        if encoded.len() < encoded_len {
+            return Err(format!("{} bytes is too short for {} bytes of data with parity", encoded.len(), data_len));
+        }
+        let mut data = recover_error_correction_with(&encoded[..encoded_len], self)?;
+        data.truncate(data_len);
+        Ok(data)
+    }
+
+    /// This layout as the outer code of a [`ConcatenatedCode`], behind an
+    /// interleaver of `interleave_depth` rows and the inner code `inner`.
+    pub fn concatenated(self, inner: ConvolutionalCode, interleave_depth: usize) -> ConcatenatedCode {
+        ConcatenatedCode { outer: self, interleave_depth, inner }
+    }
+
+    /// Bytes of a full block with its parity, `None` if the data is one block.
+    fn encoded_block_len(&self) -> Option<usize> {
+        self.block_shard_len.map(|len| len * self.total_shards())
+    }
+}
+
+/// Bytes of simulated data behind the channel estimate of [`recommend`].
+const RECOMMEND_SAMPLE_LEN: usize = 50_000;
+
+/// Seed of the simulation behind [`recommend`], so recommendations are
+/// reproducible.
+const RECOMMEND_SEED: u64 = 0x4543_4352;
+
+/// Most shards of a codeword whose errors can be located (one evaluation
+/// point per nonzero element of GF(2^8)).
+const MAX_SHARDS: usize = 255;
+
+/// The ECC layout with the least overhead whose predicted BER after
+/// correction is at most `target_ber` on the default codec under `profile`.
+///
+/// See [`recommend_with_config`].
+pub fn recommend(profile: &NoiseProfile, target_ber: f64) -> Result<EccConfig, String> {
+    recommend_with_config(profile, target_ber, &CodecConfig::default())
+}
+
+/// [`recommend`] for data modulated with `codec`: measures the channel by
+/// simulation ([`estimate_channel`]) and searches every Reed-Solomon shard
+/// layout and SECDED with the residual error model of
+/// [`predicted_residual_ber`], in blocks of [`DEFAULT_BLOCK_SHARD_LEN`] bytes
+/// per shard.
+///
+/// The simulation covers 50 000 bytes, so the channel is never assumed to
+/// be better than about one error in that many. Fails if no layout reaches
+/// the target.
+pub fn recommend_with_config(profile: &NoiseProfile, target_ber: f64, codec: &CodecConfig) -> Result<EccConfig, String> {
+    codec.validate()?;
+    let channel = estimate_channel(RECOMMEND_SAMPLE_LEN, profile, codec, &mut StdRng::seed_from_u64(RECOMMEND_SEED));
+    let meets = |config: &EccConfig| predicted_residual_ber(&channel, config) <= target_ber;
+
+    let mut best = Some(EccConfig::secded()).filter(meets);
+    for parity_shards in 1..MAX_SHARDS {
+        // Fewer data shards per codeword always help: find the most that do
+        let (mut low, mut high) = (1, MAX_SHARDS - parity_shards);
+        if !meets(&EccConfig::new(low, parity_shards)) {
+            continue;
+        }
+        while low < high {
+            let middle = (low + high).div_ceil(2);
+            if meets(&EccConfig::new(middle, parity_shards)) {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+        let candidate = EccConfig::new(low, parity_shards);
+        if best.is_none_or(|best| candidate.overhead_ratio() < best.overhead_ratio()) {
+            best = Some(candidate);
+        }
+    }
+    best.ok_or_else(|| format!("No ECC layout reaches a BER of {:e} on this channel (byte error rate {:.3e})", target_ber, channel.byte_error_rate))
+}
 
 /// Adds Reed-Solomon error correction parity bytes to the data.
 /// Returns (Original Data + Parity).
 pub fn add_error_correction(data: &[u8]) -> Vec<u8> {
-    // Basic configuration: 2 parity shards per 10 data shards (example).
-    // To keep it simple for arbitrary length, we'll blockify.
-    // For PoC, let's just append parity for the whole block if possible,
-    // or use a fixed block size.
-    // RS crate works with "shards".
-
-    // Let's use a simple approach: Split data into N chunks, add K parity chunks.
-    // N = data length (byte by byte is too slow for big data, but for PoC fine).
-    // Actually, RS works on "shards" where each shard is a Vec<u8> of same size.
-    // If we treat each byte as a shard of size 1, it's easy.
+    add_error_correction_with(data, &EccConfig::default())
+}
 
-    // Let's define: 10 data shards, 4 parity shards.
-    // This allows recovering from 4 lost shards (erasures) or 2 corrupted shards (errors).
-    let data_shards = 10;
-    let parity_shards = 4;
-    let total_shards = data_shards + parity_shards;
+/// [`add_error_correction`] with the layout of `config`. Empty data stays
+/// empty.
+///
+/// Panics if `config` is invalid.
+pub fn add_error_correction_with(data: &[u8], config: &EccConfig) -> Vec<u8> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid ECC config: {}", e);
+    }
+    let coder = BlockCoder::new(config).unwrap();
+    match config.block_len() {
+        Some(block_len) => data.chunks(block_len).flat_map(|block| coder.encode(block)).collect(),
+        None => coder.encode(data),
+    }
+}
 
-    let rs = ReedSolomon::new(data_shards, parity_shards).unwrap();
+/// Reed-Solomon coder of the blocks of one layout, built once per buffer or
+/// stream (building it inverts a matrix).
+struct BlockCoder {
+    config: EccConfig,
+    /// Reed-Solomon coder, `None` for SECDED.
+    rs: Option<ReedSolomon>,
+    /// Built on the first block that fails verification.
+    locator: Option<ErrorLocator>,
+}
 
-    // Pad data to be multiple of data_shards
-    let mut padded_data = data.to_vec();
-    while !padded_data.len().is_multiple_of(data_shards) {
-        padded_data.push(0);
+impl BlockCoder {
+    fn new(config: &EccConfig) -> Result<Self, String> {
+        config.validate()?;
+        let rs = match config.scheme {
+            EccScheme::ReedSolomon => Some(ReedSolomon::new(config.data_shards, config.parity_shards).map_err(|e| format!("{:?}", e))?),
+            EccScheme::Secded => None,
+        };
+        Ok(Self { config: *config, rs, locator: None })
     }
 
-    // Split into shards of size = length / data_shards?
-    // No, usually we fix shard size.
-    // Let's make shard size = 1 byte for simplicity of illustration,
-    // or better, spread the file into 10 shards.
+    /// Shards and parity of one block.
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if data.is_empty() {
+            return Vec::new();
+        }
 
-    let shard_size = padded_data.len() / data_shards;
+        // The block is cut into `data_shards` equal shards followed by the
+        // parity shards: codeword `j` is byte `j` of every shard.
+        let data_shards = self.config.data_shards;
+        let total_shards = self.config.total_shards();
 
-    // Create the shards
-    let mut shards: Vec<Vec<u8>> = (0..total_shards).map(|_| vec![0u8; shard_size]).collect();
+        // Pad data to be multiple of data_shards
+        let mut padded_data = data.to_vec();
+        while !padded_data.len().is_multiple_of(data_shards) {
+            padded_data.push(0);
+        }
 
-    // Fill data shards
-    for (i, shard) in shards.iter_mut().enumerate().take(data_shards) {
-        let start = i * shard_size;
-        let end = start + shard_size;
-        shard.copy_from_slice(&padded_data[start..end]);
-    }
+        let shard_size = padded_data.len() / data_shards;
+
+        // Create the shards
+        let mut shards: Vec<Vec<u8>> = (0..total_shards).map(|_| vec![0u8; shard_size]).collect();
+
+        // Fill data shards
+        for (i, shard) in shards.iter_mut().enumerate().take(data_shards) {
+            let start = i * shard_size;
+            let end = start + shard_size;
+            shard.copy_from_slice(&padded_data[start..end]);
+        }
 
-    // Compute parity
-    rs.encode(&mut shards).unwrap();
+        // Compute parity
+        match &self.rs {
+            Some(rs) => rs.encode(&mut shards).unwrap(),
+            None => {
+                let checks: Vec<u8> = (0..shard_size).map(|offset| secded_check(std::array::from_fn(|shard| shards[shard][offset]))).collect();
+                shards[8] = checks;
+            }
+        }
+
+        // Flatten back to a single Vec<u8>
+        let mut result = Vec::with_capacity(total_shards * shard_size);
+        for shard in shards {
+            result.extend(shard);
+        }
 
-    // Flatten back to a single Vec<u8>
-    let mut result = Vec::with_capacity(total_shards * shard_size);
-    for shard in shards {
-        result.extend(shard);
+        result
     }
 
-    result
+    /// Corrects the errors of one encoded block in place, helped by `hints`
+    /// (one per byte of the block). Returns the number of corrected bytes and
+    /// of codewords with too many errors to correct.
+    fn correct(&mut self, block: &mut [u8], hints: Hints) -> Result<(usize, usize), String> {
+        let total_shards = self.config.total_shards();
+        let shard_size = block.len() / total_shards;
+        if shard_size == 0 {
+            return Ok((0, 0));
+        }
+
+        let Some(rs) = &self.rs else {
+            return Ok(secded_correct(block, shard_size));
+        };
+
+        // Our noise model perturbs values, so we get *corrupted* bytes at
+        // unknown locations rather than erasures: verify first, and only
+        // locate and correct the errors if the parity does not match.
+        let shards: Vec<&[u8]> = block.chunks_exact(shard_size).collect();
+        if rs.verify(&shards).map_err(|e| format!("{:?}", e))? {
+            return Ok((0, 0));
+        }
+        let locator = match &mut self.locator {
+            Some(locator) => locator,
+            None => self.locator.insert(ErrorLocator::new(&self.config)?),
+        };
+
+        let (mut corrected, mut failed) = (0, 0);
+        let mut codeword = vec![0u8; total_shards];
+        let mut erased = Vec::new();
+        for offset in 0..shard_size {
+            for (shard, byte) in codeword.iter_mut().enumerate() {
+                *byte = block[shard * shard_size + offset];
+            }
+            let result = match hints {
+                Hints::None => locator.correct(&mut codeword, &[]),
+                Hints::Erasures(erasures) => {
+                    erased.clear();
+                    erased.extend((0..total_shards).filter(|&shard| erasures[shard * shard_size + offset]));
+                    locator.correct(&mut codeword, &erased)
+                }
+                Hints::Reliability(reliability) => locator.correct_soft(&mut codeword, || {
+                    let mut shards: Vec<usize> = (0..total_shards).collect();
+                    shards.sort_by(|&a, &b| reliability[a * shard_size + offset].total_cmp(&reliability[b * shard_size + offset]));
+                    shards
+                }),
+            };
+            match result {
+                Some(0) => {}
+                Some(errors) => {
+                    corrected += errors;
+                    for (shard, byte) in codeword.iter().enumerate() {
+                        block[shard * shard_size + offset] = *byte;
+                    }
+                }
+                None => failed += 1,
+            }
+        }
+        Ok((corrected, failed))
+    }
 }
 
 /// Decodes data and corrects errors using Reed-Solomon.
 /// Returns the original data (stripping parity).
 pub fn recover_error_correction(data_with_parity: &[u8]) -> Result<Vec<u8>, String> {
-    let data_shards = 10;
-    let parity_shards = 4;
-    let total_shards = data_shards + parity_shards;
+    recover_error_correction_with(data_with_parity, &EccConfig::default())
+}
+
+/// [`recover_error_correction`] with the layout of `config`. The result still
+/// carries the zero padding of the last data shard: see [`EccConfig::recover`]
+/// to strip it.
+///
+/// Corrupted bytes are located and corrected (see [`correct_errors_with`]);
+/// fails if a codeword has more errors than the parity can correct.
+pub fn recover_error_correction_with(data_with_parity: &[u8], config: &EccConfig) -> Result<Vec<u8>, String> {
+    let mut corrected = data_with_parity.to_vec();
+    correct_errors_with(&mut corrected, config)?;
+    Ok(strip_parity(&corrected, config))
+}
 
-    if !data_with_parity.len().is_multiple_of(total_shards) {
+/// The data shards of every block of `data_with_parity`, as they are.
+pub(crate) fn strip_parity(data_with_parity: &[u8], config: &EccConfig) -> Vec<u8> {
+    // The data shards lead every block
+    let block_len = config.encoded_block_len().unwrap_or(data_with_parity.len()).max(1);
+    data_with_parity
+        .chunks(block_len)
+        .flat_map(|block| &block[..block.len() / config.total_shards() * config.data_shards])
+        .copied()
+        .collect()
+}
+
+/// Corrects corrupted bytes of `data_with_parity` (laid out as
+/// [`add_error_correction_with`] writes it) in place, without knowing where
+/// they are, and returns the number of corrected bytes.
+///
+/// Each byte offset within the shards of a block is one Reed-Solomon codeword,
+/// of which up to `parity_shards / 2` bytes can be corrected (see
+/// [`EccConfig::correctable_errors`]). Codewords with more errors are left as
+/// they are and reported in the error, after the others have been corrected.
+pub fn correct_errors_with(data_with_parity: &mut [u8], config: &EccConfig) -> Result<usize, String> {
+    let (corrected, failed, codewords) = correct_blocks(data_with_parity, Hints::None, config)?;
+    if failed > 0 {
+        return Err(format!(
+            "Data corrupted (ECC check failed): {} of {} codewords have more than {} errors",
+            failed,
+            codewords,
+            config.correctable_errors()
+        ));
+    }
+    Ok(corrected)
+}
+
+/// [`correct_errors_with`] that also knows which bytes are unreliable:
+/// `erasures` flags every byte of `data_with_parity` (e.g. from
+/// [`erasures_from_confidence`]). A flagged byte costs one parity shard to
+/// correct instead of two, so a Reed-Solomon codeword with `e` flagged bytes
+/// and `t` other errors is corrected as long as `e + 2t <= parity_shards` and
+/// `e < parity_shards`.
+///
+/// Codewords with as many flagged bytes as parity shards or more are decoded
+/// as if none were flagged, since nothing would be left to check the result.
+/// SECDED ignores the flags.
+pub fn correct_errors_and_erasures_with(data_with_parity: &mut [u8], erasures: &[bool], config: &EccConfig) -> Result<usize, String> {
+    if erasures.len() != data_with_parity.len() {
+        return Err(format!("{} erasure flags for {} bytes", erasures.len(), data_with_parity.len()));
+    }
+    let (corrected, failed, codewords) = correct_blocks(data_with_parity, Hints::Erasures(erasures), config)?;
+    if failed > 0 {
+        return Err(format!("Data corrupted (ECC check failed): {} of {} codewords have too many errors and erasures", failed, codewords));
+    }
+    Ok(corrected)
+}
+
+/// [`correct_errors_with`] guided by the `reliability` of every byte of
+/// `data_with_parity` (higher is likelier right, e.g.
+/// [`SoftByte::min_confidence`]): a Reed-Solomon codeword with too many errors
+/// is decoded again with its least reliable bytes erased, one more at a time
+/// (generalized minimum distance decoding), which corrects up to
+/// `parity_shards - 1` errors when they are the least reliable bytes.
+///
+/// SECDED ignores the reliabilities.
+pub fn correct_errors_soft_with(data_with_parity: &mut [u8], reliability: &[f32], config: &EccConfig) -> Result<usize, String> {
+    if reliability.len() != data_with_parity.len() {
+        return Err(format!("{} reliabilities for {} bytes", reliability.len(), data_with_parity.len()));
+    }
+    let (corrected, failed, codewords) = correct_blocks(data_with_parity, Hints::Reliability(reliability), config)?;
+    if failed > 0 {
+        return Err(format!("Data corrupted (ECC check failed): {} of {} codewords could not be decoded", failed, codewords));
+    }
+    Ok(corrected)
+}
+
+/// Corrects every block of `data_with_parity` in place, with `hints` covering
+/// all its bytes. Returns the corrected bytes, the codewords left with too
+/// many errors and the number of codewords; fails only on an invalid config
+/// or length.
+pub(crate) fn correct_blocks(data_with_parity: &mut [u8], hints: Hints, config: &EccConfig) -> Result<(usize, usize, usize), String> {
+    config.validate()?;
+    let total_shards = config.total_shards();
+    let block_len = config.encoded_block_len().unwrap_or(data_with_parity.len()).max(1);
+    // Full blocks always are: only the last one may have been cut short
+    if data_with_parity.chunks(block_len).last().is_some_and(|block| !block.len().is_multiple_of(total_shards)) {
         return Err("Data length invalid for ECC parameters".to_string());
     }
 
-    let shard_size = data_with_parity.len() / total_shards;
-
-    // Reconstruct shards
-    let shards: Vec<Vec<u8>> = (0..total_shards).map(|i| {
-        let start = i * shard_size;
-        let end = start + shard_size;
-        data_with_parity[start..end].to_vec()
-    }).collect();
-
-    let rs = ReedSolomon::new(data_shards, parity_shards).unwrap();
-
-    // Try to reconstruct. RS.reconstruct helps with erasures (known missing).
-    // RS.verify checks integrity.
-    // If we have corrupted data (not erasures), we need to tell RS?
-    // The crate `reed-solomon-erasure` is primarily for erasures.
-    // However, it can verify.
-    // For proper error correction (unknown location), this crate might be limited?
-    // Documentation says: "This library implements Reed-Solomon coding ... suitable for erasure coding".
-    // Pure error correction (Berlekamp-Massey) might be different.
-    // But for "simulated readout noise" we often treat valid reads as data and "low intensity" or "flagged" as erasure.
-    // Since our noise model just perturbs values, we get *corrupted* bytes, not missing ones.
-    // Standard RS can correct E errors and E erasures such that 2*E + E <= parity.
-    // This crate might only support erasures (where we provide `None` for missing shards).
-
-    // If we can't detect *which* shard is bad, this crate might not help with *correction* of values unless we try combinations.
-    // Wait, let's check if there's a simpler crate or if I should implement a simple Hamming code.
-    // Hamming(7,4) is easy to implement.
-    // Or I can just trust that my noise model is small enough and this step is "Advanced".
-
-    // Let's assume for this PoC we mark "uncertain" voxels? No, we don't have that info from `decode_data`.
-
-    // ALTERNATIVE: Use a CRC or hash to detect which shard is bad?
-    // If we split into small blocks and CRC each, we can turn errors into erasures.
-
-    // Let's assume for now we return the data part. The user asked for "Error Correction".
-    // I will implement a wrapper that just strips parity for now and verifies.
-    // If `rs.reconstruct` is called, we need `Option<Vec<u8>>`.
-
-    // Let's try to verify.
-    if rs.verify(&shards).unwrap() {
-        // All good
-        let mut result: Vec<u8> = Vec::new();
-        for shard in shards.iter().take(data_shards) {
-            result.extend(shard);
+    let mut coder = BlockCoder::new(config)?;
+    let (mut corrected, mut failed, mut codewords) = (0, 0, 0);
+    for (index, block) in data_with_parity.chunks_mut(block_len).enumerate() {
+        let bytes = index * block_len..index * block_len + block.len();
+        let hints = match hints {
+            Hints::None => Hints::None,
+            Hints::Erasures(erasures) => Hints::Erasures(&erasures[bytes]),
+            Hints::Reliability(reliability) => Hints::Reliability(&reliability[bytes]),
+        };
+        codewords += block.len() / total_shards;
+        let (block_corrected, block_failed) = coder.correct(block, hints)?;
+        corrected += block_corrected;
+        failed += block_failed;
+    }
+    Ok((corrected, failed, codewords))
+}
+
+/// [`recover_error_correction_with`] with the bytes flagged in `erasures`
+/// treated as erasures (see [`correct_errors_and_erasures_with`]).
+pub fn recover_error_correction_with_erasures(data_with_parity: &[u8], erasures: &[bool], config: &EccConfig) -> Result<Vec<u8>, String> {
+    let mut corrected = data_with_parity.to_vec();
+    correct_errors_and_erasures_with(&mut corrected, erasures, config)?;
+    Ok(strip_parity(&corrected, config))
+}
+
+/// Flags the bytes of a decoded stream that a voxel with a relative decision
+/// margin below `threshold` contributed bits to (see
+/// [`VoxelConfidence::is_uncertain`]), for [`correct_errors_and_erasures_with`].
+///
+/// `confidence` is the per-voxel output of
+/// [`decode_data_with_confidence`](crate::decode_data_with_confidence) and
+/// `bits_per_voxel` that of the codec; one flag is returned per whole byte.
+pub fn erasures_from_confidence(confidence: &[VoxelConfidence], bits_per_voxel: usize, threshold: f32) -> Vec<bool> {
+    let mut erasures = vec![false; confidence.len() * bits_per_voxel / 8];
+    for (voxel, _) in confidence.iter().enumerate().filter(|(_, confidence)| confidence.is_uncertain(threshold)) {
+        let bits = voxel * bits_per_voxel..(voxel + 1) * bits_per_voxel;
+        for byte in bits.start / 8..bits.end.div_ceil(8).min(erasures.len()) {
+            erasures[byte] = true;
+        }
+    }
+    erasures
+}
+
+/// Corrupts `count` distinct bytes of `data` chosen at random (all of them if
+/// there are fewer), each XORed with a random nonzero value, and returns their
+/// positions in increasing order. For testing and simulating what ECC repairs.
+pub fn corrupt_random_bytes<R: Rng + ?Sized>(data: &mut [u8], count: usize, rng: &mut R) -> Vec<usize> {
+    let mut positions = rand::seq::index::sample(rng, data.len(), count.min(data.len())).into_vec();
+    positions.sort_unstable();
+    for &position in &positions {
+        data[position] ^= rng.random_range(1..=u8::MAX);
+    }
+    positions
+}
+
+/// Corrupts the `len` bytes of `data` from `start` (cut at the end of the
+/// data), each XORed with a random nonzero value, as a scratch or a dead
+/// region of a crystal would, and returns the corrupted range.
+pub fn corrupt_burst<R: Rng + ?Sized>(data: &mut [u8], start: usize, len: usize, rng: &mut R) -> Range<usize> {
+    let burst = start.min(data.len())..start.saturating_add(len).min(data.len());
+    for byte in &mut data[burst.clone()] {
+        *byte ^= rng.random_range(1..=u8::MAX);
+    }
+    burst
+}
+
+/// `Read` adapter that adds the parity of `config` to everything read through
+/// it, one block at a time, so large inputs are protected with bounded memory.
+/// The output is [`add_error_correction_with`] of the whole input.
+pub struct EccEncodingReader<R: Read> {
+    inner: R,
+    coder: BlockCoder,
+    block: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> EccEncodingReader<R> {
+    /// Panics if `config` is invalid.
+    pub fn new(inner: R, config: EccConfig) -> Self {
+        let coder = BlockCoder::new(&config).unwrap_or_else(|e| panic!("Invalid ECC config: {}", e));
+        Self { inner, coder, block: Vec::new(), position: 0, done: false }
+    }
+}
+
+impl<R: Read> Read for EccEncodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.block.len() {
+            if self.done {
+                return Ok(0);
+            }
+            let mut data = Vec::new();
+            match self.coder.config.block_len() {
+                Some(block_len) => {
+                    (&mut self.inner).take(block_len as u64).read_to_end(&mut data)?;
+                    self.done = data.len() < block_len;
+                }
+                None => {
+                    self.inner.read_to_end(&mut data)?;
+                    self.done = true;
+                }
+            }
+            self.block = self.coder.encode(&data);
+            self.position = 0;
+        }
+
+        let n = buf.len().min(self.block.len() - self.position);
+        buf[..n].copy_from_slice(&self.block[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// `Write` adapter that adds the parity of `config` to everything written
+/// through it, one block at a time: the counterpart of [`EccEncodingReader`]
+/// for producers that push data. [`finish`](Self::finish) encodes the last,
+/// possibly short, block; the output is then [`add_error_correction_with`] of
+/// everything written.
+///
+/// A config without blocks makes the whole input one block, held in memory
+/// until `finish`.
+pub struct EccEncodingWriter<W: Write> {
+    inner: W,
+    coder: BlockCoder,
+    block: Vec<u8>,
+}
+
+impl<W: Write> EccEncodingWriter<W> {
+    /// Panics if `config` is invalid.
+    pub fn new(inner: W, config: EccConfig) -> Self {
+        let coder = BlockCoder::new(&config).unwrap_or_else(|e| panic!("Invalid ECC config: {}", e));
+        Self { inner, coder, block: Vec::new() }
+    }
+
+    /// Encodes and writes the data not written yet, flushes and returns the
+    /// inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.block.is_empty() {
+            self.inner.write_all(&self.coder.encode(&self.block))?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EccEncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match self.coder.config.block_len() {
+            Some(block_len) => buf.len().min(block_len - self.block.len()),
+            None => buf.len(),
+        };
+        self.block.extend_from_slice(&buf[..n]);
+        if self.coder.config.block_len() == Some(self.block.len()) {
+            self.inner.write_all(&self.coder.encode(&self.block))?;
+            self.block.clear();
+        }
+        Ok(n)
+    }
+
+    /// Flushes the blocks written so far; the data of an unfinished block
+    /// stays buffered until it is full or [`finish`](Self::finish) is called.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `Read` adapter that checks, corrects and strips the parity of the
+/// `data_len` bytes of data encoded in `inner`, one block at a time. Bytes
+/// after the last block (e.g. symbol padding from the codec) are not read.
+///
+/// A block with more errors than the parity corrects fails the read with
+/// `InvalidData`, unless the reader is [`lenient`](Self::lenient).
+pub struct EccDecodingReader<R: Read> {
+    inner: R,
+    coder: BlockCoder,
+    /// Data bytes not yet decoded.
+    remaining: u64,
+    block: Vec<u8>,
+    position: usize,
+    blocks: usize,
+    corrected: usize,
+    damaged_blocks: Vec<usize>,
+    lenient: bool,
+}
+
+impl<R: Read> EccDecodingReader<R> {
+    /// Fails if `config` is invalid.
+    pub fn new(inner: R, config: EccConfig, data_len: u64) -> Result<Self, String> {
+        Ok(Self { inner, coder: BlockCoder::new(&config)?, remaining: data_len, block: Vec::new(), position: 0, blocks: 0, corrected: 0, damaged_blocks: Vec::new(), lenient: false })
+    }
+
+    /// Passes blocks with too many errors through as read (after correcting
+    /// what it can) instead of failing; see [`damaged_blocks`](Self::damaged_blocks).
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Bytes corrected so far.
+    pub fn corrected_bytes(&self) -> usize {
+        self.corrected
+    }
+
+    /// Blocks (from 0) that had more errors than the parity corrects.
+    pub fn damaged_blocks(&self) -> &[usize] {
+        &self.damaged_blocks
+    }
+}
+
+impl<R: Read> Read for EccDecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.block.len() {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+            let config = self.coder.config;
+            let len = match config.block_len() {
+                Some(block_len) => self.remaining.min(block_len as u64) as usize,
+                None => usize::try_from(self.remaining).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "data too large for one ECC block"))?,
+            };
+            let mut block = vec![0u8; config.encoded_len(len)];
+            self.inner.read_exact(&mut block)?;
+            let (corrected, failed) = self.coder.correct(&mut block, Hints::None).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.corrected += corrected;
+            if failed > 0 {
+                if !self.lenient {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("ECC block {}: {} codewords have more than {} errors", self.blocks, failed, config.correctable_errors()),
+                    ));
+                }
+                self.damaged_blocks.push(self.blocks);
+            }
+            // The data shards lead the block
+            block.truncate(len);
+            self.block = block;
+            self.position = 0;
+            self.blocks += 1;
+            self.remaining -= len as u64;
+        }
+
+        let n = buf.len().min(self.block.len() - self.position);
+        buf[..n].copy_from_slice(&self.block[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Concatenated code in the style of the CIRC of optical discs: the outer code
+/// of an [`EccConfig`], a block interleaver, and an inner
+/// [`ConvolutionalCode`] decoded from soft decisions.
+///
+/// The inner Viterbi decoder clears the scattered errors of the readout noise;
+/// where it fails, it leaves short bursts, which the interleaver spreads over
+/// many outer codewords so each sees few enough errors to correct. Interleaver
+/// rows are `outer.total_shards()` bytes: with single-byte shards
+/// (`block_shard_len: Some(1)`) every row is one outer codeword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConcatenatedCode {
+    pub outer: EccConfig,
+    /// Rows of the interleaver; 1 leaves the outer code in order.
+    pub interleave_depth: usize,
+    pub inner: ConvolutionalCode,
+}
+
+impl ConcatenatedCode {
+    pub fn validate(&self) -> Result<(), String> {
+        self.outer.validate()?;
+        self.inner.validate()?;
+        if self.interleave_depth == 0 {
+            return Err("Interleave depth must be at least one".to_string());
+        }
+        Ok(())
+    }
+
+    /// Length of `data_len` bytes once encoded by both codes.
+    pub fn encoded_len(&self, data_len: usize) -> usize {
+        self.inner.encoded_len(self.outer.encoded_len(data_len))
+    }
+
+    /// Adds the outer parity, interleaves and encodes with the inner code.
+    ///
+    /// Panics if the code is invalid.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if let Err(e) = self.validate() {
+            panic!("Invalid concatenated code: {}", e);
         }
-        return Ok(result);
+        self.inner.encode(&self.interleaver().interleave(&add_error_correction_with(data, &self.outer)))
     }
 
-    // If verify fails...
-    let mut result: Vec<u8> = Vec::new();
-    for shard in shards.iter().take(data_shards) {
-        result.extend(shard);
+    /// Decodes `data_len` bytes from the demodulator LLRs of the encoded bytes
+    /// ([`decode_soft_with_scheme`](crate::decode_soft_with_scheme)): soft
+    /// Viterbi decoding, deinterleaving, then outer correction.
+    ///
+    /// Fails if an outer codeword has more errors than its parity corrects.
+    pub fn decode_soft(&self, soft: &[SoftByte], data_len: usize) -> Result<Vec<u8>, String> {
+        let (data, outcome) = self.decode_soft_partial(soft, data_len);
+        outcome.map(|_| data)
     }
 
-    // Warn about corruption
-    Err("Data corrupted (ECC check failed)".to_string())
+    /// [`decode_soft`](Self::decode_soft) that keeps the data of outer
+    /// codewords it cannot correct, with the outcome of the outer correction.
+    pub(crate) fn decode_soft_partial(&self, soft: &[SoftByte], data_len: usize) -> (Vec<u8>, Result<usize, String>) {
+        if let Err(e) = self.validate() {
+            return (Vec::new(), Err(e));
+        }
+        let outer_len = self.outer.encoded_len(data_len);
+        let mut received = self.interleaver().deinterleave(&self.inner.decode_soft(soft, outer_len));
+        let outcome = correct_errors_with(&mut received, &self.outer);
+        let mut data = strip_parity(&received, &self.outer);
+        data.truncate(data_len);
+        (data, outcome)
+    }
+
+    fn interleaver(&self) -> Interleaver {
+        Interleaver::new(self.interleave_depth, self.outer.total_shards())
+    }
+}
+
+/// What [`BlockCoder::correct`] knows about the bytes of a block besides
+/// their values.
+#[derive(Clone, Copy)]
+pub(crate) enum Hints<'a> {
+    None,
+    /// Bytes known to be unreliable.
+    Erasures(&'a [bool]),
+    /// Reliability of every byte: the higher, the likelier it is right.
+    Reliability(&'a [f32]),
+}
+
+/// Syndrome decoder for the codewords of one shard layout.
+///
+/// `reed-solomon-erasure` builds its code from a Vandermonde matrix, so the
+/// shards of a codeword are the values of a polynomial of degree below
+/// `data_shards` at the points 0, 1, 2, ... of GF(2^8). Shifting every point by
+/// the shard count keeps the code and makes all points non-zero, as
+/// Berlekamp-Massey needs.
+struct ErrorLocator {
+    parity_shards: usize,
+    /// Point of each shard.
+    points: Vec<u8>,
+    /// `weights[shard][l]`: the multiplier of the shard in syndrome `l`, i.e.
+    /// its point to the `l` times its column multiplier in the dual code.
+    weights: Vec<Vec<u8>>,
+}
+
+impl ErrorLocator {
+    fn new(config: &EccConfig) -> Result<Self, String> {
+        let total_shards = config.total_shards();
+        if total_shards > MAX_SHARDS {
+            return Err(format!("Invalid ECC config: cannot locate errors with {} shards (at most {})", total_shards, MAX_SHARDS));
+        }
+        let shift = total_shards as u8;
+        let points: Vec<u8> = (0..total_shards).map(|shard| shard as u8 ^ shift).collect();
+        let weights = (0..total_shards)
+            .map(|shard| {
+                // 1 / prod(x_shard - x_other); the shift cancels in the differences
+                let product = (0..total_shards).filter(|&other| other != shard).fold(1, |product, other| mul(product, shard as u8 ^ other as u8));
+                let multiplier = div(1, product);
+                (0..config.parity_shards).map(|l| mul(multiplier, exp(points[shard], l))).collect()
+            })
+            .collect();
+        Ok(Self { parity_shards: config.parity_shards, points, weights })
+    }
+
+    /// Corrects `codeword` (one byte from every shard) in place with
+    /// Berlekamp-Massey, a Chien search over the shard points and Forney's
+    /// formula, given the shards in `erasures` as erased. Returns the number of
+    /// corrected bytes, or `None` if there are too many errors to correct.
+    ///
+    /// With as many erasures as parity shards or more, or if decoding with
+    /// them fails, the codeword is decoded as if nothing were erased.
+    fn correct(&self, codeword: &mut [u8], erasures: &[usize]) -> Option<usize> {
+        let syndromes = self.syndromes(codeword);
+        if syndromes.iter().all(|&syndrome| syndrome == 0) {
+            return Some(0);
+        }
+        // One parity shard is kept back to check the result: with as many
+        // erasures as parity shards any codeword would fit
+        if !erasures.is_empty() && erasures.len() < self.parity_shards {
+            if let Some(corrected) = self.correct_with_erasures(codeword, &syndromes, erasures) {
+                return Some(corrected);
+            }
+        }
+        self.correct_with_erasures(codeword, &syndromes, &[])
+    }
+
+    /// Generalized minimum distance decoding: corrects `codeword` like
+    /// [`correct`](Self::correct) without erasures and, if it has too many
+    /// errors, erases its 1, 2, ... least reliable shards (as listed, least
+    /// reliable first, by `least_reliable`) until it decodes, keeping one
+    /// parity shard back.
+    fn correct_soft(&self, codeword: &mut [u8], least_reliable: impl FnOnce() -> Vec<usize>) -> Option<usize> {
+        let syndromes = self.syndromes(codeword);
+        if syndromes.iter().all(|&syndrome| syndrome == 0) {
+            return Some(0);
+        }
+        if let Some(corrected) = self.correct_with_erasures(codeword, &syndromes, &[]) {
+            return Some(corrected);
+        }
+        let order = least_reliable();
+        (1..self.parity_shards).find_map(|erased| self.correct_with_erasures(codeword, &syndromes, &order[..erased]))
+    }
+
+    fn syndromes(&self, codeword: &[u8]) -> Vec<u8> {
+        (0..self.parity_shards)
+            .map(|l| codeword.iter().zip(&self.weights).fold(0, |syndrome, (&byte, weights)| add(syndrome, mul(byte, weights[l]))))
+            .collect()
+    }
+
+    fn correct_with_erasures(&self, codeword: &mut [u8], syndromes: &[u8], erasures: &[usize]) -> Option<usize> {
+        // Erasure locator: prod(1 - x_k z) over the erased points
+        let erasure_locator = erasures.iter().fold(vec![1u8], |product, &shard| {
+            let mut next = product.clone();
+            next.push(0);
+            for (i, &c) in product.iter().enumerate() {
+                next[i + 1] = add(next[i + 1], mul(c, self.points[shard]));
+            }
+            next
+        });
+        // The Forney syndromes (erasure locator times syndromes, past the
+        // erasures) only see the errors
+        let modified = multiply_truncated(&erasure_locator, syndromes, self.parity_shards);
+        let error_locator = berlekamp_massey(&modified[erasures.len()..]);
+        let errors = error_locator.len() - 1;
+        if 2 * errors + erasures.len() > self.parity_shards {
+            return None;
+        }
+        let locator = multiply_truncated(&error_locator, &erasure_locator, errors + erasures.len() + 1);
+        let degree = locator.len() - 1;
+        let positions: Vec<usize> = (0..codeword.len()).filter(|&shard| evaluate(&locator, div(1, self.points[shard])) == 0).collect();
+        if positions.len() != degree {
+            return None;
+        }
+
+        // Error evaluator: syndromes times locator, modulo z^parity_shards
+        let evaluator = multiply_truncated(&locator, syndromes, self.parity_shards);
+        // Formal derivative: in characteristic 2 only the odd terms remain
+        let derivative: Vec<u8> = locator.iter().enumerate().skip(1).map(|(i, &c)| if i % 2 == 1 { c } else { 0 }).collect();
+        let mut corrections = Vec::with_capacity(degree);
+        for shard in positions {
+            let point = self.points[shard];
+            let inverse = div(1, point);
+            let slope = evaluate(&derivative, inverse);
+            if slope == 0 {
+                return None;
+            }
+            let value = mul(point, div(evaluate(&evaluator, inverse), slope));
+            // The syndromes saw the error times the shard's column multiplier
+            corrections.push((shard, div(value, self.weights[shard][0])));
+        }
+        // Erased bytes may have been read right after all
+        corrections.retain(|&(_, error)| error != 0);
+        for &(shard, error) in &corrections {
+            codeword[shard] ^= error;
+        }
+        Some(corrections.len())
+    }
+}
+
+/// Position of every data bit in a (72,64) Hamming codeword: the positions
+/// from 3 to 71 that are not powers of two, which hold the 7 check bits.
+const SECDED_POSITIONS: [u8; 64] = {
+    let mut positions = [0u8; 64];
+    let (mut bit, mut position) = (0, 3u8);
+    while bit < 64 {
+        if !position.is_power_of_two() {
+            positions[bit] = position;
+            bit += 1;
+        }
+        position += 1;
+    }
+    positions
+};
+
+/// XOR of the positions of the set bits of `data`.
+fn secded_syndrome(data: [u8; 8]) -> u8 {
+    let word = u64::from_le_bytes(data);
+    (0..64).filter(|&bit| word >> bit & 1 == 1).fold(0, |syndrome, bit| syndrome ^ SECDED_POSITIONS[bit])
+}
+
+/// Check byte of 8 data bytes: the 7 Hamming check bits, then the parity of
+/// the whole codeword.
+fn secded_check(data: [u8; 8]) -> u8 {
+    let check = secded_syndrome(data);
+    let parity = (u64::from_le_bytes(data).count_ones() + check.count_ones()) % 2;
+    check | (parity as u8) << 7
+}
+
+/// Corrects single flipped bits in the SECDED codewords of a block (8 data
+/// shards and the check shard). Returns the number of corrected bytes and of
+/// codewords with a double error.
+fn secded_correct(block: &mut [u8], shard_size: usize) -> (usize, usize) {
+    let (mut corrected, mut failed) = (0, 0);
+    for offset in 0..shard_size {
+        let data: [u8; 8] = std::array::from_fn(|shard| block[shard * shard_size + offset]);
+        let check = block[8 * shard_size + offset];
+        let syndrome = secded_syndrome(data) ^ (check & 0x7F);
+        let odd = (u64::from_le_bytes(data).count_ones() + check.count_ones()) % 2 == 1;
+        match (syndrome, odd) {
+            (0, false) => continue,
+            // An even number of flipped bits: detected, not correctable
+            (_, false) => failed += 1,
+            // The overall parity bit or a check bit flipped
+            (0, true) => block[8 * shard_size + offset] ^= 0x80,
+            (syndrome, true) if syndrome.is_power_of_two() => block[8 * shard_size + offset] ^= syndrome,
+            (syndrome, true) => match SECDED_POSITIONS.iter().position(|&position| position == syndrome) {
+                Some(bit) => block[bit / 8 * shard_size + offset] ^= 1 << (bit % 8),
+                // Points past the codeword: three or more flipped bits
+                None => {
+                    failed += 1;
+                    continue;
+                }
+            },
+        }
+        if odd {
+            corrected += 1;
+        }
+    }
+    (corrected, failed)
+}
+
+/// Shortest linear feedback shift register generating `syndromes`: the error
+/// locator polynomial, lowest degree first, its degree being the number of
+/// errors.
+fn berlekamp_massey(syndromes: &[u8]) -> Vec<u8> {
+    let mut current = vec![1u8];
+    let mut previous = vec![1u8];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = 1u8;
+
+    for n in 0..syndromes.len() {
+        let discrepancy = (1..=errors).fold(syndromes[n], |d, i| add(d, mul(*current.get(i).unwrap_or(&0), syndromes[n - i])));
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let scale = div(discrepancy, previous_discrepancy);
+        let mut next = current.clone();
+        next.resize(next.len().max(previous.len() + shift), 0);
+        for (i, &c) in previous.iter().enumerate() {
+            next[i + shift] = add(next[i + shift], mul(scale, c));
+        }
+        if 2 * errors <= n {
+            previous = std::mem::replace(&mut current, next);
+            errors = n + 1 - errors;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            current = next;
+            shift += 1;
+        }
+    }
+
+    current.resize(errors + 1, 0);
+    current
+}
+
+/// Product of the polynomials `a` and `b` (lowest degree first), modulo `z^len`.
+fn multiply_truncated(a: &[u8], b: &[u8], len: usize) -> Vec<u8> {
+    let mut product = vec![0u8; len.min(a.len() + b.len() - 1)];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate().take(product.len().saturating_sub(i)) {
+            product[i + j] = add(product[i + j], mul(x, y));
+        }
+    }
+    product
+}
+
+/// Value of the polynomial `coefficients` (lowest degree first) at `x`.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0, |value, &c| add(mul(value, x), c))
 }
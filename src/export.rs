@@ -0,0 +1,186 @@
+use crate::analysis::{BerGrid, ConstellationSample};
+use crate::structs::PhotonicVoxel;
+use std::io::{self, Read, Write};
+
+/// First bytes of every `.npy` file.
+pub const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Columns of the exported array, in order.
+pub const NPY_COLUMNS: [&str; 4] = ["intensity", "polarization", "phase", "wavelength"];
+
+/// Writes `voxels` as a NumPy `.npy` file holding an `(N, 4)` little-endian
+/// `float32` array, one row per voxel with the columns of [`NPY_COLUMNS`], so
+/// `numpy.load` reads it directly.
+pub fn to_npy<W: Write>(mut writer: W, voxels: &[PhotonicVoxel]) -> io::Result<()> {
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, 4), }}", voxels.len());
+    // Magic, version and header length take 10 bytes; the data starts 64-byte aligned
+    let total = (10 + header.len() + 1).next_multiple_of(64);
+    header.extend(std::iter::repeat_n(' ', total - 10 - header.len() - 1));
+    header.push('\n');
+
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for voxel in voxels {
+        for field in [voxel.intensity, voxel.polarization, voxel.phase, voxel.wavelength] {
+            writer.write_all(&field.to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+/// Reads voxels from a `.npy` file holding an `(N, 4)` `float32` array laid
+/// out like [`to_npy`] writes it (C or Fortran order, either byte order), e.g.
+/// measured voxels produced with NumPy.
+///
+/// Fails with `InvalidData` on any other file.
+pub fn from_npy<R: Read>(mut reader: R) -> io::Result<Vec<PhotonicVoxel>> {
+    let mut preamble = [0u8; 8];
+    reader.read_exact(&mut preamble)?;
+    if !is_npy(&preamble) {
+        return Err(invalid_data("not a .npy file (bad magic)"));
+    }
+    let header_len = match preamble[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        major => return Err(invalid_data(format!("unsupported .npy version {}", major))),
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid_data("the .npy header is not text"))?;
+
+    let little_endian = match header_value(&header, "descr") {
+        Some("'<f4'") => true,
+        Some("'>f4'") => false,
+        other => return Err(invalid_data(format!("expected a float32 array, found descr {}", other.unwrap_or("none")))),
+    };
+    let fortran_order = match header_value(&header, "fortran_order") {
+        Some("False") => false,
+        Some("True") => true,
+        _ => return Err(invalid_data("missing fortran_order in the .npy header")),
+    };
+    let rows = header_value(&header, "shape")
+        .and_then(|shape| shape.strip_prefix('(')?.strip_suffix(')'))
+        .and_then(|shape| {
+            let dims: Vec<&str> = shape.split(',').map(str::trim).filter(|dim| !dim.is_empty()).collect();
+            match dims[..] {
+                [rows, "4"] => rows.parse::<usize>().ok(),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| invalid_data("expected an (N, 4) array"))?;
+
+    let mut data = vec![0u8; rows.checked_mul(16).ok_or_else(|| invalid_data("array too large"))?];
+    reader.read_exact(&mut data)?;
+    let values: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|bytes| {
+            let bytes = bytes.try_into().expect("4 bytes");
+            if little_endian { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) }
+        })
+        .collect();
+    // Fortran order stores the array column by column
+    let value = |row: usize, column: usize| if fortran_order { values[column * rows + row] } else { values[row * 4 + column] };
+    Ok((0..rows).map(|row| PhotonicVoxel::new(value(row, 0), value(row, 1), value(row, 2), value(row, 3))).collect())
+}
+
+/// Columns of [`constellation_to_csv`], in order.
+pub const CONSTELLATION_COLUMNS: [&str; 11] = [
+    "noise_level",
+    "symbol",
+    "decided",
+    "written_intensity",
+    "written_polarization",
+    "written_phase",
+    "written_wavelength",
+    "received_intensity",
+    "received_polarization",
+    "received_phase",
+    "received_wavelength",
+];
+
+/// Writes constellation samples as CSV with a header row of
+/// [`CONSTELLATION_COLUMNS`], one row per sample, for plotting the received
+/// values of each dimension against the written ones.
+pub fn constellation_to_csv<W: Write>(mut writer: W, samples: &[ConstellationSample]) -> io::Result<()> {
+    writeln!(writer, "{}", CONSTELLATION_COLUMNS.join(","))?;
+    for sample in samples {
+        let (written, received) = (&sample.written, &sample.received);
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            sample.noise_level,
+            sample.symbol,
+            sample.decided,
+            written.intensity,
+            written.polarization,
+            written.phase,
+            written.wavelength,
+            received.intensity,
+            received.polarization,
+            received.phase,
+            received.wavelength
+        )?;
+    }
+    writer.flush()
+}
+
+/// Writes constellation samples as a JSON array of objects with the fields
+/// of [`ConstellationSample`], as the `serde` feature would serialize them.
+pub fn constellation_to_json<W: Write>(mut writer: W, samples: &[ConstellationSample]) -> io::Result<()> {
+    let voxel = |v: &PhotonicVoxel| format!(r#"{{"intensity":{},"polarization":{},"phase":{},"wavelength":{}}}"#, v.intensity, v.polarization, v.phase, v.wavelength);
+    writer.write_all(b"[")?;
+    for (i, sample) in samples.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        write!(
+            writer,
+            r#"{}{{"noise_level":{},"symbol":{},"decided":{},"written":{},"received":{}}}"#,
+            separator,
+            sample.noise_level,
+            sample.symbol,
+            sample.decided,
+            voxel(&sample.written),
+            voxel(&sample.received)
+        )?;
+    }
+    writer.write_all(b"]\n")?;
+    writer.flush()
+}
+
+/// Writes a [`BerGrid`] as long-format CSV, one row per point with the
+/// swept parameters under the column names of `names`, then `TotalBits`,
+/// `ErrorBits` and `BER`, ready to pivot into a heatmap (e.g.
+/// `df.pivot(index=names[1], columns=names[0], values="BER")`).
+pub fn grid_to_csv<W: Write>(mut writer: W, grid: &BerGrid, names: [&str; 2]) -> io::Result<()> {
+    writeln!(writer, "{},{},TotalBits,ErrorBits,BER", names[0], names[1])?;
+    for point in &grid.points {
+        writeln!(writer, "{},{},{},{},{}", point.x, point.y, point.total_bits, point.error_bits, point.ber)?;
+    }
+    writer.flush()
+}
+
+/// True if `bytes` start with the `.npy` magic.
+pub fn is_npy(bytes: &[u8]) -> bool {
+    bytes.starts_with(NPY_MAGIC)
+}
+
+/// Text of the value of `key` in a `.npy` header dictionary.
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}':", key))? + key.len() + 3;
+    let value = header[start..].trim_start();
+    let end = if value.starts_with('(') { value.find(')')? + 1 } else { value.find([',', '}'])? };
+    Some(value[..end].trim())
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,88 @@
+use crate::codec::{decode_soft_with_profile, CodecConfig, SoftByte};
+use crate::ecc::{correct_blocks, strip_parity, EccConfig, Hints};
+#[cfg(feature = "ldpc")]
+use crate::ldpc::LdpcCode;
+use crate::noise::NoiseProfile;
+use crate::structs::PhotonicVoxel;
+
+/// What the forward error correction of [`decode_with_fec`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FecReport {
+    /// Bits that differ from the hard decisions of the demodulator.
+    pub corrected_bits: usize,
+    /// Bytes that differ from the hard decisions of the demodulator.
+    pub corrected_bytes: usize,
+    /// Codewords decoded.
+    pub codewords: usize,
+    /// Codewords with too many errors to correct, left as decided.
+    pub failed_codewords: usize,
+}
+
+/// Data and report of a successful [`decode_with_fec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FecDecoded {
+    pub data: Vec<u8>,
+    pub report: FecReport,
+}
+
+/// Decodes `data_len` bytes protected by `ecc` from the per-bit LLRs of the
+/// encoded bytes, e.g. from [`decode_soft_with_profile`]: the LLRs give the
+/// reliability of every byte, and the Reed-Solomon decoder erases the least
+/// reliable bytes of codewords with too many errors
+/// ([`correct_errors_soft_with`](crate::correct_errors_soft_with)).
+///
+/// Bytes missing at the end of `llrs` are decoded as erased. Fails with the
+/// report if a codeword cannot be corrected.
+///
+/// Panics if `ecc` is invalid.
+pub fn decode_with_fec(llrs: &[SoftByte], ecc: &EccConfig, data_len: usize) -> Result<FecDecoded, FecReport> {
+    let erased = SoftByte { llrs: [0.0; 8] };
+    let soft = || llrs.iter().copied().chain(std::iter::repeat(erased)).take(ecc.encoded_len(data_len));
+    let hard: Vec<u8> = soft().map(|byte| byte.hard_decision()).collect();
+    let reliability: Vec<f32> = soft().map(|byte| byte.min_confidence()).collect();
+
+    let mut encoded = hard.clone();
+    let (_, failed_codewords, codewords) = correct_blocks(&mut encoded, Hints::Reliability(&reliability), ecc).unwrap_or_else(|e| panic!("Invalid ECC config: {}", e));
+    let report = FecReport { failed_codewords, codewords, ..corrections(&hard, &encoded) };
+    if failed_codewords > 0 {
+        return Err(report);
+    }
+    let mut data = strip_parity(&encoded, ecc);
+    data.truncate(data_len);
+    Ok(FecDecoded { data, report })
+}
+
+/// [`decode_with_fec`] of voxels written with `codec`, keeping the soft
+/// information of the demodulator all the way: the LLRs are those of a
+/// channel with the noise of `profile` ([`decode_soft_with_profile`]).
+///
+/// Panics if `codec` or `ecc` is invalid.
+pub fn decode_voxels_with_fec(voxels: &[PhotonicVoxel], codec: &CodecConfig, ecc: &EccConfig, profile: &NoiseProfile, data_len: usize) -> Result<FecDecoded, FecReport> {
+    decode_with_fec(&decode_soft_with_profile(voxels, codec, profile), ecc, data_len)
+}
+
+/// [`decode_voxels_with_fec`] for data protected by an LDPC code: the LLRs go
+/// straight into belief propagation ([`LdpcCode::decode_soft`]). Corrections
+/// are counted over the data bits.
+#[cfg(feature = "ldpc")]
+pub fn decode_with_ldpc(voxels: &[PhotonicVoxel], codec: &CodecConfig, code: &LdpcCode, profile: &NoiseProfile, data_len: usize) -> Result<FecDecoded, FecReport> {
+    let soft = decode_soft_with_profile(voxels, codec, profile);
+    let decoded = code.decode_soft(&soft, data_len);
+
+    // Hard decisions of the data bytes, which lead every codeword
+    let config = code.config();
+    let (data_bytes, codeword_bytes) = (config.data_bits / 8, (config.data_bits + config.parity_bits) / 8);
+    let hard: Vec<u8> = (0..data_len).map(|byte| soft.get(byte / data_bytes * codeword_bytes + byte % data_bytes).map_or(0, SoftByte::hard_decision)).collect();
+    let report = FecReport { failed_codewords: decoded.failed_codewords.len(), codewords: data_len.div_ceil(data_bytes), ..corrections(&hard, &decoded.data) };
+    if report.failed_codewords > 0 {
+        return Err(report);
+    }
+    Ok(FecDecoded { data: decoded.data, report })
+}
+
+/// Report of the bits and bytes changed from `hard` to `corrected`.
+fn corrections(hard: &[u8], corrected: &[u8]) -> FecReport {
+    let changed = || hard.iter().zip(corrected).map(|(a, b)| a ^ b).filter(|&flips| flips != 0);
+    FecReport { corrected_bits: changed().map(|flips| flips.count_ones() as usize).sum(), corrected_bytes: changed().count(), ..FecReport::default() }
+}
@@ -0,0 +1,267 @@
+use std::io;
+
+/// Failure probability parameter of the robust soliton distribution.
+const SOLITON_DELTA: f64 = 0.5;
+/// Spike parameter of the robust soliton distribution.
+const SOLITON_C: f64 = 0.1;
+
+/// Bytes of a symbol id in [`FountainSymbol::to_bytes`].
+pub const SYMBOL_ID_LEN: usize = 4;
+
+/// One encoded symbol of a fountain code: `data` is the XOR of the source
+/// symbols selected by `id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FountainSymbol {
+    pub id: u32,
+    pub data: Vec<u8>,
+}
+
+impl FountainSymbol {
+    /// The id (little-endian `u32`) followed by the data, for storing the
+    /// symbol on its own.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SYMBOL_ID_LEN + self.data.len());
+        bytes.extend_from_slice(&self.id.to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Reads back a symbol written by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < SYMBOL_ID_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "fountain symbol shorter than its id"));
+        }
+        let (id, data) = bytes.split_at(SYMBOL_ID_LEN);
+        Ok(Self { id: u32::from_le_bytes(id.try_into().expect("4 id bytes")), data: data.to_vec() })
+    }
+}
+
+/// Rateless (LT) encoder: cuts the data into source symbols of `symbol_len`
+/// bytes and produces any number of encoded symbols from them.
+///
+/// The code is systematic: symbols `0..source_symbols()` are the source
+/// symbols themselves, and every id from there on is a repair symbol, the XOR
+/// of a pseudo-random set of source symbols whose size follows the robust
+/// soliton distribution. The set depends only on the id and the source symbol
+/// count, so repair symbols can be generated at any time, in any number, from
+/// any id, e.g. to fill spare regions written long after the data.
+#[derive(Debug, Clone)]
+pub struct FountainEncoder {
+    symbols: Vec<Vec<u8>>,
+    degrees: Degrees,
+}
+
+impl FountainEncoder {
+    /// Panics if `symbol_len` is zero.
+    pub fn new(data: &[u8], symbol_len: usize) -> Self {
+        assert!(symbol_len > 0, "Fountain symbols need at least one byte");
+        let symbols: Vec<Vec<u8>> = data
+            .chunks(symbol_len)
+            .map(|chunk| {
+                let mut symbol = chunk.to_vec();
+                symbol.resize(symbol_len, 0);
+                symbol
+            })
+            .collect();
+        let degrees = Degrees::new(symbols.len());
+        Self { symbols, degrees }
+    }
+
+    /// Number of source symbols: the minimum number of symbols any decoder
+    /// needs.
+    pub fn source_symbols(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Encoded symbol `id`.
+    pub fn symbol(&self, id: u32) -> FountainSymbol {
+        let mut data = vec![0u8; self.symbols.first().map_or(0, Vec::len)];
+        for source in self.degrees.neighbours(id) {
+            for (byte, &source_byte) in data.iter_mut().zip(&self.symbols[source]) {
+                *byte ^= source_byte;
+            }
+        }
+        FountainSymbol { id, data }
+    }
+
+    /// The `count` repair symbols following the source symbols, starting
+    /// `skip` symbols in: successive calls with growing `skip` never repeat
+    /// a symbol.
+    pub fn repair_symbols(&self, skip: u32, count: u32) -> impl Iterator<Item = FountainSymbol> + '_ {
+        let first = self.symbols.len() as u32 + skip;
+        (first..first + count).map(|id| self.symbol(id))
+    }
+}
+
+/// Decoder of [`FountainEncoder`] symbols: recovers the data from any set of
+/// symbols that determines every source symbol, typically a few more than
+/// there are source symbols, whichever ones they are.
+///
+/// Symbols are reduced by Gaussian elimination as they are added, so
+/// [`is_complete`](Self::is_complete) tells exactly when enough have arrived.
+#[derive(Debug, Clone)]
+pub struct FountainDecoder {
+    data_len: usize,
+    symbol_len: usize,
+    degrees: Degrees,
+    /// Row of the symbol whose lowest source symbol is each index, once known:
+    /// the source symbols it covers as a bitset, and its data.
+    pivots: Vec<Option<(Vec<u64>, Vec<u8>)>>,
+    rank: usize,
+}
+
+impl FountainDecoder {
+    /// Decoder for `data_len` bytes encoded in symbols of `symbol_len` bytes.
+    ///
+    /// Panics if `symbol_len` is zero.
+    pub fn new(data_len: usize, symbol_len: usize) -> Self {
+        assert!(symbol_len > 0, "Fountain symbols need at least one byte");
+        let source_symbols = data_len.div_ceil(symbol_len);
+        Self { data_len, symbol_len, degrees: Degrees::new(source_symbols), pivots: vec![None; source_symbols], rank: 0 }
+    }
+
+    /// Adds a received symbol. Returns whether it told the decoder anything
+    /// new; symbols of the wrong length are ignored.
+    pub fn add(&mut self, symbol: &FountainSymbol) -> bool {
+        if symbol.data.len() != self.symbol_len || self.is_complete() {
+            return false;
+        }
+        let mut row = vec![0u64; self.pivots.len().div_ceil(64)];
+        for source in self.degrees.neighbours(symbol.id) {
+            row[source / 64] ^= 1 << (source % 64);
+        }
+        let mut data = symbol.data.clone();
+
+        // Eliminate the known pivots, lowest first: each only has higher bits
+        for source in 0..self.pivots.len() {
+            if row[source / 64] >> (source % 64) & 1 == 0 {
+                continue;
+            }
+            match &self.pivots[source] {
+                Some((pivot_row, pivot_data)) => {
+                    xor_into(&mut row, pivot_row);
+                    xor_into(&mut data, pivot_data);
+                }
+                None => {
+                    self.pivots[source] = Some((row, data));
+                    self.rank += 1;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// True once the symbols added determine every source symbol.
+    pub fn is_complete(&self) -> bool {
+        self.rank == self.pivots.len()
+    }
+
+    /// Number of source symbols determined so far, out of
+    /// [`source_symbols`](Self::source_symbols).
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub fn source_symbols(&self) -> usize {
+        self.pivots.len()
+    }
+
+    /// The data, if [`is_complete`](Self::is_complete).
+    pub fn decode(&self) -> Option<Vec<u8>> {
+        if !self.is_complete() {
+            return None;
+        }
+        // Back-substitution, highest pivot first
+        let mut solved: Vec<Vec<u8>> = vec![Vec::new(); self.pivots.len()];
+        for source in (0..self.pivots.len()).rev() {
+            let (row, data) = self.pivots[source].as_ref().expect("complete decoder has every pivot");
+            let mut symbol = data.clone();
+            for (higher, solved) in solved.iter().enumerate().skip(source + 1) {
+                if row[higher / 64] >> (higher % 64) & 1 == 1 {
+                    xor_into(&mut symbol, solved);
+                }
+            }
+            solved[source] = symbol;
+        }
+        let mut data: Vec<u8> = solved.concat();
+        data.truncate(self.data_len);
+        Some(data)
+    }
+}
+
+/// Source symbols of every symbol id for a given source symbol count.
+#[derive(Debug, Clone)]
+struct Degrees {
+    source_symbols: usize,
+    /// Cumulative robust soliton distribution: `cumulative[d - 1]` is the
+    /// probability of a degree of at most `d`.
+    cumulative: Vec<f64>,
+}
+
+impl Degrees {
+    fn new(source_symbols: usize) -> Self {
+        let k = source_symbols.max(1) as f64;
+        // Robust soliton: ideal soliton plus a spike at k / R
+        let r = SOLITON_C * (k / SOLITON_DELTA).ln() * k.sqrt();
+        let spike = ((k / r).floor() as usize).clamp(1, source_symbols.max(1));
+        let weights: Vec<f64> = (1..=source_symbols.max(1))
+            .map(|d| {
+                let ideal = if d == 1 { 1.0 / k } else { 1.0 / (d * (d - 1)) as f64 };
+                let extra = match d.cmp(&spike) {
+                    std::cmp::Ordering::Less => r / (d as f64 * k),
+                    std::cmp::Ordering::Equal => r * (r / SOLITON_DELTA).ln() / k,
+                    std::cmp::Ordering::Greater => 0.0,
+                };
+                ideal + extra.max(0.0)
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let cumulative = weights
+            .iter()
+            .scan(0.0, |sum, weight| {
+                *sum += weight / total;
+                Some(*sum)
+            })
+            .collect();
+        Self { source_symbols, cumulative }
+    }
+
+    /// Distinct source symbols XORed into symbol `id`.
+    fn neighbours(&self, id: u32) -> Vec<usize> {
+        let k = self.source_symbols;
+        if (id as usize) < k {
+            return vec![id as usize];
+        }
+        // Drawn from a generator seeded by the id alone, so encoder and
+        // decoder agree on every symbol without exchanging anything
+        let mut state = id as u64 ^ (k as u64) << 32;
+        let uniform = |state: &mut u64| (splitmix64(state) >> 11) as f64 / (1u64 << 53) as f64;
+        let draw = uniform(&mut state);
+        let degree = self.cumulative.partition_point(|&p| p < draw).min(k - 1) + 1;
+        let mut neighbours: Vec<usize> = Vec::with_capacity(degree);
+        while neighbours.len() < degree {
+            let source = (splitmix64(&mut state) % k as u64) as usize;
+            if !neighbours.contains(&source) {
+                neighbours.push(source);
+            }
+        }
+        neighbours
+    }
+}
+
+/// SplitMix64 step: a fixed generator, so symbol ids map to the same source
+/// symbols in every build.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn xor_into<T: Copy + std::ops::BitXorAssign>(target: &mut [T], source: &[T]) {
+    for (target, &source) in target.iter_mut().zip(source) {
+        *target ^= source;
+    }
+}
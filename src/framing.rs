@@ -0,0 +1,151 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::CodecConfig;
+use std::f32::consts::PI;
+
+// Sync word: dark (unwritten) voxels with a fixed polarization pattern.
+// Data voxels are never dark (the lowest intensity level is 1/levels), and a
+// scratched region reads dark with a constant polarization, so the pattern
+// cannot be mistaken for either.
+const SYNC_POLARIZATIONS: [f32; 3] = [0.0, PI / 2.0, 0.0];
+const SYNC_WAVELENGTH: f32 = 532.0;
+
+/// Tolerance on the sync voxel polarization when detecting markers.
+const SYNC_POLARIZATION_TOLERANCE: f32 = PI / 8.0;
+
+/// Number of voxels in a sync marker.
+pub const SYNC_LEN: usize = SYNC_POLARIZATIONS.len();
+
+/// Framing parameters for the voxel stream.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameConfig {
+    /// Data voxels between two sync markers.
+    pub payload_voxels: usize,
+    /// A voxel dimmer than this is considered dark (part of a sync marker).
+    pub dark_threshold: f32,
+}
+
+impl Default for FrameConfig {
+    fn default() -> Self {
+        Self::for_codec(1024, &CodecConfig::default())
+    }
+}
+
+impl FrameConfig {
+    /// Frames of `payload_voxels` data voxels, with the dark threshold placed
+    /// halfway between zero and the lowest intensity level of `codec`.
+    pub fn for_codec(payload_voxels: usize, codec: &CodecConfig) -> Self {
+        Self {
+            payload_voxels,
+            dark_threshold: 0.5 / codec.intensity_levels as f32,
+        }
+    }
+
+    fn is_sync_at(&self, voxels: &[PhotonicVoxel], start: usize) -> bool {
+        voxels.len() >= start + SYNC_LEN
+            && SYNC_POLARIZATIONS.iter().enumerate().all(|(i, &angle)| {
+                let v = &voxels[start + i];
+                let mut dist = (v.polarization - angle).rem_euclid(PI);
+                dist = dist.min(PI - dist);
+                v.intensity < self.dark_threshold && dist < SYNC_POLARIZATION_TOLERANCE
+            })
+    }
+}
+
+/// Result of stripping sync markers from a received voxel stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deframed {
+    /// Payload voxels with markers removed. Every frame but the last contributes
+    /// exactly `payload_voxels` voxels, so data after a damaged frame stays aligned.
+    pub voxels: Vec<PhotonicVoxel>,
+    /// Indices of frames whose length did not match (voxels lost or inserted).
+    /// Their content was truncated or padded with dark voxels.
+    pub damaged_frames: Vec<usize>,
+}
+
+/// Prepends a sync marker to every frame of `config.payload_voxels` voxels.
+///
+/// Panics if `config.payload_voxels` is zero.
+pub fn insert_sync_markers(voxels: &[PhotonicVoxel], config: &FrameConfig) -> Vec<PhotonicVoxel> {
+    assert!(config.payload_voxels > 0, "Frame payload must hold at least one voxel");
+
+    let frames = voxels.len().div_ceil(config.payload_voxels);
+    let mut framed = Vec::with_capacity(voxels.len() + frames * SYNC_LEN);
+    for frame in voxels.chunks(config.payload_voxels) {
+        framed.extend_from_slice(&sync_marker());
+        framed.extend_from_slice(frame);
+    }
+    framed
+}
+
+/// Detects sync markers, strips them and realigns the payload.
+///
+/// The stream between two detected markers should hold one frame. If a marker
+/// was destroyed, the segment is split into as many frames as its length
+/// suggests. Segments whose length does not match are reported in
+/// `damaged_frames` and truncated or padded to the frame size, so a run of
+/// missing or extra voxels only affects the frames it touches instead of
+/// shifting all subsequent data. Voxels before the first marker are dropped.
+pub fn strip_sync_markers(voxels: &[PhotonicVoxel], config: &FrameConfig) -> Deframed {
+    assert!(config.payload_voxels > 0, "Frame payload must hold at least one voxel");
+
+    // Locate all markers (non-overlapping)
+    let mut markers = Vec::new();
+    let mut i = 0;
+    while i < voxels.len() {
+        if config.is_sync_at(voxels, i) {
+            markers.push(i);
+            i += SYNC_LEN;
+        } else {
+            i += 1;
+        }
+    }
+
+    let n = config.payload_voxels;
+    let mut deframed = Deframed { voxels: Vec::with_capacity(voxels.len()), damaged_frames: Vec::new() };
+    let mut frame_index = 0;
+
+    for (k, &marker) in markers.iter().enumerate() {
+        let start = marker + SYNC_LEN;
+        let end = markers.get(k + 1).copied().unwrap_or(voxels.len());
+        let segment = &voxels[start..end];
+        let is_last = k + 1 == markers.len();
+
+        // Frames in this segment, assuming lost markers still occupy their slots.
+        // Only the final frame of the stream may be shorter than `n`.
+        let (frames, exact) = if is_last {
+            let frames = (segment.len() + SYNC_LEN).div_ceil(n + SYNC_LEN).max(1);
+            let tail = segment.len() as isize - ((frames - 1) * (n + SYNC_LEN)) as isize;
+            (frames, tail >= 1)
+        } else {
+            let frames = ((segment.len() + SYNC_LEN) as f32 / (n + SYNC_LEN) as f32).round().max(1.0) as usize;
+            (frames, segment.len() == frames * n + (frames - 1) * SYNC_LEN)
+        };
+
+        for f in 0..frames {
+            let frame_start = (f * (n + SYNC_LEN)).min(segment.len());
+            let frame_end = (frame_start + n).min(segment.len());
+            let frame = &segment[frame_start..frame_end];
+
+            deframed.voxels.extend_from_slice(frame);
+            if !exact {
+                deframed.damaged_frames.push(frame_index);
+                deframed.voxels.extend(std::iter::repeat_n(dark_voxel(), n - frame.len()));
+            }
+            frame_index += 1;
+        }
+    }
+
+    deframed
+}
+
+/// The sync marker written in front of every frame. Useful for streaming
+/// writers that insert markers themselves.
+pub fn sync_marker() -> [PhotonicVoxel; SYNC_LEN] {
+    SYNC_POLARIZATIONS.map(|polarization| PhotonicVoxel::new(0.0, polarization, 0.0, SYNC_WAVELENGTH))
+}
+
+/// Placeholder for voxels lost from a damaged frame.
+fn dark_voxel() -> PhotonicVoxel {
+    PhotonicVoxel::new(0.0, 0.0, 0.0, SYNC_WAVELENGTH)
+}
@@ -0,0 +1,290 @@
+use crate::analysis::SimulationResult;
+use crate::structs::VoxelLattice;
+use std::io::{self, Write};
+
+/// Name of the lattice dataset, shaped `(depth, height, width, 4)`.
+pub const LATTICE_DATASET: &str = "lattice";
+
+/// Name of the group holding one dataset per [`SimulationResult`] field.
+pub const RESULTS_GROUP: &str = "results";
+
+/// Columns of the last axis of the lattice dataset, in order.
+pub const LATTICE_COLUMNS: [&str; 4] = ["intensity", "polarization", "phase", "wavelength"];
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const SUPERBLOCK_LEN: usize = 48;
+const UNDEFINED_ADDRESS: u64 = u64::MAX;
+
+// Object header message types
+const MSG_DATASPACE: u8 = 0x01;
+const MSG_LINK_INFO: u8 = 0x02;
+const MSG_DATATYPE: u8 = 0x03;
+const MSG_FILL_VALUE: u8 = 0x05;
+const MSG_LINK: u8 = 0x06;
+const MSG_LAYOUT: u8 = 0x08;
+const MSG_GROUP_INFO: u8 = 0x0A;
+const MSG_ATTRIBUTE: u8 = 0x0C;
+
+/// Message flag for data that never changes (datatypes, fill values).
+const MSG_CONSTANT: u8 = 0x01;
+
+/// Writes an HDF5 file holding `lattice` (if any) as the `(depth, height,
+/// width, 4)` float32 dataset [`LATTICE_DATASET`], with `width`, `height` and
+/// `depth` attributes and the column names in `columns`, and `results` (if
+/// any) as the group [`RESULTS_GROUP`] of one dataset per field:
+/// `noise_level` (float32), `total_bits` and `error_bits` (uint64) and `ber`
+/// (float64).
+///
+/// The writer needs no libhdf5: it uses the subset of the format HDF5 1.10+
+/// readers (h5py, ParaView) accept for small files, i.e. a version 2
+/// superblock, version 2 object headers, compact groups and contiguous
+/// datasets.
+pub fn write<W: Write>(mut writer: W, lattice: Option<&VoxelLattice>, results: &[SimulationResult]) -> io::Result<()> {
+    let mut file = FileBuilder::new();
+    let mut root_links = Vec::new();
+
+    if let Some(lattice) = lattice {
+        let shape = lattice.shape();
+        let data: Vec<u8> = lattice
+            .voxels()
+            .iter()
+            .flat_map(|voxel| [voxel.intensity, voxel.polarization, voxel.phase, voxel.wavelength])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let column_len = LATTICE_COLUMNS.iter().map(|column| column.len()).max().unwrap_or(0) + 1;
+        let columns: Vec<u8> = LATTICE_COLUMNS
+            .iter()
+            .flat_map(|column| column.bytes().chain(std::iter::repeat(0)).take(column_len))
+            .collect();
+        let attributes = [
+            Attribute { name: "width", datatype: Datatype::U64, dims: vec![], data: (shape.width as u64).to_le_bytes().to_vec() },
+            Attribute { name: "height", datatype: Datatype::U64, dims: vec![], data: (shape.height as u64).to_le_bytes().to_vec() },
+            Attribute { name: "depth", datatype: Datatype::U64, dims: vec![], data: (shape.depth as u64).to_le_bytes().to_vec() },
+            Attribute { name: "columns", datatype: Datatype::String(column_len), dims: vec![LATTICE_COLUMNS.len() as u64], data: columns },
+        ];
+        let dims = [shape.depth as u64, shape.height as u64, shape.width as u64, 4];
+        root_links.push((LATTICE_DATASET, file.dataset(Datatype::F32, &dims, &data, &attributes)));
+    }
+
+    if !results.is_empty() {
+        let rows = [results.len() as u64];
+        let column = |datatype, data: Vec<u8>| (datatype, data);
+        let columns = [
+            ("noise_level", column(Datatype::F32, results.iter().flat_map(|r| r.noise_level.to_le_bytes()).collect())),
+            ("total_bits", column(Datatype::U64, results.iter().flat_map(|r| (r.total_bits as u64).to_le_bytes()).collect())),
+            ("error_bits", column(Datatype::U64, results.iter().flat_map(|r| (r.error_bits as u64).to_le_bytes()).collect())),
+            ("ber", column(Datatype::F64, results.iter().flat_map(|r| r.ber.to_le_bytes()).collect())),
+        ];
+        let links: Vec<(&str, u64)> = columns.into_iter().map(|(name, (datatype, data))| (name, file.dataset(datatype, &rows, &data, &[]))).collect();
+        root_links.push((RESULTS_GROUP, file.group(&links)));
+    }
+
+    let root = file.group(&root_links);
+    writer.write_all(&file.finish(root))?;
+    writer.flush()
+}
+
+/// Element types of the datasets and attributes written.
+#[derive(Clone, Copy)]
+enum Datatype {
+    F32,
+    F64,
+    U64,
+    /// Null-padded ASCII string of this many bytes.
+    String(usize),
+}
+
+impl Datatype {
+    /// Datatype message (version 1).
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match *self {
+            Datatype::F32 | Datatype::F64 => {
+                let double = matches!(self, Datatype::F64);
+                // Little-endian, implied mantissa MSB, sign in the top bit
+                let (size, exponent_location, exponent_size, mantissa_size, bias): (u32, u8, u8, u8, u32) =
+                    if double { (8, 52, 11, 52, 1023) } else { (4, 23, 8, 23, 127) };
+                bytes.extend_from_slice(&[0x11, 0x20, (size * 8 - 1) as u8, 0]);
+                bytes.extend_from_slice(&size.to_le_bytes());
+                bytes.extend_from_slice(&0u16.to_le_bytes());
+                bytes.extend_from_slice(&((size * 8) as u16).to_le_bytes());
+                bytes.extend_from_slice(&[exponent_location, exponent_size, 0, mantissa_size]);
+                bytes.extend_from_slice(&bias.to_le_bytes());
+            }
+            Datatype::U64 => {
+                bytes.extend_from_slice(&[0x10, 0, 0, 0]);
+                bytes.extend_from_slice(&8u32.to_le_bytes());
+                bytes.extend_from_slice(&0u16.to_le_bytes());
+                bytes.extend_from_slice(&64u16.to_le_bytes());
+            }
+            Datatype::String(len) => {
+                // Null padding, ASCII
+                bytes.extend_from_slice(&[0x13, 0x01, 0, 0]);
+                bytes.extend_from_slice(&(len as u32).to_le_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    datatype: Datatype,
+    /// Empty for a scalar.
+    dims: Vec<u64>,
+    data: Vec<u8>,
+}
+
+/// Dataspace message (version 2) of a scalar (no `dims`) or simple dataspace.
+fn dataspace(dims: &[u64]) -> Vec<u8> {
+    let kind = if dims.is_empty() { 0 } else { 1 };
+    let mut bytes = vec![2, dims.len() as u8, 0, kind];
+    for dim in dims {
+        bytes.extend_from_slice(&dim.to_le_bytes());
+    }
+    bytes
+}
+
+/// The file image, objects appended after the superblock as they are built.
+struct FileBuilder {
+    bytes: Vec<u8>,
+}
+
+impl FileBuilder {
+    fn new() -> Self {
+        Self { bytes: vec![0; SUPERBLOCK_LEN] }
+    }
+
+    /// Appends `data`, returning its address.
+    fn append(&mut self, data: &[u8]) -> u64 {
+        let address = self.bytes.len() as u64;
+        self.bytes.extend_from_slice(data);
+        address
+    }
+
+    /// Appends a version 2 object header holding `messages` (type, flags,
+    /// data), returning its address.
+    fn object_header(&mut self, messages: &[(u8, u8, Vec<u8>)]) -> u64 {
+        let mut chunk = Vec::new();
+        for (kind, flags, data) in messages {
+            chunk.push(*kind);
+            chunk.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            chunk.push(*flags);
+            chunk.extend_from_slice(data);
+        }
+        // Flags: the chunk size takes 4 bytes, nothing optional is stored
+        let mut header = b"OHDR".to_vec();
+        header.extend_from_slice(&[2, 0x02]);
+        header.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        header.extend_from_slice(&chunk);
+        header.extend_from_slice(&lookup3(&header).to_le_bytes());
+        self.append(&header)
+    }
+
+    /// Appends a contiguous dataset of `data` (little-endian elements) and its
+    /// object header, returning the address of the header.
+    fn dataset(&mut self, datatype: Datatype, dims: &[u64], data: &[u8], attributes: &[Attribute]) -> u64 {
+        let address = if data.is_empty() { UNDEFINED_ADDRESS } else { self.append(data) };
+        let mut layout = vec![3, 1];
+        layout.extend_from_slice(&address.to_le_bytes());
+        layout.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+        let mut messages = vec![
+            (MSG_DATASPACE, 0, dataspace(dims)),
+            (MSG_DATATYPE, MSG_CONSTANT, datatype.encode()),
+            // Version 3, allocated late, written if set, no fill value defined
+            (MSG_FILL_VALUE, MSG_CONSTANT, vec![3, 0x0A]),
+            (MSG_LAYOUT, 0, layout),
+        ];
+        for attribute in attributes {
+            let (datatype, dataspace) = (attribute.datatype.encode(), dataspace(&attribute.dims));
+            let mut message = vec![3, 0];
+            message.extend_from_slice(&(attribute.name.len() as u16 + 1).to_le_bytes());
+            message.extend_from_slice(&(datatype.len() as u16).to_le_bytes());
+            message.extend_from_slice(&(dataspace.len() as u16).to_le_bytes());
+            message.push(0);
+            message.extend_from_slice(attribute.name.as_bytes());
+            message.push(0);
+            message.extend_from_slice(&datatype);
+            message.extend_from_slice(&dataspace);
+            message.extend_from_slice(&attribute.data);
+            messages.push((MSG_ATTRIBUTE, 0, message));
+        }
+        self.object_header(&messages)
+    }
+
+    /// Appends the object header of a group with compact (in-header) links to
+    /// the objects at the given addresses, returning its address.
+    fn group(&mut self, links: &[(&str, u64)]) -> u64 {
+        // Version 0, no creation order, no fractal heap or name index
+        let mut link_info = vec![0, 0];
+        link_info.extend_from_slice(&UNDEFINED_ADDRESS.to_le_bytes());
+        link_info.extend_from_slice(&UNDEFINED_ADDRESS.to_le_bytes());
+
+        let mut messages = vec![(MSG_LINK_INFO, 0, link_info), (MSG_GROUP_INFO, 0, vec![0, 0])];
+        for (name, address) in links {
+            // Version 1, hard link, one-byte name length
+            let mut link = vec![1, 0, name.len() as u8];
+            link.extend_from_slice(name.as_bytes());
+            link.extend_from_slice(&address.to_le_bytes());
+            messages.push((MSG_LINK, 0, link));
+        }
+        self.object_header(&messages)
+    }
+
+    /// Fills in the version 2 superblock pointing at the root group.
+    fn finish(mut self, root: u64) -> Vec<u8> {
+        let end_of_file = self.bytes.len() as u64;
+        let mut superblock = SIGNATURE.to_vec();
+        // Version 2, 8-byte offsets and lengths, no consistency flags
+        superblock.extend_from_slice(&[2, 8, 8, 0]);
+        for address in [0, UNDEFINED_ADDRESS, end_of_file, root] {
+            superblock.extend_from_slice(&address.to_le_bytes());
+        }
+        superblock.extend_from_slice(&lookup3(&superblock).to_le_bytes());
+        self.bytes[..SUPERBLOCK_LEN].copy_from_slice(&superblock);
+        self.bytes
+    }
+}
+
+/// Bob Jenkins' lookup3 `hashlittle` with a zero seed, the checksum of HDF5
+/// metadata.
+fn lookup3(key: &[u8]) -> u32 {
+    fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *a = a.wrapping_sub(*c); *a ^= c.rotate_left(4); *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a); *b ^= a.rotate_left(6); *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b); *c ^= b.rotate_left(8); *b = b.wrapping_add(*a);
+        *a = a.wrapping_sub(*c); *a ^= c.rotate_left(16); *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a); *b ^= a.rotate_left(19); *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b); *c ^= b.rotate_left(4); *b = b.wrapping_add(*a);
+    }
+    fn finish(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *c ^= *b; *c = c.wrapping_sub(b.rotate_left(14));
+        *a ^= *c; *a = a.wrapping_sub(c.rotate_left(11));
+        *b ^= *a; *b = b.wrapping_sub(a.rotate_left(25));
+        *c ^= *b; *c = c.wrapping_sub(b.rotate_left(16));
+        *a ^= *c; *a = a.wrapping_sub(c.rotate_left(4));
+        *b ^= *a; *b = b.wrapping_sub(a.rotate_left(14));
+        *c ^= *b; *c = c.wrapping_sub(b.rotate_left(24));
+    }
+    let word = |bytes: &[u8]| bytes.iter().rev().fold(0u32, |word, &byte| (word << 8) | byte as u32);
+
+    let init = 0xdeadbeefu32.wrapping_add(key.len() as u32);
+    let (mut a, mut b, mut c) = (init, init, init);
+    let mut rest = key;
+    while rest.len() > 12 {
+        a = a.wrapping_add(word(&rest[0..4]));
+        b = b.wrapping_add(word(&rest[4..8]));
+        c = c.wrapping_add(word(&rest[8..12]));
+        mix(&mut a, &mut b, &mut c);
+        rest = &rest[12..];
+    }
+    if rest.is_empty() {
+        return c;
+    }
+    a = a.wrapping_add(word(&rest[..rest.len().min(4)]));
+    b = b.wrapping_add(word(rest.get(4..rest.len().min(8)).unwrap_or(&[])));
+    c = c.wrapping_add(word(rest.get(8..).unwrap_or(&[])));
+    finish(&mut a, &mut b, &mut c);
+    c
+}
@@ -0,0 +1,404 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{decode_data_with_config, decode_with_scheme, encode_data, encode_data_with_config, encode_with_scheme, CodecConfig, ModulationScheme};
+use crate::ecc::{EccConfig, EccScheme};
+use crate::noise::{apply_noise, NoiseProfile};
+use crate::qam::{CustomConstellation, QamScheme};
+use crate::scrambler::descramble;
+use std::f32::consts::PI;
+
+/// Version of the stream header layout written by this crate. Version 2 added
+/// the optional ECC and data length fields, version 3 the ECC block size,
+/// version 4 the ECC scheme, version 5 the scrambler seed, version 6 the magic
+/// number, the checksum and the QAM and custom constellation schemes. Headers
+/// before version 6 cannot be told apart from data reliably and are read as
+/// headerless data.
+pub const CODEC_VERSION: u8 = 6;
+
+// Preamble: dark voxels, like the framing sync marker, but with diagonal
+// polarizations so the two cannot be confused. Data voxels can look like this
+// too (dark QAM points, attenuated reads), so the preamble only says where to
+// look: the magic number and the checksum after it identify a header.
+const PREAMBLE_POLARIZATIONS: [f32; 3] = [PI / 4.0, 3.0 * PI / 4.0, PI / 4.0];
+const PREAMBLE_WAVELENGTH: f32 = 532.0;
+const PREAMBLE_POLARIZATION_TOLERANCE: f32 = PI / 8.0;
+const DARK_THRESHOLD: f32 = 0.5 / 4.0;
+const MAGIC: [u8; 4] = *b"PVHD";
+
+// Header fields after the magic, one byte per voxel (default codec): version,
+// scheme id, three scheme parameters, flags, palette length, then the palette
+// as little-endian f32s, the points of a custom constellation (intensity,
+// polarization, phase and wavelength as little-endian f32s), the ECC scheme
+// id, data and parity shard counts and the little-endian u32 bytes per shard
+// of a block (0: one block) if FLAG_ECC is set, the little-endian u64 data
+// length if FLAG_DATA_LEN is set, the little-endian u32 scrambler seed if
+// FLAG_SCRAMBLE is set and finally the little-endian CRC-32 of the magic and
+// all fields.
+//
+// Scheme parameters: log2 of the intensity, polarization and phase level
+// counts (levels), log2 of the order and of the polarization level count and
+// 0 (QAM), log2 of the point count and 0, 0 (custom constellation, no palette).
+const FIXED_FIELDS: usize = 7;
+const FLAG_GRAY_CODE: u8 = 1;
+const FLAG_ECC: u8 = 2;
+const FLAG_DATA_LEN: u8 = 4;
+const FLAG_SCRAMBLE: u8 = 8;
+const POINT_FIELDS: usize = 16;
+const ECC_FIELDS: usize = 7;
+const DATA_LEN_FIELDS: usize = 8;
+const SCRAMBLE_FIELDS: usize = 4;
+const CRC_FIELDS: usize = 4;
+
+const SCHEME_LEVELS: u8 = 0;
+const SCHEME_QAM: u8 = 1;
+const SCHEME_CONSTELLATION: u8 = 2;
+
+/// Longest possible header, in voxels (255-entry palette or 256-point
+/// constellation). Readers that stream from disk can buffer this many voxels
+/// before calling [`read_header`].
+pub const MAX_HEADER_VOXELS: usize = PREAMBLE_POLARIZATIONS.len()
+    + MAGIC.len()
+    + FIXED_FIELDS
+    + POINT_FIELDS * crate::qam::MAX_CONSTELLATION_POINTS
+    + ECC_FIELDS
+    + DATA_LEN_FIELDS
+    + SCRAMBLE_FIELDS
+    + CRC_FIELDS;
+
+/// Modulation scheme recorded in a stream header. It modulates and
+/// demodulates like the scheme it describes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemeDescriptor {
+    /// Per-dimension level quantization ([`CodecConfig`]).
+    Levels(CodecConfig),
+    /// Joint intensity and phase constellation ([`QamScheme`]).
+    Qam(QamScheme),
+    /// Point table ([`CustomConstellation`]).
+    Constellation(CustomConstellation),
+}
+
+impl SchemeDescriptor {
+    fn id(&self) -> u8 {
+        match self {
+            SchemeDescriptor::Levels(_) => SCHEME_LEVELS,
+            SchemeDescriptor::Qam(_) => SCHEME_QAM,
+            SchemeDescriptor::Constellation(_) => SCHEME_CONSTELLATION,
+        }
+    }
+
+    /// Checks the parameters of the described scheme.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SchemeDescriptor::Levels(config) => config.validate(),
+            SchemeDescriptor::Qam(qam) => qam.validate(),
+            SchemeDescriptor::Constellation(constellation) => constellation.validate(),
+        }
+    }
+
+    /// Modulates `data` with the described scheme.
+    ///
+    /// Panics if the scheme is invalid.
+    pub fn encode(&self, data: &[u8]) -> Vec<PhotonicVoxel> {
+        match self {
+            SchemeDescriptor::Levels(config) => encode_data_with_config(data, config),
+            scheme => {
+                scheme.validate().unwrap_or_else(|e| panic!("Invalid modulation scheme: {}", e));
+                encode_with_scheme(data, scheme)
+            }
+        }
+    }
+
+    /// Demodulates `voxels` with the described scheme, after adding readout
+    /// noise if `simulate_noise` is true.
+    pub fn decode(&self, voxels: &[PhotonicVoxel], simulate_noise: bool) -> Vec<u8> {
+        match self {
+            SchemeDescriptor::Levels(config) => decode_data_with_config(voxels, simulate_noise, config),
+            scheme if simulate_noise => decode_with_scheme(&apply_noise(voxels, NoiseProfile::default()), scheme),
+            scheme => decode_with_scheme(voxels, scheme),
+        }
+    }
+}
+
+impl ModulationScheme for SchemeDescriptor {
+    fn bits_per_voxel(&self) -> usize {
+        match self {
+            SchemeDescriptor::Levels(config) => config.bits_per_voxel(),
+            SchemeDescriptor::Qam(qam) => qam.bits_per_voxel(),
+            SchemeDescriptor::Constellation(constellation) => constellation.bits_per_voxel(),
+        }
+    }
+
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        match self {
+            SchemeDescriptor::Levels(config) => config.modulate(symbol),
+            SchemeDescriptor::Qam(qam) => qam.modulate(symbol),
+            SchemeDescriptor::Constellation(constellation) => constellation.modulate(symbol),
+        }
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        match self {
+            SchemeDescriptor::Levels(config) => config.demodulate(voxel),
+            SchemeDescriptor::Qam(qam) => qam.demodulate(voxel),
+            SchemeDescriptor::Constellation(constellation) => constellation.demodulate(voxel),
+        }
+    }
+
+    fn demodulate_soft(&self, voxel: &PhotonicVoxel, llrs: &mut Vec<f32>) {
+        match self {
+            SchemeDescriptor::Levels(config) => config.demodulate_soft(voxel, llrs),
+            SchemeDescriptor::Qam(qam) => qam.demodulate_soft(voxel, llrs),
+            SchemeDescriptor::Constellation(constellation) => constellation.demodulate_soft(voxel, llrs),
+        }
+    }
+}
+
+/// Codec identification written in front of the data voxels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamHeader {
+    pub version: u8,
+    pub scheme: SchemeDescriptor,
+    /// ECC layout of the data, if parity was added before modulation.
+    /// Requires `data_len`.
+    pub ecc: Option<EccConfig>,
+    /// Length of the original data in bytes, if known when the header was
+    /// written: decoders drop the ECC parity and symbol padding beyond it.
+    pub data_len: Option<u64>,
+    /// Seed of the [scrambler](crate::scrambler) that whitened the bytes
+    /// (after ECC, before modulation), if any: decoders descramble with it.
+    pub scramble: Option<u32>,
+}
+
+impl StreamHeader {
+    /// Header for a stream written by this version with `config`.
+    pub fn new(config: &CodecConfig) -> Self {
+        Self::with_scheme(SchemeDescriptor::Levels(config.clone()))
+    }
+
+    /// Header for a stream written by this version with any recorded scheme.
+    pub fn with_scheme(scheme: SchemeDescriptor) -> Self {
+        Self { version: CODEC_VERSION, scheme, ecc: None, data_len: None, scramble: None }
+    }
+
+    /// The header followed by `data` modulated with the recorded scheme. ECC
+    /// and scrambling, if recorded, are the caller's to apply to `data`.
+    ///
+    /// Panics like [`to_voxels`](Self::to_voxels) and if the scheme is invalid.
+    pub fn encode(&self, data: &[u8]) -> Vec<PhotonicVoxel> {
+        let mut voxels = self.to_voxels();
+        voxels.extend(self.scheme.encode(data));
+        voxels
+    }
+
+    /// Descrambles, checks, strips the parity of and truncates the decoded
+    /// stream `data` as described by the header.
+    ///
+    /// Fails if the recorded data length is more than `data` holds.
+    pub fn finish_data(&self, mut data: Vec<u8>) -> Result<Vec<u8>, String> {
+        if let Some(seed) = self.scramble {
+            data = descramble(&data, seed);
+        }
+        let data_len = match self.data_len {
+            Some(len) => match usize::try_from(len) {
+                Ok(len) if len <= data.len() => Some(len),
+                _ => return Err(format!("Stream header records {} bytes of data, the stream holds {}", len, data.len())),
+            },
+            None => None,
+        };
+        match (self.ecc, data_len) {
+            (Some(ecc), Some(len)) => ecc.recover(&data, len),
+            (Some(_), None) => Err("ECC header without data length".to_string()),
+            (None, Some(len)) => {
+                data.truncate(len);
+                Ok(data)
+            }
+            (None, None) => Ok(data),
+        }
+    }
+
+    /// Serializes the header into voxels: a dark preamble followed by the
+    /// magic number, the fields and their checksum, encoded with the default
+    /// codec so any reader can parse them.
+    ///
+    /// Panics if the palette has more than 255 wavelengths, the constellation
+    /// more than [`MAX_CONSTELLATION_POINTS`](crate::MAX_CONSTELLATION_POINTS)
+    /// points, the ECC more than 255 shards of either kind or blocks of 4 GiB
+    /// per shard.
+    pub fn to_voxels(&self) -> Vec<PhotonicVoxel> {
+        let (params, levels, points): ([usize; 3], Option<&CodecConfig>, &[PhotonicVoxel]) = match &self.scheme {
+            SchemeDescriptor::Levels(config) => ([config.intensity_levels, config.polarization_levels, config.phase_levels], Some(config), &[]),
+            SchemeDescriptor::Qam(qam) => ([qam.order, qam.levels.polarization_levels, 1], Some(&qam.levels), &[]),
+            SchemeDescriptor::Constellation(constellation) => {
+                assert!(constellation.points.len() <= crate::qam::MAX_CONSTELLATION_POINTS, "Constellation too large for the stream header");
+                ([constellation.points.len(), 1, 1], None, &constellation.points)
+            }
+        };
+        let palette = levels.map_or(&[][..], |config| &config.wavelengths);
+        assert!(palette.len() <= 255, "Palette too large for the stream header");
+
+        let mut flags = if levels.is_some_and(|config| config.gray_code) { FLAG_GRAY_CODE } else { 0 };
+        if self.ecc.is_some() {
+            flags |= FLAG_ECC;
+        }
+        if self.data_len.is_some() {
+            flags |= FLAG_DATA_LEN;
+        }
+        if self.scramble.is_some() {
+            flags |= FLAG_SCRAMBLE;
+        }
+        let mut fields = MAGIC.to_vec();
+        fields.extend([self.version, self.scheme.id()]);
+        fields.extend(params.map(|count| count.trailing_zeros() as u8));
+        fields.extend([flags, palette.len() as u8]);
+        for wavelength in palette {
+            fields.extend_from_slice(&wavelength.to_le_bytes());
+        }
+        for point in points {
+            for value in [point.intensity, point.polarization, point.phase, point.wavelength] {
+                fields.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if let Some(ecc) = self.ecc {
+            fields.push(ecc.scheme.id());
+            for shards in [ecc.data_shards, ecc.parity_shards] {
+                fields.push(u8::try_from(shards).expect("Too many ECC shards for the stream header"));
+            }
+            let block_shard_len = u32::try_from(ecc.block_shard_len.unwrap_or(0)).expect("ECC blocks too large for the stream header");
+            fields.extend_from_slice(&block_shard_len.to_le_bytes());
+        }
+        if let Some(len) = self.data_len {
+            fields.extend_from_slice(&len.to_le_bytes());
+        }
+        if let Some(seed) = self.scramble {
+            fields.extend_from_slice(&seed.to_le_bytes());
+        }
+        fields.extend_from_slice(&crc32fast::hash(&fields).to_le_bytes());
+
+        let mut voxels: Vec<PhotonicVoxel> = PREAMBLE_POLARIZATIONS
+            .iter()
+            .map(|&polarization| PhotonicVoxel::new(0.0, polarization, 0.0, PREAMBLE_WAVELENGTH))
+            .collect();
+        voxels.extend(encode_data(&fields));
+        voxels
+    }
+}
+
+/// Reads the header fields one byte per voxel, keeping what it read for the checksum.
+struct FieldReader<'a> {
+    voxels: &'a [PhotonicVoxel],
+    simulate_noise: bool,
+    pos: usize,
+    read: Vec<u8>,
+}
+
+impl FieldReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let voxels = self.voxels.get(self.pos..self.pos + len).ok_or_else(|| "Truncated stream header".to_string())?;
+        let start = self.read.len();
+        self.read.extend(decode_data_with_config(voxels, self.simulate_noise, &CodecConfig::default()));
+        self.pos += len;
+        Ok(&self.read[start..])
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("N field bytes"))
+    }
+}
+
+/// Looks for a header at the start of `voxels`.
+///
+/// Returns `Ok(None)` if the stream does not start with the preamble and the
+/// magic number, or the header and the number of voxels it occupies. Fails if
+/// the magic is present but the header is truncated, fails its checksum, is
+/// from a newer version or describes an invalid configuration.
+pub fn read_header(voxels: &[PhotonicVoxel], simulate_noise: bool) -> Result<Option<(StreamHeader, usize)>, String> {
+    if !has_preamble(voxels) {
+        return Ok(None);
+    }
+    let mut reader = FieldReader { voxels, simulate_noise, pos: PREAMBLE_POLARIZATIONS.len(), read: Vec::new() };
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Ok(None);
+    }
+
+    let [version, scheme_id, a, b, c, flags, palette_len] = reader.take_array()?;
+    if version == 0 || version > CODEC_VERSION {
+        return Err(format!("Unsupported codec version {} (this build reads up to {})", version, CODEC_VERSION));
+    }
+    let palette: Vec<f32> = reader.take(4 * palette_len as usize)?.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+    let points = if scheme_id == SCHEME_CONSTELLATION {
+        let count = 1usize.checked_shl(a as u32).filter(|&count| count <= crate::qam::MAX_CONSTELLATION_POINTS).ok_or("Constellation too large in stream header")?;
+        let values: Vec<f32> = reader.take(POINT_FIELDS * count)?.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        values.chunks_exact(4).map(|v| PhotonicVoxel::new(v[0], v[1], v[2], v[3])).collect()
+    } else {
+        Vec::new()
+    };
+    let ecc = if flags & FLAG_ECC != 0 {
+        let [id, data_shards, parity_shards, l0, l1, l2, l3] = reader.take_array()?;
+        let block_shard_len = u32::from_le_bytes([l0, l1, l2, l3]) as usize;
+        Some((id, data_shards as usize, parity_shards as usize, (block_shard_len > 0).then_some(block_shard_len)))
+    } else {
+        None
+    };
+    let data_len = if flags & FLAG_DATA_LEN != 0 { Some(u64::from_le_bytes(reader.take_array()?)) } else { None };
+    let scramble = if flags & FLAG_SCRAMBLE != 0 { Some(u32::from_le_bytes(reader.take_array()?)) } else { None };
+    let checksum = crc32fast::hash(&reader.read);
+    if u32::from_le_bytes(reader.take_array()?) != checksum {
+        return Err("Stream header checksum mismatch".to_string());
+    }
+
+    if [a, b, c].iter().any(|&bits| bits as u32 >= usize::BITS) {
+        return Err("Invalid level count in stream header".to_string());
+    }
+    let levels = |intensity_levels, phase_levels| CodecConfig {
+        intensity_levels,
+        polarization_levels: 1 << b,
+        phase_levels,
+        wavelengths: palette.clone(),
+        gray_code: flags & FLAG_GRAY_CODE != 0,
+    };
+    let scheme = match scheme_id {
+        SCHEME_LEVELS => SchemeDescriptor::Levels(levels(1 << a, 1 << c)),
+        SCHEME_QAM => SchemeDescriptor::Qam(QamScheme { order: 1 << a, levels: levels(1, 1) }),
+        SCHEME_CONSTELLATION => SchemeDescriptor::Constellation(CustomConstellation::new(points)),
+        id => return Err(format!("Unknown modulation scheme id {}", id)),
+    };
+    scheme.validate().map_err(|e| format!("Invalid codec config in stream header: {}", e))?;
+
+    let ecc = match ecc {
+        Some((id, data_shards, parity_shards, block_shard_len)) => {
+            let scheme = EccScheme::from_id(id).ok_or_else(|| format!("Unknown ECC scheme id {} in stream header", id))?;
+            let ecc = EccConfig { scheme, data_shards, parity_shards, block_shard_len };
+            ecc.validate().map_err(|e| format!("Invalid ECC parameters in stream header: {}", e))?;
+            Some(ecc)
+        }
+        None => None,
+    };
+
+    let header = StreamHeader { version, scheme, ecc, data_len, scramble };
+    Ok(Some((header, reader.pos)))
+}
+
+/// Encodes `data` with `config`, preceded by a header recording the codec so
+/// [`decode_data`](crate::decode_data) can pick the right demodulator.
+pub fn encode_with_header(data: &[u8], config: &CodecConfig) -> Vec<PhotonicVoxel> {
+    StreamHeader::new(config).encode(data)
+}
+
+/// Decodes a stream, using the scheme recorded in its header if it has one and
+/// the default codec otherwise. If the header records a scrambler seed, ECC
+/// parameters or the data length, the data is descrambled, the parity checked
+/// and stripped and the data truncated.
+pub fn decode_auto(voxels: &[PhotonicVoxel], simulate_noise: bool) -> Result<Vec<u8>, String> {
+    match read_header(voxels, simulate_noise)? {
+        Some((header, len)) => header.finish_data(header.scheme.decode(&voxels[len..], simulate_noise)),
+        None => Ok(decode_data_with_config(voxels, simulate_noise, &CodecConfig::default())),
+    }
+}
+
+fn has_preamble(voxels: &[PhotonicVoxel]) -> bool {
+    voxels.len() >= PREAMBLE_POLARIZATIONS.len()
+        && PREAMBLE_POLARIZATIONS.iter().zip(voxels).all(|(&angle, v)| {
+            let mut dist = (v.polarization - angle).rem_euclid(PI);
+            dist = dist.min(PI - dist);
+            v.intensity < DARK_THRESHOLD && dist < PREAMBLE_POLARIZATION_TOLERANCE
+        })
+}
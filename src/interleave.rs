@@ -0,0 +1,88 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{decode_data_with_config, encode_data_with_config, CodecConfig};
+use crate::ecc::EccConfig;
+
+/// Block interleaver placed between the ECC layer and the modulator.
+///
+/// Bytes are written row by row into blocks of `depth` rows of `width` bytes
+/// and read out column by column, so bytes `width` apart end up adjacent.
+///
+/// ECC blocks are laid out shard by shard: codeword `j` of a block is byte `j`
+/// of every shard, so up to `block_shard_len` consecutive bytes of a block
+/// belong to different codewords. [`Interleaver::for_ecc`] makes every row one
+/// encoded ECC block; a burst of up to `depth * block_shard_len` consecutive
+/// damaged bytes then hits every codeword of the full blocks at most once,
+/// where without interleaving only bursts of up to `block_shard_len` bytes do.
+/// With single-byte shards (`block_shard_len: Some(1)`) every row is one
+/// codeword and the burst can be `depth` bytes long.
+///
+/// Interleaving preserves the length: a final partial block is permuted over
+/// the rows it actually fills. [`Interleaver::deinterleave`] therefore needs
+/// exactly the interleaved byte count back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interleaver {
+    pub depth: usize,
+    pub width: usize,
+}
+
+impl Interleaver {
+    /// Panics if `depth` or `width` is zero.
+    pub fn new(depth: usize, width: usize) -> Self {
+        assert!(depth > 0 && width > 0, "Interleaver depth and width must be at least one");
+        Self { depth, width }
+    }
+
+    /// Interleaver of `depth` rows of one encoded block of `ecc` each.
+    ///
+    /// Fails if `ecc` is invalid or protects the data as one block
+    /// (`block_shard_len: None`), whose shards already span the whole stream.
+    pub fn for_ecc(depth: usize, ecc: &EccConfig) -> Result<Self, String> {
+        ecc.validate()?;
+        if depth == 0 {
+            return Err("Interleaver depth must be at least one".to_string());
+        }
+        let block_shard_len = ecc.block_shard_len.ok_or("Interleaving needs ECC blocks of a fixed size")?;
+        Ok(Self::new(depth, block_shard_len * ecc.total_shards()))
+    }
+
+    /// Reorders `data` for transmission.
+    pub fn interleave(&self, data: &[u8]) -> Vec<u8> {
+        self.permutation(data.len()).map(|i| data[i]).collect()
+    }
+
+    /// Restores the order of bytes produced by [`Interleaver::interleave`].
+    pub fn deinterleave(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; data.len()];
+        for (&byte, i) in data.iter().zip(self.permutation(data.len())) {
+            out[i] = byte;
+        }
+        out
+    }
+
+    /// Input index of each output byte, for a stream of `len` bytes.
+    fn permutation(&self, len: usize) -> impl Iterator<Item = usize> + '_ {
+        let block = self.depth * self.width;
+        (0..len).step_by(block).flat_map(move |offset| {
+            let size = block.min(len - offset);
+            (0..self.width)
+                .flat_map(move |col| (0..self.depth).map(move |row| row * self.width + col))
+                .filter(move |&idx| idx < size)
+                .map(move |idx| offset + idx)
+        })
+    }
+}
+
+/// Interleaves `data` and modulates it with `config`.
+pub fn encode_interleaved(data: &[u8], interleaver: &Interleaver, config: &CodecConfig) -> Vec<PhotonicVoxel> {
+    encode_data_with_config(&interleaver.interleave(data), config)
+}
+
+/// Demodulates voxels written with [`encode_interleaved`] and restores the
+/// byte order. `len` is the number of bytes that were encoded; it drops the
+/// codec's padding before deinterleaving.
+pub fn decode_interleaved(voxels: &[PhotonicVoxel], simulate_noise: bool, len: usize, interleaver: &Interleaver, config: &CodecConfig) -> Vec<u8> {
+    let mut received = decode_data_with_config(voxels, simulate_noise, config);
+    received.truncate(len);
+    interleaver.deinterleave(&received)
+}
@@ -0,0 +1,140 @@
+use crate::structs::PhotonicVoxel;
+use num_complex::Complex32;
+use std::f32::consts::PI;
+
+/// Full polarization state of a voxel as a Jones vector: the complex field
+/// components `ex` and `ey` (amplitude and phase along x and y).
+///
+/// Unlike the single angle of [`PhotonicVoxel`], it represents elliptical and
+/// circular polarization, and optical elements act on it as 2x2 matrices
+/// ([`JonesMatrix`]). Conversion from the simple model gives linear
+/// polarization: `sqrt(intensity) * e^(i * phase) * (cos θ, sin θ)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JonesVoxel {
+    pub ex: Complex32,
+    pub ey: Complex32,
+    /// Wavelength in nanometers (nm).
+    pub wavelength: f32,
+}
+
+impl JonesVoxel {
+    pub fn new(ex: Complex32, ey: Complex32, wavelength: f32) -> Self {
+        Self { ex, ey, wavelength }
+    }
+
+    /// Elliptical polarization with the major axis at `orientation` (radians
+    /// from x) and ellipticity angle `ellipticity` in [-PI/4, PI/4]: 0 is linear,
+    /// PI/4 circular (positive S3, see [`ellipticity`](Self::ellipticity)).
+    pub fn elliptical(intensity: f32, orientation: f32, ellipticity: f32, phase: f32, wavelength: f32) -> Self {
+        let field = Complex32::from_polar(intensity.max(0.0).sqrt(), phase);
+        let (sin_o, cos_o) = orientation.sin_cos();
+        let (sin_e, cos_e) = ellipticity.sin_cos();
+        Self {
+            ex: field * Complex32::new(cos_o * cos_e, -sin_o * sin_e),
+            ey: field * Complex32::new(sin_o * cos_e, cos_o * sin_e),
+            wavelength,
+        }
+    }
+
+    /// Total intensity `|Ex|² + |Ey|²`.
+    pub fn intensity(&self) -> f32 {
+        self.ex.norm_sqr() + self.ey.norm_sqr()
+    }
+
+    /// Orientation of the polarization ellipse's major axis, in [0, PI).
+    pub fn orientation(&self) -> f32 {
+        let s1 = self.ex.norm_sqr() - self.ey.norm_sqr();
+        let s2 = 2.0 * (self.ex.conj() * self.ey).re;
+        let orientation = (0.5 * s2.atan2(s1)).rem_euclid(PI);
+        if orientation >= PI { 0.0 } else { orientation }
+    }
+
+    /// Ellipticity angle in [-PI/4, PI/4]: `tan` of it is the ratio of the
+    /// ellipse's minor to major axis, its sign the handedness (sign of S3).
+    pub fn ellipticity(&self) -> f32 {
+        let s0 = self.intensity();
+        if s0 <= 0.0 {
+            return 0.0;
+        }
+        let s3 = 2.0 * (self.ex.conj() * self.ey).im;
+        0.5 * (s3 / s0).clamp(-1.0, 1.0).asin()
+    }
+
+    /// Phase of the field along the ellipse's major axis, in [0, 2PI).
+    pub fn phase(&self) -> f32 {
+        let (sin_o, cos_o) = self.orientation().sin_cos();
+        let major = self.ex * cos_o + self.ey * sin_o;
+        let phase = major.arg().rem_euclid(2.0 * PI);
+        if phase >= 2.0 * PI { 0.0 } else { phase }
+    }
+
+    /// Projects back onto the simple model: intensity, ellipse orientation as
+    /// the polarization angle and major-axis phase. The ellipticity is lost, so
+    /// this is exact only for linear polarization.
+    pub fn to_voxel(&self) -> PhotonicVoxel {
+        PhotonicVoxel::new(self.intensity(), self.orientation(), self.phase(), self.wavelength)
+    }
+
+    /// The state after passing through `element`.
+    pub fn apply(&self, element: &JonesMatrix) -> Self {
+        let [[a, b], [c, d]] = element.0;
+        Self { ex: a * self.ex + b * self.ey, ey: c * self.ex + d * self.ey, wavelength: self.wavelength }
+    }
+}
+
+impl From<PhotonicVoxel> for JonesVoxel {
+    /// Linear polarization at the voxel's angle.
+    fn from(voxel: PhotonicVoxel) -> Self {
+        Self::elliptical(voxel.intensity, voxel.polarization, 0.0, voxel.phase, voxel.wavelength)
+    }
+}
+
+impl From<JonesVoxel> for PhotonicVoxel {
+    fn from(voxel: JonesVoxel) -> Self {
+        voxel.to_voxel()
+    }
+}
+
+/// A linear optical element acting on Jones vectors, as a 2x2 complex matrix
+/// (row-major).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JonesMatrix(pub [[Complex32; 2]; 2]);
+
+impl JonesMatrix {
+    pub fn identity() -> Self {
+        let (one, zero) = (Complex32::new(1.0, 0.0), Complex32::new(0.0, 0.0));
+        Self([[one, zero], [zero, one]])
+    }
+
+    /// Rotation of the frame by `angle` (radians).
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self([[Complex32::new(cos, 0.0), Complex32::new(-sin, 0.0)], [Complex32::new(sin, 0.0), Complex32::new(cos, 0.0)]])
+    }
+
+    /// Linear retarder (birefringent element) with its slow axis at `axis`
+    /// radians from x, delaying the slow component by `retardance` radians
+    /// relative to the fast one: PI/2 for a quarter-wave plate, PI for a half-wave plate.
+    pub fn retarder(axis: f32, retardance: f32) -> Self {
+        let zero = Complex32::new(0.0, 0.0);
+        let delay = Self([
+            [Complex32::from_polar(1.0, retardance / 2.0), zero],
+            [zero, Complex32::from_polar(1.0, -retardance / 2.0)],
+        ]);
+        Self::rotation(axis).after(&delay).after(&Self::rotation(-axis))
+    }
+
+    /// Ideal linear polarizer transmitting along `axis` radians from x.
+    pub fn polarizer(axis: f32) -> Self {
+        let (sin, cos) = axis.sin_cos();
+        let c = |v: f32| Complex32::new(v, 0.0);
+        Self([[c(cos * cos), c(cos * sin)], [c(cos * sin), c(sin * sin)]])
+    }
+
+    /// This element placed after `first`: the matrix product `self * first`.
+    pub fn after(&self, first: &Self) -> Self {
+        let (a, b) = (self.0, first.0);
+        let entry = |i: usize, j: usize| a[i][0] * b[0][j] + a[i][1] * b[1][j];
+        Self([[entry(0, 0), entry(0, 1)], [entry(1, 0), entry(1, 1)]])
+    }
+}
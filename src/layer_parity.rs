@@ -0,0 +1,148 @@
+use crate::address::LatticeGeometry;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+/// Parity layers across the z-axis of a lattice, like RAID-5/6 across disks.
+///
+/// The stream is cut into layers of `layer_bytes` bytes, and every
+/// `data_layers` consecutive data layers are followed by `parity_layers`
+/// layers of Reed-Solomon parity computed position by position across them:
+/// byte `i` of a parity layer protects byte `i` of each data layer of its
+/// group, which is stored at the same (x, y) when the layers are whole voxel
+/// layers ([`for_geometry`](Self::for_geometry)). Any `parity_layers` layers
+/// of a group can be lost entirely, e.g. to a delaminated or mis-focused layer,
+/// and rebuilt from the others once it is known which ones they are: one
+/// parity layer per group protects like RAID-5, two like RAID-6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerParity {
+    /// Bytes per layer.
+    pub layer_bytes: usize,
+    /// Data layers per parity group.
+    pub data_layers: usize,
+    /// Parity layers per parity group: how many layers of a group can be lost.
+    pub parity_layers: usize,
+}
+
+impl LayerParity {
+    /// Parity across the voxel layers of `geometry`. Fails if a layer does not
+    /// hold a whole number of bytes, as then no byte position is the same voxel
+    /// position in every layer.
+    pub fn for_geometry(geometry: &LatticeGeometry, data_layers: usize, parity_layers: usize) -> Result<Self, String> {
+        geometry.validate()?;
+        let layer_bits = geometry.width * geometry.height * geometry.bits_per_voxel;
+        if !layer_bits.is_multiple_of(8) {
+            return Err(format!("Layers of {}x{} voxels of {} bits do not hold whole bytes", geometry.width, geometry.height, geometry.bits_per_voxel));
+        }
+        let parity = Self { layer_bytes: layer_bits / 8, data_layers, parity_layers };
+        parity.validate()?;
+        Ok(parity)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.layer_bytes == 0 {
+            return Err("Layers must hold at least one byte".to_string());
+        }
+        if self.data_layers == 0 || self.parity_layers == 0 {
+            return Err(format!("Parity groups need data and parity layers (got {}+{})", self.data_layers, self.parity_layers));
+        }
+        if self.group_layers() > 256 {
+            return Err(format!("Parity groups have at most 256 layers (got {})", self.group_layers()));
+        }
+        Ok(())
+    }
+
+    /// Layers per parity group, data and parity.
+    pub fn group_layers(&self) -> usize {
+        self.data_layers + self.parity_layers
+    }
+
+    /// Length of `data_len` bytes once encoded: whole groups, the last data
+    /// layers zero-padded.
+    pub fn encoded_len(&self, data_len: usize) -> usize {
+        data_len.div_ceil(self.layer_bytes * self.data_layers) * self.group_layers() * self.layer_bytes
+    }
+
+    /// Whether layer `layer` of the encoded stream is a parity layer.
+    pub fn is_parity_layer(&self, layer: usize) -> bool {
+        layer % self.group_layers() >= self.data_layers
+    }
+
+    /// Splits `data` into groups of data layers, each followed by its parity
+    /// layers.
+    ///
+    /// Panics if the config is invalid.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let rs = self.coder();
+        let mut encoded = Vec::with_capacity(self.encoded_len(data.len()));
+        for group in data.chunks(self.layer_bytes * self.data_layers) {
+            let mut layers: Vec<Vec<u8>> = group.chunks(self.layer_bytes).map(<[u8]>::to_vec).collect();
+            layers.resize(self.group_layers(), Vec::new());
+            for layer in &mut layers {
+                layer.resize(self.layer_bytes, 0);
+            }
+            rs.encode(&mut layers).expect("layers sized for the coder");
+            encoded.extend(layers.concat());
+        }
+        encoded
+    }
+
+    /// Rebuilds the layers `lost` (indices into the encoded stream, parity
+    /// layers included) of `encoded` in place from the rest of their groups.
+    ///
+    /// Fails if `encoded` is not whole groups, a lost layer is outside it, or
+    /// a group lost more layers than it has parity layers.
+    ///
+    /// Panics if the config is invalid.
+    pub fn reconstruct(&self, encoded: &mut [u8], lost: &[usize]) -> Result<(), String> {
+        let rs = self.coder();
+        let group_len = self.group_layers() * self.layer_bytes;
+        if !encoded.len().is_multiple_of(group_len) {
+            return Err(format!("{} bytes are not whole groups of {} layers of {} bytes", encoded.len(), self.group_layers(), self.layer_bytes));
+        }
+        let layer_count = encoded.len() / self.layer_bytes;
+        if let Some(&layer) = lost.iter().find(|&&layer| layer >= layer_count) {
+            return Err(format!("Layer {} is outside the {} encoded layers", layer, layer_count));
+        }
+
+        for (group, bytes) in encoded.chunks_mut(group_len).enumerate() {
+            let first = group * self.group_layers();
+            let lost_here = |layer: usize| lost.contains(&(first + layer));
+            if !(0..self.group_layers()).any(lost_here) {
+                continue;
+            }
+            let mut layers: Vec<Option<Vec<u8>>> =
+                bytes.chunks(self.layer_bytes).enumerate().map(|(layer, bytes)| (!lost_here(layer)).then(|| bytes.to_vec())).collect();
+            rs.reconstruct(&mut layers)
+                .map_err(|_| format!("Group {} (layers {}..{}) lost more than {} layers", group, first, first + self.group_layers(), self.parity_layers))?;
+            for (target, layer) in bytes.chunks_mut(self.layer_bytes).zip(layers) {
+                target.copy_from_slice(&layer.expect("reconstructed layer"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The first `data_len` data bytes of `encoded`, after rebuilding the
+    /// layers `lost` (see [`reconstruct`](Self::reconstruct)).
+    pub fn decode(&self, encoded: &[u8], lost: &[usize], data_len: usize) -> Result<Vec<u8>, String> {
+        let mut encoded = encoded.to_vec();
+        self.reconstruct(&mut encoded, lost)?;
+        let mut data: Vec<u8> = encoded
+            .chunks(self.layer_bytes)
+            .enumerate()
+            .filter(|(layer, _)| !self.is_parity_layer(*layer))
+            .flat_map(|(_, bytes)| bytes.iter().copied())
+            .collect();
+        if data.len() < data_len {
+            return Err(format!("{} encoded bytes hold only {} data bytes, not {}", encoded.len(), data.len(), data_len));
+        }
+        data.truncate(data_len);
+        Ok(data)
+    }
+
+    fn coder(&self) -> ReedSolomon {
+        if let Err(e) = self.validate() {
+            panic!("Invalid layer parity config: {}", e);
+        }
+        ReedSolomon::new(self.data_layers, self.parity_layers).expect("validated layer counts")
+    }
+}
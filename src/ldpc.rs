@@ -0,0 +1,238 @@
+use crate::codec::SoftByte;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// Scale applied to check-to-bit messages by the normalized min-sum decoder,
+/// making up for min-sum overestimating their reliability.
+const MIN_SUM_SCALE: f32 = 0.75;
+
+/// Largest channel LLR magnitude taken from the demodulator, so that saturated
+/// inputs stay finite through the message sums.
+const MAX_CHANNEL_LLR: f32 = 1.0e4;
+
+/// Seed of the parity-check matrix construction: encoder and decoder built
+/// from the same config share the same graph.
+const GRAPH_SEED: u64 = 0x4C44_5043;
+
+/// Shape of a systematic irregular repeat-accumulate LDPC code.
+///
+/// Every codeword is `data_bits` data bits followed by `parity_bits` parity
+/// bits. Each data bit takes part in `column_weight` parity checks and the
+/// parity bits form an accumulator chain (a dual-diagonal parity part, as in
+/// DVB-S2), so encoding is linear time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LdpcConfig {
+    /// Data bits per codeword, a multiple of 8.
+    pub data_bits: usize,
+    /// Parity bits per codeword, a multiple of 8.
+    pub parity_bits: usize,
+    /// Parity checks every data bit takes part in.
+    pub column_weight: usize,
+    /// Decoder iterations before giving up on a codeword.
+    pub max_iterations: usize,
+}
+
+impl Default for LdpcConfig {
+    /// Rate 1/2 with 1024 data bits per codeword.
+    fn default() -> Self {
+        Self { data_bits: 1024, parity_bits: 1024, column_weight: 3, max_iterations: 50 }
+    }
+}
+
+impl LdpcConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data_bits == 0 || self.parity_bits == 0 {
+            return Err("LDPC codewords need data and parity bits".to_string());
+        }
+        if !self.data_bits.is_multiple_of(8) || !self.parity_bits.is_multiple_of(8) {
+            return Err(format!("LDPC data and parity bits must be whole bytes (got {}+{})", self.data_bits, self.parity_bits));
+        }
+        if self.column_weight == 0 || self.column_weight > self.parity_bits {
+            return Err(format!("LDPC column weight must be in 1..={} (got {})", self.parity_bits, self.column_weight));
+        }
+        if self.max_iterations == 0 {
+            return Err("LDPC decoding needs at least one iteration".to_string());
+        }
+        Ok(())
+    }
+
+    /// Data bits per codeword bit.
+    pub fn rate(&self) -> f64 {
+        self.data_bits as f64 / (self.data_bits + self.parity_bits) as f64
+    }
+
+    /// Length of `data_len` bytes once encoded: whole codewords, the last one
+    /// zero-padded.
+    pub fn encoded_len(&self, data_len: usize) -> usize {
+        data_len.div_ceil(self.data_bits / 8) * (self.data_bits + self.parity_bits) / 8
+    }
+}
+
+/// Data and outcome of [`LdpcCode::decode_soft`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdpcDecoded {
+    /// The decoded data, with the hard decisions of codewords that did not
+    /// converge.
+    pub data: Vec<u8>,
+    /// Codewords whose parity checks were still failing after the last
+    /// iteration.
+    pub failed_codewords: Vec<usize>,
+}
+
+/// LDPC encoder and soft-decision decoder for an [`LdpcConfig`].
+///
+/// Built once per config: the parity-check graph is derived deterministically
+/// from it, so codes built from equal configs are interchangeable.
+#[derive(Debug, Clone)]
+pub struct LdpcCode {
+    config: LdpcConfig,
+    /// Data bits of every parity check.
+    check_data: Vec<Vec<usize>>,
+    /// Codeword bits of every parity check (data bits, then the parity bits of
+    /// the check and of the one before it), flattened, with the offset of
+    /// each check in `check_start`.
+    edges: Vec<usize>,
+    check_start: Vec<usize>,
+}
+
+impl LdpcCode {
+    /// Builds the parity-check graph of `config`.
+    pub fn new(config: &LdpcConfig) -> Result<Self, String> {
+        config.validate()?;
+        let (data_bits, parity_bits, weight) = (config.data_bits, config.parity_bits, config.column_weight);
+
+        // Spread the data bit connections evenly over the checks, then shuffle
+        // them into columns of distinct checks
+        let mut rng = StdRng::seed_from_u64(GRAPH_SEED ^ (data_bits as u64) << 32 ^ parity_bits as u64);
+        let mut sockets: Vec<usize> = (0..data_bits * weight).map(|socket| socket % parity_bits).collect();
+        sockets.shuffle(&mut rng);
+        for column in 0..data_bits {
+            let columns = column * weight..(column + 1) * weight;
+            for socket in columns.clone() {
+                while sockets[columns.start..socket].contains(&sockets[socket]) {
+                    let other = rng.random_range(0..sockets.len());
+                    let start = other - other % weight;
+                    if !sockets[start..start + weight].contains(&sockets[socket]) || start == columns.start {
+                        sockets.swap(socket, other);
+                    }
+                }
+            }
+        }
+
+        let mut check_data = vec![Vec::new(); parity_bits];
+        for (socket, &check) in sockets.iter().enumerate() {
+            check_data[check].push(socket / weight);
+        }
+        let (mut edges, mut check_start) = (Vec::new(), Vec::with_capacity(parity_bits + 1));
+        for (check, bits) in check_data.iter().enumerate() {
+            check_start.push(edges.len());
+            edges.extend(bits);
+            edges.push(data_bits + check);
+            if check > 0 {
+                edges.push(data_bits + check - 1);
+            }
+        }
+        check_start.push(edges.len());
+
+        Ok(Self { config: *config, check_data, edges, check_start })
+    }
+
+    pub fn config(&self) -> &LdpcConfig {
+        &self.config
+    }
+
+    /// Encodes `data` into codewords of the data bytes followed by the parity
+    /// bytes (bits least significant first). The last codeword is zero-padded.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let data_len = self.config.data_bits / 8;
+        let mut encoded = Vec::with_capacity(self.config.encoded_len(data.len()));
+        for chunk in data.chunks(data_len) {
+            let mut block = chunk.to_vec();
+            block.resize(data_len, 0);
+            let bit = |index: usize| block[index / 8] >> (index % 8) & 1;
+
+            // Accumulator: every parity bit closes its check with the one before
+            let mut parity = vec![0u8; self.config.parity_bits / 8];
+            let mut accumulator = 0;
+            for (check, bits) in self.check_data.iter().enumerate() {
+                accumulator ^= bits.iter().fold(0, |sum, &index| sum ^ bit(index));
+                parity[check / 8] |= accumulator << (check % 8);
+            }
+            encoded.extend(block);
+            encoded.extend(parity);
+        }
+        encoded
+    }
+
+    /// Decodes the soft demodulator output of encoded bytes
+    /// ([`decode_soft_with_scheme`](crate::decode_soft_with_scheme)) back into
+    /// `data_len` bytes with layered normalized min-sum belief propagation.
+    ///
+    /// Bytes missing at the end of `soft` are treated as erased. Codewords that
+    /// do not converge keep their hard decisions and are reported.
+    pub fn decode_soft(&self, soft: &[SoftByte], data_len: usize) -> LdpcDecoded {
+        let codeword_bytes = (self.config.data_bits + self.config.parity_bits) / 8;
+        let codewords = data_len.div_ceil(self.config.data_bits / 8);
+        let mut data = Vec::with_capacity(codewords * self.config.data_bits / 8);
+        let mut failed_codewords = Vec::new();
+
+        for codeword in 0..codewords {
+            let mut posterior: Vec<f32> = (codeword * codeword_bytes..(codeword + 1) * codeword_bytes)
+                .flat_map(|byte| soft.get(byte).map_or([0.0; 8], |soft| soft.llrs))
+                .map(|llr| llr.clamp(-MAX_CHANNEL_LLR, MAX_CHANNEL_LLR))
+                .collect();
+            if !self.decode_codeword(&mut posterior) {
+                failed_codewords.push(codeword);
+            }
+            data.extend(posterior[..self.config.data_bits].chunks_exact(8).map(|bits| {
+                bits.iter().enumerate().fold(0u8, |byte, (bit, &llr)| if llr < 0.0 { byte | (1 << bit) } else { byte })
+            }));
+        }
+        data.truncate(data_len);
+        LdpcDecoded { data, failed_codewords }
+    }
+
+    /// Runs belief propagation on the LLRs of one codeword, in place. Returns
+    /// whether all parity checks are satisfied.
+    fn decode_codeword(&self, posterior: &mut [f32]) -> bool {
+        let mut messages = vec![0.0f32; self.edges.len()];
+        let mut incoming = Vec::new();
+        for _ in 0..self.config.max_iterations {
+            if self.satisfied(posterior) {
+                return true;
+            }
+            for check in 0..self.config.parity_bits {
+                let range = self.check_start[check]..self.check_start[check + 1];
+
+                // Bit-to-check messages: the posterior without this check's own
+                incoming.clear();
+                incoming.extend(range.clone().map(|edge| posterior[self.edges[edge]] - messages[edge]));
+                let (mut min1, mut min2, mut min_at, mut negative) = (f32::MAX, f32::MAX, 0, false);
+                for (at, &llr) in incoming.iter().enumerate() {
+                    negative ^= llr < 0.0;
+                    if llr.abs() < min1 {
+                        (min2, min1, min_at) = (min1, llr.abs(), at);
+                    } else if llr.abs() < min2 {
+                        min2 = llr.abs();
+                    }
+                }
+
+                // Check-to-bit messages: the least reliable of the other bits,
+                // with the sign that satisfies the check
+                for (at, edge) in range.enumerate() {
+                    let magnitude = MIN_SUM_SCALE * if at == min_at { min2 } else { min1 };
+                    let message = if negative ^ (incoming[at] < 0.0) { -magnitude } else { magnitude };
+                    messages[edge] = message;
+                    posterior[self.edges[edge]] = incoming[at] + message;
+                }
+            }
+        }
+        self.satisfied(posterior)
+    }
+
+    fn satisfied(&self, posterior: &[f32]) -> bool {
+        self.check_start
+            .windows(2)
+            .all(|check| self.edges[check[0]..check[1]].iter().filter(|&&bit| posterior[bit] < 0.0).count().is_multiple_of(2))
+    }
+}
@@ -4,11 +4,106 @@ pub mod security;
 pub mod ecc;
 pub mod analysis;
 pub mod physics; // Export physics
+pub mod framing;
+pub mod pilots;
+pub mod scrambler;
+pub mod linecode;
+pub mod header;
+pub mod interleave;
+pub mod qam;
+pub mod noise;
+pub mod blind;
+pub mod quantizer;
+pub mod stream;
+pub mod multiplex;
+pub mod page;
+pub mod address;
+pub mod quantized;
+pub mod jones;
+pub mod birefringence;
+pub mod stokes;
+pub mod container;
+pub mod serialization;
+pub mod archive;
+pub mod export;
+pub mod volume;
+pub mod convolutional;
+pub mod fountain;
+pub mod layer_parity;
+pub mod fec;
+pub mod detector;
+pub mod defects;
+pub mod channel;
+pub mod material;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
+#[cfg(feature = "ldpc")]
+pub mod ldpc;
 
 // Re-export for easier access
-pub use structs::PhotonicVoxel;
-pub use codec::{encode_data, decode_data};
+pub use structs::{LatticeShape, PhotonicVoxel, PhotonicVoxel64, PhotonicVoxelBuilder, SparseVoxelLattice, VoxelFloat, VoxelLattice, WAVELENGTH_RANGE_NM};
+pub use codec::{encode_data, decode_data, encode_data_with_config, decode_data_with_config, CodecConfig};
+pub use codec::{decode_soft, decode_soft_with_config, decode_soft_with_profile, SoftByte};
+pub use codec::{encode_with_scheme, decode_with_scheme, decode_soft_with_scheme, ModulationScheme};
+pub use codec::{VoxelEncoder, VoxelDecoder};
+pub use codec::{decode_data_with_confidence, VoxelConfidence};
+pub use codec::{decode_data_with_report, DecodeReport, DimensionReport, MARGIN_HISTOGRAM_BINS};
+pub use codec::{decode_data_noisy, decode_data_noisy_with_config, decode_data_with_rng, decode_data_noisy_with_rng};
 pub use security::{read_ignoring_polarization, verify_obfuscation};
-pub use ecc::{add_error_correction, recover_error_correction};
-pub use analysis::{run_ber_simulation, SimulationResult};
-pub use physics::simulate_crosstalk;
+pub use ecc::{add_error_correction, add_error_correction_with, correct_errors_with, recover_error_correction, recover_error_correction_with, ConcatenatedCode, EccConfig, EccScheme};
+pub use ecc::{correct_errors_and_erasures_with, correct_errors_soft_with, erasures_from_confidence, recover_error_correction_with_erasures};
+pub use analysis::{run_ber_simulation, run_ber_simulation_with_config, run_ber_simulation_with_scheme, run_ber_simulation_with_rng, run_ber_simulation_with_profile, run_ber_simulation_with_ecc, run_ber_simulation_with_erasures, run_ber_simulation_with_convolutional, run_ber_simulation_with_concatenated, DimensionErrors, SimulationResult};
+pub use analysis::{run_coded_ber_simulation, CodedResult};
+pub use analysis::{run_ber_simulation_with_trials, ConfidenceResult, SimulationConfig};
+pub use analysis::{run_ber_simulation_adaptive, AdaptiveConfig, AdaptiveResult};
+pub use analysis::{estimate_ber_importance, ImportanceResult};
+pub use analysis::{measure_signal_quality, SignalQuality};
+pub use analysis::{record_constellation, ConstellationSample};
+pub use analysis::{estimate_mutual_information, MutualInformation};
+pub use analysis::{estimate_channel, predicted_residual_ber, ChannelEstimate};
+pub use analysis::{run_ber_by_depth, run_ber_by_focus_error, DepthResult, FocusResult};
+pub use analysis::{run_ber_with_equalization, EqualizationResult};
+pub use analysis::{run_ber_by_age, AgingResult};
+pub use analysis::{run_ber_grid, BerGrid, GridPoint};
+pub use analysis::{optimal_read_power, run_ber_by_read_power, ReadPowerResult};
+pub use analysis::{min_safe_pitch, run_ber_by_write_pitch, WritePitchResult};
+pub use analysis::{run_ber_by_photon_budget, PhotonBudgetResult};
+pub use analysis::{endurance_limit, run_endurance, EnduranceResult};
+#[cfg(feature = "ldpc")]
+pub use analysis::run_ber_simulation_with_ldpc;
+pub use physics::{attenuation_at, compensate_sensor_response, equalize, optimize_geometry, optimize_geometry_with_limits, simulate_aberration, simulate_aging, simulate_attenuation, simulate_birefringence_drift_with_rng, simulate_birefringent_aging, simulate_crosstalk, simulate_crosstalk_with_config, simulate_focus_error, simulate_polarization_drift_with_rng, simulate_position_jitter, simulate_position_jitter_with_rng, simulate_psf, simulate_read_bleaching, simulate_rewrite_cycles, simulate_rewrite_cycles_with_rng, simulate_scattering, simulate_sensor_response, simulate_spectral_crosstalk, simulate_write, simulate_write_interference, simulate_write_interference_with_rng, simulate_write_with_rng, spectral_overlap, AberratedPsf, AgingParams, BirefringenceDrift, Connectivity, CrosstalkConfig, FatigueModel, FocusError, GeometryLimits, MaterialResponse, OpticsConfig, PositionJitter, PsfKernel, ReadPower, SensorResponse, WriteInterference, WriteParams};
+#[cfg(feature = "fft")]
+pub use physics::simulate_psf_fft;
+pub use framing::{insert_sync_markers, strip_sync_markers, Deframed, FrameConfig};
+pub use pilots::{insert_pilots, decode_with_pilots, Calibration, PilotConfig};
+pub use scrambler::{scramble, descramble, Scrambler, Descrambler};
+pub use linecode::{rll_encode, rll_decode, RllStats};
+pub use header::{encode_with_header, decode_auto, read_header, StreamHeader, SchemeDescriptor, CODEC_VERSION};
+pub use interleave::{encode_interleaved, decode_interleaved, Interleaver};
+pub use qam::{CustomConstellation, QamScheme, MAX_CONSTELLATION_POINTS};
+pub use noise::{apply_noise, apply_noise_with_rng, NoiseDistribution, NoiseParams, NoiseProfile};
+pub use blind::{decode_blind, LearnedLevels};
+pub use quantizer::Quantizer;
+pub use stream::{encode_stream, decode_stream, read_voxel, write_voxel, VoxelReader};
+pub use multiplex::{encode_multiplexed, encode_multiplexed_with_config, decode_multiplexed, decode_multiplexed_with_config};
+pub use page::{encode_pages, read_page, decode_pages, Page, PageConfig, PageReadout};
+pub use address::{LatticeGeometry, VoxelAddress};
+pub use quantized::{pack_voxels, unpack_voxels, QuantizedVoxel};
+pub use jones::{JonesMatrix, JonesVoxel};
+pub use birefringence::{encode_birefringent, decode_birefringent, BirefringentConfig, BirefringentVoxel};
+pub use stokes::{StokesDetector, StokesVector};
+pub use detector::{DetectorModel, PhotonBudget};
+pub use defects::{Defect, DefectConfig, DefectMap};
+pub use channel::{ChannelPipeline, ChannelStage};
+pub use material::Material;
+pub use container::{Container, ContainerHeader, ContainerInfo, FileMetadata, IntegrityReport, RepairReport, Session, CONTAINER_MAGIC, CONTAINER_VERSION};
+pub use serialization::{voxel_from_bytes, voxel_to_bytes, voxels_from_bytes, voxels_to_bytes, VOXEL_RECORD_LEN};
+pub use archive::{Archive, ArchiveEntry, ARCHIVE_MAGIC, ARCHIVE_VERSION};
+pub use export::{constellation_to_csv, constellation_to_json, from_npy, grid_to_csv, to_npy};
+pub use volume::{JoinedVolumes, VolumeHeader, VOLUME_MAGIC, VOLUME_VERSION};
+pub use convolutional::ConvolutionalCode;
+pub use fountain::{FountainDecoder, FountainEncoder, FountainSymbol};
+pub use layer_parity::LayerParity;
+pub use fec::{decode_voxels_with_fec, decode_with_fec, FecDecoded, FecReport};
+#[cfg(feature = "ldpc")]
+pub use fec::decode_with_ldpc;
@@ -0,0 +1,131 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::CodecConfig;
+
+/// Run-length-limited line coding by voxel stuffing.
+///
+/// After `max_run` consecutive maximum-intensity voxels the encoder inserts an
+/// unwritten (dark) voxel, which bounds the heat deposited by a bright run and
+/// the crosstalk bloom around it. The decoder tracks the same runs on the
+/// received stream and drops the stuffed voxels. Overhead depends on the data:
+/// zero for streams without long bright runs, at most `1 / max_run`.
+///
+/// Run detection on the receiver uses the intensity decision threshold, so a
+/// noise error inside a long bright run can desynchronize the stuffing; use
+/// framing or ECC above this stage.
+#[derive(Debug, Clone)]
+pub struct RllEncoder {
+    max_run: usize,
+    threshold: f32,
+    wavelength: f32,
+    run: usize,
+    stats: RllStats,
+}
+
+/// Inverse of [`RllEncoder`]. Must be created with the same `max_run` and codec.
+#[derive(Debug, Clone)]
+pub struct RllDecoder {
+    max_run: usize,
+    threshold: f32,
+    run: usize,
+}
+
+/// Overhead accounting of the line coding stage.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RllStats {
+    pub data_voxels: usize,
+    pub stuffed_voxels: usize,
+}
+
+impl RllStats {
+    /// Stuffed voxels per data voxel.
+    pub fn overhead(&self) -> f64 {
+        if self.data_voxels == 0 {
+            0.0
+        } else {
+            self.stuffed_voxels as f64 / self.data_voxels as f64
+        }
+    }
+}
+
+/// Intensity above which a voxel counts as maximum intensity: halfway between
+/// the two highest levels of `codec`.
+fn max_intensity_threshold(codec: &CodecConfig) -> f32 {
+    1.0 - 0.5 / codec.intensity_levels as f32
+}
+
+impl RllEncoder {
+    /// Panics if `max_run` is zero.
+    pub fn new(max_run: usize, codec: &CodecConfig) -> Self {
+        assert!(max_run > 0, "Run length limit must be at least one voxel");
+        Self {
+            max_run,
+            threshold: max_intensity_threshold(codec),
+            wavelength: codec.wavelengths[0],
+            run: 0,
+            stats: RllStats::default(),
+        }
+    }
+
+    /// Accounts for one data voxel and returns the stuffing voxel to write
+    /// right after it, if the run limit was reached.
+    pub fn push(&mut self, voxel: &PhotonicVoxel) -> Option<PhotonicVoxel> {
+        self.stats.data_voxels += 1;
+        if voxel.intensity <= self.threshold {
+            self.run = 0;
+            return None;
+        }
+        self.run += 1;
+        if self.run < self.max_run {
+            return None;
+        }
+        self.run = 0;
+        self.stats.stuffed_voxels += 1;
+        Some(PhotonicVoxel::new(0.0, 0.0, 0.0, self.wavelength))
+    }
+
+    pub fn stats(&self) -> RllStats {
+        self.stats
+    }
+}
+
+impl RllDecoder {
+    /// Panics if `max_run` is zero.
+    pub fn new(max_run: usize, codec: &CodecConfig) -> Self {
+        assert!(max_run > 0, "Run length limit must be at least one voxel");
+        Self { max_run, threshold: max_intensity_threshold(codec), run: 0 }
+    }
+
+    /// Returns `true` if the received voxel carries data, `false` if it is a
+    /// stuffed voxel to drop.
+    pub fn accept(&mut self, voxel: &PhotonicVoxel) -> bool {
+        if self.run == self.max_run {
+            self.run = 0;
+            return false;
+        }
+        if voxel.intensity > self.threshold {
+            self.run += 1;
+        } else {
+            self.run = 0;
+        }
+        true
+    }
+}
+
+/// Limits runs of maximum-intensity voxels to `max_run`, returning the coded
+/// stream and the overhead incurred.
+pub fn rll_encode(voxels: &[PhotonicVoxel], max_run: usize, codec: &CodecConfig) -> (Vec<PhotonicVoxel>, RllStats) {
+    let mut encoder = RllEncoder::new(max_run, codec);
+    let mut out = Vec::with_capacity(voxels.len() + voxels.len() / max_run);
+    for voxel in voxels {
+        out.push(*voxel);
+        out.extend(encoder.push(voxel));
+    }
+    (out, encoder.stats())
+}
+
+/// Drops the voxels stuffed by [`rll_encode`].
+pub fn rll_decode(voxels: &[PhotonicVoxel], max_run: usize, codec: &CodecConfig) -> Vec<PhotonicVoxel> {
+    let mut decoder = RllDecoder::new(max_run, codec);
+    voxels.iter().filter(|v| decoder.accept(v)).copied().collect()
+}
@@ -1,8 +1,28 @@
 use clap::{Parser, Subcommand};
-use std::fs;
-use std::path::PathBuf;
-use std::io::Write;
-use photon_core::{encode_data, decode_data, add_error_correction, recover_error_correction, run_ber_simulation, PhotonicVoxel};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use photon_core::ecc::{EccDecodingReader, EccEncodingReader};
+use photon_core::{add_error_correction_with, run_ber_simulation_with_trials, run_coded_ber_simulation, SignalQuality, SimulationConfig, run_ber_simulation_with_erasures, run_ber_simulation_with_rng, EccConfig, CodecConfig, PhotonicVoxel, VoxelEncoder, VoxelDecoder};
+use photon_core::{run_ber_by_depth, LatticeShape, NoiseParams};
+use photon_core::{constellation_to_csv, constellation_to_json, estimate_mutual_information, record_constellation, NoiseProfile};
+use photon_core::{grid_to_csv, run_ber_grid, ChannelPipeline, OpticsConfig};
+use photon_core::{run_ber_simulation_adaptive, AdaptiveConfig};
+use photon_core::{estimate_ber_importance, NoiseDistribution};
+use photon_core::{run_ber_simulation_with_scheme, run_ber_simulation_with_convolutional, run_ber_simulation_with_concatenated, simulate_crosstalk_with_config, Connectivity, ConvolutionalCode, CrosstalkConfig, ModulationScheme, QamScheme, SensorResponse, SimulationResult};
+use photon_core::framing::{sync_marker, strip_sync_markers, FrameConfig};
+use photon_core::scrambler::{Descrambler, ScramblingReader};
+use photon_core::linecode::{RllDecoder, RllEncoder};
+use photon_core::header::{read_header, SchemeDescriptor, StreamHeader, MAX_HEADER_VOXELS};
+use photon_core::interleave::Interleaver;
+use photon_core::stream::{write_voxel, VoxelReader};
+use photon_core::serialization::{voxels_to_bytes, VOXEL_RECORD_LEN};
+use photon_core::export;
+use photon_core::volume::{self, JoinedVolumes};
+use photon_core::container::{self, Container, ContainerHeader, FileMetadata};
+use photon_core::archive::{self, Archive};
+use rand::{rngs::StdRng, SeedableRng};
 
 #[derive(Parser)]
 #[command(name = "photon_cli")]
@@ -16,31 +36,256 @@ struct Cli {
 enum Commands {
     /// Encodes a file into Photonic Voxels (simulated binary output)
     Encode {
-        /// Input file path
+        /// Input file path (`-` for stdin)
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output file path (defaults to input.vox)
+        /// Output file path, `-` for stdout (defaults to input.vox, input.pvox
+        /// with --container, or stdout when reading stdin)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
         /// Add Error Correction
         #[arg(long)]
         ecc: bool,
+
+        /// Reed-Solomon shard counts as DATA+PARITY (default 10+4); implies --ecc
+        #[arg(long, value_name = "DATA+PARITY", value_parser = parse_ecc_shards)]
+        ecc_shards: Option<EccConfig>,
+
+        /// ECC scheme: reed-solomon (default) or secded (extended Hamming, 8
+        /// data + 1 check shard, corrects one flipped bit per codeword); implies --ecc
+        #[arg(long, value_name = "SCHEME", value_parser = parse_ecc_scheme, conflicts_with = "ecc_shards")]
+        ecc_scheme: Option<EccConfig>,
+
+        /// Bytes per shard in each ECC block (default 4096; 1 gives contiguous
+        /// codewords, 0 one block for the whole input); implies --ecc
+        #[arg(long, value_name = "BYTES")]
+        ecc_block: Option<usize>,
+
+        /// Write a versioned .pvox container (codec, ECC, length and checksum
+        /// recorded in its header) instead of a raw voxel stream
+        #[arg(long, conflicts_with_all = ["frame", "max_run", "interleave"])]
+        container: bool,
+
+        /// zstd-compress the container payload (needs the `zstd` feature)
+        #[arg(long, requires = "container")]
+        compress: bool,
+
+        /// Store runs of the voxel of symbol 0 (layer padding, zero bytes) as
+        /// run lengths instead of voxel records
+        #[arg(long, requires = "container", conflicts_with = "append")]
+        sparse: bool,
+
+        /// MIME type of the input, recorded in the container with its name,
+        /// size and modification time
+        #[arg(long, value_name = "TYPE", requires = "container", conflicts_with = "append")]
+        mime: Option<String>,
+
+        /// Append the input to the existing container at the output path as a
+        /// new write session, with the codec, ECC and layer size it records
+        #[arg(long, requires = "container", conflicts_with_all = ["ecc", "ecc_shards", "ecc_scheme", "ecc_block", "layer", "wavelengths", "scramble"])]
+        append: bool,
+
+        /// Lay the container payload out in layers of WIDTHxHEIGHT voxels and
+        /// record the lattice shape in its header
+        #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_layer, requires = "container")]
+        layer: Option<(usize, usize)>,
+
+        /// Wavelength palette in nm, comma separated (e.g. 405,488,561,640)
+        #[arg(long, value_delimiter = ',')]
+        wavelengths: Option<Vec<f32>>,
+
+        /// Insert a sync marker every N voxels
+        #[arg(long, value_name = "N")]
+        frame: Option<usize>,
+
+        /// Whiten the data with the self-synchronizing scrambler before
+        /// modulation; the seed is recorded in the codec or container header
+        #[arg(long, value_name = "SEED")]
+        scramble: Option<u32>,
+
+        /// Limit runs of maximum-intensity voxels to N (run-length-limited coding)
+        #[arg(long, value_name = "N")]
+        max_run: Option<usize>,
+
+        /// Interleave DEPTH encoded ECC blocks byte by byte, so a burst of
+        /// damaged voxels is spread over their codewords; needs ECC blocks
+        #[arg(long, value_name = "DEPTH")]
+        interleave: Option<usize>,
     },
     /// Decodes a voxel file back to original data
     Decode {
-        /// Input voxel file path
+        /// Input voxel file path (`-` for stdin)
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output file path
+        /// Output file path, `-` for stdout (defaults to the file name recorded
+        /// in a container, next to the input)
         #[arg(short, long)]
-        output: PathBuf,
+        output: Option<PathBuf>,
 
         /// Simulate readout noise
         #[arg(long)]
         noise: bool,
+
+        /// Simulate crosstalk between neighbouring voxels with this leakage
+        /// factor (e.g. 0.01), using the lattice shape recorded in a container
+        #[arg(long, value_name = "FACTOR")]
+        crosstalk: Option<f32>,
+
+        /// With --crosstalk, couple neighbours by the overlap of their spectral
+        /// lines of this standard deviation, so distant wavelengths barely leak
+        #[arg(long, value_name = "NM", requires = "crosstalk")]
+        spectral_linewidth: Option<f32>,
+
+        /// With --crosstalk, neighbours leaking into a voxel: 6 (faces), 18
+        /// (and edges) or 26 (and corners)
+        #[arg(long, value_name = "6|18|26", value_parser = parse_connectivity, default_value = "6", requires = "crosstalk")]
+        connectivity: Connectivity,
+
+        /// With --crosstalk, farthest neighbour along any axis, in voxels
+        #[arg(long, value_name = "VOXELS", default_value_t = 1, requires = "crosstalk")]
+        crosstalk_radius: usize,
+
+        /// With --crosstalk, detector response to the intensity read: clamp[:LEVEL]
+        /// (hard clip), soft[:LEVEL] (tanh saturation) or gamma:GAMMA[:LEVEL]
+        #[arg(long, value_name = "RESPONSE", value_parser = parse_sensor_response, default_value = "clamp:1.5", requires = "crosstalk")]
+        sensor_response: SensorResponse,
+
+        /// With --crosstalk, undo the simulated intensity crosstalk before
+        /// demodulating, as a deconvolving reader would
+        #[arg(long, requires = "crosstalk")]
+        equalize: bool,
+
+        /// Wavelength palette of a file without codec header, comma separated
+        #[arg(long, value_delimiter = ',')]
+        wavelengths: Option<Vec<f32>>,
+
+        /// Frame size the file was encoded with (strips and resyncs on markers)
+        #[arg(long, value_name = "N")]
+        frame: Option<usize>,
+
+        /// Scrambler seed of a file without codec header
+        #[arg(long, value_name = "SEED")]
+        scramble: Option<u32>,
+
+        /// Run length limit the file was encoded with
+        #[arg(long, value_name = "N")]
+        max_run: Option<usize>,
+
+        /// Interleaver depth the file was encoded with
+        #[arg(long, value_name = "DEPTH")]
+        interleave: Option<usize>,
+    },
+    /// Checks the chunk checksums and file digest of a .pvox container without decoding it
+    Verify {
+        /// Container file path (`-` for stdin)
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Rebuilds the corrupted chunks of a .pvox container with its ECC and
+    /// writes the repaired container
+    Repair {
+        /// Damaged container path (`-` for stdin)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Repaired container path, `-` for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Exports the voxels of a voxel file or container as an (N, 4) float32 NumPy .npy array
+    Export {
+        /// Voxel file or container path (`-` for stdin)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output .npy path, `-` for stdout (defaults to input.npy)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Splits a file (e.g. a large container) into numbered volumes input.001,
+    /// input.002, ... of at most SIZE bytes each
+    Split {
+        /// File to split
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Maximum volume size in bytes, with an optional K, M or G suffix (e.g. 100M)
+        #[arg(long, value_parser = parse_size)]
+        size: u64,
+    },
+    /// Reassembles split volumes (given in any order), checking their digests
+    Join {
+        /// Volume files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output file path (`-` for stdout)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Describes a .pvox container (codec, ECC, layout, original file) from its header alone
+    Inspect {
+        /// Container file path (`-` for stdin)
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Stores several files in one .pvox container with a table of contents
+    Archive {
+        /// Files to store, under their file names
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Archive container path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Add Error Correction
+        #[arg(long)]
+        ecc: bool,
+
+        /// Reed-Solomon shard counts as DATA+PARITY (default 10+4); implies --ecc
+        #[arg(long, value_name = "DATA+PARITY", value_parser = parse_ecc_shards)]
+        ecc_shards: Option<EccConfig>,
+
+        /// ECC scheme: reed-solomon (default) or secded (extended Hamming, 8
+        /// data + 1 check shard, corrects one flipped bit per codeword); implies --ecc
+        #[arg(long, value_name = "SCHEME", value_parser = parse_ecc_scheme, conflicts_with = "ecc_shards")]
+        ecc_scheme: Option<EccConfig>,
+
+        /// Bytes per shard in each ECC block (default 4096; 1 gives contiguous
+        /// codewords, 0 one block for the whole input); implies --ecc
+        #[arg(long, value_name = "BYTES")]
+        ecc_block: Option<usize>,
+
+        /// zstd-compress the container payload (needs the `zstd` feature)
+        #[arg(long)]
+        compress: bool,
+
+        /// Wavelength palette in nm, comma separated (e.g. 405,488,561,640)
+        #[arg(long, value_delimiter = ',')]
+        wavelengths: Option<Vec<f32>>,
+    },
+    /// Lists the files of an archive container without decoding them
+    Ls {
+        /// Archive container path
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Extracts files from an archive container
+    Extract {
+        /// Archive container path
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Directory to extract into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Files to extract (all of them if none are given)
+        names: Vec<String>,
     },
     /// Runs a research experiment (BER Simulation)
     Experiment {
@@ -51,6 +296,99 @@ enum Commands {
         /// Maximum noise level to test
         #[arg(long, default_value_t = 0.2)]
         max_noise: f32,
+
+        /// Also run the sweep with Gray-coded level mapping for comparison
+        #[arg(long)]
+        gray: bool,
+
+        /// Also run the sweep with a joint intensity/phase QAM constellation of
+        /// ORDER points (e.g. 16) for comparison
+        #[arg(long, value_name = "ORDER")]
+        qam: Option<usize>,
+
+        /// Seed for the test data and noise, to replay a run exactly
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Repeat the main sweep N times per noise level, reporting the mean BER
+        /// with its confidence interval (BerLow, BerHigh columns)
+        #[arg(long, value_name = "N")]
+        trials: Option<usize>,
+
+        /// Confidence level of the --trials or --target-errors intervals
+        #[arg(long, default_value_t = 0.95)]
+        confidence: f64,
+
+        /// Run the main sweep adaptively: read 1 KB batches per noise level
+        /// until N bit errors are seen or --max-bits are read, reporting the
+        /// interval, the batches read and whether N was reached (Converged)
+        #[arg(long, value_name = "N", conflicts_with = "trials")]
+        target_errors: Option<usize>,
+
+        /// Bit cap per noise level of --target-errors
+        #[arg(long, default_value_t = 10_000_000, requires = "target_errors")]
+        max_bits: usize,
+
+        /// Also run the sweep with Reed-Solomon of these shard counts
+        /// (DATA+PARITY, repeatable), reporting the channel errors of the stored
+        /// stream and the errors left after recovery
+        #[arg(long, value_name = "DATA+PARITY", value_parser = parse_ecc_shards)]
+        ecc: Vec<EccConfig>,
+
+        /// With --ecc, also run each layout treating the bytes of voxels whose
+        /// relative decision margin is below THRESHOLD (0 to 1) as erasures
+        #[arg(long, value_name = "THRESHOLD", requires = "ecc")]
+        erasure_threshold: Option<f32>,
+
+        /// Also run the sweep with the rate-1/2, K = 7 convolutional code,
+        /// Viterbi-decoded from soft LLRs
+        #[arg(long)]
+        convolutional: bool,
+
+        /// Also run the sweep with each --ecc layout (10+4 without one) as the
+        /// outer code of a concatenated code: interleaved over DEPTH rows, inside
+        /// the soft-decoded convolutional code
+        #[arg(long, value_name = "DEPTH")]
+        concatenated: Option<usize>,
+
+        /// Also run the sweep with the rate-1/2 LDPC code decoded from soft
+        /// LLRs, for coded vs uncoded waterfalls (needs the `ldpc` feature)
+        #[arg(long)]
+        ldpc: bool,
+
+        /// Also measure the BER of each layer of a 64x64x32 lattice read
+        /// through COEFF absorption per layer (Beer–Lambert) at --max-noise
+        #[arg(long, value_name = "COEFF")]
+        absorption: Option<f32>,
+
+        /// CSV file path for the --absorption BER by layer
+        #[arg(long, default_value = "ber_by_depth.csv")]
+        depth_output: PathBuf,
+
+        /// Also record the raw reads of 256 voxels per noise level, for
+        /// constellation plots (JSON if FILE ends in .json, else CSV)
+        #[arg(long, value_name = "FILE")]
+        constellation_output: Option<PathBuf>,
+
+        /// Also sweep a 32x32x8 lattice over noise (0 to --max-noise) against
+        /// the crosstalk factor (0 to 0.1) or the voxel pitch (0.3 to 0.8 µm),
+        /// for heatmaps of the operating envelope
+        #[arg(long, value_name = "crosstalk|pitch", value_parser = ["crosstalk", "pitch"])]
+        grid: Option<String>,
+
+        /// Long-format CSV file path for the --grid BER
+        #[arg(long, default_value = "ber_grid.csv")]
+        grid_output: PathBuf,
+
+        /// Also estimate the BER under Gaussian noise of the standard deviations
+        /// of each noise level by importance sampling (10,000 voxels per level),
+        /// down to rates no plain simulation can reach
+        #[arg(long)]
+        importance: bool,
+
+        /// CSV file path for the --importance BER
+        #[arg(long, default_value = "ber_importance.csv")]
+        importance_output: PathBuf,
     }
 }
 
@@ -58,109 +396,1056 @@ fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Encode { input, output, ecc } => {
-            println!("Reading input file: {:?}", input);
-            let data = fs::read(input).expect("Failed to read input file");
+        Commands::Encode { input, output, ecc, ecc_shards, ecc_scheme, ecc_block, container, compress, sparse, mime, append, layer, wavelengths, frame, scramble, max_run, interleave } => {
+            let config = codec_config(wavelengths);
+            let ecc = ecc_config(*ecc, ecc_shards.or(*ecc_scheme), *ecc_block);
+            let interleaver = interleave.map(|depth| {
+                ecc.ok_or_else(|| "--interleave spreads bursts over ECC codewords: add --ecc".to_string())
+                    .and_then(|ecc| Interleaver::for_ecc(depth, &ecc))
+                    .unwrap_or_else(|e| {
+                        eprintln!("Invalid interleaver: {}", e);
+                        std::process::exit(2);
+                    })
+            });
+
+            // Status goes to stderr: stdout may carry the voxel stream.
+            eprintln!("Reading input file: {:?}", input);
+            if !is_stdio(input) && fs::metadata(input).expect("Failed to read input file").len() == 0 {
+                eprintln!("Warning: Input file is empty.");
+            }
+
+            if *container {
+                let mut data = Vec::new();
+                open_input(input).read_to_end(&mut data).expect("Failed to read input file");
+                let output_path = output.clone().unwrap_or_else(|| default_output(input, "pvox"));
+
+                let encoded = if *append {
+                    // The voxels already written are copied as they are, not re-encoded
+                    let mut encoded = match File::open(&output_path).map(BufReader::new).and_then(container::read) {
+                        Ok(encoded) => encoded,
+                        Err(e) => {
+                            eprintln!("Cannot append to {:?}: {}", output_path, e);
+                            std::process::exit(2);
+                        }
+                    };
+                    let before = encoded.voxels.len();
+                    eprintln!("Appending session {} to container...", encoded.sessions().len() + 1);
+                    encoded.append(&data);
+                    encoded.header.compressed |= *compress;
+                    if let Some(metadata) = &mut encoded.header.metadata {
+                        metadata.size = encoded.header.data_len;
+                    }
+                    eprintln!("Generated {} voxels ({} in total).", encoded.voxels.len() - before, encoded.voxels.len());
+                    encoded
+                } else {
+                    eprintln!("Encoding into container (Density: {} bits/voxel{})...", config.bits_per_voxel(), ecc.map(|ecc| format!(", {}", ecc.scheme.name())).unwrap_or_default());
+                    let mut header = ContainerHeader::new(&config, data.len());
+                    header.ecc = ecc;
+                    header.scramble = *scramble;
+                    let mut encoded = Container::encode_with_header(&data, header);
+                    if let Some((width, height)) = layer {
+                        encoded = encoded.with_geometry(*width, *height);
+                    }
+                    encoded.header.compressed = *compress;
+                    encoded.header.sparse = sparse.then(|| config.modulate(0));
+                    if !is_stdio(input) {
+                        let mut metadata = FileMetadata::from_path(input).expect("Failed to read input file");
+                        metadata.mime = mime.clone();
+                        encoded.header.metadata = Some(metadata);
+                    }
+                    eprintln!("Generated {} voxels.", encoded.voxels.len());
+                    encoded
+                };
+                if let Err(e) = container::write(create_output(&output_path), &encoded) {
+                    eprintln!("Failed to write container: {}", e);
+                    std::process::exit(2);
+                }
 
-            if data.is_empty() {
-                println!("Warning: Input file is empty.");
+                eprintln!("Saved to {:?}", output_path);
+                return;
             }
 
-            let data_to_encode = if *ecc {
-                println!("Adding Error Correction (Reed-Solomon)...");
-                add_error_correction(&data)
+            // Record the codec (and, below, the ECC layout) so the decoder does not need to be told.
+            let mut header = StreamHeader::new(&config);
+            header.scramble = *scramble;
+
+            // Interleaving (and ECC of stdin, whose length the header needs up
+            // front) works on the whole buffer; ECC blocks and plain encoding
+            // stream straight from the input.
+            let reader: Box<dyn Read> = if let (Some(ecc), None, false) = (ecc, interleave, is_stdio(input)) {
+                header.data_len = Some(fs::metadata(input).expect("Failed to read input file").len());
+                header.ecc = Some(ecc);
+                eprintln!("Adding Error Correction ({})...", describe_ecc(&ecc));
+                Box::new(EccEncodingReader::new(open_input(input), ecc))
+            } else if ecc.is_some() || interleave.is_some() {
+                let mut data = Vec::new();
+                open_input(input).read_to_end(&mut data).expect("Failed to read input file");
+                header.data_len = Some(data.len() as u64);
+                if let Some(ecc) = ecc {
+                    header.ecc = Some(ecc);
+                    eprintln!("Adding Error Correction ({})...", describe_ecc(&ecc));
+                    data = add_error_correction_with(&data, &ecc);
+                }
+                if let Some(interleaver) = interleaver {
+                    eprintln!("Interleaving {} ECC blocks of {} bytes...", interleaver.depth, interleaver.width);
+                    data = interleaver.interleave(&data);
+                }
+                Box::new(Cursor::new(data))
             } else {
-                data
+                open_input(input)
+            };
+            let reader: Box<dyn Read> = match scramble {
+                Some(seed) => Box::new(ScramblingReader::new(reader, *seed)),
+                None => reader,
             };
 
-            println!("Encoding {} bytes (Density: 8 bits/voxel)...", data_to_encode.len());
-            let voxels = encode_data(&data_to_encode);
-            println!("Generated {} voxels.", voxels.len());
+            let output_path = output.clone().unwrap_or_else(|| default_output(input, "vox"));
 
-            let voxel_bytes = unsafe {
-                std::slice::from_raw_parts(
-                    voxels.as_ptr() as *const u8,
-                    voxels.len() * std::mem::size_of::<PhotonicVoxel>(),
-                )
-            };
+            eprintln!("Encoding (Density: {} bits/voxel)...", config.bits_per_voxel());
+            let mut writer = create_output(&output_path);
+            let mut count = 0usize;
+            let frame_size = frame.unwrap_or(0);
+            let mut rll = max_run.map(|n| RllEncoder::new(n, &config));
 
-            let output_path = output.clone().unwrap_or_else(|| {
-                let mut p = input.clone();
-                p.set_extension("vox");
-                p
-            });
+            for voxel in header.to_voxels() {
+                write_voxel(&mut writer, &voxel).expect("Failed to write output file");
+            }
+
+            for voxel in VoxelEncoder::with_config(reader, config) {
+                let voxel = voxel.expect("Failed to read input file");
+                let stuffing = rll.as_mut().and_then(|rll| rll.push(&voxel));
+                for voxel in std::iter::once(voxel).chain(stuffing) {
+                    if frame_size > 0 && count.is_multiple_of(frame_size) {
+                        for marker in sync_marker() {
+                            write_voxel(&mut writer, &marker).expect("Failed to write output file");
+                        }
+                    }
+                    write_voxel(&mut writer, &voxel).expect("Failed to write output file");
+                    count += 1;
+                }
+            }
+            writer.flush().expect("Failed to write output file");
 
-            fs::write(&output_path, voxel_bytes).expect("Failed to write output file");
-            println!("Saved to {:?}", output_path);
+            eprintln!("Generated {} voxels.", count);
+            if let Some(rll) = &rll {
+                let stats = rll.stats();
+                eprintln!("RLL stuffing: {} voxels ({:.2}% overhead).", stats.stuffed_voxels, stats.overhead() * 100.0);
+            }
+            if frame_size > 0 {
+                eprintln!("Inserted {} sync markers.", count.div_ceil(frame_size));
+            }
+            eprintln!("Saved to {:?}", output_path);
         }
-        Commands::Decode { input, output, noise } => {
-            println!("Reading voxel file: {:?}", input);
-            let raw_bytes = fs::read(input).expect("Failed to read voxel file");
+        Commands::Decode { input, output, noise, crosstalk, spectral_linewidth, connectivity, crosstalk_radius, sensor_response, equalize, wavelengths, frame, scramble, max_run, interleave } => {
+            let config = codec_config(wavelengths);
 
-            let struct_size = std::mem::size_of::<PhotonicVoxel>();
-            if raw_bytes.len() % struct_size != 0 {
-                panic!("File size is not a multiple of Voxel size ({} bytes). Corrupt file?", struct_size);
+            // Status goes to stderr: stdout may carry the decoded data.
+            eprintln!("Reading voxel file: {:?}", input);
+            // The voxel count drives the ECC and interleaver layout, so stdin is
+            // read up front; files are streamed.
+            let (mut reader, mut file_len): (Box<dyn BufRead>, usize) = if is_stdio(input) {
+                let mut received = Vec::new();
+                io::stdin().read_to_end(&mut received).expect("Failed to read voxel file");
+                let len = received.len();
+                (Box::new(Cursor::new(received)), len)
+            } else {
+                let file = File::open(input).expect("Failed to read voxel file");
+                let len = file.metadata().expect("Failed to read voxel file").len() as usize;
+                (Box::new(BufReader::new(file)), len)
+            };
+
+            // The first volume of a split file stands for the whole set
+            if volume::is_volume(reader.fill_buf().expect("Failed to read voxel file")) {
+                let paths = if is_stdio(input) { Vec::new() } else { volume_set(input) };
+                let volumes = paths.iter().map(|path| File::open(path).map(BufReader::new)).collect::<io::Result<Vec<_>>>().expect("Failed to read voxel file");
+                let joined = JoinedVolumes::new(volumes).unwrap_or_else(|e| {
+                    eprintln!("Cannot reassemble the volumes of {:?}: {}", input, e);
+                    std::process::exit(2);
+                });
+                eprintln!("Split file: {} volume(s), {} bytes.", paths.len(), joined.total_len());
+                file_len = joined.total_len() as usize;
+                reader = Box::new(BufReader::new(joined));
             }
 
-            let count = raw_bytes.len() / struct_size;
-            let mut voxels = Vec::with_capacity(count);
+            // NumPy arrays (e.g. measured voxels) are read like a raw voxel stream
+            if export::is_npy(reader.fill_buf().expect("Failed to read voxel file")) {
+                let voxels = export::from_npy(reader).unwrap_or_else(|e| {
+                    eprintln!("Unreadable .npy file: {}", e);
+                    std::process::exit(2);
+                });
+                eprintln!("NumPy array: {} voxels.", voxels.len());
+                let records = voxels_to_bytes(&voxels);
+                file_len = records.len();
+                reader = Box::new(Cursor::new(records));
+            }
 
-            unsafe {
-                let ptr = raw_bytes.as_ptr() as *const PhotonicVoxel;
-                for i in 0..count {
-                    // Safety: We use read_unaligned because Vec<u8> might not be aligned to f32 (4 bytes).
-                    voxels.push(std::ptr::read_unaligned(ptr.add(i)));
+            // Containers describe themselves; the options below are for raw streams.
+            if container::is_container(reader.fill_buf().expect("Failed to read voxel file")) {
+                let mut container = match container::read(reader) {
+                    Ok(container) => container,
+                    Err(e) => {
+                        eprintln!("Unreadable container: {}", e);
+                        std::process::exit(2);
+                    }
+                };
+                let header = &container.header;
+                eprintln!(
+                    "Container: version {}, {} bits/voxel, {} voxels, {} bytes in {} session(s){}{}{}{}.",
+                    header.version,
+                    header.codec.bits_per_voxel(),
+                    container.voxels.len(),
+                    header.data_len,
+                    container.sessions().len(),
+                    header.ecc.map(|ecc| format!(", {}", ecc.scheme.name())).unwrap_or_default(),
+                    if header.compressed { ", zstd" } else { "" },
+                    if header.sparse.is_some() { ", sparse" } else { "" },
+                    if header.scramble.is_some() { ", scrambled" } else { "" }
+                );
+                // Report damage before ECC gets a chance to repair (or choke on) it
+                let corrupted = container.corrupted_chunks();
+                if !corrupted.is_empty() {
+                    eprintln!("Warning: {} of {} chunk(s) corrupted: {:?}", corrupted.len(), container.chunk_count(), corrupted);
+                } else if !container.digest_ok() {
+                    eprintln!("Warning: file digest mismatch, the header is corrupted.");
                 }
+
+                if let Some(shape) = container.header.geometry {
+                    eprintln!("Lattice: {}x{}x{} voxels.", shape.width, shape.height, shape.depth);
+                }
+                if let Some(metadata) = &container.header.metadata {
+                    eprintln!("File: {} ({} bytes{}).", metadata.name, metadata.size, metadata.mime.as_ref().map(|mime| format!(", {}", mime)).unwrap_or_default());
+                }
+                let output = match (output, &container.header.metadata) {
+                    (Some(output), _) => output.clone(),
+                    // Only the recorded file name is used, so the file lands next to the input
+                    (None, Some(metadata)) => match Path::new(&metadata.name).file_name() {
+                        Some(name) if input.with_file_name(name) != *input => input.with_file_name(name),
+                        _ => {
+                            eprintln!("Cannot restore the recorded file name {:?}: pass --output.", metadata.name);
+                            std::process::exit(2);
+                        }
+                    },
+                    (None, None) => {
+                        eprintln!("The container records no file name: pass --output.");
+                        std::process::exit(2);
+                    }
+                };
+                if let Some(factor) = crosstalk {
+                    let Some(lattice) = container.lattice() else {
+                        eprintln!("Crosstalk needs a container with a lattice shape (encode with --layer).");
+                        std::process::exit(2);
+                    };
+                    let config = CrosstalkConfig { factor: *factor, connectivity: *connectivity, radius: *crosstalk_radius, linewidth_nm: *spectral_linewidth, response: *sensor_response };
+                    if let Err(e) = config.validate() {
+                        eprintln!("Invalid crosstalk: {}", e);
+                        std::process::exit(2);
+                    }
+                    match spectral_linewidth {
+                        Some(linewidth) => eprintln!("Simulating crosstalk (factor {}, {} neighbours, spectral linewidth {} nm)...", factor, config.offsets().len(), linewidth),
+                        None => eprintln!("Simulating crosstalk (factor {}, {} neighbours)...", factor, config.offsets().len()),
+                    }
+                    let mut lattice = simulate_crosstalk_with_config(&lattice, &config);
+                    if *equalize {
+                        eprintln!("Equalizing crosstalk...");
+                        lattice = photon_core::equalize(&lattice, &config);
+                    }
+                    container.voxels = lattice.into_voxels();
+                }
+
+                let data = container.decode(*noise).unwrap_or_else(|e| {
+                    eprintln!("Decoding failed: {}", e);
+                    std::process::exit(1);
+                });
+                let mut writer = create_output(&output);
+                writer.write_all(&data).expect("Failed to write output file");
+                writer.flush().expect("Failed to write output file");
+                drop(writer);
+                if let Some(modified) = container.header.metadata.as_ref().and_then(|metadata| metadata.modified).filter(|_| !is_stdio(&output)) {
+                    File::options().write(true).open(&output).and_then(|file| file.set_modified(modified)).expect("Failed to restore modification time");
+                }
+                eprintln!("Decoded data saved to {:?}", output);
+                return;
+            }
+            let Some(output) = output else {
+                eprintln!("Raw voxel streams record no file name: pass --output.");
+                std::process::exit(2);
+            };
+
+            if !file_len.is_multiple_of(VOXEL_RECORD_LEN) {
+                panic!("File size is not a multiple of Voxel size ({} bytes). Corrupt file?", VOXEL_RECORD_LEN);
             }
 
-            println!("Decoding {} voxels...", voxels.len());
-            let decoded_raw = decode_data(&voxels, *noise);
+            let mut count = file_len / VOXEL_RECORD_LEN;
+            let mut voxels = VoxelReader::new(reader).map(|voxel| voxel.expect("Failed to read voxel file"));
 
-            let final_data = if decoded_raw.len().is_multiple_of(14) {
-                 println!("Auto-detect: Checking for ECC structure (14-byte blocks)...");
-                 match recover_error_correction(&decoded_raw) {
-                     Ok(corrected) => {
-                         println!("ECC Verification: SUCCESS. Parity stripped.");
-                         corrected
-                     },
-                     Err(_) => {
-                         println!("ECC Verification: Failed or not ECC data. Saving raw output.");
-                         decoded_raw
-                     }
-                 }
+            // Files with a codec header override the command line palette.
+            // Framing and RLL markers are read with the level codec, or the
+            // command line one for QAM and custom constellations.
+            let prefix: Vec<PhotonicVoxel> = voxels.by_ref().take(MAX_HEADER_VOXELS).collect();
+            let (scheme, header_len, header) = match read_header(&prefix, false) {
+                Ok(Some((header, len))) => {
+                    eprintln!("Codec header: version {}, {} bits/voxel.", header.version, header.scheme.bits_per_voxel());
+                    (header.scheme.clone(), len, Some(header))
+                }
+                Ok(None) => (SchemeDescriptor::Levels(config.clone()), 0, None),
+                Err(e) => {
+                    eprintln!("Unreadable codec header: {}", e);
+                    std::process::exit(2);
+                }
+            };
+            count -= header_len;
+            let voxels = prefix.into_iter().skip(header_len).chain(voxels);
+            let config = match &scheme {
+                SchemeDescriptor::Levels(config) => config.clone(),
+                _ => config,
+            };
+
+            // Resynchronizing needs the whole stream to scan for markers.
+            let voxels: Box<dyn Iterator<Item = PhotonicVoxel>> = match frame {
+                Some(frame_size) => {
+                    let received: Vec<PhotonicVoxel> = voxels.collect();
+                    let deframed = strip_sync_markers(&received, &FrameConfig::for_codec(*frame_size, &config));
+                    if !deframed.damaged_frames.is_empty() {
+                        eprintln!("Warning: {} damaged frame(s) realigned: {:?}", deframed.damaged_frames.len(), deframed.damaged_frames);
+                    }
+                    count = deframed.voxels.len();
+                    Box::new(deframed.voxels.into_iter())
+                }
+                None => Box::new(voxels),
+            };
+
+            let voxels: Box<dyn Iterator<Item = PhotonicVoxel>> = match max_run {
+                Some(max_run) => {
+                    let mut rll = RllDecoder::new(*max_run, &config);
+                    let data: Vec<PhotonicVoxel> = voxels.filter(|v| rll.accept(v)).collect();
+                    count = data.len();
+                    Box::new(data.into_iter())
+                }
+                None => voxels,
+            };
+
+            eprintln!("Decoding {} voxels...", count);
+            let decoded_len = count * scheme.bits_per_voxel() / 8;
+            // An untrusted length must not size any buffer beyond what was received
+            if let Some(data_len) = header.as_ref().and_then(|header| header.data_len).filter(|&len| len > decoded_len as u64) {
+                eprintln!("Codec header records {} bytes of data, the stream holds {}.", data_len, decoded_len);
+                std::process::exit(2);
+            }
+            let decoded: Box<dyn Iterator<Item = u8>> = match scheme {
+                SchemeDescriptor::Levels(config) => Box::new(VoxelDecoder::with_config(voxels, config, *noise)),
+                scheme => Box::new(VoxelDecoder::with_scheme(voxels, scheme, *noise)),
+            };
+            let decoded: Box<dyn Iterator<Item = u8>> = match header.as_ref().map_or(*scramble, |header| header.scramble) {
+                Some(seed) => {
+                    let mut descrambler = Descrambler::new(seed);
+                    Box::new(decoded.map(move |byte| descrambler.process_byte(byte)))
+                }
+                None => decoded,
+            };
+            let decoded: Box<dyn Iterator<Item = u8>> = match interleave {
+                Some(depth) => {
+                    let Some(ecc) = header.as_ref().and_then(|header| header.ecc) else {
+                        eprintln!("Deinterleaving needs the ECC layout of a codec header.");
+                        std::process::exit(2);
+                    };
+                    let interleaver = Interleaver::for_ecc(*depth, &ecc).unwrap_or_else(|e| {
+                        eprintln!("Invalid interleaver: {}", e);
+                        std::process::exit(2);
+                    });
+                    // Only the interleaved bytes, not the codec's symbol padding
+                    let interleaved_len = header.as_ref().and_then(|header| header.data_len).map_or(decoded_len, |len| ecc.encoded_len(len as usize));
+                    let received: Vec<u8> = decoded.take(interleaved_len).collect();
+                    Box::new(interleaver.deinterleave(&received).into_iter())
+                }
+                None => decoded,
+            };
+
+            // The header says whether there is parity to strip; nothing is guessed from the length.
+            if let Some((ecc, data_len)) = header.as_ref().and_then(|header| header.ecc.zip(header.data_len)) {
+                // Blocks are checked and corrected as they stream through
+                let mut reader = EccDecodingReader::new(IterReader(decoded), ecc, data_len).expect("Invalid ECC config in codec header").lenient();
+                let mut writer = create_output(output);
+                io::copy(&mut reader, &mut writer).unwrap_or_else(|e| {
+                    eprintln!("Decoding failed: {}", e);
+                    std::process::exit(1);
+                });
+                writer.flush().expect("Failed to write output file");
+                match reader.damaged_blocks() {
+                    [] => eprintln!("ECC Verification ({}+{} {}): SUCCESS, {} byte(s) corrected. Parity stripped.", ecc.data_shards, ecc.parity_shards, ecc.scheme.name(), reader.corrected_bytes()),
+                    // The data shards come first: they are saved uncorrected
+                    damaged => eprintln!("ECC Verification: FAILED in {} block(s) {:?}. Saved their data uncorrected.", damaged.len(), damaged),
+                }
+            } else if let Some(data_len) = header.and_then(|header| header.data_len) {
+                // Symbol padding beyond the recorded length is dropped
+                let mut writer = create_output(output);
+                for byte in decoded.take(data_len as usize) {
+                    writer.write_all(&[byte]).expect("Failed to write output file");
+                }
+                writer.flush().expect("Failed to write output file");
+            } else {
+                let mut writer = create_output(output);
+                for byte in decoded {
+                    writer.write_all(&[byte]).expect("Failed to write output file");
+                }
+                writer.flush().expect("Failed to write output file");
+            }
+
+            eprintln!("Decoded data saved to {:?}", output);
+        }
+        Commands::Verify { input } => {
+            let report = match container::verify(BufReader::new(open_input(input))) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Unreadable container: {}", e);
+                    std::process::exit(2);
+                }
+            };
+            println!("Chunks: {} of {} voxels", report.chunks, report.chunk_voxels);
+            println!("Corrupted chunks: {}", if report.corrupted_chunks.is_empty() { "none".to_string() } else { format!("{:?}", report.corrupted_chunks) });
+            println!("File digest (BLAKE3): {}", if report.digest_ok { "ok" } else { "MISMATCH" });
+            if !report.is_intact() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Repair { input, output } => {
+            let mut container = match container::read(BufReader::new(open_input(input))) {
+                Ok(container) => container,
+                Err(e) => {
+                    eprintln!("Unreadable container: {}", e);
+                    std::process::exit(2);
+                }
+            };
+            let digest_ok = container.digest_ok();
+            let report = container.repair();
+            let corrupted = report.repaired_chunks.len() + report.unrecoverable_chunks.len();
+            println!("Chunks: {} of {} voxels, {} corrupted", report.chunks, container.header.chunk_voxels, corrupted);
+            println!("Repaired: {} chunk(s), {} voxel(s), {} byte(s) corrected by ECC", report.repaired_chunks.len(), report.repaired_voxels, report.corrected_bytes);
+            println!("Unrecoverable chunks: {}", if report.unrecoverable_chunks.is_empty() { "none".to_string() } else { format!("{:?}", report.unrecoverable_chunks) });
+            if !digest_ok && corrupted == 0 {
+                eprintln!("Warning: the file digest did not match but every chunk did; the header may be damaged.");
+            }
+            if let Err(e) = container::write(create_output(output), &container) {
+                eprintln!("Failed to write container: {}", e);
+                std::process::exit(2);
+            }
+            eprintln!("Repaired container saved to {:?}", output);
+            if !report.is_complete() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Export { input, output } => {
+            let mut reader = BufReader::new(open_input(input));
+            // A container exports its payload; a raw stream (codec header included) as it is
+            let voxels = if container::is_container(reader.fill_buf().expect("Failed to read voxel file")) {
+                container::read(reader).map(|container| container.voxels)
+            } else {
+                VoxelReader::new(reader).collect()
+            };
+            let voxels = voxels.unwrap_or_else(|e| {
+                eprintln!("Unreadable voxel file: {}", e);
+                std::process::exit(2);
+            });
+
+            let output = output.clone().unwrap_or_else(|| default_output(input, "npy"));
+            export::to_npy(create_output(&output), &voxels).expect("Failed to write output file");
+            eprintln!("Exported {} voxels to {:?}", voxels.len(), output);
+        }
+        Commands::Split { input, size } => {
+            let file = File::open(input).expect("Failed to read input file");
+            let len = file.metadata().expect("Failed to read input file").len();
+            let count = volume::split(BufReader::new(file), len, *size, |sequence| {
+                File::create(volume_path(input, sequence)).map(BufWriter::new)
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to split {:?}: {}", input, e);
+                std::process::exit(2);
+            });
+            eprintln!("Split {} bytes into {} volume(s): {:?} ... {:?}", len, count, volume_path(input, 0), volume_path(input, count - 1));
+        }
+        Commands::Join { inputs, output } => {
+            let volumes = inputs.iter().map(|path| File::open(path).map(BufReader::new)).collect::<io::Result<Vec<_>>>().expect("Failed to read input file");
+            match volume::join(volumes, create_output(output)) {
+                Ok(len) => eprintln!("Joined {} volume(s) into {} bytes at {:?}", inputs.len(), len, output),
+                Err(e) => {
+                    eprintln!("Failed to join volumes: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Inspect { input } => {
+            let info = container::inspect(BufReader::new(open_input(input))).unwrap_or_else(|e| {
+                eprintln!("Unreadable container: {}", e);
+                std::process::exit(2);
+            });
+            let header = &info.header;
+            println!("Version: {}", header.version);
+            if let Some(metadata) = &header.metadata {
+                println!("File: {}", metadata.name);
+                println!("Size: {} bytes", metadata.size);
+                if let Some(since_epoch) = metadata.modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+                    println!("Modified: {} (Unix time)", since_epoch.as_secs());
+                }
+                if let Some(mime) = &metadata.mime {
+                    println!("MIME type: {}", mime);
+                }
+            }
+            println!("Data: {} bytes in {} session(s)", header.data_len, info.sessions.len());
+            println!(
+                "Codec: {} intensity x {} polarization x {} phase levels, {} wavelengths ({} bits/voxel{})",
+                header.codec.intensity_levels,
+                header.codec.polarization_levels,
+                header.codec.phase_levels,
+                header.codec.wavelengths.len(),
+                header.codec.bits_per_voxel(),
+                if header.codec.gray_code { ", Gray code" } else { "" }
+            );
+            match header.ecc {
+                Some(ecc) => match ecc.block_shard_len {
+                    Some(len) => println!("ECC: {}, blocks of {} bytes per shard", describe_ecc(&ecc), len),
+                    None => println!("ECC: {}, one block", describe_ecc(&ecc)),
+                },
+                None => println!("ECC: none"),
+            }
+            if let Some(seed) = header.scramble {
+                println!("Scrambler: seed {}", seed);
+            }
+            println!(
+                "Voxels: {} in {} chunk(s) of up to {}{}{}",
+                info.voxel_count,
+                info.chunks,
+                header.chunk_voxels,
+                if header.sparse.is_some() { ", sparse" } else { "" },
+                if header.compressed { ", zstd-compressed" } else { "" }
+            );
+            if let Some(shape) = header.geometry {
+                println!("Lattice: {}x{}x{}", shape.width, shape.height, shape.depth);
+            }
+        }
+        Commands::Archive { inputs, output, ecc, ecc_shards, ecc_scheme, ecc_block, compress, wavelengths } => {
+            let config = codec_config(wavelengths);
+            let mut contents = Archive::new();
+            for input in inputs {
+                let name = input.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                let data = fs::read(input).expect("Failed to read input file");
+                eprintln!("Adding {} ({} bytes)", name, data.len());
+                if let Err(e) = contents.add(name, data) {
+                    eprintln!("Cannot add {:?}: {}", input, e);
+                    std::process::exit(2);
+                }
+            }
+
+            let mut encoded = contents.to_container(&config, ecc_config(*ecc, ecc_shards.or(*ecc_scheme), *ecc_block));
+            encoded.header.compressed = *compress;
+            if let Err(e) = container::write(create_output(output), &encoded) {
+                eprintln!("Failed to write container: {}", e);
+                std::process::exit(2);
+            }
+            eprintln!("Archived {} file(s) in {} voxels to {:?}", contents.len(), encoded.voxels.len(), output);
+        }
+        Commands::Ls { input } => {
+            let entries = archive::list(BufReader::new(File::open(input).expect("Failed to read input file"))).unwrap_or_else(|e| {
+                eprintln!("Unreadable archive: {}", e);
+                std::process::exit(2);
+            });
+            for entry in &entries {
+                println!("{:>12}  {}", entry.len, entry.name);
+            }
+            eprintln!("{} file(s), {} bytes", entries.len(), entries.iter().map(|entry| entry.len).sum::<u64>());
+        }
+        Commands::Extract { input, output, names } => {
+            let mut reader = BufReader::new(File::open(input).expect("Failed to read input file"));
+            let names = if names.is_empty() {
+                let entries = archive::list(&mut reader).unwrap_or_else(|e| {
+                    eprintln!("Unreadable archive: {}", e);
+                    std::process::exit(2);
+                });
+                entries.into_iter().map(|entry| entry.name).collect()
             } else {
-                decoded_raw
+                names.clone()
             };
 
-            fs::write(output, final_data).expect("Failed to write output file");
-            println!("Decoded data saved to {:?}", output);
+            for name in &names {
+                reader.rewind().expect("Failed to read input file");
+                let data = archive::extract(&mut reader, name).unwrap_or_else(|e| {
+                    eprintln!("Cannot extract {}: {}", name, e);
+                    std::process::exit(2);
+                });
+                // Entry names are relative paths without `..`, so this stays below `output`
+                let path = output.join(name);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).expect("Failed to create output directory");
+                }
+                fs::write(&path, &data).expect("Failed to write output file");
+                eprintln!("Extracted {:?} ({} bytes)", path, data.len());
+            }
         }
-        Commands::Experiment { output, max_noise } => {
+        Commands::Experiment { output, max_noise, gray, qam, seed, trials, confidence, target_errors, max_bits, ecc, erasure_threshold, convolutional, concatenated, ldpc, absorption, depth_output, constellation_output, grid, grid_output, importance, importance_output } => {
             println!("Running BER Experiment...");
             println!("Max Noise: {}, Data Size: 10KB, Steps: 20", max_noise);
 
-            let results = run_ber_simulation(10_000, 20, *max_noise);
+            // With a seed, every sweep replays the same data and noise
+            let sweep = |scheme: &dyn ModulationScheme| match seed {
+                Some(seed) => run_ber_simulation_with_rng(10_000, 20, *max_noise, scheme, &mut StdRng::seed_from_u64(*seed)),
+                None => run_ber_simulation_with_scheme(10_000, 20, *max_noise, scheme),
+            };
+            // With --trials, the main curve is the mean of repeated runs with its interval
+            let intervals = trials.map(|trials| {
+                let config = SimulationConfig { trials, confidence: *confidence };
+                if let Err(e) = config.validate() {
+                    eprintln!("Invalid simulation config: {}", e);
+                    std::process::exit(2);
+                }
+                println!("Running {} trials per noise level...", trials);
+                match seed {
+                    Some(seed) => run_ber_simulation_with_trials(10_000, 20, *max_noise, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(*seed)),
+                    None => run_ber_simulation_with_trials(10_000, 20, *max_noise, &CodecConfig::default(), &config, &mut rand::rng()),
+                }
+            });
+            // With --target-errors, every noise level reads until its BER is measured
+            let adaptive = target_errors.map(|target_errors| {
+                let config = AdaptiveConfig { target_errors, max_bits: *max_bits, confidence: *confidence, ..AdaptiveConfig::default() };
+                if let Err(e) = config.validate() {
+                    eprintln!("Invalid adaptive config: {}", e);
+                    std::process::exit(2);
+                }
+                println!("Reading each noise level until {} errors or {} bits...", target_errors, max_bits);
+                match seed {
+                    Some(seed) => run_ber_simulation_adaptive(20, *max_noise, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(*seed)),
+                    None => run_ber_simulation_adaptive(20, *max_noise, &CodecConfig::default(), &config, &mut rand::rng()),
+                }
+            });
+            let results = match (&intervals, &adaptive) {
+                (Some(intervals), _) => intervals.iter().map(|r| SimulationResult { noise_level: r.noise_level, total_bits: r.total_bits, error_bits: r.error_bits, ber: r.ber, dimension_errors: r.dimension_errors }).collect(),
+                (_, Some(adaptive)) => adaptive.iter().map(|r| SimulationResult { noise_level: r.noise_level, total_bits: r.total_bits, error_bits: r.error_bits, ber: r.ber, dimension_errors: r.dimension_errors }).collect(),
+                _ => sweep(&CodecConfig::default()),
+            };
+            let bounds: Option<Vec<(f64, f64)>> = match (&intervals, &adaptive) {
+                (Some(intervals), _) => Some(intervals.iter().map(|r| (r.ber_low, r.ber_high)).collect()),
+                (_, Some(adaptive)) => Some(adaptive.iter().map(|r| (r.ber_low, r.ber_high)).collect()),
+                _ => None,
+            };
 
-            let mut file = fs::File::create(output).expect("Failed to create results file");
-            writeln!(file, "NoiseLevel,BER,ErrorBits,TotalBits").unwrap();
+            // Comparison sweeps, reported as extra columns
+            let mut comparisons: Vec<(String, Vec<SimulationResult>)> = Vec::new();
+            if *gray {
+                println!("Running Gray-coded comparison sweep...");
+                let config = CodecConfig { gray_code: true, ..CodecConfig::default() };
+                comparisons.push(("Gray".to_string(), sweep(&config)));
+            }
+            if let Some(order) = qam {
+                let scheme = QamScheme::new(*order, &CodecConfig::default());
+                if let Err(e) = scheme.validate() {
+                    eprintln!("Invalid QAM constellation: {}", e);
+                    std::process::exit(2);
+                }
+                println!("Running {}-QAM comparison sweep ({} bits/voxel)...", order, scheme.bits_per_voxel());
+                comparisons.push(("Qam".to_string(), sweep(&scheme)));
+            }
+            for ecc in ecc {
+                println!("Running {} comparison sweep...", describe_ecc(ecc));
+                let results = match seed {
+                    Some(seed) => run_coded_ber_simulation(10_000, 20, *max_noise, &CodecConfig::default(), ecc, &mut StdRng::seed_from_u64(*seed)),
+                    None => run_coded_ber_simulation(10_000, 20, *max_noise, &CodecConfig::default(), ecc, &mut rand::rng()),
+                };
+                // The channel BER of the stored stream, then what correction leaves of it
+                let (raw, corrected) = results
+                    .iter()
+                    .map(|r| {
+                        (
+                            SimulationResult { noise_level: r.noise_level, total_bits: r.raw_total_bits, error_bits: r.raw_error_bits, ber: r.raw_ber, dimension_errors: r.dimension_errors },
+                            SimulationResult { noise_level: r.noise_level, total_bits: r.total_bits, error_bits: r.error_bits, ber: r.ber, dimension_errors: r.dimension_errors },
+                        )
+                    })
+                    .unzip();
+                comparisons.push((format!("Rs{}+{}Raw", ecc.data_shards, ecc.parity_shards), raw));
+                comparisons.push((format!("Rs{}+{}", ecc.data_shards, ecc.parity_shards), corrected));
+                if let Some(threshold) = erasure_threshold {
+                    println!("Running Reed-Solomon {}+{} comparison sweep with erasures below margin {}...", ecc.data_shards, ecc.parity_shards, threshold);
+                    let results = match seed {
+                        Some(seed) => run_ber_simulation_with_erasures(10_000, 20, *max_noise, &CodecConfig::default(), ecc, *threshold, &mut StdRng::seed_from_u64(*seed)),
+                        None => run_ber_simulation_with_erasures(10_000, 20, *max_noise, &CodecConfig::default(), ecc, *threshold, &mut rand::rng()),
+                    };
+                    comparisons.push((format!("Er{}+{}", ecc.data_shards, ecc.parity_shards), results));
+                }
+            }
+            if *convolutional {
+                println!("Running convolutional (K = 7, soft Viterbi) comparison sweep...");
+                let code = ConvolutionalCode::default();
+                let results = match seed {
+                    Some(seed) => run_ber_simulation_with_convolutional(10_000, 20, *max_noise, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(*seed)),
+                    None => run_ber_simulation_with_convolutional(10_000, 20, *max_noise, &CodecConfig::default(), &code, &mut rand::rng()),
+                };
+                comparisons.push(("Conv".to_string(), results));
+            }
+            if let Some(depth) = concatenated {
+                let outers = if ecc.is_empty() { vec![EccConfig::default()] } else { ecc.clone() };
+                for outer in outers {
+                    let code = outer.concatenated(ConvolutionalCode::default(), *depth);
+                    if let Err(e) = code.validate() {
+                        eprintln!("Invalid concatenated code: {}", e);
+                        std::process::exit(2);
+                    }
+                    println!("Running concatenated (Reed-Solomon {}+{}, depth {}, convolutional) comparison sweep...", outer.data_shards, outer.parity_shards, depth);
+                    let results = match seed {
+                        Some(seed) => run_ber_simulation_with_concatenated(10_000, 20, *max_noise, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(*seed)),
+                        None => run_ber_simulation_with_concatenated(10_000, 20, *max_noise, &CodecConfig::default(), &code, &mut rand::rng()),
+                    };
+                    comparisons.push((format!("Cc{}+{}", outer.data_shards, outer.parity_shards), results));
+                }
+            }
+            if *ldpc {
+                println!("Running LDPC (soft-decision) comparison sweep...");
+                comparisons.push(("Ldpc".to_string(), ldpc_sweep(*max_noise, *seed)));
+            }
 
-            for res in &results {
-                writeln!(file, "{:.4},{:.6},{},{}", res.noise_level, res.ber, res.error_bits, res.total_bits).unwrap();
+            let mut file = fs::File::create(output).expect("Failed to create results file");
+            let header: String = comparisons.iter().map(|(name, _)| format!(",{0}BER,{0}ErrorBits", name)).collect();
+            let interval_header = match (&bounds, &adaptive) {
+                (_, Some(_)) => ",BerLow,BerHigh,Batches,Converged",
+                (Some(_), _) => ",BerLow,BerHigh",
+                _ => "",
+            };
+            writeln!(
+                file,
+                "NoiseLevel,BER,ErrorBits,TotalBits{},IntensityErrors,PolarizationErrors,PhaseErrors,WavelengthErrors,IntensitySnrDb,PolarizationSnrDb,PhaseSnrDb,WavelengthSnrDb,QFactorDb,GaussianBER,MutualInformation{}",
+                interval_header, header
+            )
+            .unwrap();
+            for (idx, res) in results.iter().enumerate() {
+                let mut interval = bounds.as_ref().map(|b| format!(",{:.6e},{:.6e}", b[idx].0, b[idx].1)).unwrap_or_default();
+                if let Some(adaptive) = &adaptive {
+                    interval += &format!(",{},{}", adaptive[idx].batches, adaptive[idx].converged);
+                }
+                let dims = &res.dimension_errors;
+                // The noise level in the units of optical communications
+                let quality = SignalQuality::from_profile(&CodecConfig::default(), &NoiseParams::uniform(res.noise_level).into());
+                let snr: String = quality.snr_db.iter().map(|snr| format!(",{:.2}", snr)).collect();
+                let profile = NoiseProfile::from(NoiseParams::uniform(res.noise_level));
+                let information = match seed {
+                    Some(seed) => estimate_mutual_information(10_000, &profile, &CodecConfig::default(), &mut StdRng::seed_from_u64(*seed)),
+                    None => estimate_mutual_information(10_000, &profile, &CodecConfig::default(), &mut rand::rng()),
+                };
+                let extra: String = comparisons.iter().map(|(_, c)| format!(",{:.6},{}", c[idx].ber, c[idx].error_bits)).collect();
+                writeln!(
+                    file,
+                    "{:.4},{:.6},{},{}{},{},{},{},{}{},{:.2},{:.3e},{:.4}{}",
+                    res.noise_level,
+                    res.ber,
+                    res.error_bits,
+                    res.total_bits,
+                    interval,
+                    dims.intensity,
+                    dims.polarization,
+                    dims.phase,
+                    dims.wavelength,
+                    snr,
+                    quality.q_factor_db(),
+                    quality.estimated_ber,
+                    information.bits_per_voxel,
+                    extra
+                )
+                .unwrap();
             }
 
             println!("Simulation complete. Results saved to {:?}", output);
 
             // Print a small summary to stdout
+            let labels: Vec<String> = std::iter::once("BER".to_string())
+                .chain(comparisons.iter().map(|(name, _)| format!("{} BER", name)))
+                .map(|label| format!("{:<7}", label))
+                .collect();
+            let row = |idx: usize| -> String {
+                let bers = std::iter::once(results[idx].ber).chain(comparisons.iter().map(|(_, c)| c[idx].ber));
+                bers.zip(&labels).map(|(ber, label)| format!("{:<1$.5}", ber, label.len())).collect::<Vec<_>>().join(" | ")
+            };
             println!("\nSummary:");
-            println!("Noise | BER");
-            println!("------+-------");
-            for res in results.iter().take(5) {
-                println!("{:.3} | {:.5}", res.noise_level, res.ber);
+            println!("Noise | {}", labels.join(" | "));
+            println!("------+{}", labels.iter().map(|label| "-".repeat(label.len() + 2)).collect::<Vec<_>>().join("+"));
+            for (idx, res) in results.iter().enumerate().take(5) {
+                println!("{:.3} | {}", res.noise_level, row(idx));
             }
             println!("...   | ...");
-            for res in results.iter().rev().take(3).rev() {
-                 println!("{:.3} | {:.5}", res.noise_level, res.ber);
+            for (idx, res) in results.iter().enumerate().rev().take(3).rev() {
+                 println!("{:.3} | {}", res.noise_level, row(idx));
+            }
+
+            if let Some(absorption) = absorption {
+                println!("\nRunning depth sweep (absorption {} per layer, noise {})...", absorption, max_noise);
+                let shape = LatticeShape { width: 64, height: 64, depth: 32 };
+                let noise = NoiseParams::uniform(*max_noise).into();
+                let layers = match seed {
+                    Some(seed) => run_ber_by_depth(shape, *absorption, &noise, &CodecConfig::default(), &mut StdRng::seed_from_u64(*seed)),
+                    None => run_ber_by_depth(shape, *absorption, &noise, &CodecConfig::default(), &mut rand::rng()),
+                };
+                let mut file = fs::File::create(depth_output).expect("Failed to create depth results file");
+                writeln!(file, "Layer,Attenuation,BER,ErrorBits,TotalBits").unwrap();
+                for layer in &layers {
+                    writeln!(file, "{},{:.6},{:.6},{},{}", layer.layer, layer.attenuation, layer.ber, layer.error_bits, layer.total_bits).unwrap();
+                }
+                if let (Some(surface), Some(deepest)) = (layers.first(), layers.last()) {
+                    println!("Layer {} BER {:.5}, layer {} BER {:.5} (intensity x{:.3})", surface.layer, surface.ber, deepest.layer, deepest.ber, deepest.attenuation);
+                }
+                println!("Depth results saved to {:?}", depth_output);
             }
+
+            if let Some(path) = constellation_output {
+                let samples = match seed {
+                    Some(seed) => record_constellation(256, 20, *max_noise, &CodecConfig::default(), &mut StdRng::seed_from_u64(*seed)),
+                    None => record_constellation(256, 20, *max_noise, &CodecConfig::default(), &mut rand::rng()),
+                };
+                let file = BufWriter::new(fs::File::create(path).expect("Failed to create constellation file"));
+                if path.extension().is_some_and(|extension| extension == "json") {
+                    constellation_to_json(file, &samples).expect("Failed to write constellation file");
+                } else {
+                    constellation_to_csv(file, &samples).expect("Failed to write constellation file");
+                }
+                println!("Constellation samples saved to {:?}", path);
+            }
+
+            if let Some(axis) = grid {
+                println!("\nRunning noise x {} grid...", axis);
+                let shape = LatticeShape { width: 32, height: 32, depth: 8 };
+                let noise_levels: Vec<f32> = (0..=10).map(|i| max_noise * i as f32 / 10.0).collect();
+                let (ys, name): (Vec<f32>, _) = match axis.as_str() {
+                    "crosstalk" => ((0..=10).map(|i| i as f32 / 100.0).collect(), "CrosstalkFactor"),
+                    _ => ((0..=10).map(|i| (30 + 5 * i) as f32 / 100.0).collect(), "PitchUm"),
+                };
+                let channel = |noise: f32, y: f32| {
+                    let factor = match axis.as_str() {
+                        "crosstalk" => y,
+                        _ => OpticsConfig { voxel_pitch_um: y, ..OpticsConfig::default() }.crosstalk_factor(),
+                    };
+                    ChannelPipeline::new().stage(CrosstalkConfig::new(factor)).stage(NoiseProfile::from(NoiseParams::uniform(noise)))
+                };
+                let grid = match seed {
+                    Some(seed) => run_ber_grid(shape, &noise_levels, &ys, &CodecConfig::default(), channel, &mut StdRng::seed_from_u64(*seed)),
+                    None => run_ber_grid(shape, &noise_levels, &ys, &CodecConfig::default(), channel, &mut rand::rng()),
+                };
+                let file = BufWriter::new(fs::File::create(grid_output).expect("Failed to create grid file"));
+                grid_to_csv(file, &grid, ["NoiseLevel", name]).expect("Failed to write grid file");
+                println!("{} of {} points error-free", grid.envelope(0.0).count(), grid.points.len());
+                println!("Grid results saved to {:?}", grid_output);
+            }
+
+            if *importance {
+                println!("\nRunning importance-sampled Gaussian BER sweep...");
+                let mut rng = match seed {
+                    Some(seed) => StdRng::seed_from_u64(*seed),
+                    None => StdRng::from_rng(&mut rand::rng()),
+                };
+                let mut file = fs::File::create(importance_output).expect("Failed to create importance sampling file");
+                writeln!(file, "NoiseLevel,ImportanceBER,StdError,ErrorEvents,GaussianBER").unwrap();
+                for level in (0..=20).map(|i| max_noise * i as f32 / 20.0) {
+                    // The standard deviations of the uniform sweep, with Gaussian tails
+                    let profile = NoiseProfile { distribution: NoiseDistribution::Gaussian, ..NoiseProfile::from(NoiseParams::uniform(level)) };
+                    let estimate = estimate_ber_importance(10_000, &profile, &CodecConfig::default(), &mut rng);
+                    let quality = SignalQuality::from_profile(&CodecConfig::default(), &profile);
+                    writeln!(file, "{:.4},{:.6e},{:.6e},{},{:.6e}", level, estimate.ber, estimate.std_error, estimate.error_events, quality.estimated_ber).unwrap();
+                }
+                println!("Importance sampling results saved to {:?}", importance_output);
+            }
+        }
+    }
+}
+
+/// BER sweep of the default LDPC code with the default codec.
+#[cfg(feature = "ldpc")]
+fn ldpc_sweep(max_noise: f32, seed: Option<u64>) -> Vec<SimulationResult> {
+    let code = photon_core::ldpc::LdpcCode::new(&Default::default()).expect("Default LDPC config is valid");
+    match seed {
+        Some(seed) => photon_core::run_ber_simulation_with_ldpc(10_000, 20, max_noise, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(seed)),
+        None => photon_core::run_ber_simulation_with_ldpc(10_000, 20, max_noise, &CodecConfig::default(), &code, &mut rand::rng()),
+    }
+}
+
+#[cfg(not(feature = "ldpc"))]
+fn ldpc_sweep(_max_noise: f32, _seed: Option<u64>) -> Vec<SimulationResult> {
+    eprintln!("LDPC sweeps need the `ldpc` feature");
+    std::process::exit(2);
+}
+
+/// Builds the codec configuration from the CLI options, exiting on invalid input.
+fn codec_config(wavelengths: &Option<Vec<f32>>) -> CodecConfig {
+    let mut config = CodecConfig::default();
+    if let Some(palette) = wavelengths {
+        config.wavelengths = palette.clone();
+    }
+    if let Err(e) = config.validate() {
+        eprintln!("Invalid codec configuration: {}", e);
+        std::process::exit(2);
+    }
+    config
+}
+
+/// True if `path` is `-`, which stands for stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Opens an input path, `-` being stdin.
+fn open_input(path: &Path) -> Box<dyn Read> {
+    if is_stdio(path) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(path).expect("Failed to read input file"))
+    }
+}
+
+/// Parses a `WIDTHxHEIGHT` layer size.
+fn parse_layer(value: &str) -> Result<(usize, usize), String> {
+    let (width, height) = value.split_once('x').ok_or("expected WIDTHxHEIGHT, e.g. 64x64")?;
+    let side = |s: &str| match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid layer side {:?}", s)),
+    };
+    Ok((side(width)?, side(height)?))
+}
+
+/// Default output path for `input`: the same name with `extension`, or
+/// stdout when reading stdin.
+fn default_output(input: &Path, extension: &str) -> PathBuf {
+    let mut path = input.to_path_buf();
+    if !is_stdio(&path) {
+        path.set_extension(extension);
+    }
+    path
+}
+
+/// ECC layout of the `--ecc`, `--ecc-shards` and `--ecc-block` options, if any.
+fn ecc_config(ecc: bool, shards: Option<EccConfig>, block_shard_len: Option<usize>) -> Option<EccConfig> {
+    if !ecc && shards.is_none() && block_shard_len.is_none() {
+        return None;
+    }
+    let mut config = shards.unwrap_or_default();
+    if let Some(len) = block_shard_len {
+        config.block_shard_len = (len > 0).then_some(len);
+    }
+    Some(config)
+}
+
+/// Cost and strength of an ECC layout, e.g. `Reed-Solomon 10+4: 28.6%
+/// overhead, corrects up to 2 byte(s) per codeword`.
+fn describe_ecc(ecc: &EccConfig) -> String {
+    format!(
+        "{} {}+{}: {:.1}% overhead, corrects up to {} byte(s) per codeword",
+        ecc.scheme.name(),
+        ecc.data_shards,
+        ecc.parity_shards,
+        100.0 * ecc.overhead_ratio(),
+        ecc.correctable_errors()
+    )
+}
+
+/// Parses Reed-Solomon shard counts written as DATA+PARITY, e.g. `10+4`.
+fn parse_ecc_shards(value: &str) -> Result<EccConfig, String> {
+    let (data, parity) = value.split_once('+').ok_or_else(|| format!("expected DATA+PARITY (e.g. 10+4), got {:?}", value))?;
+    let count = |shards: &str| shards.trim().parse::<usize>().map_err(|_| format!("invalid shard count {:?}", shards));
+    let config = EccConfig::new(count(data)?, count(parity)?);
+    config.validate()?;
+    Ok(config)
+}
+
+/// Parses an ECC scheme name into its default layout.
+fn parse_ecc_scheme(value: &str) -> Result<EccConfig, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "reed-solomon" | "rs" => Ok(EccConfig::default()),
+        "secded" | "hamming" => Ok(EccConfig::secded()),
+        _ => Err(format!("unknown ECC scheme {:?} (expected reed-solomon or secded)", value)),
+    }
+}
+
+fn parse_connectivity(value: &str) -> Result<Connectivity, String> {
+    match value {
+        "6" => Ok(Connectivity::Six),
+        "18" => Ok(Connectivity::Eighteen),
+        "26" => Ok(Connectivity::TwentySix),
+        _ => Err(format!("unknown connectivity {:?} (expected 6, 18 or 26)", value)),
+    }
+}
+
+/// Parses `clamp[:LEVEL]`, `soft[:LEVEL]` or `gamma:GAMMA[:LEVEL]`, the
+/// saturation level defaulting to 1.5.
+fn parse_sensor_response(value: &str) -> Result<SensorResponse, String> {
+    let mut parts = value.split(':');
+    let kind = parts.next().unwrap_or_default();
+    let mut number = |name: &str| parts.next().map(|part| part.parse::<f32>().map_err(|e| format!("invalid {} {:?}: {}", name, part, e))).transpose();
+    let response = match kind {
+        "clamp" => SensorResponse::Clamp { saturation: number("saturation level")?.unwrap_or(1.5) },
+        "soft" => SensorResponse::Soft { saturation: number("saturation level")?.unwrap_or(1.5) },
+        "gamma" => {
+            let gamma = number("gamma")?.ok_or("gamma needs an exponent, e.g. gamma:0.8")?;
+            SensorResponse::Gamma { gamma, saturation: number("saturation level")?.unwrap_or(1.5) }
         }
+        _ => return Err(format!("unknown sensor response {:?} (expected clamp, soft or gamma)", kind)),
+    };
+    if parts.next().is_some() {
+        return Err(format!("too many parameters in {:?}", value));
     }
+    response.validate()?;
+    Ok(response)
+}
+
+/// Parses a byte count with an optional K, M or G (binary) suffix.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => value.split_at(at),
+        None => (value, ""),
+    };
+    let shift = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        _ => return Err(format!("unknown size unit {:?} (use K, M or G)", unit)),
+    };
+    let count: u64 = digits.parse().map_err(|_| format!("expected a size like 100M, got {:?}", value))?;
+    count.checked_mul(1 << shift).ok_or_else(|| format!("size {} is too large", value))
+}
+
+/// Path of volume `sequence` (from 0) of a split of `input`: `input.001`, ...
+fn volume_path(input: &Path, sequence: u32) -> PathBuf {
+    let mut name = input.as_os_str().to_os_string();
+    name.push(format!(".{:03}", sequence + 1));
+    PathBuf::from(name)
+}
+
+/// The volumes `base.001`, `base.002`, ... next to `volume`, one of them.
+fn volume_set(volume: &Path) -> Vec<PathBuf> {
+    let numbered = |name: &str| name.rsplit_once('.').filter(|(_, number)| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())).map(|(base, _)| base.to_string());
+    let Some(base) = volume.file_name().and_then(|name| numbered(&name.to_string_lossy())) else {
+        return vec![volume.to_path_buf()];
+    };
+    let directory = volume.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+        .expect("Failed to read voxel file")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| numbered(&entry.file_name().to_string_lossy()).as_deref() == Some(base.as_str()))
+        .map(|entry| directory.join(entry.file_name()))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// `Read` over an iterator of bytes, e.g. a voxel decoder.
+struct IterReader<I>(I);
+
+impl<I: Iterator<Item = u8>> Read for IterReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        for (slot, byte) in buf.iter_mut().zip(&mut self.0) {
+            *slot = byte;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Creates a buffered output, `-` being stdout.
+fn create_output(path: &Path) -> BufWriter<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if is_stdio(path) {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(path).expect("Failed to write output file"))
+    };
+    BufWriter::new(writer)
 }
@@ -0,0 +1,152 @@
+use crate::birefringence::BirefringentConfig;
+use crate::channel::Attenuation;
+use crate::physics::{AgingParams, BirefringenceDrift, MaterialResponse, OpticsConfig, WriteParams};
+use std::str::FromStr;
+
+/// Physical constants of a storage medium, shared by every physics stage so
+/// a simulation "in fused silica" uses consistent parameters throughout:
+/// build the stage parameters with [`aging`](Self::aging),
+/// [`write_params`](Self::write_params), [`optics`](Self::optics) and so on,
+/// then adjust what the experiment varies.
+///
+/// The presets are illustrative values of the right order of magnitude, not
+/// measurements of a particular glass or polymer.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Material {
+    pub name: String,
+    /// Absorption per layer of depth (see
+    /// [`simulate_attenuation`](crate::simulate_attenuation)).
+    pub absorption_coefficient: f32,
+    pub refractive_index: f32,
+    /// Retardance of the strongest modification the medium holds, in nm.
+    pub max_retardance_nm: f32,
+    /// Activation energy of the decay of written modifications, in eV.
+    pub activation_energy_ev: f32,
+    /// Decay rate at `reference_temperature_k`, per year.
+    pub reference_rate_per_year: f64,
+    pub reference_temperature_k: f32,
+    /// Recorded modification against exposure dose.
+    pub response: MaterialResponse,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::fused_silica()
+    }
+}
+
+impl Material {
+    /// Nanogratings in fused silica: nearly transparent, up to 120 nm of
+    /// retardance, and the kinetics of [`AgingParams::new`] (stable for
+    /// geological times at room temperature).
+    pub fn fused_silica() -> Self {
+        Self {
+            name: "fused silica".to_string(),
+            absorption_coefficient: 0.002,
+            refractive_index: 1.45,
+            max_retardance_nm: 120.0,
+            activation_energy_ev: 1.8,
+            reference_rate_per_year: 24.0 * 365.25,
+            reference_temperature_k: 1273.0,
+            response: MaterialResponse::Saturating { threshold_nj: 50.0, saturation_nj: 400.0 },
+        }
+    }
+
+    /// Silver-doped phosphate glass: written at lower doses, but absorbing
+    /// more, holding less retardance and relaxing near its lower glass
+    /// transition (one e-fold per hour at 873 K).
+    pub fn doped_glass() -> Self {
+        Self {
+            name: "doped glass".to_string(),
+            absorption_coefficient: 0.01,
+            refractive_index: 1.55,
+            max_retardance_nm: 60.0,
+            activation_energy_ev: 1.4,
+            reference_rate_per_year: 24.0 * 365.25,
+            reference_temperature_k: 873.0,
+            response: MaterialResponse::Saturating { threshold_nj: 20.0, saturation_nj: 250.0 },
+        }
+    }
+
+    /// Photopolymer: linear in dose and cheap to write, but turbid, weakly
+    /// birefringent and decaying at room temperature, the top level of the
+    /// default codec lasting about ten years (one e-fold per hour at 423 K).
+    pub fn photopolymer() -> Self {
+        Self {
+            name: "photopolymer".to_string(),
+            absorption_coefficient: 0.03,
+            refractive_index: 1.50,
+            max_retardance_nm: 20.0,
+            activation_energy_ev: 1.1,
+            reference_rate_per_year: 24.0 * 365.25,
+            reference_temperature_k: 423.0,
+            response: MaterialResponse::Linear { full_dose_nj: 300.0 },
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.absorption_coefficient.is_finite() && self.absorption_coefficient >= 0.0) {
+            return Err(format!("Absorption coefficient must not be negative (got {})", self.absorption_coefficient));
+        }
+        if !(self.refractive_index.is_finite() && self.refractive_index >= 1.0) {
+            return Err(format!("Refractive index must be at least 1 (got {})", self.refractive_index));
+        }
+        self.optics().validate()?;
+        self.aging(293.15, 0.0).validate()?;
+        self.write_params().validate()?;
+        self.birefringence_drift().validate()
+    }
+
+    /// `years` of storage at `temperature_k`.
+    pub fn aging(&self, temperature_k: f32, years: f64) -> AgingParams {
+        AgingParams {
+            temperature_k,
+            years,
+            activation_energy_ev: self.activation_energy_ev,
+            reference_rate_per_year: self.reference_rate_per_year,
+            reference_temperature_k: self.reference_temperature_k,
+        }
+    }
+
+    /// Depth attenuation of a lattice written in the medium.
+    pub fn attenuation(&self) -> Attenuation {
+        Attenuation { absorption_coefficient: self.absorption_coefficient }
+    }
+
+    /// The default optics (see [`OpticsConfig::default`]) focusing into the medium.
+    pub fn optics(&self) -> OpticsConfig {
+        OpticsConfig { refractive_index: self.refractive_index, ..OpticsConfig::default() }
+    }
+
+    /// The default laser writing (see [`WriteParams::default`]) into the medium.
+    pub fn write_params(&self) -> WriteParams {
+        WriteParams { response: self.response, ..WriteParams::default() }
+    }
+
+    /// The default drift (see [`BirefringenceDrift::default`]) of voxels
+    /// written up to the medium's retardance.
+    pub fn birefringence_drift(&self) -> BirefringenceDrift {
+        BirefringenceDrift { max_retardance_nm: self.max_retardance_nm, ..BirefringenceDrift::default() }
+    }
+
+    /// The default nanograting modulation (see
+    /// [`BirefringentConfig::default`]) up to the medium's retardance.
+    pub fn birefringent_config(&self) -> BirefringentConfig {
+        BirefringentConfig { max_retardance_nm: self.max_retardance_nm, ..BirefringentConfig::default() }
+    }
+}
+
+impl FromStr for Material {
+    type Err = String;
+
+    /// A preset by name: `fused-silica`, `doped-glass` or `photopolymer`.
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "fused-silica" | "silica" => Ok(Self::fused_silica()),
+            "doped-glass" => Ok(Self::doped_glass()),
+            "photopolymer" | "polymer" => Ok(Self::photopolymer()),
+            _ => Err(format!("unknown material {:?} (expected fused-silica, doped-glass or photopolymer)", name)),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{decode_data_with_config, encode_data_with_config, CodecConfig};
+use crate::noise::{apply_noise, NoiseProfile};
+
+/// Encodes independent byte streams into one voxel sequence using the default
+/// codec: stream `k` is written on wavelength channel `k` of the palette.
+///
+/// See [`encode_multiplexed_with_config`].
+pub fn encode_multiplexed(streams: &[&[u8]]) -> Vec<PhotonicVoxel> {
+    encode_multiplexed_with_config(streams, &CodecConfig::default())
+}
+
+/// Encodes independent byte streams into one voxel sequence by spectral
+/// multiplexing: stream `k` is modulated on intensity, polarization and phase
+/// and written at `config.wavelengths[k]`, so the wavelength identifies the
+/// stream instead of carrying data bits.
+///
+/// Voxels of the streams are interleaved round-robin; once a stream is
+/// exhausted the others continue without it. Each voxel carries
+/// `config.bits_per_voxel()` minus the wavelength bits of one stream.
+///
+/// Panics if `config` is invalid or there are more streams than wavelengths.
+pub fn encode_multiplexed_with_config(streams: &[&[u8]], config: &CodecConfig) -> Vec<PhotonicVoxel> {
+    check_channels(streams.len(), config);
+
+    let channels: Vec<Vec<PhotonicVoxel>> = streams
+        .iter()
+        .enumerate()
+        .map(|(k, stream)| encode_data_with_config(stream, &channel_config(config, k)))
+        .collect();
+
+    let longest = channels.iter().map(Vec::len).max().unwrap_or(0);
+    let mut voxels = Vec::with_capacity(channels.iter().map(Vec::len).sum());
+    for i in 0..longest {
+        voxels.extend(channels.iter().filter_map(|channel| channel.get(i)));
+    }
+    voxels
+}
+
+/// Separates and decodes `channels` streams written with [`encode_multiplexed`].
+/// Simulates readout noise if `simulate_noise` is true.
+pub fn decode_multiplexed(voxels: &[PhotonicVoxel], channels: usize, simulate_noise: bool) -> Vec<Vec<u8>> {
+    decode_multiplexed_with_config(voxels, channels, simulate_noise, &CodecConfig::default())
+}
+
+/// Demultiplexer for [`encode_multiplexed_with_config`]: assigns every voxel to
+/// the channel with the nearest of the first `channels` wavelengths, then
+/// decodes each channel on its own. Returns one byte stream per channel.
+///
+/// When the per-channel symbol width does not divide evenly into bytes, a
+/// stream may end with a zero padding byte.
+///
+/// Panics if `config` is invalid or there are more channels than wavelengths.
+pub fn decode_multiplexed_with_config(voxels: &[PhotonicVoxel], channels: usize, simulate_noise: bool, config: &CodecConfig) -> Vec<Vec<u8>> {
+    check_channels(channels, config);
+
+    let noisy;
+    let received = if simulate_noise {
+        noisy = apply_noise(voxels, NoiseProfile::default());
+        &noisy[..]
+    } else {
+        voxels
+    };
+
+    let palette = &config.wavelengths[..channels];
+    let mut split = vec![Vec::new(); channels];
+    for voxel in received {
+        let k = (0..channels)
+            .min_by(|&a, &b| (voxel.wavelength - palette[a]).abs().total_cmp(&(voxel.wavelength - palette[b]).abs()))
+            .unwrap_or(0);
+        split[k].push(*voxel);
+    }
+
+    split
+        .iter()
+        .enumerate()
+        .map(|(k, channel)| decode_data_with_config(channel, false, &channel_config(config, k)))
+        .collect()
+}
+
+/// Codec of channel `k`: the levels of `config` at the single wavelength `k`.
+fn channel_config(config: &CodecConfig, k: usize) -> CodecConfig {
+    CodecConfig { wavelengths: vec![config.wavelengths[k]], ..config.clone() }
+}
+
+fn check_channels(channels: usize, config: &CodecConfig) {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+    if channels > config.wavelengths.len() {
+        panic!("Cannot multiplex {} streams on {} wavelengths", channels, config.wavelengths.len());
+    }
+}
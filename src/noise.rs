@@ -0,0 +1,234 @@
+use crate::structs::PhotonicVoxel;
+use rand::Rng;
+use rand_distr::{Distribution, Normal, Poisson};
+
+/// Shape of the noise added to each dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoiseDistribution {
+    /// Normally distributed, unbounded.
+    Gaussian,
+    /// Uniform over `[-sigma * sqrt(3), sigma * sqrt(3)]`, i.e. bounded jitter
+    /// with the given standard deviation.
+    Uniform,
+    /// Photon shot noise of a detector collecting `photons_per_voxel` photons
+    /// from a voxel of intensity 1.0: the intensity is read as a Poisson
+    /// photon count around its expected value, rescaled, so its standard
+    /// deviation is `sqrt(intensity / photons_per_voxel)` and grows
+    /// relatively as the light dims. The standard deviations of the profile
+    /// add Gaussian noise on top (e.g. detector read noise on intensity).
+    Shot { photons_per_voxel: u32 },
+}
+
+impl NoiseDistribution {
+    /// Draws one zero-mean sample with standard deviation `sigma` from `rng`:
+    /// Gaussian for [`Shot`](Self::Shot), whose shot noise is signal
+    /// dependent (see [`NoiseProfile::perturb_with_rng`]).
+    pub(crate) fn sample<R: Rng + ?Sized>(&self, sigma: f32, rng: &mut R) -> f32 {
+        // A zero sigma would be an empty range (or a degenerate normal)
+        if sigma <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            NoiseDistribution::Gaussian | NoiseDistribution::Shot { .. } => Normal::new(0.0, sigma).map_or(0.0, |normal| normal.sample(rng)),
+            NoiseDistribution::Uniform => {
+                let half_width = sigma * 3f32.sqrt();
+                rng.random_range(-half_width..half_width)
+            }
+        }
+    }
+}
+
+/// Per-dimension readout noise model: an independent standard deviation for
+/// each dimension of a voxel.
+///
+/// This is the noise description shared by the codec (simulated readout and
+/// soft-decision reliabilities), the BER analysis and calibration-driven
+/// bit-loading ([`CodecConfig::from_noise_profile`](crate::CodecConfig::from_noise_profile)).
+/// The default is the bounded jitter simulated by `decode_data(voxels, true)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseProfile {
+    pub intensity: f32,
+    /// Radians.
+    pub polarization: f32,
+    /// Radians.
+    pub phase: f32,
+    pub wavelength_nm: f32,
+    pub distribution: NoiseDistribution,
+}
+
+/// Readout noise amplitudes: half-width of the uniform jitter added to each
+/// dimension of a voxel. Shorthand for a uniform [`NoiseProfile`].
+///
+/// The default is the noise simulated by `decode_data(voxels, true)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseParams {
+    pub intensity: f32,
+    /// Radians.
+    pub polarization: f32,
+    /// Radians.
+    pub phase: f32,
+    pub wavelength_nm: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            intensity: 0.05,
+            polarization: 0.08,
+            phase: 0.1,
+            wavelength_nm: 10.0, // +/- 10nm noise
+        }
+    }
+}
+
+impl NoiseParams {
+    /// No noise at all.
+    pub fn none() -> Self {
+        Self::uniform(0.0)
+    }
+
+    /// The same amplitude on intensity, polarization and phase, and 100 times
+    /// that on wavelength (which is on a much larger scale). This is the noise
+    /// model of the BER sweeps.
+    pub fn uniform(amplitude: f32) -> Self {
+        Self {
+            intensity: amplitude,
+            polarization: amplitude,
+            phase: amplitude,
+            wavelength_nm: amplitude * 100.0,
+        }
+    }
+
+    /// Returns a copy of `voxel` with noise added to every dimension.
+    pub fn perturb(&self, voxel: &PhotonicVoxel) -> PhotonicVoxel {
+        NoiseProfile::from(*self).perturb(voxel)
+    }
+
+    /// [`NoiseParams::perturb`] drawing from `rng`, for reproducible runs.
+    pub fn perturb_with_rng<R: Rng + ?Sized>(&self, voxel: &PhotonicVoxel, rng: &mut R) -> PhotonicVoxel {
+        NoiseProfile::from(*self).perturb_with_rng(voxel, rng)
+    }
+}
+
+impl From<NoiseParams> for NoiseProfile {
+    /// Uniform noise of half-width `a` has standard deviation `a / sqrt(3)`.
+    fn from(params: NoiseParams) -> Self {
+        let sigma = |amplitude: f32| amplitude / 3f32.sqrt();
+        Self {
+            intensity: sigma(params.intensity),
+            polarization: sigma(params.polarization),
+            phase: sigma(params.phase),
+            wavelength_nm: sigma(params.wavelength_nm),
+            distribution: NoiseDistribution::Uniform,
+        }
+    }
+}
+
+impl Default for NoiseProfile {
+    fn default() -> Self {
+        NoiseParams::default().into()
+    }
+}
+
+impl NoiseProfile {
+    /// Gaussian noise with the given standard deviations.
+    pub fn gaussian(intensity: f32, polarization: f32, phase: f32, wavelength_nm: f32) -> Self {
+        Self { intensity, polarization, phase, wavelength_nm, distribution: NoiseDistribution::Gaussian }
+    }
+
+    /// No noise at all.
+    pub fn none() -> Self {
+        Self::gaussian(0.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Shot noise alone ([`NoiseDistribution::Shot`]): a detector collecting
+    /// `photons_per_voxel` photons at full intensity, with no other noise.
+    pub fn shot(photons_per_voxel: u32) -> Self {
+        Self { distribution: NoiseDistribution::Shot { photons_per_voxel }, ..Self::none() }
+    }
+
+    /// Standard deviation of the intensity read from a voxel of `intensity`:
+    /// that of the profile, combined with the shot noise of
+    /// [`NoiseDistribution::Shot`].
+    pub fn intensity_sigma_at(&self, intensity: f32) -> f32 {
+        match self.distribution {
+            NoiseDistribution::Shot { photons_per_voxel } => (self.intensity * self.intensity + intensity.max(0.0) / photons_per_voxel.max(1) as f32).sqrt(),
+            _ => self.intensity,
+        }
+    }
+
+    /// Standard deviations in the order intensity, polarization, phase, wavelength.
+    pub fn sigmas(&self) -> [f32; 4] {
+        [self.intensity, self.polarization, self.phase, self.wavelength_nm]
+    }
+
+    /// Variances in the order intensity, polarization, phase, wavelength, as
+    /// taken by [`CodecConfig::from_noise_variances`](crate::CodecConfig::from_noise_variances).
+    pub fn variances(&self) -> [f32; 4] {
+        self.sigmas().map(|s| s * s)
+    }
+
+    /// The same profile with every standard deviation multiplied by `factor`.
+    /// Shot noise scales with it through a photon count divided by
+    /// `factor²`, none at all being `u32::MAX` photons.
+    pub fn scaled(&self, factor: f32) -> Self {
+        let distribution = match self.distribution {
+            NoiseDistribution::Shot { photons_per_voxel } => {
+                NoiseDistribution::Shot { photons_per_voxel: (photons_per_voxel as f64 / (factor as f64 * factor as f64)).min(u32::MAX as f64) as u32 }
+            }
+            distribution => distribution,
+        };
+        Self {
+            intensity: self.intensity * factor,
+            polarization: self.polarization * factor,
+            phase: self.phase * factor,
+            wavelength_nm: self.wavelength_nm * factor,
+            distribution,
+        }
+    }
+
+    /// Returns a copy of `voxel` with noise added to every dimension.
+    pub fn perturb(&self, voxel: &PhotonicVoxel) -> PhotonicVoxel {
+        self.perturb_with_rng(voxel, &mut rand::rng())
+    }
+
+    /// [`NoiseProfile::perturb`] drawing from `rng`, for reproducible runs.
+    pub fn perturb_with_rng<R: Rng + ?Sized>(&self, voxel: &PhotonicVoxel, rng: &mut R) -> PhotonicVoxel {
+        let mut noisy = *voxel;
+        if let NoiseDistribution::Shot { photons_per_voxel } = self.distribution {
+            noisy.intensity = shot_noise(voxel.intensity, photons_per_voxel, rng);
+        }
+        let mut sample = |sigma: f32| self.distribution.sample(sigma, rng);
+        noisy.intensity += sample(self.intensity);
+        noisy.polarization += sample(self.polarization);
+        noisy.phase += sample(self.phase);
+        noisy.wavelength += sample(self.wavelength_nm);
+        noisy
+    }
+}
+
+/// Applies readout noise (a [`NoiseProfile`] or uniform [`NoiseParams`]) to
+/// every voxel.
+pub fn apply_noise(voxels: &[PhotonicVoxel], noise: impl Into<NoiseProfile>) -> Vec<PhotonicVoxel> {
+    apply_noise_with_rng(voxels, noise, &mut rand::rng())
+}
+
+/// [`apply_noise`] drawing from `rng`: the same seed gives the same noisy voxels.
+pub fn apply_noise_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], noise: impl Into<NoiseProfile>, rng: &mut R) -> Vec<PhotonicVoxel> {
+    let profile = noise.into();
+    voxels.iter().map(|v| profile.perturb_with_rng(v, rng)).collect()
+}
+
+/// Intensity read by a detector collecting `photons_per_voxel` photons at
+/// intensity 1.0: a Poisson count around `intensity * photons_per_voxel`,
+/// rescaled to intensity.
+fn shot_noise<R: Rng + ?Sized>(intensity: f32, photons_per_voxel: u32, rng: &mut R) -> f32 {
+    let photons = photons_per_voxel.max(1) as f64;
+    let expected = intensity.max(0.0) as f64 * photons;
+    // Poisson rejects a zero mean: no light, no photons
+    let count: f64 = Poisson::new(expected).map_or(0.0, |poisson| poisson.sample(rng));
+    (count / photons) as f32
+}
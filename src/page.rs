@@ -0,0 +1,187 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{decode_data_with_config, encode_data_with_config, CodecConfig, ModulationScheme};
+
+/// Bytes of the page header: page index, payload length and CRC-32, all `u32`
+/// little-endian.
+const HEADER_BYTES: usize = 12;
+
+/// Voxels taken by the page header, written with the default 8-bit codec
+/// whatever the payload codec, so it can be read without knowing it.
+pub const PAGE_HEADER_VOXELS: usize = HEADER_BYTES;
+
+/// Layout of holographic pages: 2-D voxel pages read out as a whole.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageConfig {
+    /// Voxels per row.
+    pub width: usize,
+    /// Rows per page.
+    pub height: usize,
+    /// Codec of the payload voxels.
+    pub codec: CodecConfig,
+}
+
+impl Default for PageConfig {
+    /// 64x64 pages with the default codec.
+    fn default() -> Self {
+        Self { width: 64, height: 64, codec: CodecConfig::default() }
+    }
+}
+
+impl PageConfig {
+    /// Checks that the codec is valid and a page has room for its header and
+    /// at least one payload byte.
+    pub fn validate(&self) -> Result<(), String> {
+        self.codec.validate()?;
+        if self.capacity() == 0 {
+            return Err(format!(
+                "A {}x{} page has no room for payload after its {}-voxel header",
+                self.width, self.height, PAGE_HEADER_VOXELS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Payload bytes carried by one page.
+    pub fn capacity(&self) -> usize {
+        self.payload_voxels() * self.codec.bits_per_voxel() / 8
+    }
+
+    fn payload_voxels(&self) -> usize {
+        (self.width * self.height).saturating_sub(PAGE_HEADER_VOXELS)
+    }
+}
+
+/// One page of voxels, stored row-major: the header first, then the payload,
+/// then filler voxels up to `width * height`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Page {
+    pub width: usize,
+    pub height: usize,
+    pub voxels: Vec<PhotonicVoxel>,
+}
+
+impl Page {
+    /// Voxel at column `x`, row `y`.
+    pub fn get(&self, x: usize, y: usize) -> &PhotonicVoxel {
+        assert!(x < self.width && y < self.height, "({}, {}) is outside the {}x{} page", x, y, self.width, self.height);
+        &self.voxels[y * self.width + x]
+    }
+
+    /// Row `y` of the page.
+    pub fn row(&self, y: usize) -> &[PhotonicVoxel] {
+        &self.voxels[y * self.width..(y + 1) * self.width]
+    }
+}
+
+/// Outcome of reading one page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageReadout {
+    /// Page index recorded in the header.
+    pub index: u32,
+    /// Payload, as long as recorded in the header (capped at the page capacity).
+    pub data: Vec<u8>,
+    /// True if the CRC over index, length and payload matched.
+    pub crc_ok: bool,
+}
+
+/// Splits `data` into pages of `config.capacity()` bytes, each with a header
+/// recording its index, payload length and CRC-32.
+///
+/// Panics if `config` is invalid (see [`PageConfig::validate`]).
+pub fn encode_pages(data: &[u8], config: &PageConfig) -> Vec<Page> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid page config: {}", e);
+    }
+
+    let header_codec = CodecConfig::default();
+    let filler = config.codec.modulate(0);
+
+    data.chunks(config.capacity())
+        .enumerate()
+        .map(|(index, payload)| {
+            let index = index as u32;
+            let header = page_header(index, payload);
+
+            let mut voxels = encode_data_with_config(&header, &header_codec);
+            voxels.extend(encode_data_with_config(payload, &config.codec));
+            voxels.resize(config.width * config.height, filler);
+
+            Page { width: config.width, height: config.height, voxels }
+        })
+        .collect()
+}
+
+/// Reads a single page: decodes its header with the default codec, then its
+/// payload with `config.codec`, and checks the CRC. Simulates readout noise if
+/// `simulate_noise` is true, so repeated reads of a page may differ.
+///
+/// Panics if `config` is invalid or the page does not match its dimensions.
+pub fn read_page(page: &Page, config: &PageConfig, simulate_noise: bool) -> PageReadout {
+    if let Err(e) = config.validate() {
+        panic!("Invalid page config: {}", e);
+    }
+    if page.voxels.len() != config.width * config.height {
+        panic!("Page has {} voxels, expected {}x{}", page.voxels.len(), config.width, config.height);
+    }
+
+    let header = decode_data_with_config(&page.voxels[..PAGE_HEADER_VOXELS], simulate_noise, &CodecConfig::default());
+    let field = |i: usize| u32::from_le_bytes([header[4 * i], header[4 * i + 1], header[4 * i + 2], header[4 * i + 3]]);
+    let (index, len, crc) = (field(0), field(1) as usize, field(2));
+
+    let mut data = decode_data_with_config(&page.voxels[PAGE_HEADER_VOXELS..], simulate_noise, &config.codec);
+    data.truncate(len.min(config.capacity()));
+
+    let crc_ok = len <= config.capacity() && page_crc(index, &data) == crc;
+    PageReadout { index, data, crc_ok }
+}
+
+/// Reads back pages written with [`encode_pages`], in order.
+///
+/// A page whose CRC fails, or whose recorded index does not match its position,
+/// is read again up to `retries` times (useful with `simulate_noise`, where
+/// each read sees fresh noise). Returns the data, with the last read of each
+/// bad page in place, and the positions of the pages that never read cleanly.
+pub fn decode_pages(pages: &[Page], config: &PageConfig, simulate_noise: bool, retries: usize) -> (Vec<u8>, Vec<usize>) {
+    let mut data = Vec::with_capacity(pages.len() * config.capacity());
+    let mut bad_pages = Vec::new();
+
+    for (position, page) in pages.iter().enumerate() {
+        let is_good = |readout: &PageReadout| readout.crc_ok && readout.index as usize == position;
+
+        let mut readout = read_page(page, config, simulate_noise);
+        for _ in 0..retries {
+            if is_good(&readout) {
+                break;
+            }
+            readout = read_page(page, config, simulate_noise);
+        }
+
+        if !is_good(&readout) {
+            bad_pages.push(position);
+        }
+        data.extend(readout.data);
+    }
+
+    (data, bad_pages)
+}
+
+/// Header bytes of page `index` carrying `payload`.
+fn page_header(index: u32, payload: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_BYTES);
+    header.extend_from_slice(&index.to_le_bytes());
+    header.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    header.extend_from_slice(&page_crc(index, payload).to_le_bytes());
+    header
+}
+
+/// CRC-32 over the page index, the payload length and the payload, so a
+/// corrupted header is detected as well.
+fn page_crc(index: u32, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&index.to_le_bytes());
+    hasher.update(&(payload.len() as u32).to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
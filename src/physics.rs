@@ -1,70 +1,1683 @@
-use crate::structs::PhotonicVoxel;
+use crate::birefringence::BirefringentVoxel;
+use crate::detector::DetectorModel;
+use crate::noise::NoiseProfile;
+use crate::structs::{LatticeShape, PhotonicVoxel, VoxelFloat, VoxelLattice};
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use std::f64::consts::PI;
 
 /// Simulates 3D Cross-talk (Inter-Symbol Interference) in a crystal lattice.
 ///
 /// This function simulates the effect of neighboring voxels "leaking" energy into
 /// the target voxel due to diffraction limits (point spread function).
 ///
-/// `voxels`: The linear sequence of voxels.
-/// `width`: The width of the 2D plane (x-axis).
-/// `height`: The height of the 2D plane (y-axis).
-/// The z-axis (depth) is inferred from the length.
+/// The leaked light carries the neighbours' polarization and phase too, so all
+/// three are perturbed:
+/// - intensity adds up;
+/// - polarization is the orientation of the intensity-weighted sum of the
+///   doubled-angle (Stokes S1, S2) vectors, so a neighbour at the same angle
+///   changes nothing and one at 45° pulls hardest;
+/// - phase is the argument of the sum of the phasors, each weighted by the
+///   intensity and by the overlap `|cos Δθ|` of its polarization with the
+///   voxel's: orthogonally polarized light does not interfere.
+///
+/// `lattice`: The voxels with their 3D layout, in `f32` or, to avoid rounding
+/// when chaining simulations, `f64` (see [`VoxelLattice::to_f64`]).
 /// `crosstalk_factor`: The fraction of energy leaked from neighbors (e.g., 0.01).
-pub fn simulate_crosstalk(voxels: &[PhotonicVoxel], width: usize, height: usize, crosstalk_factor: f32) -> Vec<PhotonicVoxel> {
-    if width == 0 || height == 0 {
-        return voxels.to_vec();
+///
+/// With the `parallel` feature, large lattices are processed in z-slabs on
+/// the rayon pool, with the same result.
+pub fn simulate_crosstalk<F: VoxelFloat>(lattice: &VoxelLattice<F>, crosstalk_factor: f32) -> VoxelLattice<F> {
+    simulate_crosstalk_with_config(lattice, &CrosstalkConfig::new(crosstalk_factor))
+}
+
+/// [`simulate_crosstalk`] with wavelength-selective coupling: light leaking
+/// from a neighbour only reaches the detector channel of the voxel as far as
+/// their spectral lines overlap. With Gaussian lines of standard deviation
+/// `linewidth_nm`, the leakage from a neighbour `Δλ` away is scaled by
+/// `exp(-Δλ² / (4 * linewidth_nm²))`: in full at the same wavelength, hardly
+/// at all between well separated channels, which is the isolation spectral
+/// multiplexing buys.
+pub fn simulate_spectral_crosstalk<F: VoxelFloat>(lattice: &VoxelLattice<F>, crosstalk_factor: f32, linewidth_nm: f32) -> VoxelLattice<F> {
+    simulate_crosstalk_with_config(lattice, &CrosstalkConfig { linewidth_nm: Some(linewidth_nm), ..CrosstalkConfig::new(crosstalk_factor) })
+}
+
+/// Overlap of two Gaussian spectral lines of standard deviation
+/// `linewidth_nm`, `delta_nm` apart, normalised to 1 when they coincide.
+pub fn spectral_overlap(delta_nm: f64, linewidth_nm: f64) -> f64 {
+    if linewidth_nm <= 0.0 {
+        return if delta_nm == 0.0 { 1.0 } else { 0.0 };
     }
+    (-delta_nm * delta_nm / (4.0 * linewidth_nm * linewidth_nm)).exp()
+}
 
-    let layer_size = width * height;
-    let depth = voxels.len().div_ceil(layer_size);
-    let mut output = voxels.to_vec();
+/// Which voxels around a voxel count as its neighbours, within a
+/// [`CrosstalkConfig::radius`] of `r` voxels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Connectivity {
+    /// Faces: offsets of at most `r` steps along the axes in total (an
+    /// octahedron; the 6 face neighbours for `r = 1`).
+    #[default]
+    Six,
+    /// Faces and edges: offsets of at most `r` along every axis and `2r` in
+    /// total (18 neighbours for `r = 1`).
+    Eighteen,
+    /// Faces, edges and corners: offsets of at most `r` along every axis (a
+    /// cube; 26 neighbours for `r = 1`).
+    TwentySix,
+}
 
-    // Helper to get index
-    let get_idx = |x: usize, y: usize, z: usize| -> Option<usize> {
-        if x >= width || y >= height || z >= depth {
-            None
-        } else {
-            let idx = z * layer_size + y * width + x;
-            if idx < voxels.len() { Some(idx) } else { None }
+impl Connectivity {
+    /// Neighbours of a voxel for a radius of one.
+    pub fn neighbours(&self) -> usize {
+        match self {
+            Connectivity::Six => 6,
+            Connectivity::Eighteen => 18,
+            Connectivity::TwentySix => 26,
+        }
+    }
+}
+
+/// Crosstalk model of [`simulate_crosstalk_with_config`]: which neighbours
+/// leak into a voxel, and how much.
+///
+/// A neighbour at Euclidean distance `d` (in voxel pitches) leaks
+/// `factor / d²` of its light: `factor` from the face neighbours, less from
+/// diagonal and farther ones. A larger radius models an optical spot wide
+/// compared to the voxel pitch, i.e. denser packing for the same optics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrosstalkConfig {
+    /// Fraction of the light leaked by the face neighbours (e.g., 0.01).
+    pub factor: f32,
+    pub connectivity: Connectivity,
+    /// Farthest offset along any axis, in voxels.
+    pub radius: usize,
+    /// Standard deviation of the spectral lines, for wavelength-selective
+    /// coupling (see [`simulate_spectral_crosstalk`]); `None` couples every
+    /// wavelength alike.
+    pub linewidth_nm: Option<f32>,
+    /// Detector response to the intensity read, crosstalk included.
+    pub response: SensorResponse,
+}
+
+impl CrosstalkConfig {
+    /// The 6-neighbour model of [`simulate_crosstalk`].
+    pub fn new(factor: f32) -> Self {
+        Self { factor, connectivity: Connectivity::Six, radius: 1, linewidth_nm: None, response: SensorResponse::default() }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.factor.is_finite() && self.factor >= 0.0) {
+            return Err(format!("Crosstalk factor must not be negative (got {})", self.factor));
+        }
+        if self.radius == 0 {
+            return Err("Crosstalk radius must be at least one voxel".to_string());
+        }
+        if self.linewidth_nm.is_some_and(|linewidth| linewidth.is_nan() || linewidth < 0.0) {
+            return Err(format!("Spectral linewidth must not be negative (got {:?})", self.linewidth_nm));
+        }
+        self.response.validate()
+    }
+
+    /// Offsets (dx, dy, dz) of the neighbours, with the fraction of light each
+    /// leaks, nearest first.
+    pub fn offsets(&self) -> Vec<((isize, isize, isize), f64)> {
+        let r = self.radius as isize;
+        let mut offsets = Vec::new();
+        for dz in -r..=r {
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let steps = dx.abs() + dy.abs() + dz.abs();
+                    let inside = match self.connectivity {
+                        Connectivity::Six => steps <= r,
+                        Connectivity::Eighteen => steps <= 2 * r,
+                        Connectivity::TwentySix => true,
+                    };
+                    if steps > 0 && inside {
+                        let distance_squared = (dx * dx + dy * dy + dz * dz) as f64;
+                        offsets.push(((dx, dy, dz), self.factor as f64 / distance_squared));
+                    }
+                }
+            }
+        }
+        offsets.sort_by(|a, b| b.1.total_cmp(&a.1));
+        offsets
+    }
+
+    /// Fraction of `neighbor`'s light leaking into `voxel` from an offset of
+    /// `weight`, after the spectral overlap.
+    fn coupling<F: VoxelFloat>(&self, weight: f64, voxel: &PhotonicVoxel<F>, neighbor: &PhotonicVoxel<F>) -> f64 {
+        match self.linewidth_nm {
+            Some(linewidth) => weight * spectral_overlap(voxel.wavelength.as_f64() - neighbor.wavelength.as_f64(), linewidth as f64),
+            None => weight,
+        }
+    }
+}
+
+/// Crosstalk between the neighbours of `config`, perturbing intensity,
+/// polarization and phase as described for [`simulate_crosstalk`].
+///
+/// Panics if `config` is invalid.
+pub fn simulate_crosstalk_with_config<F: VoxelFloat>(lattice: &VoxelLattice<F>, config: &CrosstalkConfig) -> VoxelLattice<F> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid crosstalk config: {}", e);
+    }
+    let offsets = config.offsets();
+    map_slabs(lattice, |(x, y, z), &voxel| {
+        let mut original = voxel;
+        let mut polarization = Mixture::new(voxel.intensity.as_f64(), 2.0 * voxel.polarization.as_f64());
+        let mut phase = Mixture::new(voxel.intensity.as_f64(), voxel.phase.as_f64());
+        let mut leaking = false;
+
+        for &((dx, dy, dz), weight) in &offsets {
+            // wrapping_add_signed turns -1 into usize::MAX, which `get` rejects as out of bounds.
+            if let Some(neighbor) = lattice.get(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy), z.wrapping_add_signed(dz)) {
+                let factor = config.coupling(weight, &voxel, neighbor);
+                // Add a fraction of neighbor's intensity to this voxel
+                original.intensity = original.intensity + neighbor.intensity * F::from_f64(factor);
+                leaking |= factor != 0.0;
+
+                let leaked = neighbor.intensity.as_f64() * factor;
+                polarization.add(leaked, 2.0 * neighbor.polarization.as_f64());
+                let overlap = (voxel.polarization.as_f64() - neighbor.polarization.as_f64()).cos().abs();
+                phase.add(leaked * overlap, neighbor.phase.as_f64());
+            }
+        }
+
+        // Detectors saturate: bloom from bright neighbours is compressed
+        original.intensity = F::from_f64(config.response.read(original.intensity.as_f64()));
+        if leaking {
+            if let Some(doubled) = polarization.angle() {
+                let angle = (doubled / 2.0).rem_euclid(PI);
+                original.polarization = F::from_f64(if angle >= PI { 0.0 } else { angle });
+            }
+            if let Some(phase) = phase.angle() {
+                original.phase = F::from_f64(phase.rem_euclid(2.0 * PI));
+            }
+        }
+
+        original
+    })
+}
+
+/// Voxels per z-slab of [`map_slabs`], rounded to whole layers.
+const SLAB_VOXELS: usize = 64 * 1024;
+
+/// `lattice` with every voxel replaced by `read(position, voxel)`, computed
+/// slab by slab of whole layers along z: on the rayon pool with the
+/// `parallel` feature (for lattices large enough to pay off), else in turn.
+/// Every slab reads the whole input lattice, so voxels at a slab boundary
+/// see their neighbours in the next slab exactly as in a single pass.
+fn map_slabs<F: VoxelFloat>(lattice: &VoxelLattice<F>, read: impl Fn((usize, usize, usize), &PhotonicVoxel<F>) -> PhotonicVoxel<F> + Sync) -> VoxelLattice<F> {
+    let (width, layer) = (lattice.width().max(1), (lattice.width() * lattice.height()).max(1));
+    let slab = (SLAB_VOXELS / layer).max(1) * layer;
+    let fill = |(index, voxels): (usize, &mut [PhotonicVoxel<F>])| {
+        for (offset, voxel) in voxels.iter_mut().enumerate() {
+            let i = index * slab + offset;
+            *voxel = read((i % width, i % layer / width, i / layer), &lattice.voxels()[i]);
         }
     };
 
-    for z in 0..depth {
-        for y in 0..height {
-            for x in 0..width {
-                if let Some(target_idx) = get_idx(x, y, z) {
-                    let mut original = voxels[target_idx];
-
-                    // Neighbors (6-connectivity for simplicity: left, right, up, down, front, back)
-                    let neighbors = [
-                        (x.wrapping_sub(1), y, z), (x + 1, y, z),
-                        (x, y.wrapping_sub(1), z), (x, y + 1, z),
-                        (x, y, z.wrapping_sub(1)), (x, y, z + 1)
-                    ];
-
-                    for &(nx, ny, nz) in &neighbors {
-                        // Check bounds (wrapping_sub handles < 0 check via usize overflow, but we must check max)
-                        // Actually usize wrap causes huge number, so we check < width/height/depth
-                        if nx < width && ny < height && nz < depth {
-                             if let Some(n_idx) = get_idx(nx, ny, nz) {
-                                 let neighbor = voxels[n_idx];
-                                 // Add a fraction of neighbor's intensity to this voxel
-                                 // Simplified model: intensity adds up
-                                 original.intensity += neighbor.intensity * crosstalk_factor;
-
-                                 // Polarization might rotate slightly? For now just intensity leakage.
-                             }
-                        }
+    let mut output = lattice.clone();
+    #[cfg(feature = "parallel")]
+    if lattice.len() >= crate::codec::PARALLEL_THRESHOLD {
+        use rayon::prelude::*;
+        output.voxels_mut().par_chunks_mut(slab).enumerate().for_each(fill);
+        return output;
+    }
+    output.voxels_mut().chunks_mut(slab).enumerate().for_each(fill);
+    output
+}
+
+/// Most iterations of [`equalize`].
+const EQUALIZER_ITERATIONS: usize = 100;
+
+/// Undoes the intensity crosstalk of [`simulate_crosstalk_with_config`]
+/// before demodulation: the intensities `I` a lattice was written with solve
+/// `I + W I = R`, `R` being the intensities read and `W` the leakage of
+/// `config` between neighbours, and are found by iterating
+/// `I ← max(R - W I, 0)` from `I = R` until they settle.
+///
+/// The iteration converges when the leakage into a voxel sums to less than
+/// one (e.g. a factor below 1/6 with six neighbours); the residual error
+/// shrinks by about that sum per iteration. Noise is amplified by at most
+/// `1 / (1 - sum)`. The reading is first linearized through the inverse of
+/// `config.response`, but voxels read at the saturation level cannot be
+/// recovered. Polarization and phase, mixed non-linearly, are left as read.
+///
+/// Panics if `config` is invalid.
+pub fn equalize<F: VoxelFloat>(lattice: &VoxelLattice<F>, config: &CrosstalkConfig) -> VoxelLattice<F> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid crosstalk config: {}", e);
+    }
+    // Leakage into each voxel, as (source index, coupling)
+    let offsets = config.offsets();
+    let leakage: Vec<Vec<(usize, f64)>> = lattice
+        .iter_indexed()
+        .map(|((x, y, z), voxel)| {
+            offsets
+                .iter()
+                .filter_map(|&((dx, dy, dz), weight)| {
+                    let source = lattice.index(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy), z.wrapping_add_signed(dz))?;
+                    Some((source, config.coupling(weight, voxel, &lattice.voxels()[source])))
+                })
+                .collect()
+        })
+        .collect();
+
+    let read: Vec<f64> = lattice.iter().map(|voxel| config.response.linearize(voxel.intensity.as_f64())).collect();
+    let mut estimate = read.clone();
+    for _ in 0..EQUALIZER_ITERATIONS {
+        let next: Vec<f64> = read
+            .iter()
+            .zip(&leakage)
+            .map(|(&read, sources)| (read - sources.iter().map(|&(source, coupling)| coupling * estimate[source]).sum::<f64>()).max(0.0))
+            .collect();
+        let change = next.iter().zip(&estimate).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        estimate = next;
+        if change < 1e-9 {
+            break;
+        }
+    }
+
+    let mut output = lattice.clone();
+    for (voxel, intensity) in output.iter_mut().zip(estimate) {
+        voxel.intensity = F::from_f64(intensity);
+    }
+    output
+}
+
+/// Highest fraction of the saturation level [`SensorResponse::linearize`]
+/// inverts a soft saturation from: readings closer to the level could come
+/// from almost any intensity.
+const MAX_SOFT_READING: f64 = 0.999;
+
+/// Response of the detector to the intensity reaching it, `I` below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SensorResponse {
+    /// Linear up to `saturation`, flat above: a hard clip.
+    Clamp { saturation: f32 },
+    /// `saturation * tanh(I / saturation)`: linear for weak signals, bending
+    /// smoothly towards the saturation level like a real sensor's full well.
+    Soft { saturation: f32 },
+    /// `saturation * (I / saturation)^gamma` up to `saturation`, flat above: a
+    /// power-law sensor or display-referred camera output.
+    Gamma { gamma: f32, saturation: f32 },
+}
+
+impl Default for SensorResponse {
+    /// The hard clip at 1.5 of the original crosstalk model.
+    fn default() -> Self {
+        SensorResponse::Clamp { saturation: 1.5 }
+    }
+}
+
+impl SensorResponse {
+    pub fn validate(&self) -> Result<(), String> {
+        let (SensorResponse::Clamp { saturation } | SensorResponse::Soft { saturation } | SensorResponse::Gamma { saturation, .. }) = *self;
+        if !(saturation.is_finite() && saturation > 0.0) {
+            return Err(format!("Saturation level must be positive (got {})", saturation));
+        }
+        if let SensorResponse::Gamma { gamma, .. } = *self {
+            if !(gamma.is_finite() && gamma > 0.0) {
+                return Err(format!("Gamma must be positive (got {})", gamma));
+            }
+        }
+        Ok(())
+    }
+
+    /// Detector reading of intensity `intensity`. Negative intensities (from
+    /// noise) are mirrored.
+    pub fn read(&self, intensity: f64) -> f64 {
+        match *self {
+            SensorResponse::Clamp { saturation } => intensity.min(saturation as f64),
+            SensorResponse::Soft { saturation } => saturation as f64 * (intensity / saturation as f64).tanh(),
+            SensorResponse::Gamma { gamma, saturation } => {
+                let saturation = saturation as f64;
+                let ratio = (intensity.abs() / saturation).min(1.0);
+                intensity.signum() * saturation * ratio.powf(gamma as f64)
+            }
+        }
+    }
+
+    /// Intensity that `reading` came from, undoing [`read`](Self::read) for
+    /// the decoder. Saturation cannot be undone: a clipped reading comes back
+    /// as the saturation level, and a soft one is inverted from at most 99.9%
+    /// of it.
+    pub fn linearize(&self, reading: f64) -> f64 {
+        match *self {
+            SensorResponse::Clamp { .. } => reading,
+            SensorResponse::Soft { saturation } => {
+                let saturation = saturation as f64;
+                saturation * (reading / saturation).clamp(-MAX_SOFT_READING, MAX_SOFT_READING).atanh()
+            }
+            SensorResponse::Gamma { gamma, saturation } => {
+                let saturation = saturation as f64;
+                let ratio = (reading.abs() / saturation).min(1.0);
+                reading.signum() * saturation * ratio.powf(1.0 / gamma as f64)
+            }
+        }
+    }
+}
+
+/// Passes the intensities of `lattice` through the detector `response`, for
+/// readout models without one of their own (e.g. after [`simulate_scattering`]).
+///
+/// Panics if `response` is invalid.
+pub fn simulate_sensor_response<F: VoxelFloat>(lattice: &VoxelLattice<F>, response: &SensorResponse) -> VoxelLattice<F> {
+    map_intensities(lattice, response, SensorResponse::read)
+}
+
+/// Undoes [`simulate_sensor_response`] before demodulating, as far as the
+/// response can be inverted (see [`SensorResponse::linearize`]).
+///
+/// Panics if `response` is invalid.
+pub fn compensate_sensor_response<F: VoxelFloat>(lattice: &VoxelLattice<F>, response: &SensorResponse) -> VoxelLattice<F> {
+    map_intensities(lattice, response, SensorResponse::linearize)
+}
+
+fn map_intensities<F: VoxelFloat>(lattice: &VoxelLattice<F>, response: &SensorResponse, map: fn(&SensorResponse, f64) -> f64) -> VoxelLattice<F> {
+    if let Err(e) = response.validate() {
+        panic!("Invalid sensor response: {}", e);
+    }
+    let mut output = lattice.clone();
+    for voxel in output.iter_mut() {
+        voxel.intensity = F::from_f64(map(response, voxel.intensity.as_f64()));
+    }
+    output
+}
+
+/// Weighted sum of unit vectors (phasors) at given angles.
+struct Mixture {
+    x: f64,
+    y: f64,
+}
+
+impl Mixture {
+    fn new(weight: f64, angle: f64) -> Self {
+        Self { x: weight * angle.cos(), y: weight * angle.sin() }
+    }
+
+    fn add(&mut self, weight: f64, angle: f64) {
+        self.x += weight * angle.cos();
+        self.y += weight * angle.sin();
+    }
+
+    /// Angle of the sum, unless the vectors cancel out.
+    fn angle(&self) -> Option<f64> {
+        (self.x.hypot(self.y) > 1e-12).then(|| self.y.atan2(self.x))
+    }
+}
+
+/// Simulates depth-dependent absorption (Beer–Lambert law): the light
+/// reading layer `z` crosses `z` layers on the way in and again on the way
+/// out, so its intensity is scaled by `exp(-2 * absorption_coefficient * z)`.
+///
+/// `absorption_coefficient`: Absorption per layer of depth (e.g., 0.01).
+pub fn simulate_attenuation<F: VoxelFloat>(lattice: &VoxelLattice<F>, absorption_coefficient: f32) -> VoxelLattice<F> {
+    let mut output = lattice.clone();
+    for z in 0..lattice.depth() {
+        let transmission = F::from(attenuation_at(z, absorption_coefficient));
+        for voxel in output.layer_mut(z) {
+            voxel.intensity = voxel.intensity * transmission;
+        }
+    }
+    output
+}
+
+/// Fraction of the intensity of layer `z` that [`simulate_attenuation`]
+/// leaves.
+pub fn attenuation_at(z: usize, absorption_coefficient: f32) -> f32 {
+    (-2.0 * absorption_coefficient * z as f32).exp()
+}
+
+/// Simulates volumetric scattering: written voxels scatter part of the
+/// reading light, and the scattered light reaching the detector adds a
+/// background haze to the voxel in focus. The haze of layer `z` is
+/// `scattering_coefficient` times the total intensity written in the voxels
+/// above it along the read path (layers `0..z` of its column), so contrast
+/// degrades most in dense, deep lattices.
+///
+/// `scattering_coefficient`: Fraction of a written voxel's intensity
+/// scattered into the voxels read through it (e.g., 0.005).
+pub fn simulate_scattering<F: VoxelFloat>(lattice: &VoxelLattice<F>, scattering_coefficient: f32) -> VoxelLattice<F> {
+    let mut output = lattice.clone();
+    let coefficient = F::from(scattering_coefficient);
+    for y in 0..lattice.height() {
+        for x in 0..lattice.width() {
+            let mut written = F::from(0.0);
+            for z in 0..lattice.depth() {
+                let voxel = output.get_mut(x, y, z).expect("position inside the lattice");
+                let intensity = voxel.intensity;
+                voxel.intensity = intensity + written * coefficient;
+                written = written + intensity;
+            }
+        }
+    }
+    output
+}
+
+/// Detector saturation of [`simulate_psf`] and [`simulate_psf_fft`], the
+/// default [`SensorResponse`] of [`simulate_crosstalk`].
+const SATURATION: f32 = 1.5;
+
+/// Point spread function of the readout: the fraction of a voxel's intensity
+/// read at each offset from it, in a box of odd size centred on the voxel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PsfKernel {
+    size: (usize, usize, usize),
+    weights: Vec<f32>,
+}
+
+impl PsfKernel {
+    /// A kernel of `size` (x, y, z), with `weights` in lattice order (x
+    /// fastest). Fails unless every size is odd and there is a weight for
+    /// every offset.
+    pub fn new(size: (usize, usize, usize), weights: Vec<f32>) -> Result<Self, String> {
+        let (x, y, z) = size;
+        if [x, y, z].iter().any(|n| n.is_multiple_of(2)) {
+            return Err(format!("PSF kernel sizes must be odd (got {}x{}x{})", x, y, z));
+        }
+        if weights.len() != x * y * z {
+            return Err(format!("{} weights for a {}x{}x{} PSF kernel", weights.len(), x, y, z));
+        }
+        Ok(Self { size, weights })
+    }
+
+    /// The 6-neighbour intensity leakage of [`simulate_crosstalk`]: the voxel
+    /// itself plus `crosstalk_factor` of each face neighbour.
+    pub fn six_neighbor(crosstalk_factor: f32) -> Self {
+        let mut weights = vec![0.0; 27];
+        for (x, y, z) in [(0, 1, 1), (2, 1, 1), (1, 0, 1), (1, 2, 1), (1, 1, 0), (1, 1, 2)] {
+            weights[(z * 3 + y) * 3 + x] = crosstalk_factor;
+        }
+        weights[13] = 1.0;
+        Self { size: (3, 3, 3), weights }
+    }
+
+    /// A Gaussian spot of standard deviations `sigma_lateral` (x, y) and
+    /// `sigma_axial` (z) voxels, cut off at three standard deviations, and
+    /// scaled so that the voxel itself reads in full.
+    pub fn gaussian(sigma_lateral: f32, sigma_axial: f32) -> Self {
+        let radius = |sigma: f32| (3.0 * sigma).ceil().max(0.0) as usize;
+        let (rxy, rz) = (radius(sigma_lateral), radius(sigma_axial));
+        let falloff = |d: usize, r: usize, sigma: f32| if sigma > 0.0 { (-((d as f32 - r as f32) / sigma).powi(2) / 2.0).exp() } else { 1.0 };
+        let size = (2 * rxy + 1, 2 * rxy + 1, 2 * rz + 1);
+        let mut weights = Vec::with_capacity(size.0 * size.1 * size.2);
+        for z in 0..size.2 {
+            for y in 0..size.1 {
+                for x in 0..size.0 {
+                    weights.push(falloff(x, rxy, sigma_lateral) * falloff(y, rxy, sigma_lateral) * falloff(z, rz, sigma_axial));
+                }
+            }
+        }
+        Self { size, weights }
+    }
+
+    /// The kernel scaled so that its weights sum to one: the light read is
+    /// spread over the offsets rather than added to the voxel's own.
+    pub fn normalized(mut self) -> Self {
+        let total: f32 = self.weights.iter().sum();
+        if total != 0.0 {
+            for weight in &mut self.weights {
+                *weight /= total;
+            }
+        }
+        self
+    }
+
+    /// Sizes along x, y and z.
+    pub fn size(&self) -> (usize, usize, usize) {
+        self.size
+    }
+
+    /// Weight of the offset (`dx`, `dy`, `dz`) from the centre, zero outside
+    /// the kernel.
+    pub fn weight(&self, dx: isize, dy: isize, dz: isize) -> f32 {
+        let index = |d: isize, n: usize| usize::try_from(d + (n / 2) as isize).ok().filter(|&i| i < n);
+        match (index(dx, self.size.0), index(dy, self.size.1), index(dz, self.size.2)) {
+            (Some(x), Some(y), Some(z)) => self.weights[(z * self.size.1 + y) * self.size.0 + x],
+            _ => 0.0,
+        }
+    }
+}
+
+/// Blurs the intensities of `lattice` with the point spread function
+/// `kernel`, by direct convolution: every voxel reads the weighted sum of the
+/// intensities around it (nothing beyond the lattice edges), clamped at the
+/// detector saturation like [`simulate_crosstalk`].
+///
+/// The cost grows with the kernel volume; for wide kernels on large lattices
+/// [`simulate_psf_fft`] (feature `fft`) is much faster. With the `parallel`
+/// feature, large lattices are blurred in z-slabs on the rayon pool.
+pub fn simulate_psf<F: VoxelFloat>(lattice: &VoxelLattice<F>, kernel: &PsfKernel) -> VoxelLattice<F> {
+    map_slabs(lattice, |position, &voxel| PhotonicVoxel { intensity: read_through(lattice, kernel, position), ..voxel })
+}
+
+/// Intensities of `lattice` with each layer `z` read through `kernel_at(z)`.
+fn read_layers<F: VoxelFloat>(lattice: &VoxelLattice<F>, kernel_at: impl Fn(usize) -> PsfKernel) -> VoxelLattice<F> {
+    let kernels: Vec<PsfKernel> = (0..lattice.depth()).map(kernel_at).collect();
+    map_slabs(lattice, |(x, y, z), &voxel| PhotonicVoxel { intensity: read_through(lattice, &kernels[z], (x, y, z)), ..voxel })
+}
+
+/// Intensity read at `position` of `lattice` through `kernel`, clamped at the
+/// detector saturation.
+fn read_through<F: VoxelFloat>(lattice: &VoxelLattice<F>, kernel: &PsfKernel, (x, y, z): (usize, usize, usize)) -> F {
+    let (kx, ky, kz) = kernel.size;
+    let saturation = F::from(SATURATION);
+    let mut intensity = F::from(0.0);
+    for (i, &weight) in kernel.weights.iter().enumerate() {
+        if weight == 0.0 {
+            continue;
+        }
+        let (dx, dy, dz) = (i % kx, i / kx % ky, i / (kx * ky));
+        // Source voxel at position - offset; wrapping_sub leaves the lattice
+        let source = lattice.get((x + kx / 2).wrapping_sub(dx), (y + ky / 2).wrapping_sub(dy), (z + kz / 2).wrapping_sub(dz));
+        if let Some(source) = source {
+            intensity = intensity + source.intensity * F::from(weight);
+        }
+    }
+    if intensity > saturation {
+        saturation
+    } else {
+        intensity
+    }
+}
+
+/// FWHM of a Gaussian of standard deviation 1.
+const FWHM_PER_SIGMA: f32 = 2.354_82;
+
+/// Readout optics and voxel layout in physical units, from which the spot
+/// size and the crosstalk between voxels follow.
+///
+/// The focus is diffraction limited: lateral FWHM `0.51 λ / NA`, axial FWHM
+/// `0.88 λ / (n - sqrt(n² - NA²))` inside glass of refractive index `n`, and
+/// is approximated by a Gaussian of the same widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpticsConfig {
+    /// Numerical aperture of the objective.
+    pub numerical_aperture: f32,
+    /// Wavelength of the reading light, in nm.
+    pub wavelength_nm: f32,
+    /// Refractive index of the medium (fused silica: 1.45).
+    pub refractive_index: f32,
+    /// Distance between neighbouring voxels of a layer, in µm.
+    pub voxel_pitch_um: f32,
+    /// Distance between layers, in µm.
+    pub layer_spacing_um: f32,
+}
+
+impl Default for OpticsConfig {
+    /// A 0.75 NA objective reading at 650 nm in fused silica, voxels 0.5 µm
+    /// apart in layers 3 µm apart: about 3% crosstalk from the lateral and
+    /// 4% from the axial neighbours.
+    fn default() -> Self {
+        Self { numerical_aperture: 0.75, wavelength_nm: 650.0, refractive_index: 1.45, voxel_pitch_um: 0.5, layer_spacing_um: 3.0 }
+    }
+}
+
+impl OpticsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.refractive_index.is_finite() && self.refractive_index >= 1.0) {
+            return Err(format!("Refractive index must be at least 1 (got {})", self.refractive_index));
+        }
+        if !(self.numerical_aperture > 0.0 && self.numerical_aperture < self.refractive_index) {
+            return Err(format!("Numerical aperture must be positive and below the refractive index {} (got {})", self.refractive_index, self.numerical_aperture));
+        }
+        for (name, value) in [("Wavelength", self.wavelength_nm), ("Voxel pitch", self.voxel_pitch_um), ("Layer spacing", self.layer_spacing_um)] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{} must be positive (got {})", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    fn wavelength_um(&self) -> f32 {
+        self.wavelength_nm / 1000.0
+    }
+
+    /// Diameter of the Airy disk (to its first zero), `1.22 λ / NA`, in µm.
+    pub fn spot_diameter_um(&self) -> f32 {
+        1.22 * self.wavelength_um() / self.numerical_aperture
+    }
+
+    /// Lateral FWHM of the focus, in µm.
+    pub fn lateral_fwhm_um(&self) -> f32 {
+        0.51 * self.wavelength_um() / self.numerical_aperture
+    }
+
+    /// Axial FWHM of the focus, in µm.
+    pub fn axial_fwhm_um(&self) -> f32 {
+        let n = self.refractive_index;
+        0.88 * self.wavelength_um() / (n - (n * n - self.numerical_aperture * self.numerical_aperture).sqrt())
+    }
+
+    /// Smallest voxel pitch the optics resolve (Rayleigh criterion,
+    /// `0.61 λ / NA`), in µm.
+    pub fn min_voxel_pitch_um(&self) -> f32 {
+        0.61 * self.wavelength_um() / self.numerical_aperture
+    }
+
+    /// Smallest layer spacing the optics resolve: the axial FWHM, in µm.
+    pub fn min_layer_spacing_um(&self) -> f32 {
+        self.axial_fwhm_um()
+    }
+
+    /// Lateral standard deviation of the focus, in voxel pitches.
+    pub fn sigma_lateral(&self) -> f32 {
+        self.lateral_fwhm_um() / FWHM_PER_SIGMA / self.voxel_pitch_um
+    }
+
+    /// Axial standard deviation of the focus, in layers.
+    pub fn sigma_axial(&self) -> f32 {
+        self.axial_fwhm_um() / FWHM_PER_SIGMA / self.layer_spacing_um
+    }
+
+    /// The focus as a point spread function, for [`simulate_psf`].
+    pub fn psf_kernel(&self) -> PsfKernel {
+        PsfKernel::gaussian(self.sigma_lateral(), self.sigma_axial())
+    }
+
+    /// Fraction of a voxel's light read from a lateral neighbour, the
+    /// `crosstalk_factor` of [`simulate_crosstalk`] for this layout.
+    pub fn crosstalk_factor(&self) -> f32 {
+        (-0.5 / (self.sigma_lateral() * self.sigma_lateral())).exp()
+    }
+
+    /// Fraction of a voxel's light read from an axial neighbour.
+    pub fn axial_crosstalk_factor(&self) -> f32 {
+        (-0.5 / (self.sigma_axial() * self.sigma_axial())).exp()
+    }
+}
+
+/// Room [`optimize_geometry`] may fill, and the absorption that makes depth
+/// costly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeometryLimits {
+    /// Side of the square area a layer may cover (field of view of the
+    /// scanner or size of the medium), in µm.
+    pub max_extent_um: f32,
+    /// Deepest layer the objective reaches (working distance), in µm.
+    pub max_depth_um: f32,
+    /// Absorption of the medium per µm of depth (see [`simulate_attenuation`]).
+    pub absorption_per_um: f32,
+}
+
+impl Default for GeometryLimits {
+    /// A 200 µm field down to 500 µm into glass absorbing 5e-4 per µm, so
+    /// the deepest layer reads at 61% of the surface.
+    fn default() -> Self {
+        Self { max_extent_um: 200.0, max_depth_um: 500.0, absorption_per_um: 5e-4 }
+    }
+}
+
+impl GeometryLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("Extent", self.max_extent_um), ("Depth", self.max_depth_um)] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{} must be positive (got {} µm)", name, value));
+            }
+        }
+        if !(self.absorption_per_um.is_finite() && self.absorption_per_um >= 0.0) {
+            return Err(format!("Absorption must not be negative (got {} per µm)", self.absorption_per_um));
+        }
+        Ok(())
+    }
+
+    /// Predicted signal loss of the worst voxel of a lattice of `depth`
+    /// layers laid out as `optics`: the light it reads from its four lateral
+    /// and (up to) two axial neighbours, plus the fraction absorbed on the way
+    /// to and from the deepest layer.
+    pub fn predicted_loss(&self, optics: &OpticsConfig, depth: usize) -> f32 {
+        let axial_neighbours = depth.saturating_sub(1).min(2) as f32;
+        let crosstalk = 4.0 * optics.crosstalk_factor() + axial_neighbours * optics.axial_crosstalk_factor();
+        let deepest_um = depth.saturating_sub(1) as f32 * optics.layer_spacing_um;
+        crosstalk + 1.0 - (-2.0 * self.absorption_per_um * deepest_um).exp()
+    }
+}
+
+/// Candidate layer spacings [`optimize_geometry_with_limits`] tries for each depth.
+const SPACING_STEPS: usize = 64;
+
+/// Lattice shape and layout for `n_voxels` voxels read through `optics`
+/// within `limits`, minimizing [`GeometryLimits::predicted_loss`]: fewer,
+/// wider-spaced voxels per layer cut lateral crosstalk, more widely spaced
+/// layers cut axial crosstalk, and deep stacks lose light to absorption.
+///
+/// For every depth, the layers are square and spread over the whole extent
+/// (the pitch only ever gains from room), and the layer spacing is the best
+/// of a range from the optics' axial resolution to what fits the depth.
+/// Returns the shape (holding at least `n_voxels` voxels in whole layers)
+/// and `optics` with the chosen `voxel_pitch_um` and `layer_spacing_um`, or
+/// `None` if `n_voxels` voxels cannot be resolved within the limits.
+///
+/// Panics if `optics` or `limits` is invalid.
+pub fn optimize_geometry_with_limits(n_voxels: usize, optics: &OpticsConfig, limits: &GeometryLimits) -> Option<(LatticeShape, OpticsConfig)> {
+    if let Err(e) = optics.validate() {
+        panic!("Invalid optics config: {}", e);
+    }
+    if let Err(e) = limits.validate() {
+        panic!("Invalid geometry limits: {}", e);
+    }
+    let (min_pitch, min_spacing) = (optics.min_voxel_pitch_um(), optics.min_layer_spacing_um());
+    let n_voxels = n_voxels.max(1);
+    let mut best: Option<(f32, LatticeShape, OpticsConfig)> = None;
+
+    for depth in 1..=n_voxels {
+        // The first layer lies at the surface: depth - 1 spacings below it
+        let max_spacing = if depth > 1 { limits.max_depth_um / (depth - 1) as f32 } else { min_spacing };
+        if max_spacing < min_spacing {
+            break;
+        }
+        let per_layer = n_voxels.div_ceil(depth);
+        let width = (per_layer as f64).sqrt().ceil() as usize;
+        let height = per_layer.div_ceil(width);
+        let pitch = limits.max_extent_um / width as f32;
+        if pitch < min_pitch {
+            continue;
+        }
+        for step in 0..SPACING_STEPS {
+            let spacing = min_spacing + (max_spacing - min_spacing) * step as f32 / (SPACING_STEPS - 1) as f32;
+            let layout = OpticsConfig { voxel_pitch_um: pitch, layer_spacing_um: spacing, ..*optics };
+            let loss = limits.predicted_loss(&layout, depth);
+            if best.as_ref().is_none_or(|(best_loss, ..)| loss < *best_loss) {
+                best = Some((loss, LatticeShape { width, height, depth }, layout));
+            }
+        }
+    }
+    best.map(|(_, shape, layout)| (shape, layout))
+}
+
+/// `(width, height, depth)` of the lattice [`optimize_geometry_with_limits`]
+/// chooses for `n_voxels` voxels within the default [`GeometryLimits`]. Use
+/// that function for the chosen pitch and layer spacing, or other limits.
+///
+/// Panics if `optics` is invalid or `n_voxels` voxels cannot be resolved
+/// within the default limits.
+pub fn optimize_geometry(n_voxels: usize, optics: &OpticsConfig) -> (usize, usize, usize) {
+    let (shape, _) = optimize_geometry_with_limits(n_voxels, optics, &GeometryLimits::default())
+        .unwrap_or_else(|| panic!("{} voxels do not fit within the default geometry limits", n_voxels));
+    (shape.width, shape.height, shape.depth)
+}
+
+/// A focus spot that widens with depth through spherical aberration: the
+/// refractive index mismatch at the surface spreads the rays focused at
+/// deeper layers over a longer, wider spot, so deep layers read more of
+/// their neighbours.
+///
+/// At layer `z` the spot is a [`PsfKernel::gaussian`] with both standard
+/// deviations scaled by `1 + aberration_coefficient * z`, normalised to
+/// conserve the light read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AberratedPsf {
+    /// Lateral (x, y) standard deviation of the spot at the surface, in voxels.
+    pub sigma_lateral: f32,
+    /// Axial (z) standard deviation of the spot at the surface, in layers.
+    pub sigma_axial: f32,
+    /// Relative widening of the spot per layer of depth (e.g., 0.05).
+    pub aberration_coefficient: f32,
+}
+
+impl AberratedPsf {
+    /// Widening factor of the spot focused at layer `z`.
+    pub fn widening_at(&self, z: usize) -> f32 {
+        1.0 + self.aberration_coefficient * z as f32
+    }
+
+    /// The kernel reading layer `z`.
+    pub fn kernel_at(&self, z: usize) -> PsfKernel {
+        let widening = self.widening_at(z);
+        PsfKernel::gaussian(self.sigma_lateral * widening, self.sigma_axial * widening).normalized()
+    }
+}
+
+/// Blurs the intensities of `lattice` like [`simulate_psf`], with each layer
+/// read through the spot `psf` focuses at its depth, so crosstalk grows with
+/// depth.
+pub fn simulate_aberration<F: VoxelFloat>(lattice: &VoxelLattice<F>, psf: &AberratedPsf) -> VoxelLattice<F> {
+    read_layers(lattice, |z| psf.kernel_at(z))
+}
+
+/// Focus errors of the readout: the servo focusing layer `z` misses it by
+/// `layer_offsets[z]` layers (positive is deeper; missing entries are in
+/// focus), so the spot reads the layer out of focus and with part of the
+/// signal of its neighbour layers.
+///
+/// The in-focus spot is a Gaussian of `sigma_lateral` voxels and
+/// `sigma_axial` layers. Defocused by `δ` layers, it is centred `δ` away
+/// and widens laterally like a Gaussian beam, by `sqrt(1 + (δ / z_R)²)`
+/// with a Rayleigh range `z_R` of half the axial FWHM. Kernels are
+/// normalised to conserve the light read.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FocusError {
+    /// Lateral (x, y) standard deviation of the spot in focus, in voxels.
+    pub sigma_lateral: f32,
+    /// Axial (z) standard deviation of the spot in focus, in layers.
+    pub sigma_axial: f32,
+    /// Focus offset of each layer, in layers.
+    pub layer_offsets: Vec<f32>,
+}
+
+impl FocusError {
+    /// Offsets of `depth` layers drawn from a normal distribution of
+    /// standard deviation `servo_error` layers, a servo of that precision.
+    pub fn random<R: Rng + ?Sized>(sigma_lateral: f32, sigma_axial: f32, depth: usize, servo_error: f32, rng: &mut R) -> Self {
+        let normal = Normal::new(0.0, servo_error.max(0.0)).expect("finite standard deviation");
+        Self { sigma_lateral, sigma_axial, layer_offsets: (0..depth).map(|_| normal.sample(rng)).collect() }
+    }
+
+    /// Focus offset of layer `z`.
+    pub fn offset_at(&self, z: usize) -> f32 {
+        self.layer_offsets.get(z).copied().unwrap_or(0.0)
+    }
+
+    /// Lateral widening of the spot reading layer `z`.
+    pub fn widening_at(&self, z: usize) -> f32 {
+        let rayleigh_range = self.sigma_axial * FWHM_PER_SIGMA / 2.0;
+        if rayleigh_range > 0.0 { (1.0 + (self.offset_at(z) / rayleigh_range).powi(2)).sqrt() } else { 1.0 }
+    }
+
+    /// The kernel reading layer `z`.
+    pub fn kernel_at(&self, z: usize) -> PsfKernel {
+        let offset = self.offset_at(z);
+        let sigma_lateral = self.sigma_lateral * self.widening_at(z);
+        let rxy = (3.0 * sigma_lateral).ceil().max(0.0) as usize;
+        let rz = (offset.abs() + 3.0 * self.sigma_axial).ceil().max(0.0) as usize;
+        let falloff = |d: f32, sigma: f32| if sigma > 0.0 { (-(d / sigma).powi(2) / 2.0).exp() } else if d.abs() < 0.5 { 1.0 } else { 0.0 };
+
+        let size = (2 * rxy + 1, 2 * rxy + 1, 2 * rz + 1);
+        let mut weights = Vec::with_capacity(size.0 * size.1 * size.2);
+        for dz in 0..size.2 {
+            // Kernel offset o reads the voxel o layers above: peak at o = -offset
+            let axial = falloff(dz as f32 - rz as f32 + offset, self.sigma_axial);
+            for dy in 0..size.1 {
+                for dx in 0..size.0 {
+                    weights.push(axial * falloff(dx as f32 - rxy as f32, sigma_lateral) * falloff(dy as f32 - rxy as f32, sigma_lateral));
+                }
+            }
+        }
+        PsfKernel { size, weights }.normalized()
+    }
+}
+
+/// Blurs the intensities of `lattice` like [`simulate_psf`], with each layer
+/// read through the defocused spot of `focus`.
+pub fn simulate_focus_error<F: VoxelFloat>(lattice: &VoxelLattice<F>, focus: &FocusError) -> VoxelLattice<F> {
+    read_layers(lattice, |z| focus.kernel_at(z))
+}
+
+/// Repeatability of the reader's positioning stage: every voxel is read
+/// with the focus off its nominal position by offsets drawn from normal
+/// distributions of these standard deviations, in voxel pitches (x, y) and
+/// layers (z).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionJitter {
+    pub sigma_x: f32,
+    pub sigma_y: f32,
+    pub sigma_z: f32,
+}
+
+impl PositionJitter {
+    /// The same standard deviation `sigma_lateral` along x and y.
+    pub fn new(sigma_lateral: f32, sigma_axial: f32) -> Self {
+        Self { sigma_x: sigma_lateral, sigma_y: sigma_lateral, sigma_z: sigma_axial }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if ![self.sigma_x, self.sigma_y, self.sigma_z].iter().all(|sigma| sigma.is_finite() && *sigma >= 0.0) {
+            return Err(format!("Position jitter must not be negative (got {}, {}, {})", self.sigma_x, self.sigma_y, self.sigma_z));
+        }
+        Ok(())
+    }
+
+    /// Offsets (x, y, z) of one read.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> (f32, f32, f32) {
+        let mut offset = |sigma: f32| Normal::new(0.0, sigma).map_or(0.0, |normal| normal.sample(rng));
+        (offset(self.sigma_x), offset(self.sigma_y), offset(self.sigma_z))
+    }
+}
+
+/// [`simulate_position_jitter_with_rng`] with the thread-local generator.
+pub fn simulate_position_jitter<F: VoxelFloat>(lattice: &VoxelLattice<F>, jitter: &PositionJitter) -> VoxelLattice<F> {
+    simulate_position_jitter_with_rng(lattice, jitter, &mut rand::rng())
+}
+
+/// Reads every voxel of `lattice` with the focus offset by a draw of
+/// `jitter`: along each axis, an offset `d` mixes `min(|d|, 1)` of the
+/// neighbour in its direction into the reading (trilinear interpolation;
+/// nothing is read beyond the lattice edges). Intensities add up, and
+/// polarization and phase are the intensity-weighted mixtures of the voxels
+/// read, as in [`simulate_crosstalk`].
+///
+/// Panics if `jitter` is invalid.
+pub fn simulate_position_jitter_with_rng<F: VoxelFloat, R: Rng + ?Sized>(lattice: &VoxelLattice<F>, jitter: &PositionJitter, rng: &mut R) -> VoxelLattice<F> {
+    if let Err(e) = jitter.validate() {
+        panic!("Invalid position jitter: {}", e);
+    }
+    let mut output = lattice.clone();
+    for ((x, y, z), &voxel) in lattice.iter_indexed() {
+        let (dx, dy, dz) = jitter.sample(rng);
+        if (dx, dy, dz) == (0.0, 0.0, 0.0) {
+            continue;
+        }
+        // (step towards the neighbour, fraction read from it) along each axis
+        let axis = |d: f32| (if d < 0.0 { -1 } else { 1 }, d.abs().min(1.0) as f64);
+        let ((sx, fx), (sy, fy), (sz, fz)) = (axis(dx), axis(dy), axis(dz));
+
+        let mut intensity = 0.0;
+        let mut polarization = Mixture::new(0.0, 0.0);
+        let mut phase = Mixture::new(0.0, 0.0);
+        for (cz, wz) in [(0, 1.0 - fz), (sz, fz)] {
+            for (cy, wy) in [(0, 1.0 - fy), (sy, fy)] {
+                for (cx, wx) in [(0, 1.0 - fx), (sx, fx)] {
+                    let weight = wx * wy * wz;
+                    if weight == 0.0 {
+                        continue;
                     }
+                    if let Some(source) = lattice.get(x.wrapping_add_signed(cx), y.wrapping_add_signed(cy), z.wrapping_add_signed(cz)) {
+                        let read = source.intensity.as_f64() * weight;
+                        intensity += read;
+                        polarization.add(read, 2.0 * source.polarization.as_f64());
+                        phase.add(read, source.phase.as_f64());
+                    }
+                }
+            }
+        }
+
+        let mut read = PhotonicVoxel { intensity: F::from_f64(intensity), ..voxel };
+        if let Some(doubled) = polarization.angle() {
+            let angle = (doubled / 2.0).rem_euclid(PI);
+            read.polarization = F::from_f64(if angle >= PI { 0.0 } else { angle });
+        }
+        if let Some(angle) = phase.angle() {
+            read.phase = F::from_f64(angle.rem_euclid(2.0 * PI));
+        }
+        output.set(x, y, z, read);
+    }
+    output
+}
+
+/// How the glass responds to the exposure dose of a voxel: the recorded
+/// intensity (for nanogratings, the retardance strength) as a function of the
+/// total pulse energy deposited, in nJ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MaterialResponse {
+    /// Recorded intensity proportional to the dose, reaching 1.0 at `full_dose_nj`.
+    Linear { full_dose_nj: f32 },
+    /// Nothing below the modification threshold, then a modification that
+    /// saturates towards 1.0: `1 - exp(-(dose - threshold_nj) / saturation_nj)`.
+    Saturating { threshold_nj: f32, saturation_nj: f32 },
+}
+
+impl MaterialResponse {
+    /// Recorded intensity after a dose of `dose_nj`.
+    pub fn recorded(&self, dose_nj: f64) -> f64 {
+        match *self {
+            MaterialResponse::Linear { full_dose_nj } => dose_nj.max(0.0) / full_dose_nj as f64,
+            MaterialResponse::Saturating { threshold_nj, saturation_nj } => {
+                let excess = dose_nj - threshold_nj as f64;
+                if excess <= 0.0 { 0.0 } else { 1.0 - (-excess / saturation_nj as f64).exp() }
+            }
+        }
+    }
+}
 
-                    // Clamp intensity to 1.0 + some headroom? Or let it bloom?
-                    // Physics: Detectors saturate. Let's clamp at 1.5 just to see effect but not blow up f32.
-                    if original.intensity > 1.5 { original.intensity = 1.5; }
+/// Femtosecond laser writing of [`simulate_write`]: each voxel is exposed to
+/// `pulses` pulses whose energy is the commanded intensity times
+/// `pulse_energy_nj`, and records whatever the material makes of the total
+/// dose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteParams {
+    /// Energy of a pulse writing intensity 1.0, in nJ.
+    pub pulse_energy_nj: f32,
+    /// Pulses per voxel.
+    pub pulses: u32,
+    /// Relative standard deviation of the energy of a pulse (e.g., 0.02).
+    pub energy_jitter: f32,
+    pub response: MaterialResponse,
+}
+
+impl Default for WriteParams {
+    /// Ten 100 nJ pulses into fused silica with a 50 nJ threshold and
+    /// saturation over 400 nJ, with 2% pulse-to-pulse jitter: the low levels
+    /// come out too strong and the top ones crowd together.
+    fn default() -> Self {
+        Self { pulse_energy_nj: 100.0, pulses: 10, energy_jitter: 0.02, response: MaterialResponse::Saturating { threshold_nj: 50.0, saturation_nj: 400.0 } }
+    }
+}
 
-                    output[target_idx] = original;
+impl WriteParams {
+    /// Ideal writing: a linear material calibrated to the full dose and no
+    /// jitter, so every voxel records its commanded intensity.
+    pub fn ideal() -> Self {
+        Self { pulse_energy_nj: 100.0, pulses: 10, energy_jitter: 0.0, response: MaterialResponse::Linear { full_dose_nj: 1000.0 } }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.pulse_energy_nj.is_finite() && self.pulse_energy_nj > 0.0) {
+            return Err(format!("Pulse energy must be positive (got {} nJ)", self.pulse_energy_nj));
+        }
+        if self.pulses == 0 {
+            return Err("At least one pulse per voxel is needed".to_string());
+        }
+        if !(self.energy_jitter.is_finite() && self.energy_jitter >= 0.0) {
+            return Err(format!("Energy jitter must not be negative (got {})", self.energy_jitter));
+        }
+        match self.response {
+            MaterialResponse::Linear { full_dose_nj } if !(full_dose_nj.is_finite() && full_dose_nj > 0.0) => Err(format!("Full dose must be positive (got {} nJ)", full_dose_nj)),
+            MaterialResponse::Saturating { threshold_nj, saturation_nj } if !(threshold_nj >= 0.0 && saturation_nj.is_finite() && saturation_nj > 0.0) => {
+                Err(format!("Threshold must not be negative and saturation must be positive (got {} nJ, {} nJ)", threshold_nj, saturation_nj))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Mean dose of a voxel commanded to `intensity`, in nJ.
+    pub fn dose(&self, intensity: f32) -> f64 {
+        intensity.max(0.0) as f64 * self.pulse_energy_nj as f64 * self.pulses as f64
+    }
+
+    /// Intensity a voxel commanded to `intensity` records without jitter.
+    pub fn recorded(&self, intensity: f32) -> f32 {
+        self.response.recorded(self.dose(intensity)) as f32
+    }
+
+    /// Dose of a voxel commanded to `intensity` with the pulse energy jitter
+    /// averaged over the pulses (relative standard deviation
+    /// `energy_jitter / sqrt(pulses)`).
+    fn jittered_dose<R: Rng + ?Sized>(&self, intensity: f32, rng: &mut R) -> f64 {
+        let mean = self.dose(intensity);
+        let spread = self.energy_jitter as f64 / (self.pulses as f64).sqrt();
+        Normal::new(mean, mean * spread).map_or(mean, |normal| normal.sample(rng))
+    }
+}
+
+/// Writes `voxels` with the laser and material of `params`, returning what
+/// the glass actually records before any readout noise: the intensity of each
+/// voxel is replaced by the response of the material to its dose, with the
+/// pulse energy jitter averaging down over the pulses (relative standard
+/// deviation `energy_jitter / sqrt(pulses)` of the dose). Polarization, phase
+/// and wavelength are set by the beam and pass through unchanged.
+///
+/// Panics if `params` is invalid.
+pub fn simulate_write(voxels: &[PhotonicVoxel], params: &WriteParams) -> Vec<PhotonicVoxel> {
+    simulate_write_with_rng(voxels, params, &mut rand::rng())
+}
+
+/// [`simulate_write`] drawing the pulse energies from `rng`, for reproducible runs.
+pub fn simulate_write_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], params: &WriteParams, rng: &mut R) -> Vec<PhotonicVoxel> {
+    if let Err(e) = params.validate() {
+        panic!("Invalid write parameters: {}", e);
+    }
+    voxels.iter().map(|voxel| PhotonicVoxel { intensity: params.response.recorded(params.jittered_dose(voxel.intensity, rng)) as f32, ..*voxel }).collect()
+}
+
+/// Exposure of the voxels around the one being written, in multiples of its
+/// own dose: the writing focus (the [`OpticsConfig`] of the writing beam,
+/// with its voxel pitch and layer spacing) also reaches the neighbours, and
+/// the nonlinear absorption that modifies the glass scales as the local
+/// intensity to the power `absorption_order`, so a neighbour at lateral and
+/// axial distances `r`, `z` receives `exp(-r² / 2σ² - z² / 2σz²)^order`.
+///
+/// Doses add up in the glass: a voxel records its own dose plus what the
+/// writing of every neighbour deposited, whether it was written before
+/// (partial rewriting of already written voxels) or after (pre-exposure).
+/// Below the modification threshold of a
+/// [`Saturating`](MaterialResponse::Saturating) material the stray dose
+/// leaves no trace; above it, it shifts the recorded levels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteInterference {
+    /// Focus of the writing beam and voxel layout.
+    pub optics: OpticsConfig,
+    /// Photons absorbed together to modify the glass (multiphoton order).
+    pub absorption_order: f32,
+}
+
+impl Default for WriteInterference {
+    /// A 1030 nm femtosecond laser through the default 0.75 NA objective,
+    /// voxels 0.5 µm apart in layers 5 µm apart, with two-photon absorption:
+    /// each lateral neighbour receives about 6% of a voxel's dose, each axial
+    /// one less than 0.1%.
+    fn default() -> Self {
+        Self { optics: OpticsConfig { wavelength_nm: 1030.0, layer_spacing_um: 5.0, ..OpticsConfig::default() }, absorption_order: 2.0 }
+    }
+}
+
+/// Smallest exposure [`WriteInterference::offsets`] accounts for.
+const MIN_WRITE_EXPOSURE: f64 = 1e-4;
+
+impl WriteInterference {
+    pub fn validate(&self) -> Result<(), String> {
+        self.optics.validate()?;
+        if !(self.absorption_order.is_finite() && self.absorption_order > 0.0) {
+            return Err(format!("Absorption order must be positive (got {})", self.absorption_order));
+        }
+        Ok(())
+    }
+
+    /// Exposure of the voxel at offset (`dx`, `dy`, `dz`) from the one being
+    /// written, as a fraction of the written voxel's dose.
+    pub fn exposure(&self, dx: isize, dy: isize, dz: isize) -> f64 {
+        let (sigma, sigma_axial) = (self.optics.sigma_lateral() as f64, self.optics.sigma_axial() as f64);
+        let lateral = (dx * dx + dy * dy) as f64 / (2.0 * sigma * sigma);
+        let axial = (dz * dz) as f64 / (2.0 * sigma_axial * sigma_axial);
+        (-(lateral + axial) * self.absorption_order as f64).exp()
+    }
+
+    /// Offsets (dx, dy, dz) of the neighbours exposed to at least 1e-4 of a
+    /// voxel's dose, with their exposure, most exposed first.
+    pub fn offsets(&self) -> Vec<((isize, isize, isize), f64)> {
+        // exposure(d) >= MIN_WRITE_EXPOSURE within sigma * sqrt(2 ln(1 / MIN) / order)
+        let reach = |sigma: f32| (sigma as f64 * (2.0 * (1.0 / MIN_WRITE_EXPOSURE).ln() / self.absorption_order as f64).sqrt()).floor() as isize;
+        let (r, rz) = (reach(self.optics.sigma_lateral()), reach(self.optics.sigma_axial()));
+        let mut offsets = Vec::new();
+        for dz in -rz..=rz {
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let exposure = self.exposure(dx, dy, dz);
+                    if (dx, dy, dz) != (0, 0, 0) && exposure >= MIN_WRITE_EXPOSURE {
+                        offsets.push(((dx, dy, dz), exposure));
+                    }
                 }
             }
         }
+        offsets.sort_by(|a, b| b.1.total_cmp(&a.1));
+        offsets
+    }
+
+    /// Smallest voxel pitch at which a lateral neighbour receives at most
+    /// `max_exposure` of a voxel's dose, in µm.
+    pub fn safe_pitch_um(&self, max_exposure: f64) -> f32 {
+        let sigma_um = self.optics.lateral_fwhm_um() as f64 / FWHM_PER_SIGMA as f64;
+        (sigma_um * (2.0 * (1.0 / max_exposure).ln() / self.absorption_order as f64).sqrt()) as f32
+    }
+}
+
+/// [`simulate_write_interference_with_rng`] with the thread-local generator.
+pub fn simulate_write_interference(lattice: &VoxelLattice, params: &WriteParams, interference: &WriteInterference) -> VoxelLattice {
+    simulate_write_interference_with_rng(lattice, params, interference, &mut rand::rng())
+}
+
+/// Writes `lattice` like [`simulate_write_with_rng`], every voxel recording
+/// its own dose plus the stray exposure of `interference` from writing its
+/// neighbours.
+///
+/// Panics if `params` or `interference` is invalid.
+pub fn simulate_write_interference_with_rng<R: Rng + ?Sized>(lattice: &VoxelLattice, params: &WriteParams, interference: &WriteInterference, rng: &mut R) -> VoxelLattice {
+    if let Err(e) = params.validate() {
+        panic!("Invalid write parameters: {}", e);
+    }
+    if let Err(e) = interference.validate() {
+        panic!("Invalid write interference: {}", e);
+    }
+    let offsets = interference.offsets();
+    let doses: Vec<f64> = lattice.iter().map(|voxel| params.jittered_dose(voxel.intensity, rng)).collect();
+
+    let mut output = lattice.clone();
+    for (((x, y, z), _), (voxel, &dose)) in lattice.iter_indexed().zip(output.iter_mut().zip(&doses)) {
+        let stray: f64 = offsets
+            .iter()
+            .filter_map(|&((dx, dy, dz), exposure)| Some(exposure * doses[lattice.index(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy), z.wrapping_add_signed(dz))?]))
+            .sum();
+        voxel.intensity = params.response.recorded(dose + stray) as f32;
+    }
+    output
+}
+
+/// Damage a rewritable medium accumulates over erase/rewrite cycles, for
+/// endurance studies with [`simulate_rewrite_cycles`].
+///
+/// Each cycle leaves residual modification behind, so the medium records
+/// with less contrast and more scatter:
+/// - the written intensity, polarization angle and phase shrink towards the
+///   unwritten state (zero) by their fraction lost per cycle, compounding:
+///   after `n` cycles they are scaled by `(1 - loss)^n`;
+/// - the damage noise of independent cycles adds up in variance, so after
+///   `n` cycles the voxels carry `damage_noise` scaled by `sqrt(n)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FatigueModel {
+    /// Fraction of the written intensity lost per cycle.
+    pub intensity_loss: f32,
+    /// Fraction of the written polarization angle lost per cycle.
+    pub polarization_loss: f32,
+    /// Fraction of the written phase lost per cycle.
+    pub phase_loss: f32,
+    /// Noise one cycle of damage adds to the recorded voxels.
+    pub damage_noise: NoiseProfile,
+}
+
+impl Default for FatigueModel {
+    /// Slow fatigue: 0.05% of the intensity, 0.02% of the polarization and
+    /// 0.03% of the phase contrast lost per cycle, and Gaussian damage noise
+    /// of 0.002 (intensity) and 0.002 and 0.003 rad per cycle. The top
+    /// intensity level of the default codec crosses its decision boundary
+    /// after about 270 cycles.
+    fn default() -> Self {
+        Self { intensity_loss: 0.0005, polarization_loss: 0.0002, phase_loss: 0.0003, damage_noise: NoiseProfile::gaussian(0.002, 0.002, 0.003, 0.0) }
+    }
+}
+
+impl FatigueModel {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, loss) in [("intensity", self.intensity_loss), ("polarization", self.polarization_loss), ("phase", self.phase_loss)] {
+            if !(0.0..1.0).contains(&loss) {
+                return Err(format!("Fraction of {} lost per cycle must be in [0, 1) (got {})", name, loss));
+            }
+        }
+        if self.damage_noise.sigmas().iter().any(|sigma| !(sigma.is_finite() && *sigma >= 0.0)) {
+            return Err(format!("Damage noise must not be negative (got {:?})", self.damage_noise.sigmas()));
+        }
+        Ok(())
+    }
+
+    /// Fraction of the written intensity, polarization angle and phase left
+    /// after `cycles` cycles.
+    pub fn contrast_at(&self, cycles: usize) -> [f32; 3] {
+        [self.intensity_loss, self.polarization_loss, self.phase_loss].map(|loss| (1.0 - loss as f64).powf(cycles as f64) as f32)
+    }
+
+    /// Damage noise carried by voxels written after `cycles` cycles.
+    pub fn noise_at(&self, cycles: usize) -> NoiseProfile {
+        self.damage_noise.scaled((cycles as f32).sqrt())
+    }
+}
+
+/// Writes `voxels` into a medium worn by `cycles` previous erase/rewrite
+/// cycles of `model`: the recorded voxels have the contrast of
+/// [`FatigueModel::contrast_at`] and carry the damage noise of
+/// [`FatigueModel::noise_at`]. Readout noise comes on top.
+///
+/// Panics if `model` is invalid.
+pub fn simulate_rewrite_cycles(voxels: &[PhotonicVoxel], cycles: usize, model: &FatigueModel) -> Vec<PhotonicVoxel> {
+    simulate_rewrite_cycles_with_rng(voxels, cycles, model, &mut rand::rng())
+}
+
+/// [`simulate_rewrite_cycles`] drawing the damage noise from `rng`, for
+/// reproducible runs.
+pub fn simulate_rewrite_cycles_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], cycles: usize, model: &FatigueModel, rng: &mut R) -> Vec<PhotonicVoxel> {
+    if let Err(e) = model.validate() {
+        panic!("Invalid fatigue model: {}", e);
+    }
+    let [intensity, polarization, phase] = model.contrast_at(cycles);
+    let noise = model.noise_at(cycles);
+    voxels
+        .iter()
+        .map(|voxel| {
+            let faded = PhotonicVoxel { intensity: voxel.intensity * intensity, polarization: voxel.polarization * polarization, phase: voxel.phase * phase, ..*voxel };
+            noise.perturb_with_rng(&faded, rng)
+        })
+        .collect()
+}
+
+/// Boltzmann constant in eV/K.
+const BOLTZMANN_EV_PER_K: f64 = 8.617_333e-5;
+
+/// Storage conditions and material kinetics of [`simulate_aging`]: the
+/// written modification (intensity, retardance) relaxes as a first-order
+/// thermally activated process, decaying by `exp(-k * years)` with an
+/// Arrhenius rate `k = reference_rate_per_year * exp(-Ea / kB * (1 / T - 1 / T_ref))`.
+///
+/// Accelerated aging at high temperature gives `k` at a few temperatures, and
+/// the fitted activation energy extrapolates it to storage conditions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgingParams {
+    /// Storage temperature, in K.
+    pub temperature_k: f32,
+    /// Storage time, in years.
+    pub years: f64,
+    /// Activation energy `Ea` of the decay, in eV.
+    pub activation_energy_ev: f32,
+    /// Decay rate at `reference_temperature_k`, per year.
+    pub reference_rate_per_year: f64,
+    /// Temperature `T_ref` at which the rate was measured, in K.
+    pub reference_temperature_k: f32,
+}
+
+impl Default for AgingParams {
+    /// A thousand years at room temperature.
+    fn default() -> Self {
+        Self::new(293.15, 1000.0)
+    }
+}
+
+impl AgingParams {
+    /// `years` at `temperature_k` of nanogratings in fused silica, taken to
+    /// decay with an activation energy of 1.8 eV at a rate of one e-fold per
+    /// hour at 1273 K (illustrative values of the order of annealing
+    /// experiments): practically forever at room temperature, days at 900 K.
+    pub fn new(temperature_k: f32, years: f64) -> Self {
+        Self { temperature_k, years, activation_energy_ev: 1.8, reference_rate_per_year: 24.0 * 365.25, reference_temperature_k: 1273.0 }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, temperature) in [("Temperature", self.temperature_k), ("Reference temperature", self.reference_temperature_k)] {
+            if !(temperature.is_finite() && temperature > 0.0) {
+                return Err(format!("{} must be positive (got {} K)", name, temperature));
+            }
+        }
+        if !(self.years.is_finite() && self.years >= 0.0) {
+            return Err(format!("Storage time must not be negative (got {} years)", self.years));
+        }
+        if !(self.activation_energy_ev.is_finite() && self.activation_energy_ev >= 0.0 && self.reference_rate_per_year.is_finite() && self.reference_rate_per_year >= 0.0) {
+            return Err(format!("Activation energy and rate must not be negative (got {} eV, {} per year)", self.activation_energy_ev, self.reference_rate_per_year));
+        }
+        Ok(())
+    }
+
+    /// Decay rate at `temperature_k`, per year.
+    pub fn decay_rate_per_year(&self) -> f64 {
+        let inverse_temperatures = 1.0 / self.temperature_k as f64 - 1.0 / self.reference_temperature_k as f64;
+        self.reference_rate_per_year * (-(self.activation_energy_ev as f64) / BOLTZMANN_EV_PER_K * inverse_temperatures).exp()
+    }
+
+    /// Fraction of the written modification left after `years`.
+    pub fn retention(&self) -> f64 {
+        (-self.decay_rate_per_year() * self.years).exp()
+    }
+
+    /// Years at `temperature_k` until `retention` of the modification is
+    /// left, e.g. 0.875 for the top intensity level of the default codec to
+    /// reach its decision boundary. Infinite without decay.
+    pub fn years_to_retention(&self, retention: f64) -> f64 {
+        -retention.ln() / self.decay_rate_per_year()
+    }
+}
+
+/// Ages `lattice` by `params`: every intensity is scaled by
+/// [`AgingParams::retention`].
+///
+/// Panics if `params` is invalid.
+pub fn simulate_aging<F: VoxelFloat>(lattice: &VoxelLattice<F>, params: &AgingParams) -> VoxelLattice<F> {
+    if let Err(e) = params.validate() {
+        panic!("Invalid aging parameters: {}", e);
+    }
+    let retention = F::from_f64(params.retention());
+    let mut output = lattice.clone();
+    for voxel in output.iter_mut() {
+        voxel.intensity = voxel.intensity * retention;
     }
     output
 }
+
+/// [`simulate_aging`] for nanograting voxels: every retardance is scaled by
+/// [`AgingParams::retention`].
+///
+/// Panics if `params` is invalid.
+pub fn simulate_birefringent_aging(voxels: &[BirefringentVoxel], params: &AgingParams) -> Vec<BirefringentVoxel> {
+    if let Err(e) = params.validate() {
+        panic!("Invalid aging parameters: {}", e);
+    }
+    let retention = params.retention() as f32;
+    voxels.iter().map(|voxel| BirefringentVoxel::new(voxel.slow_axis, voxel.retardance_nm * retention)).collect()
+}
+
+/// Read laser power and its two effects: the photons collected per voxel,
+/// and with them the SNR, grow with the power, but so does the bleaching of
+/// the voxels read, which accumulates read after read. Shot noise falls as
+/// `1 / sqrt(power)` while bleaching per read grows as
+/// `power^bleaching_order`, so a medium read often is best read gently.
+///
+/// The detector's gain follows the power (the exposure is adjusted), so its
+/// ADC keeps the same intensity range; dark current and read noise, in
+/// electrons, stay fixed and matter less at high power.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadPower {
+    /// Read power relative to the nominal power of `detector`.
+    pub power: f32,
+    /// Readout chain at nominal power.
+    pub detector: DetectorModel,
+    /// Fraction of the written modification erased by one read at nominal power.
+    pub bleaching_per_read: f64,
+    /// Exponent of the power law of bleaching, e.g. 2 for two-photon
+    /// absorption of the read beam.
+    pub bleaching_order: f32,
+}
+
+impl Default for ReadPower {
+    /// Nominal power of a low-light reader: 400 photoelectrons per voxel at
+    /// full intensity, 10 electrons of read noise, and two-photon bleaching
+    /// of one part in a million per read.
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl ReadPower {
+    /// `power` times the nominal power of the default reader.
+    pub fn new(power: f32) -> Self {
+        Self {
+            power,
+            detector: DetectorModel { photons_per_voxel: 400.0, dark_current: 5.0, read_noise: 10.0, adc_bits: 12, gain: 0.25 },
+            bleaching_per_read: 1e-6,
+            bleaching_order: 2.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.power.is_finite() && self.power > 0.0) {
+            return Err(format!("Read power must be positive (got {})", self.power));
+        }
+        if !((0.0..=1.0).contains(&self.bleaching_per_read) && self.bleaching_order.is_finite() && self.bleaching_order >= 0.0) {
+            return Err(format!("Bleaching must be in [0, 1] per read with a non-negative order (got {}, order {})", self.bleaching_per_read, self.bleaching_order));
+        }
+        self.detector.validate()
+    }
+
+    /// The readout chain at `power`.
+    pub fn detector(&self) -> DetectorModel {
+        DetectorModel { photons_per_voxel: self.detector.photons_per_voxel * self.power, gain: self.detector.gain * self.power, ..self.detector }
+    }
+
+    /// Fraction of the written modification erased by one read at `power`.
+    pub fn bleaching(&self) -> f64 {
+        (self.bleaching_per_read * (self.power as f64).powf(self.bleaching_order as f64)).min(1.0)
+    }
+
+    /// Fraction of the written modification left after `reads` reads.
+    pub fn retention_after(&self, reads: u64) -> f64 {
+        (1.0 - self.bleaching()).powf(reads as f64)
+    }
+}
+
+/// Bleaches `lattice` by `reads` reads at `read`: every intensity is scaled
+/// by [`ReadPower::retention_after`]. Read the result through
+/// [`ReadPower::detector`] for the next read.
+///
+/// Panics if `read` is invalid.
+pub fn simulate_read_bleaching<F: VoxelFloat>(lattice: &VoxelLattice<F>, read: &ReadPower, reads: u64) -> VoxelLattice<F> {
+    if let Err(e) = read.validate() {
+        panic!("Invalid read power: {}", e);
+    }
+    let retention = F::from_f64(read.retention_after(reads));
+    let mut output = lattice.clone();
+    for voxel in output.iter_mut() {
+        voxel.intensity = voxel.intensity * retention;
+    }
+    output
+}
+
+/// Drift of the birefringence stored in nanograting voxels between writing
+/// and reading, so that slow-axis (polarization) and retardance (intensity)
+/// errors follow from the physics of the glass rather than from uniform
+/// jitter.
+///
+/// Birefringences of a voxel and of the glass around it add as vectors of
+/// length the retardance at twice the slow-axis angle (exact for weak
+/// retardances). Reading a voxel of retardance `Δ` at slow axis `θ`:
+/// - its retardance changes by `thermal_coefficient` per kelvin of
+///   `temperature_delta_k` (thermo-optic and thermal stress effects);
+/// - `stress_retardance_nm` of uniform stress birefringence, e.g. from
+///   mounting, adds at `stress_axis`;
+/// - residual stress frozen in by writing adds `residual_stress_nm` (standard
+///   deviation per component) at random orientations, voxel by voxel.
+///
+/// The same stress turns the slow axis of weakly written voxels further than
+/// that of strong ones, as the angle error is about the stress over `Δ`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BirefringenceDrift {
+    /// Temperature at reading minus temperature at writing, in K.
+    pub temperature_delta_k: f32,
+    /// Relative change of retardance per kelvin.
+    pub thermal_coefficient: f32,
+    /// Uniform stress birefringence, in nm.
+    pub stress_retardance_nm: f32,
+    /// Slow axis of the uniform stress birefringence, in radians.
+    pub stress_axis: f32,
+    /// Standard deviation of the residual stress birefringence, in nm.
+    pub residual_stress_nm: f32,
+    /// Retardance of a [`PhotonicVoxel`] of intensity 1.0, in nm, for
+    /// [`simulate_polarization_drift_with_rng`].
+    pub max_retardance_nm: f32,
+}
+
+impl Default for BirefringenceDrift {
+    /// A medium read 10 K warmer than written, with a retardance change of
+    /// 0.1% per kelvin, 2 nm of mounting stress along x and 1 nm of residual
+    /// stress, holding up to 120 nm of retardance (as
+    /// [`BirefringentConfig::default`](crate::BirefringentConfig::default)).
+    fn default() -> Self {
+        Self { temperature_delta_k: 10.0, thermal_coefficient: 1e-3, stress_retardance_nm: 2.0, stress_axis: 0.0, residual_stress_nm: 1.0, max_retardance_nm: 120.0 }
+    }
+}
+
+impl BirefringenceDrift {
+    /// No drift: voxels read back as written.
+    pub fn none() -> Self {
+        Self { temperature_delta_k: 0.0, stress_retardance_nm: 0.0, residual_stress_nm: 0.0, ..Self::default() }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if ![self.temperature_delta_k, self.thermal_coefficient, self.stress_retardance_nm, self.stress_axis].iter().all(|value| value.is_finite()) {
+            return Err("Temperature change, thermal coefficient and stress must be finite".to_string());
+        }
+        if !(self.residual_stress_nm.is_finite() && self.residual_stress_nm >= 0.0) {
+            return Err(format!("Residual stress must not be negative (got {} nm)", self.residual_stress_nm));
+        }
+        if !(self.max_retardance_nm.is_finite() && self.max_retardance_nm > 0.0) {
+            return Err(format!("Maximum retardance must be positive (got {} nm)", self.max_retardance_nm));
+        }
+        Ok(())
+    }
+
+    /// `voxel` as read after the drift, drawing the residual stress from `rng`.
+    pub fn drift<R: Rng + ?Sized>(&self, voxel: &BirefringentVoxel, rng: &mut R) -> BirefringentVoxel {
+        let scale = 1.0 + self.thermal_coefficient as f64 * self.temperature_delta_k as f64;
+        let written = voxel.retardance_nm as f64 * scale;
+        let (sin, cos) = (2.0 * voxel.slow_axis as f64).sin_cos();
+        let (stress_sin, stress_cos) = (2.0 * self.stress_axis as f64).sin_cos();
+        let mut residual = || Normal::new(0.0, self.residual_stress_nm as f64).map_or(0.0, |normal| normal.sample(rng));
+        let x = written * cos + self.stress_retardance_nm as f64 * stress_cos + residual();
+        let y = written * sin + self.stress_retardance_nm as f64 * stress_sin + residual();
+
+        let slow_axis = (y.atan2(x) / 2.0).rem_euclid(PI) as f32;
+        BirefringentVoxel::new(if slow_axis >= std::f32::consts::PI { 0.0 } else { slow_axis }, x.hypot(y) as f32)
+    }
+}
+
+/// Reads `voxels` after the birefringence drift of `drift`, for
+/// [`decode_birefringent`](crate::decode_birefringent).
+///
+/// Panics if `drift` is invalid.
+pub fn simulate_birefringence_drift_with_rng<R: Rng + ?Sized>(voxels: &[BirefringentVoxel], drift: &BirefringenceDrift, rng: &mut R) -> Vec<BirefringentVoxel> {
+    if let Err(e) = drift.validate() {
+        panic!("Invalid birefringence drift: {}", e);
+    }
+    voxels.iter().map(|voxel| drift.drift(voxel, rng)).collect()
+}
+
+/// [`simulate_birefringence_drift_with_rng`] for the voxels of the codec,
+/// taken as nanogratings with the polarization angle as slow axis and a
+/// retardance of `intensity * max_retardance_nm`: the drifted slow axis and
+/// retardance come back as polarization and intensity, phase and wavelength
+/// unchanged.
+///
+/// Panics if `drift` is invalid.
+pub fn simulate_polarization_drift_with_rng<R: Rng + ?Sized>(voxels: &[PhotonicVoxel], drift: &BirefringenceDrift, rng: &mut R) -> Vec<PhotonicVoxel> {
+    if let Err(e) = drift.validate() {
+        panic!("Invalid birefringence drift: {}", e);
+    }
+    voxels
+        .iter()
+        .map(|voxel| {
+            let drifted = drift.drift(&BirefringentVoxel::new(voxel.polarization, voxel.intensity * drift.max_retardance_nm), rng);
+            PhotonicVoxel { intensity: drifted.retardance_nm / drift.max_retardance_nm, polarization: drifted.slow_axis, ..*voxel }
+        })
+        .collect()
+}
+
+/// [`simulate_psf`] computed with 3D FFTs of the zero-padded lattice and
+/// kernel, in time independent of the kernel size. Results match the direct
+/// convolution up to rounding.
+#[cfg(feature = "fft")]
+pub fn simulate_psf_fft<F: VoxelFloat>(lattice: &VoxelLattice<F>, kernel: &PsfKernel) -> VoxelLattice<F> {
+    use num_complex::Complex;
+
+    let (kx, ky, kz) = kernel.size;
+    // Linear, not circular, convolution: pad to the full output size
+    let shape = (lattice.width() + kx - 1, lattice.height() + ky - 1, lattice.depth() + kz - 1);
+    let index = |x: usize, y: usize, z: usize| (z * shape.1 + y) * shape.0 + x;
+
+    let mut signal = vec![Complex::new(0.0f64, 0.0); shape.0 * shape.1 * shape.2];
+    for ((x, y, z), voxel) in lattice.iter_indexed() {
+        signal[index(x, y, z)].re = voxel.intensity.as_f64();
+    }
+    let mut response = vec![Complex::new(0.0f64, 0.0); signal.len()];
+    for (i, &weight) in kernel.weights.iter().enumerate() {
+        response[index(i % kx, i / kx % ky, i / (kx * ky))].re = weight.into();
+    }
+
+    let mut planner = rustfft::FftPlanner::new();
+    fft_3d(&mut planner, &mut signal, shape, rustfft::FftDirection::Forward);
+    fft_3d(&mut planner, &mut response, shape, rustfft::FftDirection::Forward);
+    for (value, response) in signal.iter_mut().zip(&response) {
+        *value *= response;
+    }
+    fft_3d(&mut planner, &mut signal, shape, rustfft::FftDirection::Inverse);
+
+    let scale = 1.0 / signal.len() as f64;
+    let mut output = lattice.clone();
+    for ((x, y, z), &voxel) in lattice.iter_indexed() {
+        let intensity = (signal[index(x + kx / 2, y + ky / 2, z + kz / 2)].re * scale).min(SATURATION.into());
+        output.set(x, y, z, PhotonicVoxel { intensity: F::from_f64(intensity), ..voxel });
+    }
+    output
+}
+
+/// In-place (unnormalised) FFT along each axis of a `shape` (x, y, z) volume
+/// stored x fastest.
+#[cfg(feature = "fft")]
+fn fft_3d(planner: &mut rustfft::FftPlanner<f64>, data: &mut [num_complex::Complex<f64>], shape: (usize, usize, usize), direction: rustfft::FftDirection) {
+    let (nx, ny, nz) = shape;
+    // Rows along x are contiguous
+    planner.plan_fft(nx, direction).process(data);
+
+    let mut line = Vec::new();
+    for (n, stride) in [(ny, nx), (nz, nx * ny)] {
+        let fft = planner.plan_fft(n, direction);
+        for start in 0..data.len() / n {
+            // Lines along this axis start at every position of the others
+            let first = start % stride + start / stride * stride * n;
+            line.clear();
+            line.extend((0..n).map(|i| data[first + i * stride]));
+            fft.process(&mut line);
+            for (i, value) in line.iter().enumerate() {
+                data[first + i * stride] = *value;
+            }
+        }
+    }
+}
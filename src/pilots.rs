@@ -0,0 +1,161 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{decode_data_with_config, CodecConfig};
+use std::f32::consts::PI;
+
+// Pilot pattern: two intensities (so both gain and offset are observable) and
+// all four canonical polarization angles (so rotation is observable).
+const PILOT_INTENSITIES: [f32; 2] = [0.5, 1.0];
+const PILOT_POLARIZATIONS: [f32; 4] = [0.0, PI / 4.0, PI / 2.0, 3.0 * PI / 4.0];
+
+/// Placement of the known pilot (training) voxels.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PilotConfig {
+    /// Data voxels between two pilots. A pilot precedes every group.
+    pub interval: usize,
+    /// Number of neighbouring pilots on each side used to estimate the
+    /// calibration of a group. `None` uses all pilots (one global estimate);
+    /// small windows track drift along the stream.
+    pub window: Option<usize>,
+}
+
+impl Default for PilotConfig {
+    fn default() -> Self {
+        Self { interval: 32, window: Some(4) }
+    }
+}
+
+/// Systematic receiver offsets estimated from pilots.
+///
+/// The received values are modelled as
+/// `intensity' = gain * intensity + offset`, `polarization' = polarization + rotation`
+/// and `phase' = phase + phase_offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    pub intensity_gain: f32,
+    pub intensity_offset: f32,
+    /// Polarization rotation in radians, in (-PI/2, PI/2].
+    pub polarization_rotation: f32,
+    /// Phase offset in radians, in (-PI, PI].
+    pub phase_offset: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { intensity_gain: 1.0, intensity_offset: 0.0, polarization_rotation: 0.0, phase_offset: 0.0 }
+    }
+}
+
+impl Calibration {
+    /// Undoes the estimated offsets on a received voxel.
+    pub fn apply(&self, voxel: &PhotonicVoxel) -> PhotonicVoxel {
+        let mut v = *voxel;
+        v.intensity = (v.intensity - self.intensity_offset) / self.intensity_gain;
+        v.polarization = (v.polarization - self.polarization_rotation).rem_euclid(PI);
+        v.phase = (v.phase - self.phase_offset).rem_euclid(2.0 * PI);
+        v
+    }
+
+    /// Estimates the offsets from pairs of (expected, received) pilot voxels.
+    pub fn estimate(pilots: &[(PhotonicVoxel, PhotonicVoxel)]) -> Self {
+        if pilots.is_empty() {
+            return Self::default();
+        }
+        let n = pilots.len() as f32;
+
+        // Least-squares fit of received = gain * expected + offset
+        let mean_x = pilots.iter().map(|(e, _)| e.intensity).sum::<f32>() / n;
+        let mean_y = pilots.iter().map(|(_, r)| r.intensity).sum::<f32>() / n;
+        let cov: f32 = pilots.iter().map(|(e, r)| (e.intensity - mean_x) * (r.intensity - mean_y)).sum();
+        let var: f32 = pilots.iter().map(|(e, _)| (e.intensity - mean_x).powi(2)).sum();
+        let (intensity_gain, intensity_offset) = if var > f32::EPSILON && cov.abs() > f32::EPSILON {
+            let gain = cov / var;
+            (gain, mean_y - gain * mean_x)
+        } else {
+            // Single pilot level: assume a pure gain
+            (mean_y / mean_x.max(f32::EPSILON), 0.0)
+        };
+
+        // Circular means of the angle errors (polarization has period PI)
+        let polarization_rotation = circular_mean(pilots.iter().map(|(e, r)| r.polarization - e.polarization), PI);
+        let phase_offset = circular_mean(pilots.iter().map(|(e, r)| r.phase - e.phase), 2.0 * PI);
+
+        Self { intensity_gain, intensity_offset, polarization_rotation, phase_offset }
+    }
+}
+
+/// The `k`-th pilot voxel, written at `wavelength`.
+pub fn pilot_voxel(k: usize, wavelength: f32) -> PhotonicVoxel {
+    PhotonicVoxel::new(
+        PILOT_INTENSITIES[k % PILOT_INTENSITIES.len()],
+        PILOT_POLARIZATIONS[(k / PILOT_INTENSITIES.len()) % PILOT_POLARIZATIONS.len()],
+        0.0,
+        wavelength,
+    )
+}
+
+/// Interleaves a pilot voxel in front of every `config.interval` data voxels.
+/// Pilots use the first wavelength of `codec`'s palette.
+///
+/// Panics if `config.interval` is zero.
+pub fn insert_pilots(voxels: &[PhotonicVoxel], config: &PilotConfig, codec: &CodecConfig) -> Vec<PhotonicVoxel> {
+    assert!(config.interval > 0, "Pilot interval must be at least one voxel");
+
+    let wavelength = codec.wavelengths[0];
+    let mut out = Vec::with_capacity(voxels.len() + voxels.len().div_ceil(config.interval));
+    for (k, group) in voxels.chunks(config.interval).enumerate() {
+        out.push(pilot_voxel(k, wavelength));
+        out.extend_from_slice(group);
+    }
+    out
+}
+
+/// Removes the pilots from a received stream, estimates the receiver offsets
+/// from them and returns the compensated data voxels, along with the
+/// calibration applied to each group of `config.interval` voxels.
+pub fn calibrate(received: &[PhotonicVoxel], config: &PilotConfig, codec: &CodecConfig) -> (Vec<PhotonicVoxel>, Vec<Calibration>) {
+    assert!(config.interval > 0, "Pilot interval must be at least one voxel");
+
+    let wavelength = codec.wavelengths[0];
+    let groups: Vec<&[PhotonicVoxel]> = received.chunks(config.interval + 1).collect();
+    let pilots: Vec<(PhotonicVoxel, PhotonicVoxel)> = groups
+        .iter()
+        .enumerate()
+        .map(|(k, group)| (pilot_voxel(k, wavelength), group[0]))
+        .collect();
+
+    let global = Calibration::estimate(&pilots);
+    let mut data = Vec::with_capacity(received.len());
+    let mut calibrations = Vec::with_capacity(groups.len());
+
+    for (k, group) in groups.iter().enumerate() {
+        let calibration = match config.window {
+            Some(window) => {
+                let start = k.saturating_sub(window);
+                let end = (k + window + 1).min(pilots.len());
+                Calibration::estimate(&pilots[start..end])
+            }
+            None => global,
+        };
+        data.extend(group[1..].iter().map(|v| calibration.apply(v)));
+        calibrations.push(calibration);
+    }
+
+    (data, calibrations)
+}
+
+/// Decoder mode for streams written with [`insert_pilots`]: compensates the
+/// offsets measured on the pilots before quantizing the data voxels.
+pub fn decode_with_pilots(received: &[PhotonicVoxel], config: &PilotConfig, codec: &CodecConfig) -> Vec<u8> {
+    let (data, _) = calibrate(received, config, codec);
+    decode_data_with_config(&data, false, codec)
+}
+
+/// Mean of angle differences on a circle of circumference `period`,
+/// in (-period/2, period/2].
+fn circular_mean(diffs: impl Iterator<Item = f32>, period: f32) -> f32 {
+    let scale = 2.0 * PI / period;
+    let (sin, cos) = diffs.fold((0.0f32, 0.0f32), |(s, c), d| (s + (d * scale).sin(), c + (d * scale).cos()));
+    sin.atan2(cos) / scale
+}
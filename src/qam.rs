@@ -0,0 +1,179 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{circular_distance, gray_decode, gray_encode, CodecConfig, ModulationScheme};
+
+/// Joint (intensity, phase) constellation: square QAM on the optical field.
+///
+/// Instead of quantizing intensity and phase independently, the two are
+/// treated as the magnitude and argument of a complex amplitude
+/// `sqrt(intensity) * e^(i * phase)`, whose in-phase and quadrature components
+/// each take `sqrt(order)` evenly spaced values. Decoding picks the
+/// constellation point nearest to the received field (minimum Euclidean
+/// distance), which for a square grid is a per-axis decision. Each axis is
+/// Gray coded, so neighbouring points differ in a single bit.
+///
+/// Polarization and wavelength keep their per-dimension levels, taken from
+/// `levels`. The field is scaled so the outermost points reach intensity 1.0;
+/// no point is dark.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QamScheme {
+    /// Number of constellation points: 4, 16, 64, ...
+    pub order: usize,
+    /// Polarization levels and wavelength palette. Intensity and phase are
+    /// carried by the constellation, so both must have a single level.
+    pub levels: CodecConfig,
+}
+
+impl QamScheme {
+    /// An `order`-point constellation combined with the polarization levels,
+    /// palette and Gray setting of `config`.
+    pub fn new(order: usize, config: &CodecConfig) -> Self {
+        Self {
+            order,
+            levels: CodecConfig { intensity_levels: 1, phase_levels: 1, ..config.clone() },
+        }
+    }
+
+    /// Checks that `order` is a power of four, that `levels` leaves intensity and
+    /// phase to the constellation and that a voxel carries at most 32 bits.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.order < 4 || !self.order.is_power_of_two() || !self.order.trailing_zeros().is_multiple_of(2) {
+            return Err(format!("QAM order must be a power of four, at least 4 (got {})", self.order));
+        }
+        if self.levels.intensity_levels != 1 || self.levels.phase_levels != 1 {
+            return Err("intensity and phase are carried by the constellation and must have one level".to_string());
+        }
+        // Same palette and width checks as a level config carrying the same bits
+        CodecConfig { intensity_levels: self.order, ..self.levels.clone() }.validate()
+    }
+
+    /// Values per axis.
+    fn side(&self) -> usize {
+        1 << (self.order.trailing_zeros() / 2)
+    }
+
+    fn axis_bits(&self) -> usize {
+        self.order.trailing_zeros() as usize / 2
+    }
+
+    /// Field amplitude per grid unit, so the corner points have intensity 1.0.
+    fn scale(&self) -> f32 {
+        1.0 / ((self.side() - 1) as f32 * std::f32::consts::SQRT_2)
+    }
+
+    /// Grid coordinate of axis level `idx`: -(side - 1), ..., -1, 1, ..., side - 1.
+    fn coordinate(&self, idx: usize) -> f32 {
+        2.0 * idx as f32 - (self.side() - 1) as f32
+    }
+
+    /// Nearest axis level to a field component.
+    fn nearest(&self, component: f32) -> usize {
+        let idx = ((component / self.scale() + (self.side() - 1) as f32) / 2.0).round();
+        idx.clamp(0.0, (self.side() - 1) as f32) as usize
+    }
+
+    /// The constellation points as (intensity, phase) pairs, indexed by the
+    /// constellation bits of the symbol.
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        (0..self.order as u32)
+            .map(|bits| {
+                let v = self.modulate(bits);
+                (v.intensity, v.phase)
+            })
+            .collect()
+    }
+}
+
+impl ModulationScheme for QamScheme {
+    fn bits_per_voxel(&self) -> usize {
+        2 * self.axis_bits() + self.levels.bits_per_voxel()
+    }
+
+    /// Lowest bits: in-phase axis, then quadrature axis, then the polarization
+    /// and wavelength bits of `levels`.
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        let width = self.axis_bits();
+        let mask = (1u32 << width) - 1;
+        let i = gray_decode((symbol & mask) as usize);
+        let q = gray_decode(((symbol >> width) & mask) as usize);
+
+        let re = self.coordinate(i) * self.scale();
+        let im = self.coordinate(q) * self.scale();
+
+        let mut voxel = self.levels.modulate(symbol >> (2 * width));
+        voxel.intensity = re * re + im * im;
+        voxel.phase = im.atan2(re).rem_euclid(2.0 * std::f32::consts::PI);
+        voxel
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        let width = self.axis_bits();
+        let amplitude = voxel.intensity.max(0.0).sqrt();
+        let i = self.nearest(amplitude * voxel.phase.cos());
+        let q = self.nearest(amplitude * voxel.phase.sin());
+
+        let constellation = gray_encode(i) as u32 | (gray_encode(q) as u32) << width;
+        constellation | self.levels.demodulate(voxel) << (2 * width)
+    }
+}
+
+/// Largest [`CustomConstellation`]: 8 bits per voxel, so its point table fits
+/// in a stream header.
+pub const MAX_CONSTELLATION_POINTS: usize = 256;
+
+/// Arbitrary constellation given as a table of points: symbol `s` is written
+/// as `points[s]` and read as the nearest point.
+///
+/// Distances combine intensity and phase as the optical field, like
+/// [`QamScheme`], with the polarization angle (modulo π) in units of π/2 and
+/// the wavelength in units of 100 nm.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomConstellation {
+    pub points: Vec<PhotonicVoxel>,
+}
+
+impl CustomConstellation {
+    pub fn new(points: Vec<PhotonicVoxel>) -> Self {
+        Self { points }
+    }
+
+    /// Checks that there are 2 to [`MAX_CONSTELLATION_POINTS`] points, a power
+    /// of two, with finite coordinates.
+    pub fn validate(&self) -> Result<(), String> {
+        let len = self.points.len();
+        if !(2..=MAX_CONSTELLATION_POINTS).contains(&len) || !len.is_power_of_two() {
+            return Err(format!("constellation must have a power of two points, 2 to {} (got {})", MAX_CONSTELLATION_POINTS, len));
+        }
+        if self.points.iter().any(|p| ![p.intensity, p.polarization, p.phase, p.wavelength].iter().all(|x| x.is_finite())) {
+            return Err("constellation points must have finite coordinates".to_string());
+        }
+        Ok(())
+    }
+
+    fn distance(a: &PhotonicVoxel, b: &PhotonicVoxel) -> f32 {
+        let field = |v: &PhotonicVoxel| {
+            let amplitude = v.intensity.max(0.0).sqrt();
+            (amplitude * v.phase.cos(), amplitude * v.phase.sin())
+        };
+        let ((ar, ai), (br, bi)) = (field(a), field(b));
+        let polarization = circular_distance(a.polarization, b.polarization, std::f32::consts::PI) / std::f32::consts::FRAC_PI_2;
+        let wavelength = (a.wavelength - b.wavelength) / 100.0;
+        (ar - br).powi(2) + (ai - bi).powi(2) + polarization.powi(2) + wavelength.powi(2)
+    }
+}
+
+impl ModulationScheme for CustomConstellation {
+    fn bits_per_voxel(&self) -> usize {
+        self.points.len().trailing_zeros() as usize
+    }
+
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        self.points[symbol as usize]
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        let distances = self.points.iter().map(|point| Self::distance(point, voxel));
+        distances.enumerate().min_by(|a, b| a.1.total_cmp(&b.1)).map_or(0, |(symbol, _)| symbol as u32)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{CodecConfig, ModulationScheme};
+use crate::quantizer::Quantizer;
+
+/// A voxel stored as its level indices instead of analog values: 4 bytes
+/// instead of 16.
+///
+/// `wavelength` is an index into the codec palette. Converting an ideal voxel
+/// (one written by the codec) to a `QuantizedVoxel` and back is lossless;
+/// received voxels are snapped to their nearest levels, as by the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuantizedVoxel {
+    pub intensity: u8,
+    pub polarization: u8,
+    pub phase: u8,
+    pub wavelength: u8,
+}
+
+impl QuantizedVoxel {
+    /// Checks that `config` is valid and no dimension has more than 256 levels.
+    pub fn check_config(config: &CodecConfig) -> Result<(), String> {
+        config.validate()?;
+        let dimensions = [
+            ("intensity_levels", config.intensity_levels),
+            ("polarization_levels", config.polarization_levels),
+            ("phase_levels", config.phase_levels),
+            ("wavelengths", config.wavelengths.len()),
+        ];
+        for (name, levels) in dimensions {
+            if levels > 256 {
+                return Err(format!("{} must be at most 256 to fit in a byte (got {})", name, levels));
+            }
+        }
+        Ok(())
+    }
+
+    /// Level indices of the nearest levels of `config` to `voxel`.
+    ///
+    /// Panics if `config` does not pass [`check_config`](Self::check_config).
+    pub fn from_voxel(voxel: &PhotonicVoxel, config: &CodecConfig) -> Self {
+        if let Err(e) = Self::check_config(config) {
+            panic!("Invalid codec config: {}", e);
+        }
+        let levels = config.decide(voxel).map(|d| d.level as u8);
+        Self { intensity: levels[0], polarization: levels[1], phase: levels[2], wavelength: levels[3] }
+    }
+
+    /// The ideal voxel of these levels under `config`.
+    ///
+    /// Panics if a level index is out of range for `config`.
+    pub fn to_voxel(&self, config: &CodecConfig) -> PhotonicVoxel {
+        let check = |name: &str, level: u8, levels: usize| {
+            if level as usize >= levels {
+                panic!("{} level {} is out of range ({} levels)", name, level, levels);
+            }
+        };
+        check("intensity", self.intensity, config.intensity_levels);
+        check("polarization", self.polarization, config.polarization_levels);
+        check("phase", self.phase, config.phase_levels);
+        check("wavelength", self.wavelength, config.wavelengths.len());
+
+        PhotonicVoxel::new(
+            config.intensity_level(self.intensity as usize),
+            config.polarization_level(self.polarization as usize),
+            config.phase_level(self.phase as usize),
+            config.wavelengths[self.wavelength as usize],
+        )
+    }
+
+    /// Byte representation: intensity, polarization, phase, wavelength.
+    pub fn to_bytes(self) -> [u8; 4] {
+        [self.intensity, self.polarization, self.phase, self.wavelength]
+    }
+
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self { intensity: bytes[0], polarization: bytes[1], phase: bytes[2], wavelength: bytes[3] }
+    }
+}
+
+/// Packs voxels at `config.bits_per_voxel()` bits each (the symbol they carry),
+/// e.g. 1 byte per voxel with the default codec: 16x smaller than the analog
+/// representation. The last byte is zero-padded.
+///
+/// Lossless for ideal voxels; received voxels are snapped to their nearest
+/// levels. Use [`unpack_voxels`] with the same `config` to restore them.
+/// Panics if `config` is invalid.
+pub fn pack_voxels(voxels: &[PhotonicVoxel], config: &CodecConfig) -> Vec<u8> {
+    let quantizer = Quantizer::new(config);
+    let bits = config.bits_per_voxel();
+
+    let mut packed = Vec::with_capacity((voxels.len() * bits).div_ceil(8));
+    let mut acc: u64 = 0;
+    let mut acc_bits = 0;
+    for voxel in voxels {
+        acc |= (quantizer.demodulate(voxel) as u64) << acc_bits;
+        acc_bits += bits;
+        while acc_bits >= 8 {
+            packed.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    }
+    if acc_bits > 0 {
+        packed.push(acc as u8);
+    }
+    packed
+}
+
+/// Restores `count` voxels packed with [`pack_voxels`].
+///
+/// Panics if `config` is invalid or `packed` holds fewer than `count` voxels.
+pub fn unpack_voxels(packed: &[u8], count: usize, config: &CodecConfig) -> Vec<PhotonicVoxel> {
+    if let Err(e) = config.validate() {
+        panic!("Invalid codec config: {}", e);
+    }
+    let bits = config.bits_per_voxel();
+    if packed.len() * 8 < count * bits {
+        panic!("{} packed bytes cannot hold {} voxels of {} bits", packed.len(), count, bits);
+    }
+
+    let mut voxels = Vec::with_capacity(count);
+    let mut acc: u64 = 0;
+    let mut acc_bits = 0;
+    let mut bytes = packed.iter();
+    while voxels.len() < count {
+        while acc_bits < bits {
+            // Enough bytes were checked above
+            acc |= (*bytes.next().unwrap_or(&0) as u64) << acc_bits;
+            acc_bits += 8;
+        }
+        voxels.push(config.modulate((acc & ((1u64 << bits) - 1)) as u32));
+        acc >>= bits;
+        acc_bits -= bits;
+    }
+    voxels
+}
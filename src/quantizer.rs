@@ -0,0 +1,145 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{level_bits, CodecConfig, ModulationScheme};
+use std::f32::consts::PI;
+
+// Upper bound on the cells of the wavelength lookup table
+const MAX_TABLE_CELLS: usize = 4096;
+
+/// Constant-time hard-decision demodulator for a [`CodecConfig`].
+///
+/// Built once per configuration, it replaces the linear nearest-level search
+/// with direct index computation for the evenly spaced dimensions (intensity,
+/// polarization, phase) and a decision-boundary lookup table for the
+/// wavelength palette. Level-to-bit mappings (Gray or natural) are tabulated
+/// too. Decisions match [`CodecConfig::demodulate`] except, possibly, for
+/// values exactly on a decision boundary.
+///
+/// [`decode_data_with_config`](crate::decode_data_with_config) and
+/// [`VoxelDecoder`](crate::VoxelDecoder) use it internally.
+#[derive(Debug, Clone)]
+pub struct Quantizer {
+    config: CodecConfig,
+    /// Bits carried by each level of intensity, polarization and phase.
+    intensity_bits: Vec<u32>,
+    polarization_bits: Vec<u32>,
+    phase_bits: Vec<u32>,
+    /// Bits carried by each wavelength, by increasing wavelength.
+    wavelength_bits: Vec<u32>,
+    /// Shift of each dimension's bits in the symbol.
+    shifts: [u32; 4],
+    /// Sorted decision boundaries between data wavelengths.
+    boundaries: Vec<f32>,
+    /// Number of boundaries below the start of each table cell.
+    table: Vec<u16>,
+    table_origin: f32,
+    table_step: f32,
+}
+
+impl Quantizer {
+    /// Precomputes the decision tables for `config`.
+    pub fn new(config: &CodecConfig) -> Self {
+        let level_table = |levels: usize| (0..levels).map(|i| config.level_to_bits(i) as u32).collect::<Vec<u32>>();
+        let wavelength_bits = config
+            .wavelengths_by_rank()
+            .into_iter()
+            .map(|idx| config.wavelength_idx_to_bits(idx) as u32)
+            .collect();
+
+        let i_width = level_bits(config.intensity_levels) as u32;
+        let p_width = level_bits(config.polarization_levels) as u32;
+        let ph_width = level_bits(config.phase_levels) as u32;
+
+        // Cells no wider than the narrowest decision region hold at most one
+        // boundary each, so a lookup needs at most one comparison.
+        let boundaries = config.wavelength_decision_boundaries();
+        let (table_origin, range) = match (boundaries.first(), boundaries.last()) {
+            (Some(&first), Some(&last)) => (first, last - first),
+            _ => (0.0, 0.0),
+        };
+        let narrowest = boundaries.windows(2).map(|w| w[1] - w[0]).fold(f32::INFINITY, f32::min);
+        let cells = if narrowest.is_finite() && narrowest > 0.0 {
+            ((range / narrowest).ceil() as usize + 1).min(MAX_TABLE_CELLS)
+        } else {
+            1
+        };
+        let table_step = if cells > 1 { range / (cells - 1) as f32 } else { f32::INFINITY };
+        let table = (0..cells)
+            .map(|c| {
+                let start = table_origin + c as f32 * table_step;
+                boundaries.partition_point(|&b| b <= start) as u16
+            })
+            .collect();
+
+        Self {
+            config: config.clone(),
+            intensity_bits: level_table(config.intensity_levels),
+            polarization_bits: level_table(config.polarization_levels),
+            phase_bits: level_table(config.phase_levels),
+            wavelength_bits,
+            shifts: [0, i_width, i_width + p_width, i_width + p_width + ph_width],
+            boundaries,
+            table,
+            table_origin,
+            table_step,
+        }
+    }
+
+    pub fn config(&self) -> &CodecConfig {
+        &self.config
+    }
+
+    /// Nearest intensity level: levels sit at `(i + 1) / n`.
+    fn intensity_level(&self, intensity: f32) -> usize {
+        let n = self.config.intensity_levels;
+        // ceil(x - 0.5) rounds halves down, like the first-wins linear search
+        let idx = (intensity * n as f32 - 1.5).ceil();
+        (idx.max(0.0) as usize).min(n - 1)
+    }
+
+    /// Nearest of `n` angles `i * period / n` on a circle of circumference `period`.
+    fn angle_level(value: f32, n: usize, period: f32) -> usize {
+        let t = (value * (n as f32 / period)).rem_euclid(n as f32);
+        ((t - 0.5).ceil().max(0.0) as usize) % n
+    }
+
+    /// Rank (by increasing wavelength) of the nearest data wavelength.
+    fn wavelength_rank(&self, wavelength: f32) -> usize {
+        if self.boundaries.is_empty() || wavelength < self.table_origin {
+            return 0;
+        }
+        let cell = ((wavelength - self.table_origin) / self.table_step) as usize;
+        let mut rank = self.table[cell.min(self.table.len() - 1)] as usize;
+        // At most one step when the table resolves every region; the loops
+        // also absorb rounding at cell edges.
+        while rank < self.boundaries.len() && wavelength >= self.boundaries[rank] {
+            rank += 1;
+        }
+        while rank > 0 && wavelength < self.boundaries[rank - 1] {
+            rank -= 1;
+        }
+        rank
+    }
+}
+
+impl ModulationScheme for Quantizer {
+    fn bits_per_voxel(&self) -> usize {
+        self.config.bits_per_voxel()
+    }
+
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        self.config.modulate(symbol)
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        let i = self.intensity_bits[self.intensity_level(voxel.intensity)];
+        let p = self.polarization_bits[Self::angle_level(voxel.polarization, self.config.polarization_levels, PI)];
+        let ph = self.phase_bits[Self::angle_level(voxel.phase, self.config.phase_levels, 2.0 * PI)];
+        let w = self.wavelength_bits[self.wavelength_rank(voxel.wavelength)];
+
+        i << self.shifts[0] | p << self.shifts[1] | ph << self.shifts[2] | w << self.shifts[3]
+    }
+
+    fn demodulate_soft(&self, voxel: &PhotonicVoxel, llrs: &mut Vec<f32>) {
+        self.config.demodulate_soft(voxel, llrs)
+    }
+}
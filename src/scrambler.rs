@@ -0,0 +1,116 @@
+use std::io::{self, Read};
+
+// Self-synchronizing (multiplicative) scrambler with the ITU-T V.34 polynomial
+// 1 + x^-18 + x^-23: y[n] = x[n] ^ y[n-18] ^ y[n-23].
+const TAP_A: u32 = 18;
+const TAP_B: u32 = 23;
+const STATE_MASK: u32 = (1 << TAP_B) - 1;
+
+/// Seed used when none (or an all-zero one) is given. An all-zero state would
+/// let a run of zero bytes pass through unscrambled.
+pub const DEFAULT_SEED: u32 = 0x5A_5A5A;
+
+/// Whitening stage applied to the byte stream before modulation.
+///
+/// Long runs of identical bytes produce pathological lattices (e.g. blocks of
+/// maximum-intensity voxels that worsen crosstalk). Scrambling turns them into
+/// a pseudo-random bit pattern.
+///
+/// The scrambler is self-synchronizing: the descrambler only needs the last 23
+/// received bits, so after a burst of errors it recovers on its own and only
+/// the first 23 bits depend on the seed. The price is that every channel bit
+/// error becomes three data bit errors.
+#[derive(Debug, Clone)]
+pub struct Scrambler {
+    /// Last 23 scrambled bits, most recent in bit 0.
+    state: u32,
+}
+
+/// Inverse of [`Scrambler`]. Must be created with the same seed.
+#[derive(Debug, Clone)]
+pub struct Descrambler {
+    /// Last 23 received (scrambled) bits, most recent in bit 0.
+    state: u32,
+}
+
+/// Normalizes a user seed into a non-zero 23-bit register value.
+fn initial_state(seed: u32) -> u32 {
+    match seed & STATE_MASK {
+        0 => DEFAULT_SEED,
+        state => state,
+    }
+}
+
+/// Feedback bit y[n-18] ^ y[n-23] for the given register.
+fn feedback(state: u32) -> u8 {
+    (((state >> (TAP_A - 1)) ^ (state >> (TAP_B - 1))) & 1) as u8
+}
+
+impl Scrambler {
+    pub fn new(seed: u32) -> Self {
+        Self { state: initial_state(seed) }
+    }
+
+    /// Scrambles one byte, least significant bit first.
+    pub fn process_byte(&mut self, byte: u8) -> u8 {
+        let mut out = 0u8;
+        for bit in 0..8 {
+            let y = ((byte >> bit) & 1) ^ feedback(self.state);
+            self.state = ((self.state << 1) | y as u32) & STATE_MASK;
+            out |= y << bit;
+        }
+        out
+    }
+}
+
+impl Descrambler {
+    pub fn new(seed: u32) -> Self {
+        Self { state: initial_state(seed) }
+    }
+
+    /// Descrambles one byte, least significant bit first.
+    pub fn process_byte(&mut self, byte: u8) -> u8 {
+        let mut out = 0u8;
+        for bit in 0..8 {
+            let y = (byte >> bit) & 1;
+            out |= (y ^ feedback(self.state)) << bit;
+            self.state = ((self.state << 1) | y as u32) & STATE_MASK;
+        }
+        out
+    }
+}
+
+/// Scrambles a whole buffer with a fresh [`Scrambler`].
+pub fn scramble(data: &[u8], seed: u32) -> Vec<u8> {
+    let mut scrambler = Scrambler::new(seed);
+    data.iter().map(|&b| scrambler.process_byte(b)).collect()
+}
+
+/// Descrambles a whole buffer with a fresh [`Descrambler`].
+pub fn descramble(data: &[u8], seed: u32) -> Vec<u8> {
+    let mut descrambler = Descrambler::new(seed);
+    data.iter().map(|&b| descrambler.process_byte(b)).collect()
+}
+
+/// `Read` adapter that scrambles everything read through it, so it can sit
+/// between a file and a streaming encoder.
+pub struct ScramblingReader<R: Read> {
+    inner: R,
+    scrambler: Scrambler,
+}
+
+impl<R: Read> ScramblingReader<R> {
+    pub fn new(inner: R, seed: u32) -> Self {
+        Self { inner, scrambler: Scrambler::new(seed) }
+    }
+}
+
+impl<R: Read> Read for ScramblingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for byte in &mut buf[..n] {
+            *byte = self.scrambler.process_byte(*byte);
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::structs::PhotonicVoxel;
+
+/// Size of one serialized voxel: the four `f32` fields of [`PhotonicVoxel`]
+/// (intensity, polarization, phase, wavelength), little-endian.
+pub const VOXEL_RECORD_LEN: usize = 16;
+
+/// Serializes a voxel into its little-endian record. The layout does not
+/// depend on the platform or on the in-memory struct layout.
+pub fn voxel_to_bytes(voxel: &PhotonicVoxel) -> [u8; VOXEL_RECORD_LEN] {
+    let mut record = [0u8; VOXEL_RECORD_LEN];
+    let fields = [voxel.intensity, voxel.polarization, voxel.phase, voxel.wavelength];
+    for (chunk, field) in record.chunks_exact_mut(4).zip(fields) {
+        chunk.copy_from_slice(&field.to_le_bytes());
+    }
+    record
+}
+
+/// Inverse of [`voxel_to_bytes`].
+pub fn voxel_from_bytes(record: &[u8; VOXEL_RECORD_LEN]) -> PhotonicVoxel {
+    let field = |i: usize| f32::from_le_bytes([record[4 * i], record[4 * i + 1], record[4 * i + 2], record[4 * i + 3]]);
+    PhotonicVoxel::new(field(0), field(1), field(2), field(3))
+}
+
+/// Serializes voxels into consecutive records.
+pub fn voxels_to_bytes(voxels: &[PhotonicVoxel]) -> Vec<u8> {
+    voxels.iter().flat_map(voxel_to_bytes).collect()
+}
+
+/// Parses consecutive records. Fails if `bytes` is not a whole number of records.
+pub fn voxels_from_bytes(bytes: &[u8]) -> Result<Vec<PhotonicVoxel>, String> {
+    if !bytes.len().is_multiple_of(VOXEL_RECORD_LEN) {
+        return Err(format!("{} bytes is not a whole number of {}-byte voxel records", bytes.len(), VOXEL_RECORD_LEN));
+    }
+    Ok(bytes
+        .chunks_exact(VOXEL_RECORD_LEN)
+        .map(|chunk| voxel_from_bytes(chunk.try_into().expect("chunk of one record")))
+        .collect())
+}
@@ -0,0 +1,144 @@
+use crate::jones::JonesVoxel;
+use crate::noise::NoiseDistribution;
+use crate::structs::PhotonicVoxel;
+use rand::Rng;
+use std::f32::consts::PI;
+
+/// Polarization state as the Stokes parameters a polarimeter measures:
+/// total intensity `s0`, linear horizontal/vertical `s1`, linear ±45° `s2`
+/// and circular `s3` (positive for the handedness with positive
+/// [`JonesVoxel::ellipticity`]).
+///
+/// Unlike a Jones vector it carries no absolute phase, and it can describe
+/// partially polarized light (`s1² + s2² + s3² < s0²`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StokesVector {
+    pub s0: f32,
+    pub s1: f32,
+    pub s2: f32,
+    pub s3: f32,
+}
+
+impl StokesVector {
+    pub fn new(s0: f32, s1: f32, s2: f32, s3: f32) -> Self {
+        Self { s0, s1, s2, s3 }
+    }
+
+    /// Components in the order S0, S1, S2, S3.
+    pub fn components(&self) -> [f32; 4] {
+        [self.s0, self.s1, self.s2, self.s3]
+    }
+
+    /// Intensity of the polarized part, `sqrt(s1² + s2² + s3²)`.
+    pub fn polarized_intensity(&self) -> f32 {
+        (self.s1 * self.s1 + self.s2 * self.s2 + self.s3 * self.s3).sqrt()
+    }
+
+    /// Degree of polarization: 1 for fully polarized light, 0 for unpolarized.
+    /// Noisy measurements may exceed 1.
+    pub fn degree_of_polarization(&self) -> f32 {
+        if self.s0 <= 0.0 { 0.0 } else { self.polarized_intensity() / self.s0 }
+    }
+
+    /// Orientation of the polarization ellipse, in [0, PI).
+    pub fn orientation(&self) -> f32 {
+        let orientation = (0.5 * self.s2.atan2(self.s1)).rem_euclid(PI);
+        if orientation >= PI { 0.0 } else { orientation }
+    }
+
+    /// Ellipticity angle in [-PI/4, PI/4], from the polarized part.
+    pub fn ellipticity(&self) -> f32 {
+        let polarized = self.polarized_intensity();
+        if polarized <= 0.0 { 0.0 } else { 0.5 * (self.s3 / polarized).clamp(-1.0, 1.0).asin() }
+    }
+}
+
+impl From<JonesVoxel> for StokesVector {
+    fn from(voxel: JonesVoxel) -> Self {
+        let cross = voxel.ex.conj() * voxel.ey;
+        Self {
+            s0: voxel.intensity(),
+            s1: voxel.ex.norm_sqr() - voxel.ey.norm_sqr(),
+            s2: 2.0 * cross.re,
+            s3: 2.0 * cross.im,
+        }
+    }
+}
+
+impl From<PhotonicVoxel> for StokesVector {
+    /// Linear polarization at the voxel's angle: `s3` is zero.
+    fn from(voxel: PhotonicVoxel) -> Self {
+        JonesVoxel::from(voxel).into()
+    }
+}
+
+/// Polarimeter model measuring each Stokes component with independent additive
+/// noise (standard deviations in intensity units).
+///
+/// Real polarimeters derive S1–S3 from separate analyzer channels, so their
+/// errors are largely independent of each other and of S0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StokesDetector {
+    pub s0: f32,
+    pub s1: f32,
+    pub s2: f32,
+    pub s3: f32,
+    pub distribution: NoiseDistribution,
+}
+
+impl Default for StokesDetector {
+    /// Gaussian noise of 0.02 on every component.
+    fn default() -> Self {
+        Self::gaussian(0.02)
+    }
+}
+
+impl StokesDetector {
+    /// Gaussian noise with standard deviation `sigma` on every component.
+    pub fn gaussian(sigma: f32) -> Self {
+        Self { s0: sigma, s1: sigma, s2: sigma, s3: sigma, distribution: NoiseDistribution::Gaussian }
+    }
+
+    /// A noiseless detector.
+    pub fn ideal() -> Self {
+        Self::gaussian(0.0)
+    }
+
+    /// Standard deviations in the order S0, S1, S2, S3.
+    pub fn sigmas(&self) -> [f32; 4] {
+        [self.s0, self.s1, self.s2, self.s3]
+    }
+
+    /// One noisy measurement of `stokes`.
+    pub fn measure(&self, stokes: &StokesVector) -> StokesVector {
+        self.measure_with_rng(stokes, &mut rand::rng())
+    }
+
+    /// [`StokesDetector::measure`] drawing from `rng`, for reproducible runs.
+    pub fn measure_with_rng<R: Rng + ?Sized>(&self, stokes: &StokesVector, rng: &mut R) -> StokesVector {
+        let [s0, s1, s2, s3] = stokes.components();
+        let mut sample = |sigma: f32| self.distribution.sample(sigma, rng);
+        StokesVector {
+            s0: s0 + sample(self.s0),
+            s1: s1 + sample(self.s1),
+            s2: s2 + sample(self.s2),
+            s3: s3 + sample(self.s3),
+        }
+    }
+
+    /// Reads `voxel` through the polarimeter: intensity and polarization angle
+    /// are recovered from the noisy Stokes measurement. Phase and wavelength
+    /// are not observable by a polarimeter and are passed through unchanged.
+    pub fn read_voxel_with_rng<R: Rng + ?Sized>(&self, voxel: &PhotonicVoxel, rng: &mut R) -> PhotonicVoxel {
+        let measured = self.measure_with_rng(&StokesVector::from(*voxel), rng);
+        PhotonicVoxel::new(measured.s0, measured.orientation(), voxel.phase, voxel.wavelength)
+    }
+
+    /// [`StokesDetector::read_voxel_with_rng`] over every voxel, e.g. to feed
+    /// polarimetric readouts to the decoder.
+    pub fn read_voxels_with_rng<R: Rng + ?Sized>(&self, voxels: &[PhotonicVoxel], rng: &mut R) -> Vec<PhotonicVoxel> {
+        voxels.iter().map(|v| self.read_voxel_with_rng(v, rng)).collect()
+    }
+}
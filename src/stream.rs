@@ -0,0 +1,105 @@
+use crate::structs::PhotonicVoxel;
+use crate::codec::{CodecConfig, VoxelDecoder, VoxelEncoder};
+use crate::serialization::{voxel_from_bytes, voxel_to_bytes};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// Size of one voxel record in a `.vox` stream (see [`crate::serialization`]).
+pub use crate::serialization::VOXEL_RECORD_LEN;
+
+/// Writes one little-endian voxel record.
+pub fn write_voxel<W: Write + ?Sized>(writer: &mut W, voxel: &PhotonicVoxel) -> io::Result<()> {
+    writer.write_all(&voxel_to_bytes(voxel))
+}
+
+/// Reads one voxel record. Returns `Ok(None)` at a clean end of stream and an
+/// `UnexpectedEof` error if the stream ends inside a record.
+pub fn read_voxel<R: Read + ?Sized>(reader: &mut R) -> io::Result<Option<PhotonicVoxel>> {
+    let mut record = [0u8; VOXEL_RECORD_LEN];
+    let mut filled = 0;
+    while filled < VOXEL_RECORD_LEN {
+        match reader.read(&mut record[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("truncated voxel record ({} of {} bytes)", filled, VOXEL_RECORD_LEN),
+                ))
+            }
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Some(voxel_from_bytes(&record)))
+}
+
+/// Iterator over the voxel records of a byte stream.
+///
+/// Yields an error (and then stops) if the stream fails or ends inside a record.
+/// Wrap unbuffered readers in a `BufReader`.
+pub struct VoxelReader<R: Read> {
+    reader: R,
+    failed: bool,
+}
+
+impl<R: Read> VoxelReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, failed: false }
+    }
+}
+
+impl<R: Read> Iterator for VoxelReader<R> {
+    type Item = io::Result<PhotonicVoxel>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = read_voxel(&mut self.reader).transpose();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+/// Encodes the bytes of `reader` with `config` and writes the voxel records to
+/// `writer`, without holding more than small buffers in memory: pipes and
+/// sockets can be processed as they arrive. Produces the voxels of
+/// [`encode_data_with_config`](crate::encode_data_with_config), without header.
+///
+/// Returns the number of voxels written. Panics if `config` is invalid.
+pub fn encode_stream<R: Read, W: Write>(reader: R, writer: W, config: &CodecConfig) -> io::Result<usize> {
+    let mut writer = BufWriter::new(writer);
+    let mut count = 0;
+    for voxel in VoxelEncoder::with_config(reader, config.clone()) {
+        write_voxel(&mut writer, &voxel?)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Inverse of [`encode_stream`]: reads voxel records from `reader`, decodes
+/// them with `config` (no noise added) and writes the bytes to `writer` as
+/// they are decoded.
+///
+/// Returns the number of bytes written. Panics if `config` is invalid.
+pub fn decode_stream<R: Read, W: Write>(reader: R, writer: W, config: &CodecConfig) -> io::Result<usize> {
+    let mut writer = BufWriter::new(writer);
+    let mut read_error = None;
+    let mut count = 0;
+
+    // The decoder consumes plain voxels: stop at the first read error and
+    // report it once the decoder is done.
+    let voxels = VoxelReader::new(BufReader::new(reader)).map_while(|voxel| voxel.map_err(|e| read_error = Some(e)).ok());
+    for byte in VoxelDecoder::with_config(voxels, config.clone(), false) {
+        writer.write_all(&[byte])?;
+        count += 1;
+    }
+
+    if let Some(e) = read_error {
+        return Err(e);
+    }
+    writer.flush()?;
+    Ok(count)
+}
@@ -1,3 +1,6 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul, RangeInclusive};
+
 /// Represents a single unit of data storage in the 5D optical memory crystal.
 ///
 /// This struct models the physical properties of a laser pulse used to write
@@ -13,29 +16,37 @@
 /// We use #[repr(C)] to ensure the layout corresponds to C struct layout,
 /// which with 4 f32s will be tightly packed and aligned to 4 bytes, 
 /// but the overall size is 16 bytes, fitting nicely into SIMD registers.
+///
+/// The float type defaults to `f32`, which the codec and the file formats use.
+/// Physics simulations that accumulate many small contributions can run on
+/// [`PhotonicVoxel64`] instead and convert back for storage.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub struct PhotonicVoxel {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhotonicVoxel<F = f32> {
     /// Optical Intensity (Amplitude squared). Normalized range [0.0, 1.0].
     /// Used to encode 2 bits in the PoC.
-    pub intensity: f32,
+    pub intensity: F,
 
     /// Polarization Angle in radians. Range [0, PI).
     /// Used to encode 2 bits in the PoC (0°, 45°, 90°, 135°).
-    pub polarization: f32,
+    pub polarization: F,
 
     /// Optical Phase shift in radians. Range [0, 2*PI).
     /// Can be used for additional multiplexing or holographic reconstruction.
-    pub phase: f32,
+    pub phase: F,
 
     /// Wavelength in nanometers (nm).
     /// Allows for spectral multiplexing (saving data at different colors).
-    pub wavelength: f32,
+    pub wavelength: F,
 }
 
-impl PhotonicVoxel {
+/// Double-precision voxel for precision-sensitive simulations.
+pub type PhotonicVoxel64 = PhotonicVoxel<f64>;
+
+impl<F> PhotonicVoxel<F> {
     /// Creates a new PhotonicVoxel with specified physical properties.
-    pub fn new(intensity: f32, polarization: f32, phase: f32, wavelength: f32) -> Self {
+    pub fn new(intensity: F, polarization: F, phase: F, wavelength: F) -> Self {
         Self {
             intensity,
             polarization,
@@ -44,3 +55,457 @@ impl PhotonicVoxel {
         }
     }
 }
+
+impl PhotonicVoxel {
+    /// Starts building a voxel whose values are checked against the physical
+    /// ranges (see [`PhotonicVoxelBuilder`]).
+    pub fn builder() -> PhotonicVoxelBuilder {
+        PhotonicVoxelBuilder::default()
+    }
+
+    /// Checks that the values are physically meaningful: all finite, intensity
+    /// in [0, 1], polarization in [0, PI), phase in [0, 2PI) and wavelength in
+    /// [`WAVELENGTH_RANGE_NM`].
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("intensity", self.intensity),
+            ("polarization", self.polarization),
+            ("phase", self.phase),
+            ("wavelength", self.wavelength),
+        ];
+        if let Some((name, value)) = fields.iter().find(|(_, value)| !value.is_finite()) {
+            return Err(format!("{} must be finite (got {})", name, value));
+        }
+        if !(0.0..=1.0).contains(&self.intensity) {
+            return Err(format!("intensity must be in [0, 1] (got {})", self.intensity));
+        }
+        if !(0.0..PI).contains(&self.polarization) {
+            return Err(format!("polarization must be in [0, PI) (got {})", self.polarization));
+        }
+        if !(0.0..2.0 * PI).contains(&self.phase) {
+            return Err(format!("phase must be in [0, 2PI) (got {})", self.phase));
+        }
+        if !WAVELENGTH_RANGE_NM.contains(&self.wavelength) {
+            return Err(format!(
+                "wavelength must be in {}-{} nm (got {})",
+                WAVELENGTH_RANGE_NM.start(), WAVELENGTH_RANGE_NM.end(), self.wavelength
+            ));
+        }
+        Ok(())
+    }
+
+    /// Wraps the polarization into [0, PI) and the phase into [0, 2PI); both
+    /// describe the same physical state after wrapping.
+    pub fn normalize(&mut self) {
+        self.polarization = wrap_angle(self.polarization, PI);
+        self.phase = wrap_angle(self.phase, 2.0 * PI);
+    }
+
+    /// Widens to double precision (lossless).
+    pub fn to_f64(&self) -> PhotonicVoxel64 {
+        (*self).into()
+    }
+}
+
+/// Wavelengths accepted by [`PhotonicVoxel::validate`], in nm (UV to near IR).
+pub const WAVELENGTH_RANGE_NM: RangeInclusive<f32> = 200.0..=2000.0;
+
+/// Builder for a [`PhotonicVoxel`] with physical validation.
+///
+/// Angles are always wrapped into their range. Out-of-range intensity and
+/// wavelength are rejected by [`build`](Self::build) and clamped by
+/// [`build_clamped`](Self::build_clamped); non-finite values and a missing
+/// wavelength are errors in both.
+#[derive(Debug, Clone, Default)]
+pub struct PhotonicVoxelBuilder {
+    intensity: f32,
+    polarization: f32,
+    phase: f32,
+    wavelength: Option<f32>,
+}
+
+impl PhotonicVoxelBuilder {
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn polarization(mut self, polarization: f32) -> Self {
+        self.polarization = polarization;
+        self
+    }
+
+    pub fn phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn wavelength(mut self, wavelength: f32) -> Self {
+        self.wavelength = Some(wavelength);
+        self
+    }
+
+    /// The voxel with wrapped angles, or an error if a value is out of range.
+    pub fn build(self) -> Result<PhotonicVoxel, String> {
+        let voxel = self.wrapped()?;
+        voxel.validate()?;
+        Ok(voxel)
+    }
+
+    /// The voxel with wrapped angles and intensity and wavelength clamped into
+    /// their ranges.
+    pub fn build_clamped(self) -> Result<PhotonicVoxel, String> {
+        let mut voxel = self.wrapped()?;
+        if voxel.intensity.is_finite() && voxel.wavelength.is_finite() {
+            voxel.intensity = voxel.intensity.clamp(0.0, 1.0);
+            voxel.wavelength = voxel.wavelength.clamp(*WAVELENGTH_RANGE_NM.start(), *WAVELENGTH_RANGE_NM.end());
+        }
+        voxel.validate()?;
+        Ok(voxel)
+    }
+
+    fn wrapped(self) -> Result<PhotonicVoxel, String> {
+        let wavelength = self.wavelength.ok_or_else(|| "wavelength must be set".to_string())?;
+        let mut voxel = PhotonicVoxel::new(self.intensity, self.polarization, self.phase, wavelength);
+        voxel.normalize();
+        Ok(voxel)
+    }
+}
+
+/// Wraps `angle` into [0, period). Non-finite angles are left unchanged.
+fn wrap_angle(angle: f32, period: f32) -> f32 {
+    let wrapped = angle.rem_euclid(period);
+    // rem_euclid rounds tiny negative angles up to `period` itself
+    if wrapped >= period { 0.0 } else { wrapped }
+}
+
+impl PhotonicVoxel64 {
+    /// Rounds to single precision for storage and decoding.
+    pub fn to_f32(&self) -> PhotonicVoxel {
+        PhotonicVoxel::new(self.intensity as f32, self.polarization as f32, self.phase as f32, self.wavelength as f32)
+    }
+}
+
+impl From<PhotonicVoxel> for PhotonicVoxel64 {
+    fn from(voxel: PhotonicVoxel) -> Self {
+        Self::new(voxel.intensity.into(), voxel.polarization.into(), voxel.phase.into(), voxel.wavelength.into())
+    }
+}
+
+/// Float types a [`PhotonicVoxel`] can be computed in: `f32` and `f64`.
+pub trait VoxelFloat: Copy + PartialOrd + From<f32> + Add<Output = Self> + Mul<Output = Self> + std::fmt::Debug + Send + Sync {
+    fn from_f64(value: f64) -> Self;
+    fn as_f64(self) -> f64;
+}
+
+impl VoxelFloat for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn as_f64(self) -> f64 {
+        self.into()
+    }
+}
+
+impl VoxelFloat for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Dimensions of a [`VoxelLattice`], e.g. as recorded in a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatticeShape {
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+}
+
+impl LatticeShape {
+    /// Number of voxels in a lattice of this shape.
+    pub fn len(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    /// Number of voxels in a lattice of this shape, or `None` on overflow,
+    /// e.g. for sides read from an untrusted header.
+    pub fn checked_len(&self) -> Option<usize> {
+        self.width.checked_mul(self.height)?.checked_mul(self.depth)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0 || self.depth == 0
+    }
+}
+
+/// A 3-D block of voxels: `depth` layers of `height` rows of `width` voxels.
+///
+/// Voxels are stored layer by layer, each layer row-major, so voxel `(x, y, z)`
+/// is at index `z * width * height + y * width + x` of the flat sequence. The
+/// dimensions always match the number of voxels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelLattice<F = f32> {
+    width: usize,
+    height: usize,
+    depth: usize,
+    voxels: Vec<PhotonicVoxel<F>>,
+}
+
+impl<F: Copy> VoxelLattice<F> {
+    /// A `width` x `height` x `depth` lattice filled with `fill`.
+    pub fn new(width: usize, height: usize, depth: usize, fill: PhotonicVoxel<F>) -> Self {
+        Self { width, height, depth, voxels: vec![fill; width * height * depth] }
+    }
+
+    /// Wraps a flat voxel sequence (e.g. the output of the encoder) as layers of
+    /// `width` x `height` voxels. Fails if the sequence does not fill whole layers.
+    pub fn from_voxels(width: usize, height: usize, voxels: Vec<PhotonicVoxel<F>>) -> Result<Self, String> {
+        let layer_size = width * height;
+        if layer_size == 0 {
+            return Err(format!("Layers of {}x{} voxels are empty", width, height));
+        }
+        if !voxels.len().is_multiple_of(layer_size) {
+            return Err(format!("{} voxels do not fill whole {}x{} layers", voxels.len(), width, height));
+        }
+        Ok(Self { width, height, depth: voxels.len() / layer_size, voxels })
+    }
+
+    /// Like [`from_voxels`](Self::from_voxels), padding the last layer with `fill`.
+    pub fn from_voxels_padded(width: usize, height: usize, mut voxels: Vec<PhotonicVoxel<F>>, fill: PhotonicVoxel<F>) -> Result<Self, String> {
+        let layer_size = width * height;
+        if layer_size > 0 {
+            voxels.resize(voxels.len().div_ceil(layer_size) * layer_size, fill);
+        }
+        Self::from_voxels(width, height, voxels)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn shape(&self) -> LatticeShape {
+        LatticeShape { width: self.width, height: self.height, depth: self.depth }
+    }
+
+    /// Total number of voxels.
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voxels.is_empty()
+    }
+
+    /// Flat index of `(x, y, z)`, or `None` outside the lattice.
+    pub fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        (x < self.width && y < self.height && z < self.depth).then(|| (z * self.height + y) * self.width + x)
+    }
+
+    /// Voxel at `(x, y, z)`, or `None` outside the lattice.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&PhotonicVoxel<F>> {
+        self.index(x, y, z).map(|i| &self.voxels[i])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut PhotonicVoxel<F>> {
+        self.index(x, y, z).map(|i| &mut self.voxels[i])
+    }
+
+    /// Replaces the voxel at `(x, y, z)`. Panics outside the lattice.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, voxel: PhotonicVoxel<F>) {
+        match self.get_mut(x, y, z) {
+            Some(slot) => *slot = voxel,
+            None => panic!("({}, {}, {}) is outside the {}x{}x{} lattice", x, y, z, self.width, self.height, self.depth),
+        }
+    }
+
+    /// Layer `z` as a row-major slice. Panics if `z >= depth`.
+    pub fn layer(&self, z: usize) -> &[PhotonicVoxel<F>] {
+        let size = self.width * self.height;
+        &self.voxels[z * size..(z + 1) * size]
+    }
+
+    pub fn layer_mut(&mut self, z: usize) -> &mut [PhotonicVoxel<F>] {
+        let size = self.width * self.height;
+        &mut self.voxels[z * size..(z + 1) * size]
+    }
+
+    /// Iterates over the layers, from `z = 0`.
+    pub fn layers(&self) -> impl Iterator<Item = &[PhotonicVoxel<F>]> {
+        // max(1): chunks() rejects a zero size; a lattice without voxels has no layers anyway
+        self.voxels.chunks((self.width * self.height).max(1))
+    }
+
+    /// Iterates over the voxels in storage order.
+    pub fn iter(&self) -> std::slice::Iter<'_, PhotonicVoxel<F>> {
+        self.voxels.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, PhotonicVoxel<F>> {
+        self.voxels.iter_mut()
+    }
+
+    /// Iterates over the voxels in storage order along with their `(x, y, z)` coordinates.
+    pub fn iter_indexed(&self) -> impl Iterator<Item = ((usize, usize, usize), &PhotonicVoxel<F>)> {
+        let (width, height) = (self.width, self.height);
+        self.voxels.iter().enumerate().map(move |(i, voxel)| ((i % width, i / width % height, i / (width * height)), voxel))
+    }
+
+    /// The voxels as a flat sequence, in storage order.
+    pub fn voxels(&self) -> &[PhotonicVoxel<F>] {
+        &self.voxels
+    }
+
+    /// The voxels as a flat sequence, in storage order, to modify in place
+    /// (the shape stays fixed).
+    pub fn voxels_mut(&mut self) -> &mut [PhotonicVoxel<F>] {
+        &mut self.voxels
+    }
+
+    pub fn into_voxels(self) -> Vec<PhotonicVoxel<F>> {
+        self.voxels
+    }
+}
+
+impl<F: Copy + PartialEq> VoxelLattice<F> {
+    /// Sparse copy of the lattice, storing only the voxels that differ from `blank`.
+    pub fn to_sparse(&self, blank: PhotonicVoxel<F>) -> SparseVoxelLattice<F> {
+        let voxels = self.voxels.iter().copied().enumerate().filter(|(_, voxel)| *voxel != blank).collect();
+        SparseVoxelLattice { width: self.width, height: self.height, depth: self.depth, blank, voxels }
+    }
+}
+
+impl VoxelLattice {
+    /// Widens every voxel to double precision.
+    pub fn to_f64(&self) -> VoxelLattice<f64> {
+        self.map(|voxel| voxel.to_f64())
+    }
+}
+
+impl VoxelLattice<f64> {
+    /// Rounds every voxel to single precision.
+    pub fn to_f32(&self) -> VoxelLattice {
+        self.map(|voxel| voxel.to_f32())
+    }
+}
+
+impl<F: Copy> VoxelLattice<F> {
+    fn map<G>(&self, f: impl Fn(&PhotonicVoxel<F>) -> PhotonicVoxel<G>) -> VoxelLattice<G> {
+        VoxelLattice { width: self.width, height: self.height, depth: self.depth, voxels: self.voxels.iter().map(f).collect() }
+    }
+}
+
+/// A [`VoxelLattice`] that stores only the voxels differing from a `blank`
+/// voxel, as a coordinate list, for mostly empty lattices (e.g. data written
+/// in a few layers only). Indices and coordinates are those of the dense
+/// lattice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseVoxelLattice<F = f32> {
+    width: usize,
+    height: usize,
+    depth: usize,
+    blank: PhotonicVoxel<F>,
+    /// Flat index and value of every stored voxel, by index.
+    voxels: Vec<(usize, PhotonicVoxel<F>)>,
+}
+
+impl<F: Copy + PartialEq> SparseVoxelLattice<F> {
+    /// A `width` x `height` x `depth` lattice of `blank` voxels, storing nothing.
+    pub fn new(width: usize, height: usize, depth: usize, blank: PhotonicVoxel<F>) -> Self {
+        Self { width, height, depth, blank, voxels: Vec::new() }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn shape(&self) -> LatticeShape {
+        LatticeShape { width: self.width, height: self.height, depth: self.depth }
+    }
+
+    /// Total number of voxels, blank ones included.
+    pub fn len(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The voxel every unstored position holds.
+    pub fn blank(&self) -> PhotonicVoxel<F> {
+        self.blank
+    }
+
+    /// Number of stored (non-blank) voxels.
+    pub fn stored_len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    /// Flat index of `(x, y, z)`, or `None` outside the lattice.
+    pub fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        (x < self.width && y < self.height && z < self.depth).then(|| (z * self.height + y) * self.width + x)
+    }
+
+    /// Voxel at `(x, y, z)`, or `None` outside the lattice.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&PhotonicVoxel<F>> {
+        let index = self.index(x, y, z)?;
+        Some(match self.voxels.binary_search_by_key(&index, |(i, _)| *i) {
+            Ok(position) => &self.voxels[position].1,
+            Err(_) => &self.blank,
+        })
+    }
+
+    /// Replaces the voxel at `(x, y, z)`; setting it to the blank voxel frees
+    /// its entry. Panics outside the lattice.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, voxel: PhotonicVoxel<F>) {
+        let Some(index) = self.index(x, y, z) else {
+            panic!("({}, {}, {}) is outside the {}x{}x{} lattice", x, y, z, self.width, self.height, self.depth);
+        };
+        match (self.voxels.binary_search_by_key(&index, |(i, _)| *i), voxel == self.blank) {
+            (Ok(position), true) => {
+                self.voxels.remove(position);
+            }
+            (Ok(position), false) => self.voxels[position].1 = voxel,
+            (Err(position), false) => self.voxels.insert(position, (index, voxel)),
+            (Err(_), true) => {}
+        }
+    }
+
+    /// Iterates over the stored voxels in storage order along with their
+    /// `(x, y, z)` coordinates.
+    pub fn iter_stored(&self) -> impl Iterator<Item = ((usize, usize, usize), &PhotonicVoxel<F>)> {
+        let (width, height) = (self.width, self.height);
+        self.voxels.iter().map(move |(i, voxel)| ((i % width, i / width % height, i / (width * height)), voxel))
+    }
+
+    /// Dense copy of the lattice.
+    pub fn to_dense(&self) -> VoxelLattice<F> {
+        let mut voxels = vec![self.blank; self.len()];
+        for &(index, voxel) in &self.voxels {
+            voxels[index] = voxel;
+        }
+        VoxelLattice { width: self.width, height: self.height, depth: self.depth, voxels }
+    }
+}
@@ -0,0 +1,273 @@
+use std::io::{self, Read, Write};
+
+/// First bytes of every volume of a split file.
+pub const VOLUME_MAGIC: [u8; 4] = *b"PVOL";
+
+/// Version of the volume layout written by this crate. Readers refuse
+/// volumes from newer versions.
+pub const VOLUME_VERSION: u16 = 1;
+
+/// Length of the BLAKE3 digests in a volume.
+pub const VOLUME_DIGEST_LEN: usize = 32;
+
+/// Magic, version, set id, sequence number, volume count, total length,
+/// offset, part length and the digest of the previous volume.
+pub const VOLUME_HEADER_LEN: usize = 4 + 2 + 16 + 4 + 4 + 8 + 8 + 8 + VOLUME_DIGEST_LEN;
+
+/// Bytes a volume adds to its part: the header and the trailing digest.
+pub const VOLUME_OVERHEAD: usize = VOLUME_HEADER_LEN + VOLUME_DIGEST_LEN;
+
+/// Header of one volume of a split file.
+///
+/// A volume is the header (all integers little-endian, in field order), its
+/// part of the file, and the BLAKE3 digest of the header and part. Every
+/// volume records the digest of the one before it, so the volumes of a set
+/// chain together and a missing, swapped or foreign volume is detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeHeader {
+    /// Random id shared by the volumes of one split.
+    pub set_id: [u8; 16],
+    /// Position of the volume in the set, from 0.
+    pub sequence: u32,
+    /// Number of volumes in the set.
+    pub volume_count: u32,
+    /// Length of the whole file.
+    pub total_len: u64,
+    /// Offset of this volume's part in the whole file.
+    pub offset: u64,
+    /// Length of this volume's part.
+    pub part_len: u64,
+    /// Digest of the previous volume, zero for the first one.
+    pub previous_digest: [u8; VOLUME_DIGEST_LEN],
+}
+
+impl VolumeHeader {
+    fn to_bytes(&self) -> [u8; VOLUME_HEADER_LEN] {
+        let mut bytes = [0u8; VOLUME_HEADER_LEN];
+        let mut position = 0;
+        for field in [
+            &VOLUME_MAGIC[..],
+            &VOLUME_VERSION.to_le_bytes(),
+            &self.set_id,
+            &self.sequence.to_le_bytes(),
+            &self.volume_count.to_le_bytes(),
+            &self.total_len.to_le_bytes(),
+            &self.offset.to_le_bytes(),
+            &self.part_len.to_le_bytes(),
+            &self.previous_digest,
+        ] {
+            bytes[position..position + field.len()].copy_from_slice(field);
+            position += field.len();
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; VOLUME_HEADER_LEN]) -> io::Result<Self> {
+        if !is_volume(bytes) {
+            return Err(invalid_data("not a volume (bad magic)"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version == 0 || version > VOLUME_VERSION {
+            return Err(invalid_data(format!("unsupported volume version {} (this build reads up to {})", version, VOLUME_VERSION)));
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4 bytes"));
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        Ok(Self {
+            set_id: bytes[6..22].try_into().expect("16 bytes"),
+            sequence: u32_at(22),
+            volume_count: u32_at(26),
+            total_len: u64_at(30),
+            offset: u64_at(38),
+            part_len: u64_at(46),
+            previous_digest: bytes[54..].try_into().expect("32 bytes"),
+        })
+    }
+}
+
+/// Splits the `len` bytes of `reader` into volumes of at most
+/// `max_volume_size` bytes each (header and digest included), writing volume
+/// `i` to the writer returned by `create(i)`. Returns the number of volumes.
+///
+/// Fails with `InvalidInput` if `max_volume_size` leaves no room for data or
+/// the split would need more than `u32::MAX` volumes, and with
+/// `UnexpectedEof` if `reader` ends before `len` bytes.
+pub fn split<R: Read, W: Write>(mut reader: R, len: u64, max_volume_size: u64, mut create: impl FnMut(u32) -> io::Result<W>) -> io::Result<u32> {
+    let capacity = max_volume_size.saturating_sub(VOLUME_OVERHEAD as u64);
+    if capacity == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("volumes must be larger than {} bytes", VOLUME_OVERHEAD)));
+    }
+    let volume_count = u32::try_from(len.div_ceil(capacity).max(1))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many volumes"))?;
+
+    let set_id: [u8; 16] = rand::random();
+    let mut previous_digest = [0u8; VOLUME_DIGEST_LEN];
+    for sequence in 0..volume_count {
+        let offset = sequence as u64 * capacity;
+        let header = VolumeHeader { set_id, sequence, volume_count, total_len: len, offset, part_len: capacity.min(len - offset), previous_digest };
+        let header_bytes = header.to_bytes();
+
+        let mut writer = HashingWriter { inner: create(sequence)?, hasher: blake3::Hasher::new() };
+        writer.write_all(&header_bytes)?;
+        let copied = io::copy(&mut (&mut reader).take(header.part_len), &mut writer)?;
+        if copied < header.part_len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("input ended after {} of {} bytes", offset + copied, len)));
+        }
+        previous_digest = *writer.hasher.finalize().as_bytes();
+        writer.inner.write_all(&previous_digest)?;
+        writer.inner.flush()?;
+    }
+    Ok(volume_count)
+}
+
+/// The volumes of a split file read back, in any order, as the original file.
+///
+/// Creating it checks that the headers form one complete set; reading checks
+/// the digest of every volume and the chain between them as it goes, failing
+/// with `InvalidData` at the first mismatch.
+pub struct JoinedVolumes<R> {
+    /// Volumes in sequence order, with their headers.
+    volumes: Vec<(VolumeHeader, R)>,
+    current: usize,
+    /// Part bytes left in the current volume.
+    remaining: u64,
+    hasher: blake3::Hasher,
+    previous_digest: [u8; VOLUME_DIGEST_LEN],
+}
+
+impl<R: Read> JoinedVolumes<R> {
+    /// Reads the header of every volume. Fails with `InvalidData` if they
+    /// come from different splits, or a volume is missing or repeated.
+    pub fn new(volumes: Vec<R>) -> io::Result<Self> {
+        let mut volumes = volumes
+            .into_iter()
+            .map(|mut volume| {
+                let mut bytes = [0u8; VOLUME_HEADER_LEN];
+                volume.read_exact(&mut bytes)?;
+                Ok((VolumeHeader::from_bytes(&bytes)?, volume))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        volumes.sort_by_key(|(header, _)| header.sequence);
+
+        let Some((first, _)) = volumes.first() else {
+            return Err(invalid_data("no volumes"));
+        };
+        let (set_id, volume_count, total_len) = (first.set_id, first.volume_count, first.total_len);
+        let mut offset = 0;
+        for (index, (header, _)) in volumes.iter().enumerate() {
+            if header.set_id != set_id || header.volume_count != volume_count || header.total_len != total_len {
+                return Err(invalid_data(format!("volume {} belongs to a different split", header.sequence)));
+            }
+            if header.sequence as usize != index {
+                return Err(invalid_data(format!("volume {} of {} is missing", index, volume_count)));
+            }
+            if header.offset != offset {
+                return Err(invalid_data(format!("volume {} does not continue volume {}", index, index.saturating_sub(1))));
+            }
+            offset += header.part_len;
+        }
+        if volumes.len() != volume_count as usize {
+            return Err(invalid_data(format!("volume {} of {} is missing", volumes.len(), volume_count)));
+        }
+        if offset != total_len {
+            return Err(invalid_data(format!("the volumes hold {} of {} bytes", offset, total_len)));
+        }
+
+        let mut joined = Self { volumes, current: 0, remaining: 0, hasher: blake3::Hasher::new(), previous_digest: [0; VOLUME_DIGEST_LEN] };
+        joined.start_volume()?;
+        Ok(joined)
+    }
+
+    /// Length of the original file.
+    pub fn total_len(&self) -> u64 {
+        self.volumes[0].0.total_len
+    }
+
+    /// Headers of the volumes, in sequence order.
+    pub fn headers(&self) -> impl Iterator<Item = &VolumeHeader> {
+        self.volumes.iter().map(|(header, _)| header)
+    }
+
+    fn start_volume(&mut self) -> io::Result<()> {
+        let (header, _) = &self.volumes[self.current];
+        if header.previous_digest != self.previous_digest {
+            return Err(invalid_data(format!("volume {} does not follow the volume before it (digest chain broken)", header.sequence)));
+        }
+        self.hasher = blake3::Hasher::new();
+        self.hasher.update(&header.to_bytes());
+        self.remaining = header.part_len;
+        Ok(())
+    }
+
+    /// Checks the trailing digest of the current volume.
+    fn finish_volume(&mut self) -> io::Result<()> {
+        let (header, volume) = &mut self.volumes[self.current];
+        let mut digest = [0u8; VOLUME_DIGEST_LEN];
+        volume.read_exact(&mut digest)?;
+        if *self.hasher.finalize().as_bytes() != digest {
+            return Err(invalid_data(format!("volume {} is corrupted (digest mismatch)", header.sequence)));
+        }
+        self.previous_digest = digest;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for JoinedVolumes<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            if self.current == self.volumes.len() {
+                return Ok(0);
+            }
+            self.finish_volume()?;
+            self.current += 1;
+            if self.current == self.volumes.len() {
+                return Ok(0);
+            }
+            self.start_volume()?;
+        }
+
+        let len = buf.len().min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.volumes[self.current].1.read(&mut buf[..len])?;
+        if n == 0 && len > 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("volume {} is truncated", self.current)));
+        }
+        self.hasher.update(&buf[..n]);
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Reassembles the volumes of a split file (in any order) into `writer`,
+/// checking every digest. Returns the length of the file.
+pub fn join<R: Read, W: Write>(volumes: Vec<R>, mut writer: W) -> io::Result<u64> {
+    let mut joined = JoinedVolumes::new(volumes)?;
+    let len = io::copy(&mut joined, &mut writer)?;
+    writer.flush()?;
+    Ok(len)
+}
+
+/// True if `bytes` start with the volume magic.
+pub fn is_volume(bytes: &[u8]) -> bool {
+    bytes.starts_with(&VOLUME_MAGIC)
+}
+
+/// Writer that hashes everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: blake3::Hasher,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
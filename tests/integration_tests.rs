@@ -1,4 +1,67 @@
 use photon_core::{encode_data, decode_data, read_ignoring_polarization, verify_obfuscation};
+use photon_core::{encode_data_with_config, decode_data_with_config, decode_soft, CodecConfig, PhotonicVoxel};
+use photon_core::{encode_with_scheme, decode_with_scheme, decode_soft_with_scheme, ModulationScheme};
+use photon_core::{VoxelEncoder, VoxelDecoder, decode_data_with_confidence};
+use photon_core::{insert_sync_markers, strip_sync_markers, FrameConfig};
+use photon_core::{insert_pilots, decode_with_pilots, PilotConfig};
+use photon_core::{scramble, descramble};
+use photon_core::{rll_encode, rll_decode};
+use photon_core::{encode_with_header, decode_auto, read_header};
+use photon_core::{encode_interleaved, decode_interleaved, Interleaver};
+use photon_core::QamScheme;
+use photon_core::{decode_data_noisy, decode_data_noisy_with_rng, run_ber_simulation_with_rng, NoiseParams, NoiseProfile};
+use photon_core::{decode_blind, LearnedLevels};
+use photon_core::{decode_data_with_report, MARGIN_HISTOGRAM_BINS};
+use photon_core::{encode_stream, decode_stream, VoxelReader};
+use photon_core::{encode_multiplexed, decode_multiplexed_with_config};
+use photon_core::{encode_pages, decode_pages, read_page, PageConfig};
+use photon_core::{simulate_crosstalk, simulate_psf, PsfKernel, VoxelLattice};
+use photon_core::{run_ber_by_depth, simulate_aberration, simulate_attenuation, simulate_spectral_crosstalk, spectral_overlap, AberratedPsf};
+use photon_core::{simulate_crosstalk_with_config, Connectivity, CrosstalkConfig};
+use photon_core::{simulate_write_with_rng, MaterialResponse, WriteParams};
+use photon_core::{min_safe_pitch, run_ber_by_write_pitch, simulate_write_interference_with_rng, WriteInterference};
+use photon_core::{endurance_limit, run_endurance, simulate_rewrite_cycles_with_rng, FatigueModel};
+use photon_core::{Defect, DefectConfig, DefectMap};
+use photon_core::{simulate_birefringence_drift_with_rng, simulate_polarization_drift_with_rng, BirefringenceDrift};
+use photon_core::simulate_scattering;
+use photon_core::{optimize_geometry, optimize_geometry_with_limits, GeometryLimits, OpticsConfig};
+use photon_core::{simulate_position_jitter_with_rng, PositionJitter};
+use photon_core::{run_ber_by_focus_error, simulate_focus_error, FocusError};
+use photon_core::{equalize, run_ber_with_equalization};
+use photon_core::{compensate_sensor_response, simulate_sensor_response, SensorResponse};
+use photon_core::{run_ber_by_age, simulate_aging, simulate_birefringent_aging, AgingParams};
+use photon_core::channel::{Attenuation, Scattering};
+use photon_core::{ChannelPipeline, ChannelStage};
+use photon_core::Material;
+use photon_core::{optimal_read_power, run_ber_by_read_power, simulate_read_bleaching, ReadPower};
+use photon_core::{measure_signal_quality, run_ber_simulation_with_profile, DimensionErrors, NoiseDistribution, SignalQuality};
+use photon_core::{constellation_to_csv, constellation_to_json, record_constellation};
+use photon_core::{estimate_mutual_information, MutualInformation};
+use photon_core::{grid_to_csv, run_ber_grid, BerGrid};
+use photon_core::{run_ber_simulation_adaptive, AdaptiveConfig, AdaptiveResult};
+use photon_core::{estimate_ber_importance, ImportanceResult};
+use photon_core::DetectorModel;
+use photon_core::{run_ber_by_photon_budget, PhotonBudget};
+use photon_core::{LatticeGeometry, VoxelAddress};
+use photon_core::PhotonicVoxel64;
+use photon_core::{pack_voxels, unpack_voxels, QuantizedVoxel};
+use photon_core::{JonesMatrix, JonesVoxel};
+use photon_core::{encode_birefringent, decode_birefringent, BirefringentConfig, BirefringentVoxel};
+use photon_core::{StokesDetector, StokesVector};
+use photon_core::container::{self, Container, FileMetadata};
+use photon_core::{voxel_from_bytes, voxel_to_bytes, voxels_from_bytes, voxels_to_bytes, write_voxel, VOXEL_RECORD_LEN};
+use photon_core::{LatticeShape, SparseVoxelLattice};
+use photon_core::{add_error_correction_with, correct_errors_with, recover_error_correction_with, run_ber_simulation_with_ecc, run_coded_ber_simulation, run_ber_simulation_with_trials, SimulationConfig, EccConfig, EccScheme};
+use photon_core::{correct_errors_and_erasures_with, erasures_from_confidence, recover_error_correction_with_erasures};
+use photon_core::ecc::{EccDecodingReader, EccEncodingReader, EccEncodingWriter};
+use photon_core::{FountainDecoder, FountainEncoder, FountainSymbol};
+use photon_core::LayerParity;
+use photon_core::{ecc, estimate_channel, predicted_residual_ber};
+use photon_core::{decode_soft_with_profile, decode_voxels_with_fec, decode_with_fec, FecReport};
+use photon_core::archive::{self, Archive};
+use photon_core::export;
+use photon_core::volume::{self, JoinedVolumes};
+use rand::{rngs::StdRng, SeedableRng};
 
 #[test]
 fn test_round_trip_noiseless() {
@@ -46,3 +109,3241 @@ fn test_empty_input() {
     let decoded = decode_data(&voxels, false);
     assert!(decoded.is_empty());
 }
+
+#[test]
+fn test_round_trip_with_custom_levels() {
+    // 16 levels per dimension: 4 bits each, 16 bits per voxel.
+    let config = CodecConfig {
+        intensity_levels: 16,
+        polarization_levels: 16,
+        phase_levels: 16,
+        wavelengths: (0..16).map(|i| 400.0 + 25.0 * i as f32).collect(),
+        gray_code: false,
+    };
+    assert_eq!(config.bits_per_voxel(), 16);
+
+    let data = b"Denser crystal";
+    let voxels = encode_data_with_config(data, &config);
+    assert_eq!(voxels.len(), data.len() / 2);
+    assert_eq!(decode_data_with_config(&voxels, false, &config), data);
+
+    // 8 levels per dimension with 4 colors: 11 bits per voxel, not byte aligned.
+    let config = CodecConfig { intensity_levels: 8, polarization_levels: 8, phase_levels: 8, ..CodecConfig::default() };
+    let voxels = encode_data_with_config(data, &config);
+    let decoded = decode_data_with_config(&voxels, false, &config);
+    assert!(decoded.starts_with(data), "Decoded {:?} vs Original {:?}", decoded, data);
+
+    assert!(CodecConfig { intensity_levels: 3, ..CodecConfig::default() }.validate().is_err());
+}
+
+#[test]
+fn test_gray_code_single_bit_per_level_error() {
+    let config = CodecConfig { gray_code: true, ..CodecConfig::default() };
+    let data: Vec<u8> = (0..=255).collect();
+    let voxels = encode_data_with_config(&data, &config);
+    assert_eq!(decode_data_with_config(&voxels, false, &config), data);
+
+    // Misreading intensity or wavelength as the neighbouring level flips exactly one bit.
+    for (&byte, voxel) in data.iter().zip(&voxels) {
+        let mut brighter = *voxel;
+        brighter.intensity += 0.25;
+        if brighter.intensity <= 1.0 {
+            let misread = decode_data_with_config(&[brighter], false, &config)[0];
+            assert_eq!((misread ^ byte).count_ones(), 1, "byte {:#04x}", byte);
+        }
+
+        let mut redder = *voxel;
+        redder.wavelength = match voxel.wavelength as u32 { 450 => 532.0, 532 => 650.0, 650 => 800.0, _ => continue };
+        let misread = decode_data_with_config(&[redder], false, &config)[0];
+        assert_eq!((misread ^ byte).count_ones(), 1, "byte {:#04x}", byte);
+    }
+}
+
+#[test]
+fn test_soft_decode_matches_hard_decode() {
+    let data: Vec<u8> = (0..=255).collect();
+    let voxels = encode_data(&data);
+    let soft = decode_soft(&voxels);
+    assert_eq!(soft.len(), data.len());
+    for (s, &byte) in soft.iter().zip(&data) {
+        assert_eq!(s.hard_decision(), byte);
+        assert!(s.min_confidence() > 1.0, "clean voxels should decode confidently");
+    }
+
+    // A voxel halfway between two intensity levels has an undecided bit.
+    let mut ambiguous = encode_data(&[0b0000_0000])[0];
+    ambiguous.intensity = 0.375; // between level 0 (0.25) and level 1 (0.5)
+    let soft = decode_soft(&[ambiguous])[0];
+    assert!(soft.llrs[0].abs() < 1e-3);
+    assert!(soft.llrs[1] > 1.0);
+}
+
+/// On-off keying: one bit per voxel, carried by intensity only.
+struct OnOffKeying;
+
+impl ModulationScheme for OnOffKeying {
+    fn bits_per_voxel(&self) -> usize {
+        1
+    }
+
+    fn modulate(&self, symbol: u32) -> PhotonicVoxel {
+        PhotonicVoxel::new(if symbol & 1 == 1 { 1.0 } else { 0.1 }, 0.0, 0.0, 532.0)
+    }
+
+    fn demodulate(&self, voxel: &PhotonicVoxel) -> u32 {
+        (voxel.intensity > 0.55) as u32
+    }
+}
+
+#[test]
+fn test_custom_modulation_scheme() {
+    let data = b"OOK";
+    let voxels = encode_with_scheme(data, &OnOffKeying);
+    assert_eq!(voxels.len(), data.len() * 8);
+    assert_eq!(decode_with_scheme(&voxels, &OnOffKeying), data);
+
+    // The default soft output is the hard decision with unit confidence.
+    let soft = decode_soft_with_scheme(&voxels, &OnOffKeying);
+    assert_eq!(soft[0].hard_decision(), b'O');
+    assert_eq!(soft[0].min_confidence(), 1.0);
+
+    // The built-in scheme goes through the same generic path.
+    let config = CodecConfig::default();
+    assert_eq!(encode_with_scheme(data, &config), encode_data(data));
+}
+
+#[test]
+fn test_streaming_matches_batch() {
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 31 % 251) as u8).collect();
+
+    let streamed: Vec<PhotonicVoxel> = VoxelEncoder::new(data.as_slice()).map(|v| v.unwrap()).collect();
+    assert_eq!(streamed, encode_data(&data));
+    let decoded: Vec<u8> = VoxelDecoder::new(streamed, false).collect();
+    assert_eq!(decoded, data);
+
+    // Non byte-aligned symbols must be packed identically across read buffer boundaries.
+    let config = CodecConfig { intensity_levels: 8, polarization_levels: 8, phase_levels: 8, ..CodecConfig::default() };
+    let streamed: Vec<PhotonicVoxel> = VoxelEncoder::with_config(data.as_slice(), config.clone()).map(|v| v.unwrap()).collect();
+    assert_eq!(streamed, encode_data_with_config(&data, &config));
+    let decoded: Vec<u8> = VoxelDecoder::with_config(streamed.iter().copied(), config.clone(), false).collect();
+    assert_eq!(decoded, decode_data_with_config(&streamed, false, &config));
+}
+
+#[test]
+fn test_parallel_matches_serial() {
+    // Large enough for the rayon path with the `parallel` feature; 9-bit
+    // symbols and an odd length leave a partial final symbol and uneven chunks.
+    let data: Vec<u8> = (0..3 * 16 * 1024 + 13u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+    let config = CodecConfig { intensity_levels: 8, ..CodecConfig::default() };
+    assert_eq!(config.bits_per_voxel(), 9);
+
+    let voxels = encode_data_with_config(&data, &config);
+    assert_eq!(voxels, encode_with_scheme(&data, &config));
+    let streamed: Vec<PhotonicVoxel> = VoxelEncoder::with_config(data.as_slice(), config.clone()).map(|v| v.unwrap()).collect();
+    assert_eq!(voxels, streamed);
+
+    let decoded = decode_data_with_config(&voxels, false, &config);
+    assert_eq!(decoded, decode_with_scheme(&voxels, &config));
+    assert_eq!(&decoded[..data.len()], &data[..]);
+}
+
+#[test]
+fn test_custom_wavelength_palette() {
+    // Lab lasers: 405/488/561/640 nm, given out of order.
+    let config = CodecConfig { wavelengths: vec![640.0, 405.0, 561.0, 488.0], ..CodecConfig::default() };
+    assert_eq!(config.wavelength_decision_boundaries(), vec![446.5, 524.5, 600.5]);
+
+    let data: Vec<u8> = (0..=255).collect();
+    let mut voxels = encode_data_with_config(&data, &config);
+    // Drift towards (but not across) the derived boundaries still decodes.
+    for v in voxels.iter_mut() {
+        v.wavelength += if v.wavelength < 500.0 { 36.0 } else { -36.0 };
+    }
+    assert_eq!(decode_data_with_config(&voxels, false, &config), data);
+
+    // Six colors: only the first four carry data (2 bits), the rest are unused.
+    let config = CodecConfig { wavelengths: vec![405.0, 488.0, 561.0, 640.0, 700.0, 780.0], ..CodecConfig::default() };
+    assert!(config.validate().is_ok());
+    assert_eq!(config.bits_per_voxel(), 8);
+    let voxels = encode_data_with_config(&data, &config);
+    assert!(voxels.iter().all(|v| v.wavelength < 700.0));
+    assert_eq!(decode_data_with_config(&voxels, false, &config), data);
+
+    assert!(CodecConfig { wavelengths: vec![532.0, 532.0], ..CodecConfig::default() }.validate().is_err());
+}
+
+#[test]
+fn test_bit_loading_favours_quiet_dimensions() {
+    // Intensity is very noisy, phase is quiet.
+    let variances = [0.05f32.powi(2), 0.02f32.powi(2), 0.005f32.powi(2), 5.0f32.powi(2)];
+    let config = CodecConfig::from_noise_variances(variances, 12).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.bits_per_voxel(), 12);
+    assert!(config.phase_levels > config.intensity_levels);
+    assert!(config.polarization_levels > config.intensity_levels);
+
+    let data = b"bit-loaded payload";
+    let voxels = encode_data_with_config(data, &config);
+    assert!(decode_data_with_config(&voxels, false, &config).starts_with(data));
+
+    assert!(CodecConfig::from_noise_variances(variances, 0).is_err());
+}
+
+#[test]
+fn test_decode_confidence_flags_uncertain_voxels() {
+    let config = CodecConfig::default();
+    let data = b"margin";
+    let mut voxels = encode_data(data);
+    voxels[2].intensity += 0.12; // just short of the 0.125 decision boundary
+
+    let (decoded, confidence) = decode_data_with_confidence(&voxels, false, &config);
+    assert_eq!(decoded, data);
+    assert_eq!(confidence.len(), voxels.len());
+
+    assert!((confidence[0].intensity - 0.25).abs() < 1e-6);
+    assert!((confidence[0].min_relative - 1.0).abs() < 1e-6);
+    assert!(confidence[2].intensity < 0.02);
+    let uncertain: Vec<usize> = (0..voxels.len()).filter(|&i| confidence[i].is_uncertain(0.2)).collect();
+    assert_eq!(uncertain, vec![2]);
+}
+
+#[test]
+fn test_sync_markers_resynchronize_after_lost_voxels() {
+    let data: Vec<u8> = (0..100).collect();
+    let voxels = encode_data(&data);
+    let config = FrameConfig { payload_voxels: 16, ..FrameConfig::default() };
+
+    let framed = insert_sync_markers(&voxels, &config);
+    assert_eq!(framed.len(), voxels.len() + 7 * 3);
+    let clean = strip_sync_markers(&framed, &config);
+    assert!(clean.damaged_frames.is_empty());
+    assert_eq!(clean.voxels, voxels);
+
+    // Lose 5 voxels inside frame 1: only that frame is affected.
+    let mut damaged = framed.clone();
+    damaged.drain(25..30);
+    let deframed = strip_sync_markers(&damaged, &config);
+    assert_eq!(deframed.damaged_frames, vec![1]);
+    let decoded = decode_data(&deframed.voxels, false);
+    assert_eq!(decoded.len(), data.len());
+    assert_eq!(&decoded[..16], &data[..16]);
+    assert_eq!(&decoded[32..], &data[32..]);
+
+    // Destroying a marker entirely is also tolerated.
+    let mut no_marker = framed.clone();
+    no_marker[19].intensity = 0.75;
+    let deframed = strip_sync_markers(&no_marker, &config);
+    assert!(deframed.damaged_frames.is_empty());
+    assert_eq!(deframed.voxels, voxels);
+}
+
+#[test]
+fn test_pilots_compensate_gain_drift_and_rotation() {
+    let codec = CodecConfig::default();
+    let data: Vec<u8> = (0..2000u32).map(|i| (i * 7 % 256) as u8).collect();
+    let config = PilotConfig::default();
+    let written = insert_pilots(&encode_data(&data), &config, &codec);
+
+    // Intensity gain drifting from 0.8 to 0.7 along the stream, plus a constant
+    // 0.15 rad polarization rotation: enough to break a plain decoder.
+    let n = written.len() as f32;
+    let received: Vec<PhotonicVoxel> = written
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let mut r = *v;
+            r.intensity *= 0.8 - 0.1 * i as f32 / n;
+            r.polarization += 0.15;
+            r
+        })
+        .collect();
+
+    let uncompensated: Vec<PhotonicVoxel> = received
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % (config.interval + 1) != 0)
+        .map(|(_, v)| *v)
+        .collect();
+    assert_ne!(decode_data(&uncompensated, false), data);
+    assert_eq!(decode_with_pilots(&received, &config, &codec), data);
+}
+
+#[test]
+fn test_scrambler_breaks_up_runs_and_resynchronizes() {
+    let data = vec![0xFFu8; 4096];
+    let seed = 0x1234;
+
+    // Unscrambled, every voxel sits at maximum intensity
+    assert!(encode_data(&data).iter().all(|v| v.intensity == 1.0));
+
+    let scrambled = scramble(&data, seed);
+    let voxels = encode_data(&scrambled);
+    let bright = voxels.iter().filter(|v| v.intensity == 1.0).count();
+    assert!(bright < voxels.len() / 2, "{} of {} voxels still at max intensity", bright, voxels.len());
+    assert_eq!(descramble(&decode_data(&voxels, false), seed), data);
+
+    // A corrupted byte only garbles the next few bytes
+    let mut corrupted = scrambled.clone();
+    corrupted[100] ^= 0x5A;
+    let recovered = descramble(&corrupted, seed);
+    assert_ne!(recovered[100..104], data[100..104]);
+    assert_eq!(recovered[..100], data[..100]);
+    assert_eq!(recovered[104..], data[104..]);
+}
+
+#[test]
+fn test_scrambler_seed_is_recorded_in_headers() {
+    let data = vec![0xFFu8; 3000];
+    let codec = CodecConfig::default();
+    let seed = 0xBEEF;
+
+    // Stream header: decoding descrambles without being given the seed
+    let mut header = photon_core::StreamHeader::new(&codec);
+    header.data_len = Some(data.len() as u64);
+    header.scramble = Some(seed);
+    let mut voxels = header.to_voxels();
+    voxels.extend(encode_data(&scramble(&data, seed)));
+    assert_eq!(read_header(&voxels, false).unwrap().unwrap().0, header);
+    assert_eq!(decode_auto(&voxels, false).unwrap(), data);
+
+    // Container: the seed travels in the header, through ECC, repair and ranged reads
+    let mut container_header = photon_core::ContainerHeader::new(&codec, 0);
+    container_header.ecc = Some(EccConfig::default());
+    container_header.scramble = Some(seed);
+    let encoded = Container::encode_with_header(&data, container_header);
+    assert!(encoded.voxels.iter().filter(|v| v.intensity == 1.0).count() < encoded.voxels.len() / 2);
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 1000, 50).unwrap(), &data[1000..1050]);
+    let mut read = container::read(&file[..]).unwrap();
+    assert_eq!(read.header.scramble, Some(seed));
+    read.voxels[3] = PhotonicVoxel::new(0.0, 0.0, 0.0, 405.0);
+    assert_eq!(read.decode(false).unwrap(), data);
+    assert!(read.repair().is_complete());
+    assert_eq!(read.voxels, encoded.voxels);
+}
+
+#[test]
+fn test_rll_bounds_bright_runs() {
+    let codec = CodecConfig::default();
+    let mut data = vec![0xFFu8; 256];
+    data.extend((0..256u32).map(|i| (i * 37 % 256) as u8));
+    let voxels = encode_data(&data);
+
+    let (coded, stats) = rll_encode(&voxels, 8, &codec);
+    assert_eq!(stats.data_voxels, voxels.len());
+    assert_eq!(coded.len(), voxels.len() + stats.stuffed_voxels);
+    assert!(stats.overhead() > 0.0 && stats.overhead() <= 1.0 / 8.0);
+
+    let longest_run = coded
+        .iter()
+        .fold((0, 0), |(run, longest), v| {
+            let run = if v.intensity == 1.0 { run + 1 } else { 0 };
+            (run, longest.max(run))
+        })
+        .1;
+    assert_eq!(longest_run, 8);
+
+    assert_eq!(rll_decode(&coded, 8, &codec), voxels);
+    assert_eq!(decode_data(&rll_decode(&coded, 8, &codec), false), data);
+}
+
+#[test]
+fn test_header_selects_codec_automatically() {
+    let data: Vec<u8> = (0..500u32).map(|i| (i * 13 % 256) as u8).collect();
+    let config = CodecConfig {
+        intensity_levels: 8,
+        polarization_levels: 8,
+        phase_levels: 4,
+        wavelengths: vec![405.0, 488.0, 561.0, 640.0],
+        gray_code: true,
+    };
+
+    let voxels = encode_with_header(&data, &config);
+    let (header, len) = read_header(&voxels, false).unwrap().unwrap();
+    assert_eq!(header, photon_core::StreamHeader::new(&config));
+    assert_eq!(voxels.len() - len, encode_data_with_config(&data, &config).len());
+
+    // No config needed on the decode side
+    assert_eq!(decode_data(&voxels, false)[..data.len()], data);
+
+    // Headerless streams still decode with the default codec
+    assert_eq!(read_header(&encode_data(&data), false).unwrap(), None);
+    assert_eq!(decode_auto(&encode_data(&data), false).unwrap(), data);
+
+    // Headers from a newer codec are rejected rather than misread
+    let mut future = photon_core::StreamHeader::new(&config);
+    future.version = photon_core::CODEC_VERSION + 1;
+    assert!(decode_auto(&future.to_voxels(), false).is_err());
+}
+
+#[test]
+fn test_header_detection_ignores_dark_data_and_records_constellations() {
+    use photon_core::{CustomConstellation, SchemeDescriptor, StreamHeader};
+    use std::f32::consts::PI;
+
+    // Inner 16-QAM points are dark; pick data whose first voxels also sit at
+    // the diagonal polarizations of the header preamble
+    let qam = QamScheme::new(16, &CodecConfig::default());
+    let dark_at = |angle: f32| {
+        (0..=255u8)
+            .find(|&byte| {
+                let v = qam.modulate(byte as u32);
+                v.intensity < 0.125 && (v.polarization - angle).abs() < 0.1
+            })
+            .unwrap()
+    };
+    let mut data = vec![dark_at(PI / 4.0), dark_at(3.0 * PI / 4.0), dark_at(PI / 4.0)];
+    data.extend((0..200u32).map(|i| (i * 29 % 256) as u8));
+    let headerless = encode_with_scheme(&data, &qam);
+    assert_eq!(read_header(&headerless, false).unwrap(), None);
+    assert_eq!(decode_data(&headerless, false), decode_data_with_config(&headerless, false, &CodecConfig::default()));
+
+    // Recorded QAM and custom constellations are selected automatically
+    let header = StreamHeader::with_scheme(SchemeDescriptor::Qam(qam.clone()));
+    let voxels = header.encode(&data);
+    assert_eq!(read_header(&voxels, false).unwrap().unwrap().0, header);
+    assert_eq!(decode_data(&voxels, false), data);
+    let points = (0..4).map(|i| PhotonicVoxel::new(0.25 + 0.25 * i as f32, i as f32 * PI / 4.0, 0.0, 532.0)).collect();
+    let mut header = StreamHeader::with_scheme(SchemeDescriptor::Constellation(CustomConstellation::new(points)));
+    header.data_len = Some(data.len() as u64);
+    let voxels = header.encode(&data);
+    assert_eq!(read_header(&voxels, false).unwrap().unwrap().0, header);
+    assert_eq!(decode_auto(&voxels, false).unwrap(), data);
+
+    // A damaged header fails its checksum: decode_auto reports it, decode_data
+    // falls back to headerless decoding instead of panicking
+    let mut damaged = voxels.clone();
+    damaged[8] = encode_data(&[0x42])[0];
+    assert!(decode_auto(&damaged, false).unwrap_err().contains("checksum"));
+    assert_eq!(decode_data(&damaged, false), decode_data_with_config(&damaged, false, &CodecConfig::default()));
+}
+
+#[test]
+fn test_forged_header_length_is_rejected() {
+    // A header with a valid checksum but a data length far beyond the stream
+    // must fail cleanly instead of overflowing or allocating it
+    let codec = CodecConfig::default();
+    let data = b"short payload".to_vec();
+    for ecc in [Some(EccConfig::new(4, 2)), None] {
+        let mut header = photon_core::StreamHeader::new(&codec);
+        header.ecc = ecc;
+        header.data_len = Some(u64::MAX / 2);
+        let voxels = header.encode(&data);
+        assert_eq!(read_header(&voxels, false).unwrap().unwrap().0, header);
+        assert!(decode_auto(&voxels, false).unwrap_err().contains("bytes of data"));
+        assert_eq!(decode_data(&voxels, false).len(), header.scheme.decode(&voxels[header.to_voxels().len()..], false).len());
+    }
+}
+
+#[test]
+fn test_interleaver_spreads_burst_across_blocks() {
+    let codec = CodecConfig::default();
+    // Single-byte shards: every 14 consecutive bytes are one codeword
+    let ecc = EccConfig { block_shard_len: Some(1), ..EccConfig::default() };
+    let interleaver = Interleaver::for_ecc(8, &ecc).unwrap();
+    assert_eq!(interleaver, Interleaver::new(8, 14));
+    assert!(Interleaver::for_ecc(8, &EccConfig { block_shard_len: None, ..ecc }).is_err());
+    // Three full interleaver blocks plus a partial one
+    let data: Vec<u8> = (0..250u32).map(|i| (i * 29 % 256) as u8).collect();
+    let encoded = add_error_correction_with(&data, &ecc);
+    assert_eq!(interleaver.deinterleave(&interleaver.interleave(&encoded)), encoded);
+
+    // A scratch wipes out 8 consecutive voxels, one byte each
+    let scratch = |voxels: &mut Vec<PhotonicVoxel>| {
+        for v in &mut voxels[40..48] {
+            v.intensity = 0.0;
+            v.wavelength = 0.0;
+        }
+    };
+
+    // In order, the burst puts 6 errors in one codeword: beyond its 2
+    let mut plain = encode_data_with_config(&encoded, &codec);
+    scratch(&mut plain);
+    assert!(ecc.recover(&decode_data_with_config(&plain, false, &codec), data.len()).is_err());
+
+    // Interleaved, every codeword sees at most one of them
+    let mut voxels = encode_interleaved(&encoded, &interleaver, &codec);
+    scratch(&mut voxels);
+    let received = decode_interleaved(&voxels, false, encoded.len(), &interleaver, &codec);
+    for (codeword, (got, want)) in received.chunks(14).zip(encoded.chunks(14)).enumerate() {
+        let errors = got.iter().zip(want).filter(|(a, b)| a != b).count();
+        assert!(errors <= 1, "codeword {} has {} damaged bytes", codeword, errors);
+    }
+    assert_eq!(ecc.recover(&received, data.len()).unwrap(), data);
+}
+
+#[test]
+fn test_qam_constellation_round_trip_and_min_distance() {
+    let scheme = QamScheme::new(16, &CodecConfig::default());
+    scheme.validate().unwrap();
+    assert_eq!(scheme.bits_per_voxel(), 8);
+    assert!(QamScheme::new(8, &CodecConfig::default()).validate().is_err());
+
+    // 16 distinct points, outermost at full intensity, none dark
+    let points = scheme.points();
+    assert_eq!(points.len(), 16);
+    let max = points.iter().map(|p| p.0).fold(0.0f32, f32::max);
+    assert!((max - 1.0).abs() < 1e-5);
+    assert!(points.iter().all(|p| p.0 > 0.1));
+
+    let data: Vec<u8> = (0..=255).collect();
+    let voxels = encode_with_scheme(&data, &scheme);
+    assert_eq!(decode_with_scheme(&voxels, &scheme), data);
+
+    // A small field perturbation still decodes to the nearest point
+    let perturbed: Vec<PhotonicVoxel> = voxels
+        .iter()
+        .map(|v| {
+            let mut p = *v;
+            p.phase += 0.05;
+            p.intensity *= 1.05;
+            p
+        })
+        .collect();
+    assert_eq!(decode_with_scheme(&perturbed, &scheme), data);
+}
+
+#[test]
+fn test_decode_with_explicit_noise_amplitudes() {
+    let data: Vec<u8> = (0..2000u32).map(|i| (i * 11 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+
+    assert_eq!(decode_data_noisy(&voxels, NoiseParams::none()), data);
+    // The default amplitudes are those of `decode_data(voxels, true)`, within the decision margins
+    assert_eq!(decode_data_noisy(&voxels, NoiseParams::default()), data);
+
+    // Noise on a single dimension only corrupts the bits it carries (intensity: bits 0-1)
+    let noise = NoiseParams { intensity: 0.4, ..NoiseParams::none() };
+    let decoded = decode_data_noisy(&voxels, noise);
+    assert_ne!(decoded, data);
+    assert!(decoded.iter().zip(&data).all(|(a, b)| (a ^ b) & 0b1111_1100 == 0));
+}
+
+#[test]
+fn test_seeded_runs_replay_exactly() {
+    let data: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+    let noise = NoiseParams::uniform(0.25);
+
+    let a = decode_data_noisy_with_rng(&voxels, noise, &mut StdRng::seed_from_u64(7));
+    let b = decode_data_noisy_with_rng(&voxels, noise, &mut StdRng::seed_from_u64(7));
+    let c = decode_data_noisy_with_rng(&voxels, noise, &mut StdRng::seed_from_u64(8));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    let config = CodecConfig::default();
+    let run = |seed| run_ber_simulation_with_rng(2000, 5, 0.3, &config, &mut StdRng::seed_from_u64(seed));
+    let errors = |results: Vec<photon_core::SimulationResult>| results.iter().map(|r| r.error_bits).collect::<Vec<_>>();
+    assert_eq!(errors(run(1)), errors(run(1)));
+}
+
+#[test]
+fn test_noise_profile_per_dimension_sigmas() {
+    let data: Vec<u8> = (0..4000u32).map(|i| (i * 23 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+
+    // Gaussian phase noise only touches the phase bits (4-5)
+    let profile = NoiseProfile::gaussian(0.0, 0.0, 0.6, 0.0);
+    let decoded = decode_data_noisy(&voxels, profile);
+    assert_ne!(decoded, data);
+    assert!(decoded.iter().zip(&data).all(|(a, b)| (a ^ b) & 0b1100_1111 == 0));
+
+    // Uniform amplitudes convert to the matching standard deviations
+    let from_params = NoiseProfile::from(NoiseParams::uniform(0.3));
+    assert!((from_params.intensity - 0.3 / 3f32.sqrt()).abs() < 1e-6);
+    assert!((from_params.wavelength_nm - 30.0 / 3f32.sqrt()).abs() < 1e-4);
+
+    // The profile drives bit-loading: the noisy phase gets no bits
+    let config = CodecConfig::from_noise_profile(&profile, 8).unwrap();
+    assert_eq!(config.phase_levels, 1);
+    assert_eq!(config.bits_per_voxel(), 8);
+}
+
+#[test]
+fn test_blind_decoder_tracks_drifted_levels() {
+    let codec = CodecConfig::default();
+    let data: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+    let voxels = encode_data(&data);
+
+    // Systematic offsets: intensity gain loss, polarization rotation, phase
+    // offset and a red-shifted readout laser
+    let received: Vec<PhotonicVoxel> = voxels
+        .iter()
+        .map(|v| {
+            let mut r = *v;
+            r.intensity = 0.75 * r.intensity + 0.02;
+            r.polarization += 0.2;
+            r.phase += 0.4;
+            r.wavelength += 30.0;
+            r
+        })
+        .collect();
+    assert_ne!(decode_data(&received, false), data);
+
+    let levels = LearnedLevels::estimate(&received, &codec);
+    assert!((levels.intensity[3] - 0.77).abs() < 0.01);
+    assert!((levels.wavelength[0] - 562.0).abs() < 1.0);
+    assert_eq!(decode_blind(&received, &codec), data);
+}
+
+#[test]
+fn test_decode_report_summarizes_margins() {
+    let config = CodecConfig::default();
+    let data = b"diagnostics";
+    let mut voxels = encode_data(data);
+    voxels[3].intensity -= 0.12; // just short of a decision boundary
+    voxels[5].wavelength = 592.0; // 1 nm from the 532/650 boundary
+
+    let (decoded, report) = decode_data_with_report(&voxels, false, &config, 0.1);
+    assert_eq!(decoded, data);
+    assert_eq!(report.voxels, voxels.len());
+    assert_eq!(report.near_boundary, 2);
+    assert_eq!(report.intensity.near_boundary, 1);
+    assert_eq!(report.wavelength.near_boundary, 1);
+    assert_eq!(report.phase.near_boundary, 0);
+
+    // Clean voxels sit on their levels: top bin; the nudged one in the bottom bin
+    assert_eq!(report.intensity.margin_histogram[0], 1);
+    assert_eq!(report.intensity.margin_histogram[MARGIN_HISTOGRAM_BINS - 1], voxels.len() - 1);
+    assert_eq!(report.polarization.margin_histogram.iter().sum::<usize>(), voxels.len());
+
+    let min = voxels.iter().map(|v| v.intensity).fold(f32::INFINITY, f32::min);
+    assert_eq!(report.intensity.min, min);
+    assert_eq!(report.wavelength.max, 650.0);
+    assert!((report.near_boundary_fraction() - 2.0 / 11.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_stream_round_trip_through_io() {
+    let config = CodecConfig { wavelengths: vec![405.0, 488.0, 561.0, 640.0, 700.0, 780.0, 850.0, 940.0], ..CodecConfig::default() };
+    let data: Vec<u8> = (0..3000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+
+    let mut records = Vec::new();
+    let written = encode_stream(&data[..], &mut records, &config).unwrap();
+    let voxels: Vec<PhotonicVoxel> = VoxelReader::new(&records[..]).map(Result::unwrap).collect();
+    assert_eq!(written, voxels.len());
+    assert_eq!(voxels, encode_data_with_config(&data, &config));
+
+    let mut decoded = Vec::new();
+    let len = decode_stream(&records[..], &mut decoded, &config).unwrap();
+    assert_eq!(len, decoded.len());
+    assert_eq!(&decoded[..data.len()], &data[..]);
+
+    // A stream cut inside a record is an error, not silent data loss
+    let truncated = &records[..records.len() - 3];
+    let err = decode_stream(truncated, &mut Vec::new(), &config).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_wavelength_multiplexing_separates_streams() {
+    let left = b"left channel".as_slice();
+    let right: Vec<u8> = (0..=255).collect();
+    let notes = b"notes".as_slice();
+
+    let voxels = encode_multiplexed(&[left, &right, notes]);
+    // 6 bits per voxel per stream: the wavelength only selects the channel
+    let per_stream = |len: usize| (len * 8).div_ceil(6);
+    assert_eq!(voxels.len(), per_stream(left.len()) + per_stream(right.len()) + per_stream(notes.len()));
+    assert_eq!(voxels[0].wavelength, 532.0);
+    assert_eq!(voxels[1].wavelength, 650.0);
+    assert_eq!(voxels[2].wavelength, 450.0);
+
+    // Readout at a shifted wavelength still lands on the right channel
+    let received: Vec<PhotonicVoxel> = voxels.iter().map(|v| PhotonicVoxel { wavelength: v.wavelength + 20.0, ..*v }).collect();
+    let streams = decode_multiplexed_with_config(&received, 3, false, &CodecConfig::default());
+    assert_eq!(streams.len(), 3);
+    assert_eq!(&streams[0][..left.len()], left);
+    assert_eq!(&streams[1][..right.len()], &right[..]);
+    assert_eq!(&streams[2][..notes.len()], notes);
+}
+
+#[test]
+fn test_holographic_pages_detect_damaged_page() {
+    let config = PageConfig { width: 32, height: 32, ..PageConfig::default() };
+    assert_eq!(config.capacity(), 32 * 32 - 12);
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+
+    let mut pages = encode_pages(&data, &config);
+    assert_eq!(pages.len(), 5);
+    assert!(pages.iter().all(|p| p.voxels.len() == 32 * 32));
+    assert_eq!(read_page(&pages[4], &config, false).data.len(), 5000 - 4 * 1012);
+
+    let (decoded, bad_pages) = decode_pages(&pages, &config, true, 2);
+    assert_eq!(decoded, data);
+    assert!(bad_pages.is_empty());
+
+    // A scratch across a row of page 2 fails its CRC; the others are intact
+    for x in 0..32 {
+        pages[2].voxels[20 * 32 + x].intensity = 0.0;
+    }
+    assert_eq!(pages[2].get(7, 20).intensity, 0.0);
+    let (decoded, bad_pages) = decode_pages(&pages, &config, false, 0);
+    assert_eq!(bad_pages, vec![2]);
+    assert_eq!(decoded.len(), data.len());
+    assert_eq!(decoded[..2024], data[..2024]);
+    assert_eq!(decoded[3036..], data[3036..]);
+
+    // Pages out of order are reported too
+    pages.swap(0, 1);
+    assert_eq!(decode_pages(&pages, &config, false, 0).1, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_voxel_lattice_indexing_and_crosstalk() {
+    let voxels = encode_data(&[0u8; 27]); // every voxel at intensity 0.25
+    assert!(VoxelLattice::from_voxels(4, 4, voxels.clone()).is_err());
+    let mut lattice = VoxelLattice::from_voxels(3, 3, voxels).unwrap();
+    assert_eq!((lattice.width(), lattice.height(), lattice.depth()), (3, 3, 3));
+
+    let marked = PhotonicVoxel::new(1.0, 0.0, 0.0, 650.0);
+    lattice.set(2, 1, 2, marked);
+    assert_eq!(lattice.get(2, 1, 2), Some(&marked));
+    assert_eq!(lattice.layer(2)[3 + 2], marked);
+    assert_eq!(lattice.get(3, 0, 0), None);
+    assert_eq!(lattice.layers().count(), 3);
+    let coords: Vec<_> = lattice.iter_indexed().filter(|(_, v)| **v == marked).map(|(c, _)| c).collect();
+    assert_eq!(coords, vec![(2, 1, 2)]);
+
+    // Corners have 3 neighbours, the centre 6; the marked voxel leaks into its neighbours
+    let blurred = simulate_crosstalk(&lattice, 0.1);
+    assert!((blurred.get(0, 0, 0).unwrap().intensity - (0.25 + 3.0 * 0.025)).abs() < 1e-6);
+    assert!((blurred.get(1, 1, 1).unwrap().intensity - (0.25 + 6.0 * 0.025)).abs() < 1e-6);
+    assert!((blurred.get(2, 1, 1).unwrap().intensity - (0.25 + 4.0 * 0.025 + 0.1)).abs() < 1e-6);
+}
+
+#[test]
+fn test_lattice_addressing_targets_byte_ranges() {
+    let config = CodecConfig { wavelengths: vec![405.0, 450.0, 488.0, 532.0, 561.0, 594.0, 640.0, 700.0], ..CodecConfig::default() };
+    let geometry = LatticeGeometry::for_codec(16, 16, &config);
+    assert_eq!(geometry.bits_per_voxel, 9);
+
+    assert_eq!(geometry.address(16 * 16 * 2 + 16 * 3 + 5), VoxelAddress { layer: 2, row: 3, column: 5 });
+    assert_eq!(geometry.index(&VoxelAddress { layer: 2, row: 3, column: 5 }), Some(16 * 16 * 2 + 16 * 3 + 5));
+    assert_eq!(geometry.index(&VoxelAddress { layer: 0, row: 16, column: 0 }), None);
+    // Voxel 1 holds bits 9..18: the end of byte 1 and the start of byte 2
+    assert_eq!(geometry.bytes_for_voxel(1), 1..3);
+
+    let data: Vec<u8> = (0..12_000u32).map(|i| (i % 253) as u8).collect();
+    let voxels = encode_data_with_config(&data, &config);
+    let mut lattice = VoxelLattice::from_voxels_padded(16, 16, voxels, PhotonicVoxel::new(0.0, 0.0, 0.0, 405.0)).unwrap();
+
+    // Damage exactly the voxels holding bytes 4096..8192
+    let targets: Vec<VoxelAddress> = geometry.addresses_for_bytes(4096..8192).collect();
+    assert_eq!(targets.len(), geometry.voxels_for_bytes(4096..8192).len());
+    for address in &targets {
+        lattice.get_mut(address.column, address.row, address.layer).unwrap().intensity = 0.7;
+    }
+
+    let decoded = decode_data_with_config(lattice.voxels(), false, &config);
+    let damaged: Vec<usize> = (0..data.len()).filter(|&i| decoded[i] != data[i]).collect();
+    // The first and last voxels also hold bits of the neighbouring bytes
+    let first = geometry.bytes_for_voxel(geometry.voxels_for_bytes(4096..8192).start).start;
+    let last = geometry.bytes_for_voxel(geometry.voxels_for_bytes(4096..8192).end - 1).end;
+    assert_eq!((first, last), (4095, 8193));
+    assert!(damaged.iter().all(|i| (first..last).contains(i)));
+    assert!(damaged.len() > 1000);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip_of_voxels_and_results() {
+    use photon_core::SimulationResult;
+
+    let voxels = encode_data(b"serde");
+    let json = serde_json::to_string(&voxels).unwrap();
+    assert!(json.starts_with(r#"[{"intensity":"#));
+    assert_eq!(serde_json::from_str::<Vec<PhotonicVoxel>>(&json).unwrap(), voxels);
+
+    let config = CodecConfig { gray_code: true, ..CodecConfig::default() };
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<CodecConfig>(&json).unwrap(), config);
+
+    let result = SimulationResult { noise_level: 0.1, total_bits: 800, error_bits: 4, ber: 0.005, dimension_errors: DimensionErrors { phase: 3, ..DimensionErrors::default() } };
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["error_bits"], 4);
+    let back: SimulationResult = serde_json::from_value(value).unwrap();
+    assert_eq!((back.total_bits, back.ber, back.dimension_errors.phase), (800, 0.005, 3));
+}
+
+#[test]
+fn test_f64_lattice_keeps_small_crosstalk_contributions() {
+    let voxels = encode_data(&[0u8; 27]); // every voxel at intensity 0.25
+    let lattice = VoxelLattice::from_voxels(3, 3, voxels).unwrap();
+
+    // Each pass leaks 6 * 0.25 * 1e-8 into the centre: below half an f32 ulp at 0.25
+    let mut single = lattice.clone();
+    let mut double = lattice.to_f64();
+    for _ in 0..100 {
+        single = simulate_crosstalk(&single, 1e-8);
+        double = simulate_crosstalk(&double, 1e-8);
+    }
+    assert_eq!(single.get(1, 1, 1).unwrap().intensity, 0.25);
+    let centre: &PhotonicVoxel64 = double.get(1, 1, 1).unwrap();
+    assert!((centre.intensity - 0.25 - 100.0 * 1.5e-8).abs() < 1e-10); // neighbours grow too
+
+    // Rounding back gives voxels the f32 codec can decode
+    assert_eq!(decode_data(double.to_f32().voxels(), false), vec![0u8; 27]);
+    assert_eq!(std::mem::size_of::<PhotonicVoxel>(), 16);
+}
+
+#[test]
+fn test_quantized_voxels_are_lossless_for_ideal_voxels() {
+    let config = CodecConfig { gray_code: true, wavelengths: vec![405.0, 488.0, 561.0, 640.0, 700.0, 780.0, 850.0, 940.0], ..CodecConfig::default() };
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 37 % 256) as u8).collect();
+    let voxels = encode_data_with_config(&data, &config);
+
+    let quantized: Vec<QuantizedVoxel> = voxels.iter().map(|v| QuantizedVoxel::from_voxel(v, &config)).collect();
+    assert!(quantized.iter().zip(&voxels).all(|(q, v)| q.to_voxel(&config) == *v));
+    assert_eq!(QuantizedVoxel::from_bytes(quantized[5].to_bytes()), quantized[5]);
+
+    // 9 bits per voxel instead of 128
+    let packed = pack_voxels(&voxels, &config);
+    assert_eq!(packed.len(), (voxels.len() * 9).div_ceil(8));
+    assert_eq!(unpack_voxels(&packed, voxels.len(), &config), voxels);
+
+    // Received voxels snap to their nearest levels
+    let mut received = voxels[0];
+    received.intensity += 0.1;
+    received.wavelength -= 20.0;
+    assert_eq!(QuantizedVoxel::from_voxel(&received, &config), quantized[0]);
+}
+
+#[test]
+fn test_voxel_builder_validates_and_normalizes() {
+    use std::f32::consts::PI;
+
+    let voxel = PhotonicVoxel::builder().intensity(0.5).polarization(-PI / 4.0).phase(2.5 * PI).wavelength(532.0).build().unwrap();
+    assert!((voxel.polarization - 3.0 * PI / 4.0).abs() < 1e-6);
+    assert!((voxel.phase - PI / 2.0).abs() < 1e-5);
+
+    assert!(PhotonicVoxel::builder().intensity(1.2).wavelength(532.0).build().is_err());
+    assert!(PhotonicVoxel::builder().intensity(0.5).wavelength(100.0).build().is_err());
+    assert!(PhotonicVoxel::builder().intensity(f32::NAN).wavelength(532.0).build_clamped().is_err());
+    assert!(PhotonicVoxel::builder().intensity(0.5).build().is_err()); // no wavelength
+
+    let clamped = PhotonicVoxel::builder().intensity(1.2).wavelength(2500.0).build_clamped().unwrap();
+    assert_eq!((clamped.intensity, clamped.wavelength), (1.0, 2000.0));
+
+    // Noisy readouts leave the angle ranges; normalize() brings them back
+    let mut received = PhotonicVoxel::new(0.25, -0.01, 7.0, 650.0);
+    assert!(received.validate().is_err());
+    received.normalize();
+    assert!(received.validate().is_ok());
+    assert_eq!(decode_data(&[received], false), decode_data(&[PhotonicVoxel::new(0.25, -0.01, 7.0, 650.0)], false));
+}
+
+#[test]
+fn test_jones_vectors_model_wave_plates() {
+    use std::f32::consts::PI;
+
+    let voxels = encode_data(b"jones");
+    for voxel in &voxels {
+        let jones = JonesVoxel::from(*voxel);
+        assert!(jones.ellipticity().abs() < 1e-6);
+        let back = jones.to_voxel();
+        assert!((back.intensity - voxel.intensity).abs() < 1e-5);
+        assert!((back.polarization - voxel.polarization).abs() < 1e-5);
+        assert!((back.phase - voxel.phase).abs() < 1e-5 || (back.phase - voxel.phase).abs() > 2.0 * PI - 1e-5);
+        assert_eq!(back.wavelength, voxel.wavelength);
+    }
+    assert_eq!(decode_data(&voxels.iter().map(|v| JonesVoxel::from(*v).to_voxel()).collect::<Vec<_>>(), false), b"jones");
+
+    // A quarter-wave plate turns 45° linear light circular
+    let diagonal = JonesVoxel::from(PhotonicVoxel::new(1.0, PI / 4.0, 0.0, 532.0));
+    let circular = diagonal.apply(&JonesMatrix::retarder(0.0, PI / 2.0));
+    assert!((circular.ellipticity().abs() - PI / 4.0).abs() < 1e-3);
+    assert!((circular.intensity() - 1.0).abs() < 1e-6);
+
+    // A half-wave plate mirrors linear polarization about its axis; a polarizer at 90° to it blocks it
+    let linear = JonesVoxel::from(PhotonicVoxel::new(0.5, PI / 6.0, 0.0, 532.0));
+    let mirrored = linear.apply(&JonesMatrix::retarder(0.0, PI));
+    assert!((mirrored.orientation() - 5.0 * PI / 6.0).abs() < 1e-5);
+    assert!(linear.apply(&JonesMatrix::polarizer(PI / 6.0 + PI / 2.0)).intensity() < 1e-12);
+    assert!((JonesVoxel::elliptical(1.0, 0.3, -0.2, 1.0, 650.0).ellipticity() + 0.2).abs() < 1e-5);
+}
+
+#[test]
+fn test_birefringent_voxels_round_trip() {
+    use std::f32::consts::PI;
+
+    let data: Vec<u8> = (0..=255).collect();
+    for config in [
+        BirefringentConfig::default(),
+        BirefringentConfig { axis_levels: 8, retardance_levels: 2, max_retardance_nm: 200.0, gray_code: true },
+    ] {
+        let voxels = encode_birefringent(&data, &config);
+        assert_eq!(voxels.len(), data.len() * 8 / config.bits_per_voxel());
+        assert!(voxels.iter().all(|v| (0.0..PI).contains(&v.slow_axis) && v.retardance_nm > 0.0));
+        assert_eq!(decode_birefringent(&voxels, &config), data);
+
+        // Perturbations under half a level spacing still decode; axes near PI wrap to 0
+        let axis_step = PI / config.axis_levels as f32;
+        let retardance_step = config.max_retardance_nm / config.retardance_levels as f32;
+        let noisy: Vec<_> = voxels
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+                BirefringentVoxel::new((v.slow_axis - sign * 0.4 * axis_step).rem_euclid(PI), v.retardance_nm + sign * 0.4 * retardance_step)
+            })
+            .collect();
+        assert_eq!(decode_birefringent(&noisy, &config), data);
+    }
+    assert!(BirefringentConfig { axis_levels: 3, ..Default::default() }.validate().is_err());
+
+    // A half-wave voxel at 532 nm mirrors linear polarization about its slow axis
+    let half_wave = BirefringentVoxel::new(PI / 8.0, 266.0);
+    assert!((half_wave.phase_retardance(532.0) - PI).abs() < 1e-5);
+    let probe = JonesVoxel::from(PhotonicVoxel::new(1.0, 0.0, 0.0, 532.0));
+    let out = half_wave.readout(&probe);
+    assert!((out.orientation() - PI / 4.0).abs() < 1e-4);
+    assert!((out.intensity() - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn test_stokes_readout_with_detector_noise() {
+    use std::f32::consts::PI;
+
+    // Horizontal, diagonal and circular light
+    let horizontal = StokesVector::from(PhotonicVoxel::new(2.0, 0.0, 0.0, 532.0));
+    assert!((horizontal.s0 - 2.0).abs() < 1e-6 && (horizontal.s1 - 2.0).abs() < 1e-6);
+    let diagonal = StokesVector::from(PhotonicVoxel::new(1.0, PI / 4.0, 0.0, 532.0));
+    assert!((diagonal.s2 - 1.0).abs() < 1e-6 && diagonal.s1.abs() < 1e-6);
+    let circular = StokesVector::from(JonesVoxel::elliptical(1.0, 0.0, PI / 4.0, 0.0, 532.0));
+    assert!((circular.s3 - 1.0).abs() < 1e-5 && (circular.ellipticity() - PI / 4.0).abs() < 1e-3);
+
+    let elliptical = JonesVoxel::elliptical(0.7, 0.4, -0.2, 1.3, 650.0);
+    let stokes = StokesVector::from(elliptical);
+    assert!((stokes.orientation() - elliptical.orientation()).abs() < 1e-5);
+    assert!((stokes.ellipticity() - elliptical.ellipticity()).abs() < 1e-5);
+    assert!((stokes.degree_of_polarization() - 1.0).abs() < 1e-5);
+
+    // An ideal detector reads the encoded intensity and polarization exactly
+    let voxels = encode_data(b"polarimetry");
+    let mut rng = StdRng::seed_from_u64(38);
+    assert_eq!(decode_data(&StokesDetector::ideal().read_voxels_with_rng(&voxels, &mut rng), false), b"polarimetry");
+
+    // Components are perturbed independently: noise on S3 alone leaves S0-S2 untouched
+    let detector = StokesDetector { s0: 0.0, s1: 0.0, s2: 0.0, s3: 0.1, ..StokesDetector::ideal() };
+    let measured = detector.measure_with_rng(&stokes, &mut rng);
+    assert_eq!(&measured.components()[..3], &stokes.components()[..3]);
+    assert_ne!(measured.s3, stokes.s3);
+
+    // Measured spread matches the configured sigma
+    let detector = StokesDetector::gaussian(0.05);
+    let errors: Vec<f32> = (0..4000).map(|_| detector.measure_with_rng(&horizontal, &mut rng).s2 - horizontal.s2).collect();
+    let sigma = (errors.iter().map(|e| e * e).sum::<f32>() / errors.len() as f32).sqrt();
+    assert!((sigma - 0.05).abs() < 0.005, "sigma {}", sigma);
+}
+
+#[test]
+fn test_container_round_trip_and_versioning() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 256) as u8).collect();
+    let codec = CodecConfig { intensity_levels: 8, gray_code: true, ..CodecConfig::default() };
+
+    for ecc in [false, true] {
+        let encoded = Container::encode(&data, &codec, ecc);
+        let mut file = Vec::new();
+        container::write(&mut file, &encoded).unwrap();
+        assert!(container::is_container(&file));
+
+        let read = container::read(&file[..]).unwrap();
+        assert_eq!(read, encoded);
+        assert!(read.verify());
+        assert_eq!(read.header.codec, codec);
+        assert_eq!(read.header.ecc.is_some(), ecc);
+        assert_eq!(read.decode(false).unwrap(), data);
+    }
+
+    // A corrupted payload is detected by the checksum
+    let mut file = Vec::new();
+    container::write(&mut file, &Container::encode(b"checksummed", &CodecConfig::default(), false)).unwrap();
+    let last = file.len() - 40;
+    file[last] ^= 0x40;
+    assert!(!container::read(&file[..]).unwrap().verify());
+
+    // Unknown versions, foreign files and truncated payloads are refused
+    let mut newer = file.clone();
+    newer[4..6].copy_from_slice(&(photon_core::CONTAINER_VERSION + 1).to_le_bytes());
+    assert!(container::read(&newer[..]).unwrap_err().to_string().contains("version"));
+    assert!(container::read(&b"VOX!not a container"[..]).is_err());
+    assert!(container::read(&file[..file.len() - 5]).is_err());
+
+    // A forged data length that adds up in the session table but not to the
+    // voxels is refused before anything is sized by it
+    let data_len_at = 4 + 2 + 4 + 2 + 4 * CodecConfig::default().wavelengths.len() + 7 + 12;
+    let session_at = data_len_at + 8 + 4 + 8 + 4;
+    let mut forged = file.clone();
+    for at in [data_len_at, session_at] {
+        forged[at..at + 8].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+    }
+    assert!(container::read(&forged[..]).unwrap_err().to_string().contains("do not fit"));
+}
+
+#[test]
+fn test_voxel_serialization_is_little_endian() {
+    let voxel = PhotonicVoxel::new(0.75, 1.5, -2.25, 561.0);
+    let record = voxel_to_bytes(&voxel);
+    assert_eq!(record.len(), VOXEL_RECORD_LEN);
+    assert_eq!(record[..4], 0.75f32.to_le_bytes());
+    assert_eq!(record[12..], 561.0f32.to_le_bytes());
+    assert_eq!(voxel_from_bytes(&record), voxel);
+
+    // Streams use the same records
+    let mut streamed = Vec::new();
+    write_voxel(&mut streamed, &voxel).unwrap();
+    assert_eq!(streamed, record);
+
+    let voxels = encode_data(b"endian-stable");
+    let bytes = voxels_to_bytes(&voxels);
+    assert_eq!(bytes.len(), voxels.len() * VOXEL_RECORD_LEN);
+    assert_eq!(voxels_from_bytes(&bytes).unwrap(), voxels);
+    assert!(voxels_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn test_container_chunk_checksums_locate_corruption() {
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+    let mut encoded = Container::encode(&data, &CodecConfig::default(), true);
+    encoded = Container::new(container::ContainerHeader { chunk_voxels: 1000, ..encoded.header }, encoded.voxels);
+    assert_eq!(encoded.chunk_count(), 28);
+    assert_eq!(encoded.chunk_voxels(27), 27_000..28_000);
+
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    let intact = container::verify(&file[..]).unwrap();
+    assert!(intact.is_intact() && intact.chunks == 28);
+
+    // Flip a bit in a voxel of chunk 3 and one of chunk 20
+    let payload_start = file.len() - container::DIGEST_LEN - 28_000 * VOXEL_RECORD_LEN;
+    for voxel in [3_500, 20_999] {
+        file[payload_start + voxel * VOXEL_RECORD_LEN + 1] ^= 0x01;
+    }
+    let report = container::verify(&file[..]).unwrap();
+    assert_eq!(report.corrupted_chunks, vec![3, 20]);
+    assert!(!report.digest_ok && !report.is_intact());
+
+    let damaged = container::read(&file[..]).unwrap();
+    assert_eq!(damaged.corrupted_chunks(), vec![3, 20]);
+    assert!(!damaged.digest_ok());
+
+    // A header change is caught by the file digest alone
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    let data_len_offset = 4 + 2 + 4 + 2 + 4 * 4 + 3 + 4 + 12;
+    let session_len_offset = data_len_offset + 8 + 4 + 8 + 4;
+    file[data_len_offset] ^= 0x01;
+    file[session_len_offset] ^= 0x01;
+    let report = container::verify(&file[..]).unwrap();
+    assert!(report.corrupted_chunks.is_empty() && !report.digest_ok);
+}
+
+#[test]
+fn test_container_compression_flag() {
+    let data = b"compressible compressible compressible ".repeat(200);
+    let mut encoded = Container::encode(&data, &CodecConfig::default(), true);
+    encoded.header.compressed = true;
+    let mut file = Vec::new();
+
+    #[cfg(feature = "zstd")]
+    {
+        container::write(&mut file, &encoded).unwrap();
+        let mut plain = Vec::new();
+        let mut uncompressed = encoded.clone();
+        uncompressed.header.compressed = false;
+        container::write(&mut plain, &uncompressed).unwrap();
+        assert!(file.len() * 4 < plain.len(), "{} vs {} bytes", file.len(), plain.len());
+
+        let read = container::read(&file[..]).unwrap();
+        assert!(read.header.compressed && read.verify());
+        assert_eq!(read.decode(false).unwrap(), data);
+        assert!(container::verify(&file[..]).unwrap().is_intact());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    assert_eq!(container::write(&mut file, &encoded).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_container_read_range_decodes_only_the_range() {
+    use std::io::Cursor;
+
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 31 % 253) as u8).collect();
+    let codec = CodecConfig { intensity_levels: 8, ..CodecConfig::default() };
+    let ranges = [(0, 10), (1995, 20), (12_345, 1000), (19_990, 10), (0, 20_000), (7, 0)];
+
+    for ecc in [false, true] {
+        let mut file = Vec::new();
+        container::write(&mut file, &Container::encode(&data, &codec, ecc)).unwrap();
+        for (offset, len) in ranges {
+            let bytes = container::read_range(Cursor::new(&file), offset as u64, len).unwrap();
+            assert_eq!(bytes, &data[offset..offset + len], "range {}+{} (ecc: {})", offset, len, ecc);
+        }
+        assert_eq!(container::read_range(Cursor::new(&file), 19_999, 2).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    // Damage beyond what the parity corrects (three shards of one codeword)
+    // does not matter to a range far from it, but fails a full decode
+    let mut file = Vec::new();
+    container::write(&mut file, &Container::encode(&data, &CodecConfig::default(), true)).unwrap();
+    let payload_start = file.len() - container::DIGEST_LEN - 28_000 * VOXEL_RECORD_LEN;
+    for voxel in [5_000, 7_000, 9_000] {
+        let wrong = voxel_to_bytes(&encode_data(&[!data[voxel]])[0]);
+        file[payload_start + voxel * VOXEL_RECORD_LEN..][..VOXEL_RECORD_LEN].copy_from_slice(&wrong);
+    }
+    assert!(container::read(&file[..]).unwrap().decode(false).is_err());
+    assert_eq!(container::read_range(Cursor::new(&file), 100, 50).unwrap(), &data[100..150]);
+    assert!(container::read_range(Cursor::new(&file), 4_990, 20).is_err());
+}
+
+#[test]
+fn test_container_records_lattice_shape() {
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 256) as u8).collect();
+    let encoded = Container::encode(&data, &CodecConfig::default(), false).with_geometry(32, 32);
+    assert_eq!(encoded.header.geometry, Some(LatticeShape { width: 32, height: 32, depth: 5 }));
+    assert_eq!(encoded.voxels.len(), 5 * 32 * 32);
+
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    let read = container::read(&file[..]).unwrap();
+    assert_eq!(read.decode(false).unwrap(), data);
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 4990, 10).unwrap(), &data[4990..]);
+
+    // Physics sees the recorded arrangement, not a flat stream
+    let lattice = read.lattice().unwrap();
+    assert_eq!(lattice.shape(), LatticeShape { width: 32, height: 32, depth: 5 });
+    assert_eq!(lattice.get(3, 1, 2), Some(&encoded.voxels[2 * 1024 + 32 + 3]));
+    let expected = simulate_crosstalk(&VoxelLattice::from_voxels(32, 32, encoded.voxels.clone()).unwrap(), 0.01);
+    assert_eq!(simulate_crosstalk(&lattice, 0.01), expected);
+    assert!(Container::encode(&data, &CodecConfig::default(), false).lattice().is_none());
+
+    // A shape that does not match the voxel count is rejected
+    let depth_offset = 4 + 2 + 4 + 2 + 4 * 4 + 3 + 4 + 8;
+    file[depth_offset] = 6;
+    assert!(container::read(&file[..]).unwrap_err().to_string().contains("lattice"));
+
+    // Sides whose product overflows are rejected, not wrapped into a match
+    let width_offset = depth_offset - 8;
+    let mut forged = file.clone();
+    for side in 0..3 {
+        forged[width_offset + 4 * side..width_offset + 4 * side + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    }
+    assert!(container::read(&forged[..]).unwrap_err().to_string().contains("lattice"));
+}
+
+#[test]
+fn test_ecc_layout_is_recorded_not_guessed() {
+    let data: Vec<u8> = (0..1400u32).map(|i| (i * 13 % 256) as u8).collect();
+    let codec = CodecConfig { intensity_levels: 8, ..CodecConfig::default() };
+    let ecc = EccConfig::new(6, 3);
+    assert!(EccConfig::new(200, 57).validate().is_err());
+    // 256 shards would pass GF(2^8) but leave no point to locate errors with
+    assert!(EccConfig::new(200, 56).validate().unwrap_err().contains("255"));
+    EccConfig::new(200, 55).validate().unwrap();
+
+    // Stream header: parity and symbol padding are stripped from the recorded layout
+    let mut header = photon_core::StreamHeader::new(&codec);
+    header.ecc = Some(ecc);
+    header.data_len = Some(data.len() as u64);
+    let mut voxels = header.to_voxels();
+    voxels.extend(encode_data_with_config(&add_error_correction_with(&data, &ecc), &codec));
+    assert_eq!(read_header(&voxels, false).unwrap().unwrap().0, header);
+    assert_eq!(decode_auto(&voxels, false).unwrap(), data);
+
+    // A plain stream whose length happens to be a multiple of 14 is left alone
+    let plain = encode_with_header(&data, &CodecConfig::default());
+    assert_eq!(decode_auto(&plain, false).unwrap(), data);
+
+    // Containers accept any valid layout and reject unknown schemes
+    let encoded = Container::encode_with_ecc(&data, &codec, Some(ecc));
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    let read = container::read(&file[..]).unwrap();
+    assert_eq!(read.header.ecc, Some(ecc));
+    assert_eq!(read.decode(false).unwrap(), data);
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 500, 300).unwrap(), &data[500..800]);
+
+    let scheme_offset = 4 + 2 + 4 + 2 + 4 * 4;
+    file[scheme_offset] = 7;
+    assert!(container::read(&file[..]).unwrap_err().to_string().contains("ECC scheme"));
+}
+
+#[test]
+fn test_container_append_sessions() {
+    let first: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 256) as u8).collect();
+    let second = b"written in a later session".repeat(40);
+    let codec = CodecConfig::default();
+    let mut encoded = Container::encode(&first, &codec, true).with_geometry(32, 32);
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    let (voxels, checksums) = (encoded.voxels.clone(), encoded.chunk_checksums().to_vec());
+
+    // Earlier voxels and chunks are kept; the new session gets its own
+    let mut appended = container::read(&file[..]).unwrap();
+    appended.append(&second);
+    assert_eq!(appended.sessions().len(), 2);
+    assert_eq!(appended.sessions()[1].data_len, second.len() as u64);
+    assert_eq!(&appended.voxels[..voxels.len()], &voxels[..]);
+    assert_eq!(&appended.chunk_checksums()[..checksums.len()], &checksums[..]);
+    assert_eq!(appended.chunk_voxels(checksums.len()).start, voxels.len());
+    let shape = appended.header.geometry.unwrap();
+    assert_eq!((shape.depth, shape.len()), (encoded.header.geometry.unwrap().depth + 2, appended.voxels.len()));
+
+    let mut file = Vec::new();
+    container::write(&mut file, &appended).unwrap();
+    let read = container::read(&file[..]).unwrap();
+    assert!(read.verify());
+    assert_eq!(read.decode(false).unwrap(), [first.clone(), second.clone()].concat());
+    // A range across the session boundary is read from both sessions
+    let range = container::read_range(std::io::Cursor::new(&file), 2900, 200).unwrap();
+    assert_eq!(range, [&first[2900..], &second[..100]].concat());
+
+    // Damage in the new session is located in its chunks only
+    encoded = read;
+    let last = encoded.voxels.len() - 1;
+    encoded.voxels[last].intensity += 0.5;
+    assert_eq!(encoded.corrupted_chunks(), vec![encoded.chunk_count() - 1]);
+}
+
+#[test]
+fn test_archive_lists_and_extracts_entries() {
+    let mut contents = Archive::new();
+    contents.add("notes.txt", b"read me first".to_vec()).unwrap();
+    contents.add("data/samples.bin", (0..=255u8).cycle().take(5000).collect()).unwrap();
+    contents.add("empty", Vec::new()).unwrap();
+    for bad in ["", "/etc/passwd", "../up", "a//b", "a\\b", "notes.txt"] {
+        assert!(contents.add(bad, vec![1]).is_err(), "{:?}", bad);
+    }
+    assert_eq!(Archive::from_bytes(&contents.to_bytes()).unwrap(), contents);
+
+    let mut file = Vec::new();
+    container::write(&mut file, &contents.to_container(&CodecConfig::default(), Some(EccConfig::default()))).unwrap();
+    let entries = archive::list(std::io::Cursor::new(&file)).unwrap();
+    assert_eq!(entries, contents.entries());
+    assert_eq!(entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["notes.txt", "data/samples.bin", "empty"]);
+
+    for (name, data) in contents.files() {
+        assert_eq!(archive::extract(std::io::Cursor::new(&file), name).unwrap(), data);
+    }
+    let missing = archive::extract(std::io::Cursor::new(&file), "nope").unwrap_err();
+    assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(Archive::from_container(&container::read(&file[..]).unwrap(), false).unwrap(), contents);
+
+    // A plain container is not an archive
+    let mut plain = Vec::new();
+    container::write(&mut plain, &Container::encode(b"just some bytes here", &CodecConfig::default(), false)).unwrap();
+    assert!(archive::list(std::io::Cursor::new(&plain)).is_err());
+}
+
+#[test]
+fn test_container_file_metadata() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let data = std::fs::read(&path).unwrap();
+    let metadata = FileMetadata::from_path(&path).unwrap();
+    assert_eq!((metadata.name.as_str(), metadata.size), ("Cargo.toml", data.len() as u64));
+    assert!(metadata.modified.is_some());
+
+    let mut encoded = Container::encode(&data, &CodecConfig::default(), true);
+    encoded.header.metadata = Some(FileMetadata { mime: Some("application/toml".to_string()), ..metadata.clone() });
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+
+    // The header alone describes the file
+    let info = container::inspect(&file[..]).unwrap();
+    assert_eq!(info.header, encoded.header);
+    assert_eq!((info.voxel_count, info.chunks, info.sessions.len()), (encoded.voxels.len(), encoded.chunk_count(), 1));
+    let read = container::read(&file[..]).unwrap();
+    assert_eq!(read.header.metadata.as_ref().unwrap().mime.as_deref(), Some("application/toml"));
+    assert_eq!(read.decode(false).unwrap(), data);
+
+    // Without metadata the flag stays clear and nothing is recorded
+    let mut plain = Vec::new();
+    container::write(&mut plain, &Container::encode(&data, &CodecConfig::default(), true)).unwrap();
+    assert_eq!(file.len() - plain.len(), 2 + 10 + 8 + 1 + 8 + 4 + 1 + 16);
+    assert_eq!(container::inspect(&plain[..]).unwrap().header.metadata, None);
+}
+
+#[test]
+fn test_npy_export_round_trip() {
+    let voxels = encode_data(b"numpy");
+    let mut npy = Vec::new();
+    export::to_npy(&mut npy, &voxels).unwrap();
+    assert!(export::is_npy(&npy));
+    // Version 1.0 header padded so the data starts 64-byte aligned
+    let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    assert!(std::str::from_utf8(&npy[10..10 + header_len]).unwrap().contains("'shape': (5, 4)"));
+    assert_eq!(npy.len(), 10 + header_len + voxels.len() * 16);
+    assert_eq!(export::from_npy(&npy[..]).unwrap(), voxels);
+
+    // A column-major array, as written by NumPy for a transposed view, reads the same
+    let header = b"{'descr': '<f4', 'fortran_order': True, 'shape': (5, 4), }\n";
+    let mut fortran = b"\x93NUMPY\x01\x00".to_vec();
+    fortran.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    fortran.extend_from_slice(header);
+    for column in 0..4 {
+        for voxel in &voxels {
+            let field = [voxel.intensity, voxel.polarization, voxel.phase, voxel.wavelength][column];
+            fortran.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    assert_eq!(decode_data(&export::from_npy(&fortran[..]).unwrap(), false), b"numpy");
+
+    let float64 = String::from_utf8_lossy(&npy).replace("<f4", "<f8").into_bytes();
+    assert!(export::from_npy(&float64[..]).is_err());
+}
+
+/// Minimal HDF5 reader written from the file format specification, kept
+/// apart from the writer so the export is checked against a second reading
+/// of the spec: superblock v2, v2 object headers with checksums, compact
+/// links, contiguous layouts and v3 attributes.
+#[cfg(feature = "hdf5")]
+mod h5read {
+    use std::collections::HashMap;
+
+    /// Bob Jenkins' lookup3 `hashlittle`, seed 0.
+    pub fn checksum(key: &[u8]) -> u32 {
+        fn rot(x: u32, k: u32) -> u32 {
+            x.rotate_left(k)
+        }
+        let init = 0xdeadbeefu32.wrapping_add(key.len() as u32);
+        let (mut a, mut b, mut c) = (init, init, init);
+        let mut blocks = key.chunks(12).peekable();
+        while let Some(block) = blocks.next() {
+            // The last block, short or full, is zero-padded and finalized
+            let mut padded = [0u8; 12];
+            padded[..block.len()].copy_from_slice(block);
+            let k = |i: usize| u32::from_le_bytes(padded[i..i + 4].try_into().unwrap());
+            a = a.wrapping_add(k(0));
+            b = b.wrapping_add(k(4));
+            c = c.wrapping_add(k(8));
+            if blocks.peek().is_none() {
+                c ^= b; c = c.wrapping_sub(rot(b, 14));
+                a ^= c; a = a.wrapping_sub(rot(c, 11));
+                b ^= a; b = b.wrapping_sub(rot(a, 25));
+                c ^= b; c = c.wrapping_sub(rot(b, 16));
+                a ^= c; a = a.wrapping_sub(rot(c, 4));
+                b ^= a; b = b.wrapping_sub(rot(a, 14));
+                c ^= b; c = c.wrapping_sub(rot(b, 24));
+            } else {
+                a = a.wrapping_sub(c); a ^= rot(c, 4); c = c.wrapping_add(b);
+                b = b.wrapping_sub(a); b ^= rot(a, 6); a = a.wrapping_add(c);
+                c = c.wrapping_sub(b); c ^= rot(b, 8); b = b.wrapping_add(a);
+                a = a.wrapping_sub(c); a ^= rot(c, 16); c = c.wrapping_add(b);
+                b = b.wrapping_sub(a); b ^= rot(a, 19); a = a.wrapping_add(c);
+                c = c.wrapping_sub(b); c ^= rot(b, 4); b = b.wrapping_add(a);
+            }
+        }
+        c
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+    }
+
+    /// Element type: class (0 fixed point, 1 float, 3 string) and size.
+    #[derive(Debug, PartialEq)]
+    pub struct Datatype {
+        pub class: u8,
+        pub size: usize,
+        pub signed: bool,
+    }
+
+    /// Parses a datatype message, returning it and its encoded length.
+    fn datatype(bytes: &[u8]) -> (Datatype, usize) {
+        let class = bytes[0] & 0x0f;
+        assert_eq!(bytes[0] >> 4, 1, "datatype version");
+        let size = u32_at(bytes, 4) as usize;
+        let properties = match class {
+            0 => 4,
+            1 => 12,
+            3 => 0,
+            other => panic!("unexpected datatype class {}", other),
+        };
+        if class == 1 {
+            // IEEE layout: sign bit on top, exponent right below, mantissa at 0
+            let exponent_location = bytes[12] as usize;
+            assert_eq!((bytes[2] as usize, bytes[14], exponent_location + bytes[13] as usize), (size * 8 - 1, 0, size * 8 - 1));
+        }
+        (Datatype { class, size, signed: class == 0 && bytes[1] & 0x08 != 0 }, 8 + properties)
+    }
+
+    /// Parses a dataspace message, returning its dimensions (empty for a
+    /// scalar) and its encoded length.
+    fn dataspace(bytes: &[u8]) -> (Vec<u64>, usize) {
+        let (rank, flags) = (bytes[1] as usize, bytes[2]);
+        let header = match bytes[0] {
+            1 => 8,
+            2 => 4,
+            version => panic!("dataspace version {}", version),
+        };
+        let dims = (0..rank).map(|i| u64_at(bytes, header + 8 * i)).collect();
+        let max_dims = if flags & 1 != 0 { 8 * rank } else { 0 };
+        (dims, header + 8 * rank + max_dims)
+    }
+
+    /// A group (links by name) or a dataset read from an object header.
+    #[derive(Debug, Default)]
+    pub struct Object {
+        pub links: HashMap<String, u64>,
+        pub datatype: Option<Datatype>,
+        pub dims: Vec<u64>,
+        pub data: Vec<u8>,
+        pub attributes: HashMap<String, (Datatype, Vec<u64>, Vec<u8>)>,
+    }
+
+    /// Address of the root group, after checking the superblock.
+    pub fn root(file: &[u8]) -> u64 {
+        assert_eq!(&file[..8], b"\x89HDF\r\n\x1a\n");
+        assert_eq!(file[8], 2, "superblock version");
+        assert_eq!((file[9], file[10]), (8, 8), "offset and length sizes");
+        assert_eq!(u64_at(file, 28), file.len() as u64, "end of file address");
+        assert_eq!(checksum(&file[..44]), u32_at(file, 44), "superblock checksum");
+        u64_at(file, 36)
+    }
+
+    /// Reads the version 2 object header at `address`.
+    pub fn object(file: &[u8], address: u64) -> Object {
+        let start = address as usize;
+        let header = &file[start..];
+        assert_eq!(&header[..4], b"OHDR");
+        assert_eq!(header[4], 2, "object header version");
+        let flags = header[5];
+        let mut at = 6;
+        if flags & 0x20 != 0 {
+            at += 16;
+        }
+        if flags & 0x10 != 0 {
+            at += 4;
+        }
+        let size_width = 1 << (flags & 0x03);
+        let chunk_len = header[at..at + size_width].iter().rev().fold(0usize, |len, &byte| len << 8 | byte as usize);
+        at += size_width;
+        let chunk_end = at + chunk_len;
+        assert_eq!(checksum(&header[..chunk_end]), u32_at(header, chunk_end), "object header checksum");
+
+        let mut object = Object::default();
+        let message_header = if flags & 0x04 != 0 { 6 } else { 4 };
+        while chunk_end - at >= message_header {
+            let (kind, len) = (header[at], u16_at(header, at + 1));
+            let body = &header[at + message_header..at + message_header + len];
+            at += message_header + len;
+            match kind {
+                0x01 => object.dims = dataspace(body).0,
+                0x03 => object.datatype = Some(datatype(body).0),
+                0x06 => {
+                    assert_eq!(body[0], 1, "link version");
+                    let link_flags = body[1];
+                    let mut at = 2;
+                    if link_flags & 0x08 != 0 {
+                        assert_eq!(body[at], 0, "hard link");
+                        at += 1;
+                    }
+                    if link_flags & 0x04 != 0 {
+                        at += 8;
+                    }
+                    if link_flags & 0x10 != 0 {
+                        at += 1;
+                    }
+                    let width = 1 << (link_flags & 0x03);
+                    let name_len = body[at..at + width].iter().rev().fold(0usize, |len, &byte| len << 8 | byte as usize);
+                    at += width;
+                    let name = String::from_utf8(body[at..at + name_len].to_vec()).unwrap();
+                    object.links.insert(name, u64_at(body, at + name_len));
+                }
+                0x08 => {
+                    assert_eq!((body[0], body[1]), (3, 1), "contiguous layout version 3");
+                    let (data_address, size) = (u64_at(body, 2), u64_at(body, 10) as usize);
+                    object.data = if data_address == u64::MAX { Vec::new() } else { file[data_address as usize..data_address as usize + size].to_vec() };
+                }
+                0x0C => {
+                    assert_eq!(body[0], 3, "attribute version");
+                    let (name_len, datatype_len, dataspace_len) = (u16_at(body, 2), u16_at(body, 4), u16_at(body, 6));
+                    let mut at = 9;
+                    let name = std::str::from_utf8(&body[at..at + name_len]).unwrap().trim_end_matches('\0').to_string();
+                    at += name_len;
+                    let (datatype, len) = datatype(&body[at..]);
+                    assert_eq!(len, datatype_len);
+                    at += datatype_len;
+                    let (dims, len) = dataspace(&body[at..]);
+                    assert_eq!(len, dataspace_len);
+                    at += dataspace_len;
+                    let elements: u64 = dims.iter().product();
+                    let data = body[at..].to_vec();
+                    assert_eq!(data.len() as u64, elements * datatype.size as u64, "attribute {} data", name);
+                    object.attributes.insert(name, (datatype, dims, data));
+                }
+                _ => {}
+            }
+        }
+        if let Some(datatype) = &object.datatype {
+            let elements: u64 = object.dims.iter().product();
+            assert_eq!(object.data.len() as u64, elements * datatype.size as u64, "dataset size");
+        }
+        object
+    }
+}
+
+#[cfg(feature = "hdf5")]
+#[test]
+fn test_hdf5_export_layout() {
+    use photon_core::{hdf5, run_ber_simulation_with_rng, SimulationResult};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let lattice = VoxelLattice::from_voxels_padded(3, 2, encode_data(b"hdf5 lattice"), PhotonicVoxel::new(0.0, 0.0, 0.0, 405.0)).unwrap();
+    let results: Vec<SimulationResult> = run_ber_simulation_with_rng(100, 3, 0.2, &CodecConfig::default(), &mut StdRng::seed_from_u64(5));
+    let mut file = Vec::new();
+    hdf5::write(&mut file, Some(&lattice), &results).unwrap();
+
+    // Version 2 superblock whose end-of-file address is the file length
+    assert_eq!(&file[..9], b"\x89HDF\r\n\x1a\n\x02");
+    assert_eq!(u64::from_le_bytes(file[28..36].try_into().unwrap()), file.len() as u64);
+    // The voxels are stored contiguously, in lattice order
+    let data: Vec<u8> = lattice.voxels().iter().flat_map(|v| [v.intensity, v.polarization, v.phase, v.wavelength]).flat_map(f32::to_le_bytes).collect();
+    let start = file.windows(data.len()).position(|window| window == data).unwrap();
+    assert_eq!(start, 48);
+    for name in [hdf5::LATTICE_DATASET, hdf5::RESULTS_GROUP, "noise_level", "ber", "width", "columns"] {
+        assert!(file.windows(name.len()).any(|window| window == name.as_bytes()), "{}", name);
+    }
+
+    // Read back with the independent reader
+    assert_eq!(h5read::checksum(b""), 0xdeadbeef);
+    assert_eq!(h5read::checksum(b"Four score and seven years ago"), 0x17770551);
+    let root = h5read::object(&file, h5read::root(&file));
+    assert_eq!(root.links.len(), 2);
+    let dataset = h5read::object(&file, root.links[hdf5::LATTICE_DATASET]);
+    assert_eq!(dataset.datatype, Some(h5read::Datatype { class: 1, size: 4, signed: false }));
+    assert_eq!(dataset.dims, [2, 2, 3, 4]);
+    assert_eq!(dataset.data, data);
+    for (name, value) in [("width", 3u64), ("height", 2), ("depth", 2)] {
+        let (datatype, dims, bytes) = &dataset.attributes[name];
+        assert_eq!((datatype.class, datatype.size, dims.len()), (0, 8, 0));
+        assert_eq!(bytes[..], value.to_le_bytes());
+    }
+    let (datatype, dims, bytes) = &dataset.attributes["columns"];
+    assert_eq!((datatype.class, dims.as_slice()), (3, &[4u64][..]));
+    let columns: Vec<&str> = bytes.chunks(datatype.size).map(|column| std::str::from_utf8(column).unwrap().trim_end_matches('\0')).collect();
+    assert_eq!(columns, hdf5::LATTICE_COLUMNS);
+
+    let group = h5read::object(&file, root.links[hdf5::RESULTS_GROUP]);
+    let column = |name: &str, class: u8, size: usize| {
+        let dataset = h5read::object(&file, group.links[name]);
+        assert_eq!(dataset.datatype, Some(h5read::Datatype { class, size, signed: false }), "{}", name);
+        assert_eq!(dataset.dims, [results.len() as u64]);
+        dataset.data
+    };
+    let noise: Vec<f32> = column("noise_level", 1, 4).chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+    let total: Vec<u64> = column("total_bits", 0, 8).chunks(8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).collect();
+    let errors: Vec<u64> = column("error_bits", 0, 8).chunks(8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).collect();
+    let ber: Vec<f64> = column("ber", 1, 8).chunks(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect();
+    for (i, result) in results.iter().enumerate() {
+        assert_eq!((noise[i], total[i], errors[i], ber[i]), (result.noise_level, result.total_bits as u64, result.error_bits as u64, result.ber));
+    }
+
+    let mut results_only = Vec::new();
+    hdf5::write(&mut results_only, None, &results).unwrap();
+    assert!(results_only.len() < file.len());
+    let root = h5read::object(&results_only, h5read::root(&results_only));
+    assert_eq!(root.links.keys().collect::<Vec<_>>(), [hdf5::RESULTS_GROUP]);
+}
+
+#[test]
+fn test_sparse_lattices_and_containers() {
+    let codec = CodecConfig::default();
+    let blank = codec.modulate(0);
+    let mut sparse = SparseVoxelLattice::new(16, 16, 8, blank);
+    let written = encode_data_with_config(b"only layer five holds data", &codec);
+    for (i, voxel) in written.iter().enumerate() {
+        sparse.set(i % 16, i / 16, 5, *voxel);
+    }
+    sparse.set(0, 0, 7, PhotonicVoxel::new(0.5, 0.0, 0.0, 532.0));
+    sparse.set(0, 0, 7, blank);
+    assert_eq!(sparse.stored_len(), written.iter().filter(|voxel| **voxel != blank).count());
+    assert_eq!(sparse.get(3, 0, 5), Some(&written[3]));
+    assert_eq!(sparse.get(3, 0, 6), Some(&blank));
+    assert_eq!(sparse.get(16, 0, 0), None);
+    let dense = sparse.to_dense();
+    assert_eq!(dense.to_sparse(blank), sparse);
+    assert_eq!(sparse.iter_stored().count(), sparse.stored_len());
+
+    // Blank runs are stored as run lengths; the voxels and checksums are unchanged
+    let mut header = photon_core::ContainerHeader::new(&codec, 0);
+    header.sparse = Some(blank);
+    let encoded = Container::from_lattice(header, dense.clone());
+    let (mut file, mut plain) = (Vec::new(), Vec::new());
+    container::write(&mut file, &encoded).unwrap();
+    container::write(&mut plain, &Container::from_lattice(photon_core::ContainerHeader::new(&codec, 0), dense.clone())).unwrap();
+    assert!(file.len() * 20 < plain.len(), "{} vs {} bytes", file.len(), plain.len());
+    let read = container::read(&file[..]).unwrap();
+    assert_eq!(read.lattice().unwrap(), dense);
+    assert!(read.verify() && container::verify(&file[..]).unwrap().is_intact());
+
+    // Data containers read through the sparse payload too, ranges included
+    let data = [b"head".as_slice(), &[0u8; 3000], b"tail"].concat();
+    let mut encoded = Container::encode(&data, &codec, true);
+    encoded.header.sparse = Some(blank);
+    let mut file = Vec::new();
+    container::write(&mut file, &encoded).unwrap();
+    assert!(file.len() < encoded.voxels.len() * 16 / 2);
+    assert_eq!(container::read(&file[..]).unwrap().decode(false).unwrap(), data);
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 3000, 8).unwrap(), &data[3000..3008]);
+}
+
+#[test]
+fn test_multi_volume_split_and_join() {
+    let mut file = Vec::new();
+    let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+    container::write(&mut file, &Container::encode(&data, &CodecConfig::default(), true)).unwrap();
+
+    // Each new volume is written to the end of a shared list
+    struct LastVolume<'a>(&'a std::cell::RefCell<Vec<Vec<u8>>>);
+    impl std::io::Write for LastVolume<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().last_mut().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let split = |file: &[u8]| {
+        let volumes = std::cell::RefCell::new(Vec::new());
+        let count = volume::split(file, file.len() as u64, 4096, |sequence| {
+            assert_eq!(sequence as usize, volumes.borrow().len());
+            volumes.borrow_mut().push(Vec::new());
+            Ok(LastVolume(&volumes))
+        })
+        .unwrap();
+        (count, volumes.into_inner())
+    };
+    let (count, volumes) = split(&file);
+    assert_eq!(count as usize, volumes.len());
+    assert!(count > 3 && volumes.iter().all(|volume| volume.len() <= 4096 && volume::is_volume(volume)));
+
+    // Volumes join back in any order and read as the original container
+    let shuffled: Vec<&[u8]> = volumes.iter().rev().map(Vec::as_slice).collect();
+    let mut joined = Vec::new();
+    assert_eq!(volume::join(shuffled.clone(), &mut joined).unwrap(), file.len() as u64);
+    assert_eq!(joined, file);
+    let reader = JoinedVolumes::new(shuffled).unwrap();
+    assert_eq!(reader.total_len(), file.len() as u64);
+    assert_eq!(container::read(reader).unwrap().decode(false).unwrap(), data);
+
+    // A missing, corrupted or foreign volume is refused
+    let missing: Vec<&[u8]> = volumes.iter().skip(1).map(Vec::as_slice).collect();
+    assert_eq!(JoinedVolumes::new(missing).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+    let mut corrupted = volumes.clone();
+    corrupted[1][volume::VOLUME_HEADER_LEN + 10] ^= 1;
+    let err = volume::join(corrupted.iter().map(Vec::as_slice).collect(), std::io::sink()).unwrap_err();
+    assert!(err.to_string().contains("volume 1"), "{}", err);
+    let (_, other) = split(&file);
+    let mut mixed: Vec<&[u8]> = volumes.iter().map(Vec::as_slice).collect();
+    mixed[2] = &other[2];
+    assert_eq!(JoinedVolumes::new(mixed).err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+
+    // Volumes must have room for data
+    let err = volume::split(&file[..], file.len() as u64, volume::VOLUME_OVERHEAD as u64, |_| Ok(std::io::sink())).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_ecc_shard_counts_sweep() {
+    let codec = CodecConfig::default();
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 256) as u8).collect();
+    for ecc in [EccConfig::new(4, 2), EccConfig::default(), EccConfig::new(16, 16)] {
+        let encoded = add_error_correction_with(&data, &ecc);
+        assert_eq!(encoded.len(), ecc.encoded_len(data.len()));
+        assert_eq!(ecc.recover(&encoded, data.len()).unwrap(), data);
+
+        // The layout travels in the container header
+        let mut file = Vec::new();
+        container::write(&mut file, &Container::encode_with_ecc(&data, &codec, Some(ecc))).unwrap();
+        assert_eq!(container::inspect(&file[..]).unwrap().header.ecc, Some(ecc));
+        assert_eq!(container::read(&file[..]).unwrap().decode(false).unwrap(), data);
+
+        // Noiseless sweeps of every layout read the data back exactly
+        let results = run_ber_simulation_with_ecc(500, 2, 0.0, &codec, &ecc, &mut StdRng::seed_from_u64(9));
+        assert!(results.iter().all(|result| result.error_bits == 0 && result.total_bits == 4000));
+    }
+    assert!(EccConfig::new(200, 100).validate().is_err());
+}
+
+#[test]
+fn test_ecc_corrects_errors_at_unknown_locations() {
+    use rand::Rng;
+    let mut rng = StdRng::seed_from_u64(55);
+    let data: Vec<u8> = (0..3000).map(|_| rng.random()).collect();
+    for ecc in [EccConfig::default(), EccConfig::new(16, 16), EccConfig::new(64, 30)] {
+        let encoded = add_error_correction_with(&data, &ecc);
+        let shard_len = ecc.shard_len(data.len());
+
+        // Up to parity/2 random bytes of every codeword are corrupted
+        let mut received = encoded.clone();
+        let mut corrupted = 0;
+        for offset in 0..shard_len {
+            let errors = rng.random_range(0..=ecc.correctable_errors());
+            let mut shards: Vec<usize> = (0..ecc.total_shards()).collect();
+            for _ in 0..errors {
+                let shard = shards.swap_remove(rng.random_range(0..shards.len()));
+                received[shard * shard_len + offset] ^= rng.random_range(1..=255u8);
+                corrupted += 1;
+            }
+        }
+        assert_eq!(ecc.recover(&received, data.len()).unwrap(), data, "{:?}", ecc);
+        assert_eq!(recover_error_correction_with(&received, &ecc).unwrap()[..data.len()], data[..]);
+        let mut corrected = received.clone();
+        assert_eq!(correct_errors_with(&mut corrected, &ecc).unwrap(), corrupted);
+        assert_eq!(corrected, encoded);
+
+        // One error too many in a codeword is reported, the others still corrected
+        let mut received = encoded.clone();
+        for shard in 0..=ecc.correctable_errors() {
+            received[shard * shard_len] ^= 0x5A;
+        }
+        received[shard_len + 1] ^= 0x01;
+        assert!(ecc.recover(&received, data.len()).is_err());
+        let err = correct_errors_with(&mut received, &ecc).unwrap_err();
+        assert!(err.contains("1 of"), "{}", err);
+        assert_eq!(received[shard_len + 1], encoded[shard_len + 1]);
+    }
+
+    // A noisy read within the parity's reach comes back exact from a container
+    let codec = CodecConfig::default();
+    let mut encoded = Container::encode(&data, &codec, true);
+    for voxel in encoded.voxels.iter_mut().step_by(97) {
+        voxel.intensity = 1.0 - voxel.intensity;
+    }
+    assert_eq!(encoded.decode(false).unwrap(), data);
+}
+
+#[test]
+fn test_ecc_blocks_stream_and_contain_damage() {
+    use std::io::Read;
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 13 % 256) as u8).collect();
+    let ecc = EccConfig { block_shard_len: Some(16), ..EccConfig::default() };
+    assert_eq!(ecc.block_len(), Some(160));
+
+    // Six full blocks of 16-byte shards and a short last one, encoded independently
+    let encoded = add_error_correction_with(&data, &ecc);
+    assert_eq!(encoded.len(), ecc.encoded_len(data.len()));
+    assert_eq!(encoded.len(), 6 * 16 * 14 + 4 * 14);
+    assert_eq!(encoded[..224], add_error_correction_with(&data[..160], &ecc)[..]);
+    let whole = EccConfig { block_shard_len: None, ..ecc };
+    assert_eq!(add_error_correction_with(&data, &whole).len(), 100 * 14);
+
+    // Streaming gives the same bytes, and reads them back block by block
+    let mut streamed = Vec::new();
+    EccEncodingReader::new(&data[..], ecc).read_to_end(&mut streamed).unwrap();
+    assert_eq!(streamed, encoded);
+    let mut padded = encoded.clone();
+    padded.extend([0xAA; 3]); // symbol padding after the last block is not read
+    let mut decoded = Vec::new();
+    EccDecodingReader::new(&padded[..], ecc, data.len() as u64).unwrap().read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, data);
+
+    // Too many errors in one codeword only damage its own block
+    let mut damaged = encoded.clone();
+    let block = 224 * 2;
+    for shard in 0..3 {
+        damaged[block + shard * 16 + 5] ^= 0xFF;
+    }
+    damaged[10] ^= 0x01; // correctable, in block 0
+    let err = EccDecodingReader::new(&damaged[..], ecc, data.len() as u64).unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let mut reader = EccDecodingReader::new(&damaged[..], ecc, data.len() as u64).unwrap().lenient();
+    let mut decoded = Vec::new();
+    reader.read_to_end(&mut decoded).unwrap();
+    assert_eq!((reader.damaged_blocks(), reader.corrected_bytes()), (&[2][..], 1));
+    assert_eq!(decoded[..320], data[..320]);
+    assert_eq!(decoded[480..], data[480..]);
+    assert!(recover_error_correction_with(&damaged, &ecc).is_err());
+
+    // The block size travels in stream and container headers; ranges cross blocks
+    let codec = CodecConfig::default();
+    let mut header = photon_core::StreamHeader::new(&codec);
+    header.ecc = Some(ecc);
+    header.data_len = Some(data.len() as u64);
+    assert_eq!(read_header(&header.to_voxels(), false).unwrap().unwrap().0, header);
+    let mut file = Vec::new();
+    container::write(&mut file, &Container::encode_with_ecc(&data, &codec, Some(ecc))).unwrap();
+    assert_eq!(container::inspect(&file[..]).unwrap().header.ecc, Some(ecc));
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 150, 500).unwrap(), &data[150..650]);
+    assert_eq!(container::read(&file[..]).unwrap().decode(false).unwrap(), data);
+}
+
+#[test]
+fn test_secded_scheme_corrects_single_bits() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 29 % 256) as u8).collect();
+    let ecc = EccConfig { block_shard_len: Some(25), ..EccConfig::secded() };
+    assert_eq!(ecc.scheme, EccScheme::Secded);
+    assert_eq!(ecc.correctable_errors(), 1);
+    assert!(EccConfig { scheme: EccScheme::Secded, ..EccConfig::default() }.validate().is_err());
+
+    // One check byte per 8 data bytes
+    let encoded = add_error_correction_with(&data, &ecc);
+    assert_eq!(encoded.len(), 1000 / 8 * 9);
+    assert_eq!(encoded.len(), ecc.encoded_len(data.len()));
+
+    // A flipped bit in every codeword, in data and check shards, is corrected
+    let mut damaged = encoded.clone();
+    for codeword in 0..125 {
+        let (block, column) = (codeword / 25, codeword % 25);
+        let shard = codeword % 9;
+        damaged[block * 225 + shard * 25 + column] ^= 1 << (codeword % 8);
+    }
+    assert_eq!(recover_error_correction_with(&damaged, &ecc).unwrap(), data);
+    let mut corrected = damaged.clone();
+    assert_eq!(correct_errors_with(&mut corrected, &ecc), Ok(125));
+    assert_eq!(corrected, encoded);
+
+    // Two flipped bits in one codeword are detected, not miscorrected
+    let mut damaged = encoded.clone();
+    damaged[3] ^= 0x01;
+    damaged[25 + 3] ^= 0x10;
+    assert!(correct_errors_with(&mut damaged, &ecc).unwrap_err().contains("1 of"));
+
+    // The scheme travels in stream and container headers
+    let codec = CodecConfig::default();
+    let mut header = photon_core::StreamHeader::new(&codec);
+    header.ecc = Some(ecc);
+    header.data_len = Some(data.len() as u64);
+    assert_eq!(read_header(&header.to_voxels(), false).unwrap().unwrap().0, header);
+    let mut file = Vec::new();
+    container::write(&mut file, &Container::encode_with_ecc(&data, &codec, Some(ecc))).unwrap();
+    assert_eq!(container::inspect(&file[..]).unwrap().header.ecc, Some(ecc));
+    assert_eq!(container::read_range(std::io::Cursor::new(&file), 250, 300).unwrap(), &data[250..550]);
+    assert_eq!(container::read(&file[..]).unwrap().decode(false).unwrap(), data);
+}
+
+#[cfg(feature = "ldpc")]
+#[test]
+fn test_ldpc_soft_decoding_waterfall() {
+    use photon_core::ldpc::{LdpcCode, LdpcConfig};
+    use photon_core::{decode_soft, run_ber_simulation_with_ldpc, run_ber_simulation_with_rng};
+
+    let config = LdpcConfig::default();
+    assert_eq!(config.rate(), 0.5);
+    assert!(LdpcConfig { data_bits: 12, ..config }.validate().is_err());
+    let code = LdpcCode::new(&config).unwrap();
+
+    // Systematic: the data bytes lead every codeword
+    let data: Vec<u8> = (0..300u32).map(|i| (i * 37 % 256) as u8).collect();
+    let encoded = code.encode(&data);
+    assert_eq!(encoded.len(), config.encoded_len(data.len()));
+    assert_eq!(encoded.len(), 3 * 256);
+    assert_eq!(encoded[..128], data[..128]);
+    let decoded = code.decode_soft(&decode_soft(&encode_data(&encoded)), data.len());
+    assert_eq!((decoded.data, decoded.failed_codewords), (data.clone(), vec![]));
+
+    // Bits flipped with little confidence and erased bits are recovered from
+    // the soft values of the others
+    let mut soft = decode_soft(&encode_data(&encoded));
+    for byte in (0..soft.len()).step_by(5) {
+        soft[byte].llrs[byte % 8] = -soft[byte].llrs[byte % 8].signum();
+        soft[byte + 1].llrs = [0.0; 8];
+    }
+    soft.truncate(soft.len() - 16); // parity of the last codeword cut short
+    assert_eq!(code.decode_soft(&soft, data.len()).data, data);
+
+    // Past the uncoded error floor, the coded sweep is clean
+    let uncoded = run_ber_simulation_with_rng(1000, 4, 0.4, &CodecConfig::default(), &mut StdRng::seed_from_u64(3));
+    let coded = run_ber_simulation_with_ldpc(1000, 4, 0.4, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(3));
+    assert!(uncoded[2].error_bits > 0, "{:?}", uncoded);
+    assert_eq!(coded[2].error_bits, 0, "{:?}", coded);
+    assert!(coded[4].error_bits <= uncoded[4].error_bits, "{:?} vs {:?}", coded, uncoded);
+}
+
+#[test]
+fn test_convolutional_viterbi_corrects_random_bit_errors() {
+    use photon_core::{decode_soft, run_ber_simulation_with_convolutional, run_ber_simulation_with_rng, ConvolutionalCode};
+
+    let code = ConvolutionalCode::default();
+    assert!(ConvolutionalCode { generators: [0o171, 0o400], ..code }.validate().is_err());
+    let data: Vec<u8> = (0..500u32).map(|i| (i * 53 % 256) as u8).collect();
+    let encoded = code.encode(&data);
+    assert_eq!(encoded.len(), code.encoded_len(data.len()));
+    assert_eq!(encoded.len(), 1002); // 4012 coded bits
+    assert_eq!(code.decode(&encoded, data.len()), data);
+
+    // Scattered bit errors, one in every 40 coded bits, are corrected by the
+    // hard-decision decoder
+    let mut damaged = encoded.clone();
+    for bit in (7..damaged.len() * 8).step_by(40) {
+        damaged[bit / 8] ^= 1 << (bit % 8);
+    }
+    assert_eq!(code.decode(&damaged, data.len()), data);
+
+    // With soft decisions, a dense burst of weak errors and erasures as well
+    let mut soft = decode_soft(&encode_data(&encoded));
+    for byte in (0..soft.len()).step_by(6) {
+        soft[byte].llrs[byte % 8] = -soft[byte].llrs[byte % 8].signum();
+        soft[byte + 1].llrs[3] = 0.0;
+    }
+    assert_eq!(code.decode_soft(&soft, data.len()), data);
+
+    // Random symbol errors from the noise model are mostly gone
+    let uncoded = run_ber_simulation_with_rng(1000, 4, 0.4, &CodecConfig::default(), &mut StdRng::seed_from_u64(7));
+    let coded = run_ber_simulation_with_convolutional(1000, 4, 0.4, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(7));
+    assert!(uncoded[2].error_bits > 0, "{:?}", uncoded);
+    assert_eq!(coded[2].error_bits, 0, "{:?}", coded);
+    assert!(coded[4].error_bits * 4 < uncoded[4].error_bits, "{:?} vs {:?}", coded, uncoded);
+}
+
+#[test]
+fn test_concatenated_code_clears_inner_decoder_bursts() {
+    use photon_core::{decode_soft, run_ber_simulation_with_concatenated, run_ber_simulation_with_convolutional, ConvolutionalCode, SoftByte};
+
+    let outer = EccConfig { block_shard_len: Some(1), ..EccConfig::default() };
+    let code = outer.concatenated(ConvolutionalCode::default(), 16);
+    assert!(outer.concatenated(ConvolutionalCode::default(), 0).validate().is_err());
+    let data: Vec<u8> = (0..600u32).map(|i| (i * 71 % 256) as u8).collect();
+    let encoded = code.encode(&data);
+    assert_eq!(encoded.len(), code.encoded_len(data.len()));
+    assert_eq!(encoded.len(), ConvolutionalCode::default().encoded_len(840));
+
+    // Wiping 80 coded bits defeats the Viterbi decoder, which leaves a burst of
+    // bytes; deinterleaved, it is at most one byte per outer codeword
+    let mut soft = decode_soft(&encode_data(&encoded));
+    for byte in &mut soft[400..410] {
+        *byte = SoftByte { llrs: [-1.0; 8] };
+    }
+    let inner_only = ConvolutionalCode::default().decode_soft(&soft, 840);
+    assert_ne!(inner_only, add_error_correction_with(&data, &outer));
+    assert_eq!(code.decode_soft(&soft, data.len()).unwrap(), data);
+
+    // Without the interleaver the burst sinks whole codewords
+    let plain = outer.concatenated(ConvolutionalCode::default(), 1);
+    let mut soft = decode_soft(&encode_data(&plain.encode(&data)));
+    for byte in &mut soft[400..410] {
+        *byte = SoftByte { llrs: [-1.0; 8] };
+    }
+    assert!(plain.decode_soft(&soft, data.len()).is_err());
+
+    // The outer code cleans up what the inner decoder leaves in the sweep
+    let inner = run_ber_simulation_with_convolutional(1000, 1, 0.6, &CodecConfig::default(), &ConvolutionalCode::default(), &mut StdRng::seed_from_u64(2));
+    let both = run_ber_simulation_with_concatenated(1000, 1, 0.6, &CodecConfig::default(), &code, &mut StdRng::seed_from_u64(2));
+    assert!(both[1].error_bits < inner[1].error_bits, "{:?} vs {:?}", both, inner);
+}
+
+#[test]
+fn test_low_confidence_voxels_become_erasures() {
+    use photon_core::run_ber_simulation_with_erasures;
+
+    let codec = CodecConfig::default();
+    let ecc = EccConfig::default();
+    let data: Vec<u8> = (0..100u32).map(|i| (i * 19 % 256) as u8).collect();
+    let encoded = add_error_correction_with(&data, &ecc);
+    let mut voxels = encode_data_with_config(&encoded, &codec);
+
+    // Three shards of the first codeword read just past a decision boundary:
+    // one more than the parity corrects at unknown locations
+    for voxel in [0, 10, 20] {
+        let wrong = codec.modulate(encoded[voxel] as u32 ^ 1);
+        voxels[voxel].intensity = 0.45 * voxels[voxel].intensity + 0.55 * wrong.intensity;
+    }
+    let (mut received, confidence) = decode_data_with_confidence(&voxels, false, &codec);
+    assert_eq!(received[10], encoded[10] ^ 1);
+    let erasures = erasures_from_confidence(&confidence, codec.bits_per_voxel(), 0.25);
+    assert_eq!(erasures.iter().enumerate().filter(|(_, &erased)| erased).map(|(byte, _)| byte).collect::<Vec<_>>(), vec![0, 10, 20]);
+    assert!(recover_error_correction_with(&received, &ecc).is_err());
+    assert_eq!(recover_error_correction_with_erasures(&received, &erasures, &ecc).unwrap(), data);
+
+    // Two erasures leave room for one error nothing flagged
+    let mut flagged = erasures.clone();
+    flagged[20] = false;
+    received[30] ^= 0xFF;
+    received[20] = encoded[20];
+    assert_eq!(correct_errors_and_erasures_with(&mut received, &flagged, &ecc), Ok(3));
+    assert_eq!(received, encoded);
+    assert!(correct_errors_and_erasures_with(&mut received, &flagged[1..], &ecc).is_err());
+
+    // Voxels of 6 bits straddle bytes
+    let mut uncertain = confidence[..4].to_vec();
+    uncertain[1].min_relative = 0.0;
+    assert_eq!(erasures_from_confidence(&uncertain, 6, 0.25), vec![true, true, false]);
+
+    // In the noise sweep, the flags help the decoder where the noise starts
+    let plain = run_ber_simulation_with_ecc(10_000, 10, 0.2, &codec, &ecc, &mut StdRng::seed_from_u64(1));
+    let flagged = run_ber_simulation_with_erasures(10_000, 10, 0.2, &codec, &ecc, 0.2, &mut StdRng::seed_from_u64(1));
+    assert!(plain[7].error_bits > 0, "{:?}", plain);
+    assert!(flagged[7].error_bits < plain[7].error_bits, "{:?} vs {:?}", flagged, plain);
+}
+
+#[test]
+fn test_fountain_recovers_from_any_sufficient_subset() {
+    let data: Vec<u8> = (0..5_000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+    let encoder = FountainEncoder::new(&data, 50);
+    assert_eq!(encoder.source_symbols(), 100);
+    assert_eq!(encoder.symbol(3).data, data[150..200]);
+
+    // Repair written in two rounds, long after the data, never repeats a symbol
+    let mut symbols: Vec<FountainSymbol> = encoder.repair_symbols(0, 60).collect();
+    symbols.extend(encoder.repair_symbols(60, 60));
+    assert_eq!(symbols.last().unwrap().id, 219);
+    let stored: Vec<Vec<u8>> = symbols.iter().map(FountainSymbol::to_bytes).collect();
+    let symbols: Vec<FountainSymbol> = stored.iter().map(|bytes| FountainSymbol::from_bytes(bytes).unwrap()).collect();
+
+    // The source region is lost apart from every fourth symbol
+    let survivors: Vec<FountainSymbol> = (0..100).step_by(4).map(|id| encoder.symbol(id)).chain(symbols).collect();
+    let mut decoder = FountainDecoder::new(data.len(), 50);
+    let mut used = 0;
+    for symbol in &survivors {
+        decoder.add(symbol);
+        used += 1;
+        if decoder.is_complete() {
+            break;
+        }
+    }
+    assert!(decoder.is_complete() && used < 120, "rank {} after {} symbols", decoder.rank(), used);
+    assert_eq!(decoder.decode().unwrap(), data);
+
+    // Fewer symbols than source symbols can never be enough
+    let mut short = FountainDecoder::new(data.len(), 50);
+    for symbol in encoder.repair_symbols(500, 99) {
+        short.add(&symbol);
+    }
+    assert!(!short.is_complete() && short.decode().is_none());
+    assert!(!short.add(&FountainSymbol { id: 7, data: vec![0; 49] }));
+    assert!(FountainSymbol::from_bytes(&[1, 2]).is_err());
+}
+
+#[test]
+fn test_layer_parity_rebuilds_lost_layers() {
+    let config = CodecConfig { wavelengths: vec![405.0, 450.0, 488.0, 532.0, 561.0, 594.0, 640.0, 700.0], ..CodecConfig::default() };
+    let geometry = LatticeGeometry::for_codec(16, 16, &config);
+    let parity = LayerParity::for_geometry(&geometry, 3, 2).unwrap();
+    assert_eq!(parity.layer_bytes, 288);
+    assert!(LayerParity::for_geometry(&LatticeGeometry::for_codec(3, 1, &config), 3, 2).is_err());
+
+    let data: Vec<u8> = (0..2_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let encoded = parity.encode(&data);
+    assert_eq!(encoded.len(), parity.encoded_len(data.len()));
+    assert_eq!(encoded.len(), 15 * 288);
+    assert!(parity.is_parity_layer(3) && !parity.is_parity_layer(5));
+
+    // A data and a parity layer of the first group and a data layer of the
+    // second are wiped out in the crystal
+    let voxels = encode_data_with_config(&encoded, &config);
+    let mut lattice = VoxelLattice::from_voxels(16, 16, voxels).unwrap();
+    for z in [1, 3, 7] {
+        for voxel in lattice.layer_mut(z) {
+            voxel.intensity = 0.7;
+        }
+    }
+    let received = decode_data_with_config(lattice.voxels(), false, &config);
+    assert_ne!(received[288..576], encoded[288..576]);
+    assert_eq!(parity.decode(&received, &[1, 3, 7], data.len()).unwrap(), data);
+
+    // Three layers of one group are more than its two parity layers
+    assert!(parity.decode(&received, &[1, 2, 3], data.len()).is_err());
+    assert!(parity.decode(&received, &[15], data.len()).is_err());
+    assert!(parity.decode(&received[1..], &[], data.len()).is_err());
+}
+
+#[test]
+fn test_ecc_overhead_and_capacity_reporting() {
+    let ecc = EccConfig::default();
+    assert!((ecc.overhead_ratio() - 4.0 / 14.0).abs() < 1e-12);
+    assert_eq!(ecc.correctable_errors(), 2);
+    assert!((EccConfig::secded().overhead_ratio() - 1.0 / 9.0).abs() < 1e-12);
+
+    // Capacity inverts the encoded length, across full and partial blocks
+    for config in [EccConfig::new(10, 4), EccConfig { block_shard_len: Some(3), ..EccConfig::new(5, 2) }, EccConfig { block_shard_len: None, ..EccConfig::new(7, 3) }] {
+        for bytes in [0, 1, 13, 14, 100, 1_000, 573_445] {
+            let capacity = config.effective_capacity(bytes);
+            assert!(config.encoded_len(capacity) <= bytes, "{:?}: {} bytes", config, bytes);
+            assert!(config.encoded_len(capacity + 1) > bytes, "{:?}: {} bytes", config, bytes);
+        }
+    }
+    assert_eq!(ecc.effective_capacity(14_000), 10_000);
+}
+
+#[test]
+fn test_container_repair_rebuilds_corrupted_chunks() {
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 17 % 251) as u8).collect();
+    let mut encoded = Container::encode(&data, &CodecConfig::default(), true);
+    encoded = Container::new(container::ContainerHeader { chunk_voxels: 1000, ..encoded.header }, encoded.voxels);
+    let original = encoded.voxels.clone();
+
+    // Wipe out chunk 5 and nudge a voxel of chunk 20 without changing its symbol
+    let mut damaged = encoded.clone();
+    for voxel in &mut damaged.voxels[5_000..6_000] {
+        *voxel = PhotonicVoxel::new(0.5, 0.0, 0.0, 405.0);
+    }
+    damaged.voxels[20_500].intensity += 1e-4;
+    let mut file = Vec::new();
+    container::write(&mut file, &damaged).unwrap();
+    let mut damaged = container::read(&file[..]).unwrap();
+    assert_eq!(damaged.corrupted_chunks(), vec![5, 20]);
+
+    let report = damaged.repair();
+    assert!(report.is_complete(), "{:?}", report);
+    assert_eq!(report.repaired_chunks, vec![5, 20]);
+    assert_eq!(report.repaired_voxels, damaged.voxels[5_000..6_000].len() + 1);
+    assert!(report.corrected_bytes > 900);
+    assert_eq!(damaged.voxels, original);
+    assert!(damaged.corrupted_chunks().is_empty());
+
+    // Without ECC, only damage that left every symbol readable is repaired
+    let mut plain = Container::encode(&data, &CodecConfig::default(), false);
+    plain = Container::new(container::ContainerHeader { chunk_voxels: 1000, ..plain.header }, plain.voxels);
+    let original = plain.voxels.clone();
+    plain.voxels[2_100] = PhotonicVoxel::new(0.5, 0.0, 0.0, 405.0);
+    plain.voxels[7_100].phase += 1e-4;
+    let report = plain.repair();
+    assert_eq!((report.repaired_chunks, report.unrecoverable_chunks), (vec![7], vec![2]));
+    assert_eq!(plain.voxels[7_000..8_000], original[7_000..8_000]);
+    assert_ne!(plain.voxels[2_100], original[2_100]);
+}
+
+#[test]
+fn test_ecc_encoding_writer_streams_blocks() {
+    use std::io::{Read, Write};
+
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i * 13 % 256) as u8).collect();
+    for config in [EccConfig { block_shard_len: Some(64), ..EccConfig::new(10, 4) }, EccConfig { block_shard_len: None, ..EccConfig::new(6, 2) }] {
+        let mut writer = EccEncodingWriter::new(Vec::new(), config);
+        // Writes of odd sizes straddle the block boundaries
+        for chunk in data.chunks(333) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(encoded, add_error_correction_with(&data, &config));
+
+        let mut decoded = Vec::new();
+        EccDecodingReader::new(&encoded[..], config, data.len() as u64).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    // Full blocks reach the inner writer before the input ends
+    let config = EccConfig { block_shard_len: Some(10), ..EccConfig::new(10, 4) };
+    let mut out = Vec::new();
+    let mut writer = EccEncodingWriter::new(&mut out, config);
+    writer.write_all(&data[..250]).unwrap();
+    drop(writer);
+    assert_eq!(out, add_error_correction_with(&data[..200], &config));
+}
+
+#[test]
+fn test_ecc_recommendation_meets_target_ber() {
+    let noisy: NoiseProfile = NoiseParams::uniform(0.14).into();
+    let relaxed = ecc::recommend(&noisy, 1e-3).unwrap();
+    let strict = ecc::recommend(&noisy, 1e-6).unwrap();
+    assert!(relaxed.validate().is_ok() && strict.validate().is_ok());
+    assert!(relaxed.overhead_ratio() < strict.overhead_ratio(), "{:?} vs {:?}", relaxed, strict);
+    assert!(ecc::recommend(&NoiseParams::uniform(0.05).into(), 1e-4).unwrap().overhead_ratio() < 0.01);
+    assert!(ecc::recommend(&NoiseParams::uniform(0.3).into(), 1e-6).is_err());
+
+    // The residual error model behind it agrees with simulated decoding
+    let channel = estimate_channel(20_000, &noisy, &CodecConfig::default(), &mut StdRng::seed_from_u64(3));
+    assert!(channel.byte_error_rate > channel.bit_error_rate && channel.single_bit_fraction > 0.5, "{:?}", channel);
+    assert!(predicted_residual_ber(&channel, &relaxed) < 2e-3);
+    for config in [EccConfig::new(10, 4), EccConfig::new(20, 8), EccConfig::secded()] {
+        let simulated = run_ber_simulation_with_ecc(20_000, 1, 0.14, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(7))[1].ber;
+        let predicted = predicted_residual_ber(&channel, &config);
+        assert!(predicted / 2.0 < simulated && simulated < predicted * 2.0, "{:?}: {} simulated, {} predicted", config, simulated, predicted);
+    }
+}
+
+#[test]
+fn test_decode_with_fec_uses_soft_information() {
+    let profile: NoiseProfile = NoiseParams::uniform(0.13).into();
+    let config = EccConfig { block_shard_len: Some(1), ..EccConfig::new(10, 4) };
+    let data: Vec<u8> = (0..3000u32).map(|i| (i * 37 % 251) as u8).collect();
+    let encoded = add_error_correction_with(&data, &config);
+    let voxels = photon_core::apply_noise_with_rng(&encode_data(&encoded), profile, &mut StdRng::seed_from_u64(5));
+
+    // Hard decisions leave a codeword with more errors than parity can fix,
+    // but erasing its least reliable bytes repairs it
+    assert!(correct_errors_with(&mut decode_data(&voxels, false), &config).is_err());
+    let decoded = decode_voxels_with_fec(&voxels, &CodecConfig::default(), &config, &profile, data.len()).unwrap();
+    assert_eq!(decoded.data, data);
+    let report = decoded.report;
+    assert_eq!((report.codewords, report.failed_codewords), (300, 0));
+    assert!(report.corrected_bytes > 0 && report.corrected_bits >= report.corrected_bytes, "{:?}", report);
+
+    // Far too much noise fails with the report of what could be done
+    let noisy: NoiseProfile = NoiseParams::uniform(0.16).into();
+    let voxels = photon_core::apply_noise_with_rng(&encode_data(&encoded), noisy, &mut StdRng::seed_from_u64(5));
+    let report: FecReport = decode_voxels_with_fec(&voxels, &CodecConfig::default(), &config, &noisy, data.len()).unwrap_err();
+    assert!(report.failed_codewords > 0 && report.corrected_bits > 0, "{:?}", report);
+
+    // LLRs from any demodulator go straight in; missing bytes are erasures
+    let voxels = encode_data(&encoded);
+    let llrs = decode_soft_with_profile(&voxels, &CodecConfig::default(), &profile);
+    assert_eq!(decode_with_fec(&llrs[..llrs.len() - 2], &config, data.len()).unwrap().data, data);
+    let short = &voxels[..voxels.len() - 2];
+    assert_eq!(decode_voxels_with_fec(short, &CodecConfig::default(), &config, &profile, data.len()).unwrap().data, data);
+}
+
+#[test]
+fn test_psf_convolution_matches_crosstalk_and_fft() {
+    let data: Vec<u8> = (0..2_000u32).map(|i| (i * 37 % 251) as u8).collect();
+    let lattice = VoxelLattice::from_voxels_padded(16, 16, encode_data(&data), PhotonicVoxel::new(0.0, 0.0, 0.0, 650.0)).unwrap();
+    let close = |a: &VoxelLattice, b: &VoxelLattice| a.iter().zip(b.iter()).all(|(a, b)| (a.intensity - b.intensity).abs() < 1e-5);
+
+    // The 6-neighbour kernel blurs intensities like simulate_crosstalk, and only them
+    let six = simulate_psf(&lattice, &PsfKernel::six_neighbor(0.02));
+    assert!(close(&six, &simulate_crosstalk(&lattice, 0.02)));
+    assert!(six.iter().zip(lattice.iter()).all(|(a, b)| (a.polarization, a.phase) == (b.polarization, b.phase)));
+    assert!(PsfKernel::new((3, 2, 1), vec![0.0; 6]).is_err());
+    assert!(PsfKernel::new((3, 3, 1), vec![0.0; 6]).is_err());
+
+    let kernel = PsfKernel::gaussian(0.6, 1.0);
+    assert_eq!(kernel.size(), (5, 5, 7));
+    assert_eq!((kernel.weight(0, 0, 0), kernel.weight(3, 0, 0)), (1.0, 0.0));
+    assert!(kernel.weight(1, 0, 0) < kernel.weight(0, 0, 1));
+    let blurred = simulate_psf(&lattice, &kernel);
+    assert!(blurred.iter().zip(lattice.iter()).all(|(b, v)| b.intensity >= v.intensity && b.intensity <= 1.5));
+
+    #[cfg(feature = "fft")]
+    assert!(close(&photon_core::simulate_psf_fft(&lattice, &kernel), &blurred));
+}
+
+#[test]
+fn test_attenuation_raises_ber_with_depth() {
+    let lattice = VoxelLattice::new(4, 4, 3, PhotonicVoxel::new(1.0, 0.5, 1.0, 650.0));
+    let attenuated = simulate_attenuation(&lattice, 0.1);
+    for z in 0..3 {
+        let voxel = attenuated.get(1, 2, z).unwrap();
+        assert!((voxel.intensity - (-0.2 * z as f32).exp()).abs() < 1e-6);
+        assert_eq!((voxel.polarization, voxel.phase), (0.5, 1.0));
+    }
+
+    let shape = LatticeShape { width: 32, height: 32, depth: 24 };
+    let noise: NoiseProfile = NoiseParams::uniform(0.08).into();
+    let layers = run_ber_by_depth(shape, 0.02, &noise, &CodecConfig::default(), &mut StdRng::seed_from_u64(4));
+    assert_eq!(layers.len(), 24);
+    assert_eq!((layers[0].attenuation, layers[0].error_bits, layers[0].total_bits), (1.0, 0, 32 * 32 * 8));
+    let shallow: usize = layers[..8].iter().map(|l| l.error_bits).sum();
+    let deep: usize = layers[16..].iter().map(|l| l.error_bits).sum();
+    assert!(deep > 2 * shallow && shallow > 0, "{} vs {}", shallow, deep);
+
+    // Without absorption every layer reads alike
+    let flat = run_ber_by_depth(shape, 0.0, &noise, &CodecConfig::default(), &mut StdRng::seed_from_u64(4));
+    assert!(flat.iter().all(|l| l.attenuation == 1.0 && l.ber < 1e-3));
+}
+
+#[test]
+fn test_aberration_widens_psf_with_depth() {
+    let psf = AberratedPsf { sigma_lateral: 0.3, sigma_axial: 0.3, aberration_coefficient: 0.05 };
+    assert_eq!(psf.kernel_at(0).size(), (3, 3, 3));
+    assert!(psf.kernel_at(8).size().0 > 3);
+    for z in [0, 8] {
+        let kernel = psf.kernel_at(z);
+        let (kx, ky, kz) = kernel.size();
+        let (rx, ry, rz) = ((kx / 2) as isize, (ky / 2) as isize, (kz / 2) as isize);
+        let total: f32 = (-rz..=rz).flat_map(|dz| (-ry..=ry).flat_map(move |dy| (-rx..=rx).map(move |dx| (dx, dy, dz)))).map(|(dx, dy, dz)| kernel.weight(dx, dy, dz)).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+    assert!(psf.kernel_at(8).weight(0, 0, 0) < psf.kernel_at(0).weight(0, 0, 0));
+
+    // Deep layers read more of their neighbours, and so more errors
+    let data: Vec<u8> = (0..16 * 16 * 12u32).map(|i| (i * 97 % 251) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    let blurred = simulate_aberration(&lattice, &psf);
+    let layer_errors = |z: usize| decode_data(blurred.layer(z), false).iter().zip(&data[z * 256..(z + 1) * 256]).filter(|(a, b)| a != b).count();
+    let errors: Vec<usize> = (0..12).map(layer_errors).collect();
+    assert!(errors[..4].iter().all(|&e| e == 0) && errors[8..].iter().all(|&e| e > 0), "{:?}", errors);
+
+    // Without aberration every layer reads through the same spot
+    let flat = AberratedPsf { aberration_coefficient: 0.0, ..psf };
+    assert_eq!(flat.kernel_at(0), flat.kernel_at(11));
+}
+
+#[test]
+fn test_shot_noise_is_poisson_in_intensity() {
+    // Variance intensity / photons, other dimensions untouched
+    let profile = NoiseProfile::shot(200);
+    let voxel = PhotonicVoxel::new(0.5, 0.3, 1.0, 650.0);
+    let mut rng = StdRng::seed_from_u64(6);
+    let reads: Vec<PhotonicVoxel> = (0..20_000).map(|_| profile.perturb_with_rng(&voxel, &mut rng)).collect();
+    let mean = reads.iter().map(|v| v.intensity as f64).sum::<f64>() / reads.len() as f64;
+    let variance = reads.iter().map(|v| (v.intensity as f64 - mean).powi(2)).sum::<f64>() / reads.len() as f64;
+    assert!((mean - 0.5).abs() < 0.005 && (variance - 0.5 / 200.0).abs() < 0.0003, "{} {}", mean, variance);
+    assert!(reads.iter().all(|v| (v.polarization, v.phase, v.wavelength) == (0.3, 1.0, 650.0)));
+    assert!(reads.iter().all(|v| (v.intensity * 200.0 - (v.intensity * 200.0).round()).abs() < 1e-3), "photon counts are whole");
+    assert!((profile.intensity_sigma_at(0.5) - (0.5f32 / 200.0).sqrt()).abs() < 1e-6);
+
+    // Enough photons read cleanly, few corrupt the intensity bits only
+    let data: Vec<u8> = (0..4_000u32).map(|i| (i * 37 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+    assert_eq!(decode_data_noisy_with_rng(&voxels, NoiseProfile::shot(100_000), &mut rng), data);
+    let dim = decode_data_noisy_with_rng(&voxels, NoiseProfile::shot(30), &mut rng);
+    let flips: Vec<u8> = dim.iter().zip(&data).map(|(a, b)| a ^ b).filter(|&flips| flips != 0).collect();
+    assert!(flips.len() > 100 && flips.iter().all(|&flips| flips & !0b11 == 0));
+
+    // Scaling the profile scales the photon count: no noise, then less light
+    let sweep = run_ber_simulation_with_profile(4_000, 4, &NoiseProfile::shot(30), &CodecConfig::default(), &mut rng);
+    assert_eq!(sweep[0].error_bits, 0);
+    assert!(sweep.windows(2).all(|w| w[0].ber <= w[1].ber) && sweep[4].ber > 0.01, "{:?}", sweep);
+    assert_eq!(NoiseProfile::shot(30).scaled(0.5).distribution, NoiseDistribution::Shot { photons_per_voxel: 120 });
+}
+
+#[test]
+fn test_detector_model_quantizes_and_adds_noise() {
+    let detector = DetectorModel::default();
+    assert!(detector.validate().is_ok());
+    assert!(DetectorModel { adc_bits: 0, ..detector }.validate().is_err());
+    assert!(DetectorModel { gain: 0.0, ..detector }.validate().is_err());
+    assert_eq!(detector.full_scale(), 4095);
+    assert!((detector.max_intensity() - 1.6375).abs() < 1e-4);
+    assert!((detector.intensity_step() - 4e-4).abs() < 1e-9);
+
+    // Readouts land on ADC steps around the true intensity, clipping at full scale
+    let mut rng = StdRng::seed_from_u64(8);
+    let voxel = PhotonicVoxel::new(0.5, 0.3, 1.0, 650.0);
+    let reads: Vec<PhotonicVoxel> = detector.read_voxels_with_rng(&vec![voxel; 5_000], &mut rng);
+    let mean = reads.iter().map(|v| v.intensity as f64).sum::<f64>() / reads.len() as f64;
+    assert!((mean - 0.5).abs() < 1e-3, "{}", mean);
+    assert!(reads.iter().all(|v| (v.polarization, v.phase, v.wavelength) == (0.3, 1.0, 650.0)));
+    assert!(reads.iter().all(|v| {
+        let count = (v.intensity * detector.photons_per_voxel + detector.dark_current) / detector.gain;
+        (count - count.round()).abs() < 1e-2
+    }));
+    assert_eq!(detector.read_voxel_with_rng(&PhotonicVoxel::new(3.0, 0.0, 0.0, 650.0), &mut rng).intensity, detector.max_intensity());
+
+    // A coarse, noisy chain corrupts intensity bits; an ideal one reads back the data
+    let data: Vec<u8> = (0..2_000u32).map(|i| (i * 37 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+    assert_eq!(decode_data(&DetectorModel::ideal().read_voxels_with_rng(&voxels, &mut rng), false), data);
+    let poor = DetectorModel { photons_per_voxel: 40.0, dark_current: 20.0, read_noise: 8.0, adc_bits: 4, gain: 4.0 };
+    let decoded = decode_data(&poor.read_voxels_with_rng(&voxels, &mut rng), false);
+    let flips: Vec<u8> = decoded.iter().zip(&data).map(|(a, b)| a ^ b).filter(|&flips| flips != 0).collect();
+    assert!(flips.len() > 200 && flips.iter().all(|&flips| flips & !0b11 == 0));
+}
+
+#[test]
+fn test_crosstalk_perturbs_polarization_and_phase() {
+    use std::f32::consts::PI;
+
+    let at = |polarization: f32, phase: f32| PhotonicVoxel::new(1.0, polarization, phase, 650.0);
+    let mut lattice = VoxelLattice::new(3, 1, 1, at(0.0, 0.0));
+
+    // Neighbours at the same angle and phase change nothing but intensity
+    let same = simulate_crosstalk(&lattice, 0.1);
+    assert_eq!((same.get(1, 0, 0).unwrap().polarization, same.get(1, 0, 0).unwrap().phase), (0.0, 0.0));
+
+    // A neighbour at 45° pulls the angle towards it, one at 90° only dilutes it
+    lattice.set(0, 0, 0, at(PI / 4.0, 0.0));
+    let pulled = simulate_crosstalk(&lattice, 0.1).get(1, 0, 0).unwrap().polarization;
+    assert!(pulled > 0.04 && pulled < 0.06, "{}", pulled);
+    lattice.set(0, 0, 0, at(PI / 2.0, 0.0));
+    let diluted = simulate_crosstalk(&lattice, 0.1).get(1, 0, 0).unwrap().polarization;
+    assert!(diluted.min(PI - diluted) < 1e-6, "{}", diluted);
+
+    // Phase shifts towards a co-polarized neighbour, not towards an orthogonal one
+    lattice.set(0, 0, 0, at(0.0, PI / 2.0));
+    let shifted = simulate_crosstalk(&lattice, 0.1).get(1, 0, 0).unwrap().phase;
+    assert!((shifted - (0.1f32 / 1.1).atan()).abs() < 1e-5, "{}", shifted);
+    lattice.set(0, 0, 0, at(PI / 2.0, PI / 2.0));
+    let voxel = *simulate_crosstalk(&lattice, 0.1).get(1, 0, 0).unwrap();
+    assert!(voxel.phase.min(2.0 * PI - voxel.phase) < 1e-6 && (voxel.intensity - 1.2).abs() < 1e-6);
+
+    // Enough crosstalk corrupts polarization and phase bits too
+    let data: Vec<u8> = (0..1_000u32).map(|i| (i * 37 % 256) as u8).collect();
+    let blurred = simulate_crosstalk(&VoxelLattice::from_voxels_padded(10, 10, encode_data(&data), at(0.0, 0.0)).unwrap(), 0.3);
+    let decoded = decode_data(blurred.voxels(), false);
+    assert!(decoded.iter().zip(&data).any(|(a, b)| (a ^ b) & 0b1100 != 0));
+    assert!(decoded.iter().zip(&data).any(|(a, b)| (a ^ b) & 0b11_0000 != 0));
+}
+
+#[test]
+fn test_spectral_crosstalk_isolates_wavelengths() {
+    assert_eq!(spectral_overlap(0.0, 10.0), 1.0);
+    assert!((spectral_overlap(20.0, 10.0) - (-1.0f64).exp()).abs() < 1e-12);
+    assert!(spectral_overlap(-118.0, 10.0) < 1e-14);
+    assert_eq!((spectral_overlap(0.0, 0.0), spectral_overlap(1.0, 0.0)), (1.0, 0.0));
+
+    // A single wavelength couples as much as plain crosstalk
+    let data: Vec<u8> = (0..4_096u32).map(|i| (i * 37 % 256) as u8).collect();
+    let mono = CodecConfig { wavelengths: vec![650.0], ..CodecConfig::default() };
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data_with_config(&data[..768], &mono)).unwrap();
+    assert_eq!(simulate_spectral_crosstalk(&lattice, 0.05, 10.0), simulate_crosstalk(&lattice, 0.05));
+
+    // Spectrally multiplexed neighbours barely leak into each other
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    let errors = |blurred: VoxelLattice| decode_data(blurred.voxels(), false).iter().zip(&data).filter(|(a, b)| a != b).count();
+    let flat = errors(simulate_crosstalk(&lattice, 0.05));
+    let isolated = errors(simulate_spectral_crosstalk(&lattice, 0.05, 10.0));
+    assert!(isolated * 4 < flat, "{} vs {}", isolated, flat);
+    assert_eq!(errors(simulate_spectral_crosstalk(&lattice, 0.05, 1e4)), flat);
+}
+
+#[test]
+fn test_crosstalk_config_widens_neighbourhood() {
+    let count = |connectivity, radius| CrosstalkConfig { connectivity, radius, ..CrosstalkConfig::new(0.01) }.offsets().len();
+    assert_eq!((count(Connectivity::Six, 1), count(Connectivity::Eighteen, 1), count(Connectivity::TwentySix, 1)), (6, 18, 26));
+    assert_eq!((count(Connectivity::Six, 2), count(Connectivity::TwentySix, 2)), (24, 124));
+    assert!(CrosstalkConfig { radius: 0, ..CrosstalkConfig::new(0.01) }.validate().is_err());
+    assert!(CrosstalkConfig::new(-0.01).validate().is_err());
+
+    // The default config is plain 6-neighbour crosstalk
+    let data: Vec<u8> = (0..4_096u32).map(|i| (i * 37 % 256) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    assert_eq!(simulate_crosstalk_with_config(&lattice, &CrosstalkConfig::new(0.05)), simulate_crosstalk(&lattice, 0.05));
+
+    // In a uniform lattice the centre gathers factor / d² from every neighbour
+    let uniform = VoxelLattice::from_voxels(5, 5, vec![PhotonicVoxel::new(0.5, 0.0, 0.0, 650.0); 125]).unwrap();
+    let centre = |connectivity, radius| -> f32 {
+        let config = CrosstalkConfig { connectivity, radius, ..CrosstalkConfig::new(0.01) };
+        simulate_crosstalk_with_config(&uniform, &config).get(2, 2, 2).unwrap().intensity
+    };
+    assert!((centre(Connectivity::Six, 1) - 0.53).abs() < 1e-6);
+    assert!((centre(Connectivity::Eighteen, 1) - 0.56).abs() < 1e-6);
+    assert!((centre(Connectivity::TwentySix, 1) - (0.56 + 8.0 * 0.005 / 3.0)).abs() < 1e-6);
+    assert!(centre(Connectivity::Six, 2) > centre(Connectivity::Six, 1));
+    // A corner voxel has fewer neighbours inside the lattice
+    let corner = simulate_crosstalk_with_config(&uniform, &CrosstalkConfig { connectivity: Connectivity::TwentySix, ..CrosstalkConfig::new(0.01) });
+    assert!(corner.get(0, 0, 0).unwrap().intensity < corner.get(2, 2, 2).unwrap().intensity);
+}
+
+#[test]
+fn test_write_model_distorts_commanded_intensities() {
+    let data: Vec<u8> = (0..4_096u32).map(|i| (i * 37 % 256) as u8).collect();
+    let voxels = encode_data(&data);
+    let mut rng = StdRng::seed_from_u64(80);
+
+    // An ideal writer records what it is told
+    let ideal = simulate_write_with_rng(&voxels, &WriteParams::ideal(), &mut rng);
+    assert!(ideal.iter().zip(&voxels).all(|(w, v)| (w.intensity - v.intensity).abs() < 1e-6 && w.polarization == v.polarization && w.phase == v.phase));
+
+    // A saturating material compresses the levels: the low ones come out too
+    // strong, the top one too weak, and the decoder misreads them
+    let params = WriteParams { energy_jitter: 0.0, ..WriteParams::default() };
+    let levels: Vec<f32> = [0.25, 0.5, 0.75, 1.0].iter().map(|&i| params.recorded(i)).collect();
+    assert!(levels.windows(2).all(|w| w[0] < w[1]));
+    assert!(levels[0] > 0.25 && levels[3] < 1.0);
+    let written = simulate_write_with_rng(&voxels, &params, &mut rng);
+    assert_ne!(decode_data(&written, false), data);
+
+    // Below the threshold nothing is written
+    let weak = WriteParams { response: MaterialResponse::Saturating { threshold_nj: 400.0, saturation_nj: 400.0 }, ..params };
+    assert_eq!(weak.recorded(0.25), 0.0);
+
+    // Pulse jitter averages down over more, weaker pulses of the same dose
+    let mut spread = |pulse_energy_nj, pulses| {
+        let params = WriteParams { pulse_energy_nj, pulses, energy_jitter: 0.1, ..WriteParams::ideal() };
+        let written = simulate_write_with_rng(&vec![PhotonicVoxel::new(0.5, 0.0, 0.0, 650.0); 2_000], &params, &mut rng);
+        (written.iter().map(|v| (v.intensity - 0.5).powi(2)).sum::<f32>() / written.len() as f32).sqrt()
+    };
+    let (single, many) = (spread(1000.0, 1), spread(10.0, 100));
+    assert!((single - 0.05).abs() < 0.005, "{}", single);
+    assert!(many < single / 5.0, "{} vs {}", many, single);
+
+    assert!(WriteParams { pulses: 0, ..WriteParams::default() }.validate().is_err());
+    assert!(WriteParams { response: MaterialResponse::Linear { full_dose_nj: 0.0 }, ..WriteParams::default() }.validate().is_err());
+}
+
+#[test]
+fn test_rewrite_fatigue_limits_endurance() {
+    let model = FatigueModel::default();
+    assert_eq!(model.contrast_at(0), [1.0; 3]);
+    assert_eq!(model.noise_at(0).sigmas(), [0.0; 4]);
+    assert!((model.noise_at(100).intensity - 0.02).abs() < 1e-6);
+    assert!(FatigueModel { phase_loss: 1.0, ..model }.validate().is_err());
+
+    // A fresh medium records what it is told; a worn one with less contrast
+    let voxels = encode_data(&(0..=255).collect::<Vec<u8>>());
+    let mut rng = StdRng::seed_from_u64(81);
+    assert_eq!(simulate_rewrite_cycles_with_rng(&voxels, 0, &model, &mut rng), voxels);
+    let quiet = FatigueModel { damage_noise: NoiseProfile::none(), ..model };
+    let worn = simulate_rewrite_cycles_with_rng(&voxels, 500, &quiet, &mut rng);
+    let contrast = quiet.contrast_at(500);
+    assert!(worn.iter().zip(&voxels).all(|(w, v)| (w.intensity - v.intensity * contrast[0]).abs() < 1e-6 && w.wavelength == v.wavelength));
+    assert!(contrast[0] < contrast[2] && contrast[2] < contrast[1]);
+
+    // Errors grow with the cycles until the ECC gives up
+    let ecc = EccConfig { block_shard_len: Some(1), ..EccConfig::new(10, 4) };
+    let results = run_endurance(2_000, (0..=600).step_by(50), &model, &NoiseProfile::none(), &CodecConfig::default(), &ecc, &mut rng);
+    assert_eq!(results.len(), 13);
+    assert_eq!((results[0].raw_ber, results[0].ber, results[0].failed_codewords), (0.0, 0.0, 0));
+    assert!(results.last().unwrap().raw_ber > results[1].raw_ber);
+    let limit = endurance_limit(&results).expect("the medium wears out within 600 cycles");
+    assert!((100..=600).contains(&limit), "{}", limit);
+    assert!(results.iter().filter(|r| r.cycles < limit).all(|r| r.ber == 0.0));
+    assert!(results.iter().any(|r| r.raw_ber > 0.0 && r.ber == 0.0), "the ECC absorbs early wear");
+}
+
+#[test]
+fn test_defect_map_reads_and_erases_flawed_voxels() {
+    let shape = LatticeShape { width: 32, height: 32, depth: 8 };
+    let mut rng = StdRng::seed_from_u64(82);
+
+    // Random defects reach the density, isolated or in clusters
+    let isolated = DefectMap::random(shape, &DefectConfig { density: 0.01, ..DefectConfig::default() }, &mut rng);
+    assert!((82..=90).contains(&isolated.len()), "{}", isolated.len());
+    assert!(isolated.iter().all(|(index, defect)| index < shape.len() && *defect == Defect::Dead));
+    let clustered = DefectMap::random(shape, &DefectConfig { density: 0.01, cluster_radius: 1.0, stuck_fraction: 1.0, ..DefectConfig::default() }, &mut rng);
+    let clustered_neighbours = clustered.iter().filter(|(index, _)| clustered.get(index + 1).is_some()).count();
+    let isolated_neighbours = isolated.iter().filter(|(index, _)| isolated.get(index + 1).is_some()).count();
+    assert!(clustered_neighbours > 10 * isolated_neighbours.max(1), "{} vs {}", clustered_neighbours, isolated_neighbours);
+    assert!(clustered.iter().all(|(_, defect)| matches!(defect, Defect::Stuck(_))));
+    assert!(DefectConfig { density: 1.5, ..DefectConfig::default() }.validate().is_err());
+
+    // Dead voxels read blank, stuck ones read their fixed voxel
+    let stuck = PhotonicVoxel::new(1.0, 0.0, 0.0, 532.0);
+    let mut map = DefectMap::new();
+    map.insert(1, Defect::Dead);
+    map.insert(2, Defect::Stuck(stuck));
+    map.insert(1_000, Defect::Dead);
+    let voxels = encode_data(&[0xff, 0xff, 0xff]);
+    let read = map.apply(&voxels);
+    assert_eq!(read.len(), 3);
+    assert_eq!((read[0], read[2]), (voxels[0], stuck));
+    assert_eq!((read[1].intensity, read[1].polarization, read[1].wavelength), (0.0, 0.0, voxels[1].wavelength));
+
+    // Known defects cost the ECC erasures rather than errors
+    let data: Vec<u8> = (0..2_000u32).map(|i| (i * 31 % 256) as u8).collect();
+    let ecc = EccConfig { block_shard_len: Some(1), ..EccConfig::new(10, 4) };
+    let encoded = add_error_correction_with(&data, &ecc);
+    let config = CodecConfig::default();
+    let geometry = LatticeGeometry::for_codec(32, 32, &config);
+    let defects = DefectMap::random(shape, &DefectConfig { density: 0.04, ..DefectConfig::default() }, &mut rng);
+    let received = decode_data_with_config(&defects.apply(&encode_data_with_config(&encoded, &config)), false, &config);
+    assert!(recover_error_correction_with(&received, &ecc).is_err());
+    let erasures = defects.erasures(&geometry, received.len());
+    assert_eq!(erasures.iter().filter(|&&erased| erased).count(), defects.iter().filter(|(index, _)| *index < encoded.len()).count());
+    assert_eq!(recover_error_correction_with_erasures(&received, &erasures, &ecc), Ok(data));
+}
+
+#[test]
+fn test_birefringence_drift_perturbs_weak_voxels_most() {
+    use std::f32::consts::PI;
+
+    let mut rng = StdRng::seed_from_u64(83);
+    let voxel = BirefringentVoxel::new(PI / 4.0, 60.0);
+    let unchanged = BirefringenceDrift::none().drift(&voxel, &mut rng);
+    assert!((unchanged.slow_axis - voxel.slow_axis).abs() < 1e-6 && (unchanged.retardance_nm - 60.0).abs() < 1e-4);
+
+    // Warming scales the retardance and leaves the axis alone
+    let warm = BirefringenceDrift { temperature_delta_k: 20.0, thermal_coefficient: 1e-3, ..BirefringenceDrift::none() };
+    let warmed = warm.drift(&voxel, &mut rng);
+    assert!((warmed.retardance_nm - 61.2).abs() < 1e-4 && (warmed.slow_axis - voxel.slow_axis).abs() < 1e-6);
+
+    // Stress along x turns weak voxels further towards x than strong ones
+    let stress = BirefringenceDrift { stress_retardance_nm: 5.0, ..BirefringenceDrift::none() };
+    let turn = |retardance_nm| PI / 4.0 - stress.drift(&BirefringentVoxel::new(PI / 4.0, retardance_nm), &mut StdRng::seed_from_u64(0)).slow_axis;
+    assert!(turn(30.0) > 3.0 * turn(120.0) && turn(120.0) > 0.0);
+    assert!(BirefringenceDrift { residual_stress_nm: -1.0, ..BirefringenceDrift::none() }.validate().is_err());
+
+    // The default drift is mild enough to decode; heavy residual stress is not
+    let data: Vec<u8> = (0..2_048u32).map(|i| (i * 37 % 256) as u8).collect();
+    let config = BirefringentConfig::default();
+    let voxels = encode_birefringent(&data, &config);
+    assert_eq!(decode_birefringent(&simulate_birefringence_drift_with_rng(&voxels, &BirefringenceDrift::default(), &mut rng), &config), data);
+    let stressed = BirefringenceDrift { residual_stress_nm: 8.0, ..BirefringenceDrift::default() };
+    assert_ne!(decode_birefringent(&simulate_birefringence_drift_with_rng(&voxels, &stressed, &mut rng), &config), data);
+
+    // Through the codec, polarization errors land on the dim voxels
+    let voxels = encode_data(&data);
+    let drifted = simulate_polarization_drift_with_rng(&voxels, &stressed, &mut rng);
+    let angle_error = |intensity: f32| {
+        let errors: Vec<f32> = voxels.iter().zip(&drifted).filter(|(v, _)| (v.intensity - intensity).abs() < 1e-6).map(|(v, d)| {
+            let delta = (d.polarization - v.polarization).rem_euclid(PI);
+            delta.min(PI - delta)
+        }).collect();
+        errors.iter().sum::<f32>() / errors.len() as f32
+    };
+    assert!(angle_error(0.25) > 2.5 * angle_error(1.0), "{} vs {}", angle_error(0.25), angle_error(1.0));
+    assert!(voxels.iter().zip(&drifted).all(|(v, d)| v.phase == d.phase && v.wavelength == d.wavelength));
+}
+
+#[test]
+fn test_scattering_haze_grows_with_written_depth() {
+    // The haze of a voxel is the scattered share of what is written above it
+    let mut column = VoxelLattice::new(1, 1, 4, PhotonicVoxel::new(0.5, 0.0, 0.0, 650.0));
+    column.set(0, 0, 1, PhotonicVoxel::new(0.0, 0.0, 0.0, 650.0));
+    let hazy = simulate_scattering(&column, 0.1);
+    let intensities: Vec<f32> = hazy.iter().map(|v| v.intensity).collect();
+    let expected = [0.5, 0.05, 0.55, 0.6];
+    assert!(intensities.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", intensities);
+    assert_eq!(simulate_scattering(&column, 0.0), column);
+
+    // Dense lattices lose contrast in their deep layers first
+    let data: Vec<u8> = (0..16_384u32).map(|i| (i * 37 % 256) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(32, 32, encode_data(&data)).unwrap();
+    let decoded = decode_data(simulate_scattering(&lattice, 0.02).voxels(), false);
+    let layer_errors: Vec<usize> = decoded.chunks(1_024).zip(data.chunks(1_024)).map(|(a, b)| a.iter().zip(b).filter(|(x, y)| x != y).count()).collect();
+    assert!(layer_errors[..8].iter().all(|&errors| errors == 0), "{:?}", layer_errors);
+    assert!(layer_errors.windows(2).all(|w| w[0] <= w[1]) && layer_errors[15] > layer_errors[9] && layer_errors[9] > 0, "{:?}", layer_errors);
+}
+
+#[test]
+fn test_optics_config_derives_spot_and_crosstalk() {
+    let optics = OpticsConfig::default();
+    assert!(optics.validate().is_ok());
+    assert!((optics.spot_diameter_um() - 1.057).abs() < 1e-3);
+    assert!((optics.lateral_fwhm_um() - 0.442).abs() < 1e-3);
+    assert!((optics.min_voxel_pitch_um() - 0.529).abs() < 1e-3);
+    assert!(optics.min_layer_spacing_um() > 4.0 * optics.lateral_fwhm_um());
+
+    // The kernel's lateral neighbour weight is the crosstalk factor
+    let factor = optics.crosstalk_factor();
+    assert!((0.02..0.04).contains(&factor), "{}", factor);
+    let kernel = optics.psf_kernel();
+    assert_eq!(kernel.weight(0, 0, 0), 1.0);
+    assert!((kernel.weight(1, 0, 0) - factor).abs() < 1e-6 && (kernel.weight(0, -1, 0) - factor).abs() < 1e-6);
+
+    // Packing voxels tighter or reading with less NA raises the crosstalk
+    let dense = OpticsConfig { voxel_pitch_um: 0.3, ..optics };
+    let dim = OpticsConfig { numerical_aperture: 0.5, ..optics };
+    assert!(dense.crosstalk_factor() > 5.0 * factor && dim.crosstalk_factor() > factor);
+    assert!(dense.sigma_lateral() > optics.sigma_lateral());
+
+    assert!(OpticsConfig { numerical_aperture: 1.5, ..optics }.validate().is_err());
+    assert!(OpticsConfig { voxel_pitch_um: 0.0, ..optics }.validate().is_err());
+}
+
+#[test]
+fn test_focus_error_mixes_adjacent_layers() {
+    let focus = FocusError { sigma_lateral: 0.3, sigma_axial: 0.4, layer_offsets: vec![0.0, 1.0, -0.5] };
+    assert_eq!((focus.widening_at(0), focus.widening_at(5)), (1.0, 1.0));
+    assert!(focus.widening_at(1) > focus.widening_at(2) && focus.widening_at(2) > 1.0);
+    for z in 0..3 {
+        let total: f32 = (-3..=3).flat_map(|dz| (-3..=3).flat_map(move |dy| (-3..=3).map(move |dx| (dx, dy, dz)))).map(|(dx, dy, dz)| focus.kernel_at(z).weight(dx, dy, dz)).sum();
+        assert!((total - 1.0).abs() < 1e-5, "{}", total);
+    }
+    // In focus the spot peaks on the layer itself; one layer deep it reads the next layer
+    assert!(focus.kernel_at(0).weight(0, 0, 0) > 0.5);
+    assert!(focus.kernel_at(1).weight(0, 0, -1) > 10.0 * focus.kernel_at(1).weight(0, 0, 0));
+
+    let mut lattice = VoxelLattice::new(4, 4, 3, PhotonicVoxel::new(0.25, 0.0, 0.0, 650.0));
+    for voxel in lattice.layer_mut(2) {
+        voxel.intensity = 1.0;
+    }
+    let read = simulate_focus_error(&lattice, &focus);
+    assert!(read.get(1, 1, 1).unwrap().intensity > 0.9, "{:?}", read.get(1, 1, 1));
+    assert!(read.get(1, 1, 0).unwrap().intensity < 0.3);
+
+    // The BER grows with the servo error
+    let shape = LatticeShape { width: 16, height: 16, depth: 8 };
+    let results = run_ber_by_focus_error(shape, 0.3, 0.4, [0.0, 0.2, 0.5, 1.0], &NoiseProfile::none(), &CodecConfig::default(), &mut StdRng::seed_from_u64(86));
+    assert_eq!(results.len(), 4);
+    assert_eq!((results[0].error_bits, results[0].total_bits), (0, 16 * 16 * 8 * 8));
+    assert!(results[3].ber > results[2].ber && results[2].ber > results[1].ber, "{:?}", results);
+}
+
+#[test]
+fn test_equalization_undoes_crosstalk() {
+    let data: Vec<u8> = (0..4_096u32).map(|i| (i * 37 % 256) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+
+    // Without noise the written intensities come back, up to the saturation
+    for config in [CrosstalkConfig::new(0.08), CrosstalkConfig { connectivity: Connectivity::TwentySix, ..CrosstalkConfig::new(0.03) }] {
+        let blurred = simulate_crosstalk_with_config(&lattice, &config);
+        let restored = equalize(&blurred, &config);
+        let worst = restored.iter().zip(lattice.iter()).zip(blurred.iter()).filter(|(_, b)| b.intensity < 1.5).map(|((r, v), _)| (r.intensity - v.intensity).abs()).fold(0.0, f32::max);
+        assert!(worst < 1e-4, "{}", worst);
+        assert!(restored.iter().zip(blurred.iter()).all(|(r, b)| r.polarization == b.polarization && r.phase == b.phase));
+    }
+
+    // With readout noise equalization still cuts the BER
+    let shape = LatticeShape { width: 16, height: 16, depth: 8 };
+    let results = run_ber_with_equalization(shape, &CrosstalkConfig::new(0.0), [0.0, 0.06, 0.1], &NoiseProfile::gaussian(0.02, 0.0, 0.0, 0.0), &CodecConfig::default(), &mut StdRng::seed_from_u64(87));
+    assert_eq!(results.len(), 3);
+    assert!(results[0].error_bits < 10 && results[0].equalized_error_bits < 10, "{:?}", results[0]);
+    for result in &results[1..] {
+        assert!(result.equalized_ber * 10.0 < result.ber, "{:?}", result);
+    }
+}
+
+#[test]
+fn test_aging_follows_arrhenius_decay() {
+    // Room temperature keeps data practically forever; heat speeds decay up
+    let room = AgingParams::default();
+    assert!(room.retention() > 1.0 - 1e-12);
+    assert!(room.years_to_retention(0.875) > 1e15);
+    let hot = AgingParams::new(900.0, 0.01);
+    assert!(hot.decay_rate_per_year() > 1e15 * room.decay_rate_per_year());
+    assert!((AgingParams { years: hot.years_to_retention(0.5), ..hot }.retention() - 0.5).abs() < 1e-9);
+    assert!((AgingParams::new(1273.0, 1.0 / 8766.0).retention() - (-1.0f64).exp()).abs() < 1e-9);
+    assert!(AgingParams::new(0.0, 1.0).validate().is_err());
+
+    // Intensities and retardances shrink by the retention
+    let lattice = VoxelLattice::new(2, 2, 2, PhotonicVoxel::new(1.0, 0.5, 1.0, 650.0));
+    let aged = simulate_aging(&lattice, &hot);
+    let retention = hot.retention() as f32;
+    assert!(aged.iter().all(|v| (v.intensity - retention).abs() < 1e-6 && v.polarization == 0.5 && v.phase == 1.0));
+    let nanogratings = simulate_birefringent_aging(&[BirefringentVoxel::new(0.5, 120.0)], &hot);
+    assert!((nanogratings[0].retardance_nm - 120.0 * retention).abs() < 1e-3 && nanogratings[0].slow_axis == 0.5);
+
+    // Data read fine until the top level decays past its decision boundary
+    let lifetime = hot.years_to_retention(0.875);
+    let shape = LatticeShape { width: 16, height: 16, depth: 4 };
+    let results = run_ber_by_age(shape, &hot, [0.0, 0.9 * lifetime, 1.2 * lifetime, 5.0 * lifetime], &NoiseProfile::none(), &CodecConfig::default(), &mut StdRng::seed_from_u64(88));
+    assert_eq!((results[0].error_bits, results[1].error_bits), (0, 0));
+    assert!(results[3].ber > results[2].ber && results[2].ber > 0.0, "{:?}", results);
+}
+
+#[test]
+fn test_channel_pipeline_composes_stages_in_order() {
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&[0x5Au8; 1024])).unwrap();
+    let write = WriteParams::default();
+    let aging = AgingParams::new(900.0, 0.001);
+    let crosstalk = CrosstalkConfig::new(0.02);
+    let noise = NoiseProfile::gaussian(0.02, 0.02, 0.02, 0.0);
+    let channel = ChannelPipeline::new()
+        .stage(write)
+        .stage(aging)
+        .stage(crosstalk)
+        .stage(Attenuation { absorption_coefficient: 0.01 })
+        .stage(noise);
+    assert_eq!(channel.len(), 5);
+
+    // Same result as composing the effects by hand with the same generator
+    let read = channel.apply_with_rng(&lattice, &mut StdRng::seed_from_u64(89));
+    let mut rng = StdRng::seed_from_u64(89);
+    let written = VoxelLattice::from_voxels(16, 16, simulate_write_with_rng(lattice.voxels(), &write, &mut rng)).unwrap();
+    let manual = simulate_attenuation(&simulate_crosstalk_with_config(&simulate_aging(&written, &aging), &crosstalk), 0.01);
+    let manual = VoxelLattice::from_voxels(16, 16, photon_core::apply_noise_with_rng(manual.voxels(), noise, &mut rng)).unwrap();
+    assert_eq!(read, manual);
+
+    // An empty channel changes nothing, and pipelines nest as stages
+    assert_eq!(ChannelPipeline::new().apply(&lattice), lattice);
+    let nested = ChannelPipeline::new().stage(ChannelPipeline::new().stage(write).stage(aging)).stage(crosstalk);
+    let flat = ChannelPipeline::new().stage(write).stage(aging).stage(crosstalk);
+    assert_eq!(nested.apply_with_rng(&lattice, &mut StdRng::seed_from_u64(1)), ChannelStage::apply(&flat, &lattice, &mut StdRng::seed_from_u64(1)));
+
+    // Order matters: haze from a lattice already dimmed by absorption is fainter
+    let scatter = Scattering { scattering_coefficient: 0.02 };
+    let absorb = Attenuation { absorption_coefficient: 0.05 };
+    let absorb_first = ChannelPipeline::new().stage(absorb).stage(scatter).apply(&lattice);
+    let scatter_first = ChannelPipeline::new().stage(scatter).stage(absorb).apply(&lattice);
+    assert_ne!(absorb_first, scatter_first);
+}
+
+#[test]
+fn test_position_jitter_mixes_neighbours() {
+    let mut lattice = VoxelLattice::new(8, 8, 4, PhotonicVoxel::new(0.0, 0.0, 0.0, 532.0));
+    lattice.set(4, 4, 2, PhotonicVoxel::new(1.0, 0.5, 1.0, 532.0));
+
+    // A perfectly repeatable stage reads the lattice as written
+    let still = simulate_position_jitter_with_rng(&lattice, &PositionJitter::new(0.0, 0.0), &mut StdRng::seed_from_u64(90));
+    assert_eq!(still, lattice);
+    assert!(PositionJitter::new(-0.1, 0.0).validate().is_err());
+
+    // Jitter along x only leaks the written voxel into its x neighbours
+    let jitter = PositionJitter { sigma_x: 0.2, sigma_y: 0.0, sigma_z: 0.0 };
+    let read = simulate_position_jitter_with_rng(&lattice, &jitter, &mut StdRng::seed_from_u64(90));
+    let centre = read.get(4, 4, 2).unwrap();
+    assert!(centre.intensity < 1.0 && centre.intensity > 0.3 && centre.polarization == 0.5);
+    let leaked: f32 = [(3, 4, 2), (5, 4, 2)].iter().map(|&(x, y, z)| read.get(x, y, z).unwrap().intensity).sum();
+    assert!(leaked > 0.0);
+    assert!(read.iter_indexed().all(|((x, y, z), v)| (y, z) == (4, 2) && (3..=5).contains(&x) || v.intensity == 0.0));
+
+    // Wider jitter garbles more of an encoded lattice
+    let data: Vec<u8> = (0..2048u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    let written = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    let wrong = |sigma: f32| {
+        let read = simulate_position_jitter_with_rng(&written, &PositionJitter::new(sigma, sigma / 2.0), &mut StdRng::seed_from_u64(90));
+        decode_data(&read.into_voxels(), false).iter().zip(&data).filter(|(a, b)| a != b).count()
+    };
+    let (small, large) = (wrong(0.05), wrong(0.4));
+    assert!(large > small, "{} vs {}", small, large);
+}
+
+#[test]
+fn test_sensor_response_saturates_and_compensates() {
+    // The default keeps the hard clip at 1.5
+    let clamp = SensorResponse::default();
+    assert_eq!((clamp.read(0.7), clamp.read(2.0)), (0.7, 1.5));
+
+    // Soft saturation is nearly linear for weak signals, never clips, and inverts
+    let soft = SensorResponse::Soft { saturation: 1.2 };
+    assert!((soft.read(0.05) - 0.05).abs() < 1e-4);
+    assert!(soft.read(1.0) < 1.0 && soft.read(3.0) > soft.read(2.0) && soft.read(3.0) < 1.2);
+    let gamma = SensorResponse::Gamma { gamma: 0.8, saturation: 1.5 };
+    for response in [soft, gamma] {
+        for intensity in [0.0, 0.25, 0.6, 1.1] {
+            assert!((response.linearize(response.read(intensity)) - intensity).abs() < 1e-9, "{:?} at {}", response, intensity);
+        }
+    }
+    assert!(SensorResponse::Soft { saturation: 0.0 }.validate().is_err());
+    assert!(CrosstalkConfig { response: SensorResponse::Gamma { gamma: -1.0, saturation: 1.5 }, ..CrosstalkConfig::new(0.01) }.validate().is_err());
+
+    // A standalone response round-trips a lattice below saturation
+    let lattice = VoxelLattice::from_voxels(16, 16, encode_data(&[0xA5u8; 512])).unwrap();
+    let read = simulate_sensor_response(&lattice, &soft);
+    assert_ne!(read, lattice);
+    let restored = compensate_sensor_response(&read, &soft);
+    assert!(restored.iter().zip(lattice.iter()).all(|(a, b)| (a.intensity - b.intensity).abs() < 1e-5));
+
+    // The equalizer linearizes the soft response before undoing the crosstalk
+    let data: Vec<u8> = (0..2048u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    let written = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    let config = CrosstalkConfig { response: soft, ..CrosstalkConfig::new(0.06) };
+    let crosstalk = simulate_crosstalk_with_config(&written, &config);
+    let intensity_errors = |lattice: &VoxelLattice| lattice.iter().zip(written.iter()).filter(|(a, b)| (a.intensity - b.intensity).abs() > 0.1).count();
+    let linear_only = equalize(&crosstalk, &CrosstalkConfig::new(0.06));
+    let compensated = equalize(&crosstalk, &config);
+    assert!(intensity_errors(&compensated) < intensity_errors(&linear_only), "{} vs {}", intensity_errors(&compensated), intensity_errors(&linear_only));
+    assert!(intensity_errors(&compensated) * 20 < written.len());
+}
+
+#[test]
+fn test_material_presets_parameterize_every_stage() {
+    let presets = [Material::fused_silica(), Material::doped_glass(), Material::photopolymer()];
+    for material in &presets {
+        assert!(material.validate().is_ok(), "{}", material.name);
+        assert_eq!(material.optics().refractive_index, material.refractive_index);
+        assert_eq!(material.write_params().response, material.response);
+        assert_eq!(material.birefringence_drift().max_retardance_nm, material.max_retardance_nm);
+        assert_eq!(material.birefringent_config().max_retardance_nm, material.max_retardance_nm);
+        assert_eq!(material.attenuation().absorption_coefficient, material.absorption_coefficient);
+    }
+    assert_eq!("doped-glass".parse::<Material>(), Ok(Material::doped_glass()));
+    assert!("unobtainium".parse::<Material>().is_err());
+    assert!(Material { refractive_index: 0.5, ..Material::default() }.validate().is_err());
+
+    // Fused silica keeps data at room temperature; the photopolymer does not
+    let (silica, polymer) = (&presets[0], &presets[2]);
+    assert_eq!(silica.aging(293.15, 1000.0).activation_energy_ev, AgingParams::new(293.15, 1000.0).activation_energy_ev);
+    assert!(silica.aging(293.15, 1000.0).retention() > 1.0 - 1e-12);
+    let lifetime = polymer.aging(293.15, 0.0).years_to_retention(0.875);
+    assert!((1.0..100.0).contains(&lifetime), "{}", lifetime);
+
+    // The same pipeline reads worse in the more absorbing medium
+    let data: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 9) as u8).collect();
+    let written = VoxelLattice::from_voxels(16, 16, encode_data(&data)).unwrap();
+    let wrong = |material: &Material| {
+        let channel = ChannelPipeline::new().stage(material.aging(293.15, 20.0)).stage(material.attenuation());
+        decode_data(&channel.apply(&written).into_voxels(), false).iter().zip(&data).filter(|(a, b)| a != b).count()
+    };
+    assert_eq!(wrong(silica), 0);
+    assert!(wrong(polymer) > 0);
+}
+
+#[test]
+fn test_crosstalk_tiles_large_lattices_seamlessly() {
+    // 24 layers of 64x64 voxels: processed in z-slabs, the first ending at layer 15
+    let data: Vec<u8> = (0..64 * 64 * 24u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 7) as u8).collect();
+    let lattice = VoxelLattice::from_voxels(64, 64, encode_data(&data)).unwrap();
+    let layer = 64 * 64;
+    // Layers 12..20 alone, small enough to be read in a single pass
+    let window = VoxelLattice::from_voxels(64, 64, lattice.voxels()[12 * layer..20 * layer].to_vec()).unwrap();
+
+    let config = CrosstalkConfig { connectivity: Connectivity::TwentySix, ..CrosstalkConfig::new(0.02) };
+    let kernel = PsfKernel::gaussian(0.6, 0.5).normalized();
+    let whole = (simulate_crosstalk_with_config(&lattice, &config), simulate_psf(&lattice, &kernel));
+    let part = (simulate_crosstalk_with_config(&window, &config), simulate_psf(&window, &kernel));
+    // Layers 14..18 see all their neighbours (two layers away) in both, across the slab boundary
+    assert_ne!(whole.1.layer(15), lattice.layer(15));
+    for z in 14..18 {
+        assert_eq!(whole.0.layer(z), part.0.layer(z - 12), "crosstalk layer {}", z);
+        assert_eq!(whole.1.layer(z), part.1.layer(z - 12), "psf layer {}", z);
+    }
+}
+
+#[test]
+fn test_read_power_trades_snr_for_bleaching() {
+    // More power collects more photons with the same ADC range, and bleaches faster
+    let (nominal, strong) = (ReadPower::default(), ReadPower::new(4.0));
+    assert_eq!(strong.detector().photons_per_voxel, 4.0 * nominal.detector().photons_per_voxel);
+    assert!((strong.detector().max_intensity() - nominal.detector().max_intensity()).abs() < 0.01);
+    assert!((strong.bleaching() - 16.0 * nominal.bleaching()).abs() < 1e-15);
+    assert!((nominal.retention_after(1_000_000) - (-1.0f64).exp()).abs() < 1e-3);
+    assert!(ReadPower::new(0.0).validate().is_err());
+    let lattice = VoxelLattice::new(2, 2, 2, PhotonicVoxel::new(1.0, 0.5, 1.0, 650.0));
+    let bleached = simulate_read_bleaching(&lattice, &strong, 1000);
+    assert!(bleached.iter().all(|v| (v.intensity - strong.retention_after(1000) as f32).abs() < 1e-6 && v.polarization == 0.5));
+
+    // Few reads favour high power, many reads low power
+    let shape = LatticeShape { width: 32, height: 32, depth: 4 };
+    let powers = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+    let sweep = |reads| run_ber_by_read_power(shape, &ReadPower::default(), reads, powers, &CodecConfig::default(), &mut StdRng::seed_from_u64(94));
+    let once = sweep(1);
+    assert!(once[0].ber > once[2].ber && once[5].error_bits == 0, "{:?}", once);
+    let often = sweep(10_000);
+    assert_eq!(optimal_read_power(&often), Some(2.0));
+    assert!(often[0].ber > often[3].ber && often[5].ber > often[3].ber, "{:?}", often);
+    assert_eq!(optimal_read_power(&sweep(1_000_000)), Some(0.25));
+    assert_eq!(optimal_read_power(&[]), None);
+}
+
+#[test]
+fn test_write_interference_sets_safe_pitch() {
+    let interference = WriteInterference::default();
+    assert!((interference.exposure(1, 0, 0) - 0.059).abs() < 0.001);
+    assert!(interference.exposure(0, 0, 1) < 1e-3);
+    assert!(interference.offsets().iter().all(|&(_, exposure)| exposure <= interference.exposure(1, 0, 0) && exposure >= 1e-4));
+    // A neighbour receives exactly the tolerated exposure at the safe pitch
+    let pitch = interference.safe_pitch_um(0.01);
+    let at_pitch = WriteInterference { optics: OpticsConfig { voxel_pitch_um: pitch, ..interference.optics }, ..interference };
+    assert!((at_pitch.exposure(1, 0, 0) - 0.01).abs() < 1e-4);
+    assert!(WriteInterference { absorption_order: 0.0, ..interference }.validate().is_err());
+
+    // A lone voxel partially writes its neighbours in a linear material
+    let mut lattice = VoxelLattice::new(5, 5, 3, PhotonicVoxel::new(0.0, 0.0, 0.0, 532.0));
+    lattice.set(2, 2, 1, PhotonicVoxel::new(1.0, 0.0, 0.0, 532.0));
+    let written = simulate_write_interference_with_rng(&lattice, &WriteParams::ideal(), &interference, &mut StdRng::seed_from_u64(95));
+    assert!((written.get(2, 2, 1).unwrap().intensity - 1.0).abs() < 1e-6);
+    assert!((written.get(3, 2, 1).unwrap().intensity as f64 - interference.exposure(1, 0, 0)).abs() < 1e-6);
+    // Below the threshold of a saturating material the stray dose leaves no trace
+    let saturating = WriteParams { energy_jitter: 0.0, response: MaterialResponse::Saturating { threshold_nj: 100.0, saturation_nj: 400.0 }, ..WriteParams::default() };
+    let written = simulate_write_interference_with_rng(&lattice, &saturating, &interference, &mut StdRng::seed_from_u64(95));
+    assert_eq!(written.get(3, 2, 1).unwrap().intensity, 0.0);
+
+    // Close pitches garble the data, wide ones are safe
+    let shape = LatticeShape { width: 32, height: 32, depth: 4 };
+    let results = run_ber_by_write_pitch(shape, &interference, &WriteParams::ideal(), [0.4, 0.5, 0.6, 0.8, 1.0], &CodecConfig::default(), &mut StdRng::seed_from_u64(95));
+    assert!(results[0].ber > 0.05 && results[1].ber > 0.05, "{:?}", results);
+    assert_eq!(min_safe_pitch(&results), Some(0.6));
+    assert!(results[2].neighbour_exposure > results[3].neighbour_exposure);
+}
+
+#[test]
+fn test_photon_budget_counts_photons_per_dimension() {
+    // With plenty of photons every dimension is measured accurately
+    let voxel = PhotonicVoxel::new(0.75, 1.2, 4.0, 600.0);
+    let bright = PhotonBudget::new(1e7);
+    let read = bright.read_voxel_with_rng(&voxel, &mut StdRng::seed_from_u64(96));
+    assert!((read.intensity - 0.75).abs() < 1e-3 && (read.polarization - 1.2).abs() < 1e-3, "{:?}", read);
+    assert!((read.phase - 4.0).abs() < 1e-3 && (read.wavelength - 600.0).abs() < 0.1, "{:?}", read);
+    // A dark voxel detects nothing to measure
+    let dark = PhotonBudget { dark_counts: 0.0, ..PhotonBudget::new(100.0) };
+    let read = dark.read_voxel_with_rng(&PhotonicVoxel::new(0.0, 1.2, 4.0, 600.0), &mut StdRng::seed_from_u64(96));
+    assert_eq!((read.intensity, read.polarization, read.wavelength), (0.0, 0.0, 0.0));
+    assert!(PhotonBudget::new(0.0).validate().is_err());
+
+    // Few photons scatter the readings; the spread shrinks as 1 / sqrt(N)
+    let spread = |photons: f64| {
+        let mut rng = StdRng::seed_from_u64(96);
+        let reads: Vec<f32> = (0..2000).map(|_| PhotonBudget::new(photons).read_voxel_with_rng(&voxel, &mut rng).intensity).collect();
+        let mean = reads.iter().sum::<f32>() / reads.len() as f32;
+        (reads.iter().map(|r| (r - mean).powi(2)).sum::<f32>() / reads.len() as f32).sqrt()
+    };
+    let ratio = spread(100.0) / spread(10_000.0);
+    assert!((8.0..12.0).contains(&ratio), "{}", ratio);
+
+    // The Monte Carlo sweep: errors fall with the budget, and vary between trials
+    let results = run_ber_by_photon_budget(256, &PhotonBudget::default(), [10.0, 100.0, 1000.0], 10, &CodecConfig::default(), &mut StdRng::seed_from_u64(96));
+    assert!(results[0].ber > results[1].ber && results[1].ber > results[2].ber, "{:?}", results);
+    assert!(results[0].ber_std_dev > 0.0 && results[0].total_bits == 10 * 256 * 8);
+    assert_eq!(results[2].error_bits, 0);
+    // Every trial draws from its own generator: the seed alone fixes the sweep
+    let again = run_ber_by_photon_budget(256, &PhotonBudget::default(), [10.0, 100.0, 1000.0], 10, &CodecConfig::default(), &mut StdRng::seed_from_u64(96));
+    assert_eq!(again, results);
+}
+
+#[test]
+fn test_optimize_geometry_trades_pitch_for_depth() {
+    let optics = OpticsConfig::default();
+    let limits = GeometryLimits::default();
+
+    // Few voxels spread out in a shallow lattice with little crosstalk
+    let (small, small_optics) = optimize_geometry_with_limits(10_000, &optics, &limits).unwrap();
+    assert!(small.len() >= 10_000 && small.depth <= 2, "{:?}", small);
+    assert!(small_optics.voxel_pitch_um >= 2.0 && small_optics.crosstalk_factor() < 1e-6);
+
+    // Many voxels need more layers, packed closer, losing more signal
+    let (large, large_optics) = optimize_geometry_with_limits(1_000_000, &optics, &limits).unwrap();
+    assert!(large.len() >= 1_000_000 && large.depth > small.depth, "{:?}", large);
+    assert!(large_optics.voxel_pitch_um >= optics.min_voxel_pitch_um());
+    assert!(large_optics.layer_spacing_um >= optics.min_layer_spacing_um());
+    assert!(large.width as f32 * large_optics.voxel_pitch_um <= limits.max_extent_um + 1e-3);
+    assert!((large.depth - 1) as f32 * large_optics.layer_spacing_um <= limits.max_depth_um + 1e-3);
+    assert!(limits.predicted_loss(&large_optics, large.depth) > limits.predicted_loss(&small_optics, small.depth));
+
+    // The chosen layout beats the same lattice at the default pitch
+    let default_loss = limits.predicted_loss(&optics, large.depth);
+    assert!(limits.predicted_loss(&large_optics, large.depth) <= default_loss);
+
+    // The plain form picks the same lattice within the default limits
+    assert_eq!(optimize_geometry(1_000_000, &optics), (large.width, large.height, large.depth));
+
+    // More voxels than the field and depth resolve
+    assert!(optimize_geometry_with_limits(1_000_000_000, &optics, &limits).is_none());
+    assert!(GeometryLimits { max_depth_um: 0.0, ..limits }.validate().is_err());
+}
+
+#[test]
+fn test_coded_ber_simulation_reports_raw_and_corrected_rates() {
+    let ecc = EccConfig::default();
+    let results = run_coded_ber_simulation(5_000, 10, 0.2, &CodecConfig::default(), &ecc, &mut StdRng::seed_from_u64(98));
+    assert_eq!(results.len(), 11);
+    for result in &results {
+        assert_eq!(result.total_bits, 5_000 * 8);
+        assert_eq!(result.raw_total_bits, ecc.encoded_len(5_000) * 8);
+    }
+    assert_eq!((results[0].raw_error_bits, results[0].error_bits, results[0].failed_codewords), (0, 0, 0));
+
+    // Near the noise threshold the parity removes most channel errors
+    let partial = results.iter().find(|result| result.raw_ber > 0.0).expect("noise reaches the decision threshold");
+    assert!(partial.ber < partial.raw_ber / 2.0, "{:?}", partial);
+    assert!(partial.failed_codewords < partial.codewords);
+    // Far beyond it, most codewords fail and little is gained
+    let last = results.last().unwrap();
+    assert!(last.failed_codewords > last.codewords / 2 && last.ber > last.raw_ber / 2.0, "{:?}", last);
+
+    // The corrected rates are those of the plain ECC sweep from the same seed
+    let plain = run_ber_simulation_with_ecc(5_000, 10, 0.2, &CodecConfig::default(), &ecc, &mut StdRng::seed_from_u64(98));
+    assert!(results.iter().zip(&plain).all(|(coded, plain)| coded.error_bits == plain.error_bits));
+}
+
+#[test]
+fn test_ber_trials_report_confidence_intervals() {
+    let config = SimulationConfig::default();
+    assert!(SimulationConfig { trials: 0, ..config }.validate().is_err());
+    assert!(SimulationConfig { confidence: 1.0, ..config }.validate().is_err());
+
+    // No errors still bound the BER, by about 3.84 / n at 95%
+    let (low, high) = config.interval(0, 100_000);
+    assert_eq!(low, 0.0);
+    assert!((high - 3.84e-5).abs() < 1e-6, "{}", high);
+    // The interval holds the estimate and widens with the confidence
+    let (low, high) = config.interval(50, 1000);
+    assert!(low < 0.05 && 0.05 < high, "{} {}", low, high);
+    let (wide_low, wide_high) = SimulationConfig { confidence: 0.99, ..config }.interval(50, 1000);
+    assert!(wide_low < low && wide_high > high);
+
+    let results = run_ber_simulation_with_trials(1000, 4, 0.2, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(99));
+    assert_eq!(results.len(), 5);
+    for result in &results {
+        assert_eq!((result.trials, result.total_bits), (10, 10 * 1000 * 8));
+        assert!(result.ber_low <= result.ber && result.ber <= result.ber_high, "{:?}", result);
+    }
+    assert_eq!(results[0].error_bits, 0);
+    assert!(results[0].ber_high > 0.0);
+    // More trials narrow the interval
+    let more = run_ber_simulation_with_trials(1000, 4, 0.2, &CodecConfig::default(), &SimulationConfig { trials: 40, ..config }, &mut StdRng::seed_from_u64(99));
+    assert!(more[4].ber_high - more[4].ber_low < results[4].ber_high - results[4].ber_low);
+}
+
+#[test]
+fn test_ber_sweeps_replay_with_independent_streams() {
+    // A seeded campaign replays exactly, whatever runs its tasks in parallel
+    let config = SimulationConfig { trials: 4, confidence: 0.95 };
+    let run = |seed| run_ber_simulation_with_trials(2_000, 6, 0.3, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(seed));
+    let first = run(100);
+    assert_eq!(first, run(100));
+    assert_ne!(first, run(101));
+    let sweep = |seed| run_ber_simulation_with_rng(2_000, 6, 0.3, &CodecConfig::default(), &mut StdRng::seed_from_u64(seed));
+    assert!(sweep(100).iter().zip(&sweep(100)).all(|(a, b)| a.error_bits == b.error_bits));
+
+    // Every trial and level has its own noise, and the errors still grow with it
+    assert!(first.windows(2).all(|pair| pair[0].error_bits <= pair[1].error_bits), "{:?}", first);
+    assert!(first.last().unwrap().error_bits > 0);
+}
+
+#[test]
+fn test_ber_simulation_attributes_errors_to_dimensions() {
+    // Uniform noise of the same amplitude hits the tightest levels first
+    let results = run_ber_simulation_with_rng(5_000, 10, 0.3, &CodecConfig::default(), &mut StdRng::seed_from_u64(101));
+    assert_eq!(results[0].dimension_errors, DimensionErrors::default());
+    assert_eq!(results[0].dimension_errors.weakest(), None);
+    let last = results.last().unwrap();
+    assert!(last.error_bits > 0 && last.dimension_errors.total() > 0);
+    assert_eq!(last.dimension_errors.weakest(), Some("intensity"));
+    // Every wrong voxel is wrong in some dimension, and flips at least one bit per dimension
+    assert!(last.dimension_errors.total() <= last.error_bits, "{:?}", last);
+
+    // Noise on polarization alone is blamed on polarization alone
+    let profile = NoiseProfile { polarization: 0.5, ..NoiseProfile::from(NoiseParams::uniform(0.0)) };
+    let results = run_ber_simulation_with_profile(5_000, 2, &profile, &CodecConfig::default(), &mut StdRng::seed_from_u64(101));
+    let dims = results[2].dimension_errors;
+    assert!(dims.polarization > 0 && dims.intensity + dims.phase + dims.wavelength == 0, "{:?}", dims);
+    assert_eq!(dims.weakest(), Some("polarization"));
+
+    // Repeated trials and coded sweeps report the channel misquantizations too
+    let trials = run_ber_simulation_with_trials(1_000, 2, 0.3, &CodecConfig::default(), &SimulationConfig { trials: 3, confidence: 0.95 }, &mut StdRng::seed_from_u64(101));
+    assert!(trials[2].dimension_errors.intensity > 0);
+    let coded = run_coded_ber_simulation(1_000, 2, 0.3, &CodecConfig::default(), &EccConfig::default(), &mut StdRng::seed_from_u64(101));
+    assert!(coded[2].dimension_errors.total() > 0 && coded[0].dimension_errors.total() == 0);
+}
+
+#[test]
+fn test_signal_quality_reports_q_factor_and_gaussian_ber() {
+    let gray = CodecConfig { gray_code: true, ..CodecConfig::default() };
+
+    // Two intensity levels 0.5 apart under sigma 0.1: Q = 2.5, SNR = Q^2
+    let on_off = CodecConfig { intensity_levels: 2, polarization_levels: 1, phase_levels: 1, wavelengths: vec![650.0], gray_code: false };
+    let quality = SignalQuality::new(&on_off, [0.1, 0.1, 0.1, 1.0]);
+    assert!((quality.q_factors[0] - 2.5).abs() < 1e-9);
+    assert!((quality.snr_db[0] - 20.0 * 2.5f64.log10()).abs() < 1e-9);
+    // Each level has one neighbour: P = erfc(2.5 / sqrt 2) / 2 = 6.21e-3
+    assert!((quality.symbol_error_rates[0] - 6.2097e-3).abs() < 1e-6, "{:?}", quality);
+    assert_eq!(quality.q_factors[1..], [f64::INFINITY; 3]);
+    assert_eq!(quality.estimated_ber, quality.symbol_error_rates[0]);
+    assert!((quality.q_factor_db() - 7.9588).abs() < 1e-3);
+
+    // More noise, lower Q and SNR, higher BER
+    let quiet = SignalQuality::from_profile(&gray, &NoiseProfile::gaussian(0.03, 0.06, 0.09, 6.0));
+    let loud = SignalQuality::from_profile(&gray, &NoiseProfile::gaussian(0.05, 0.1, 0.15, 10.0));
+    assert!(loud.q_factor() < quiet.q_factor() && loud.snr_db[0] < quiet.snr_db[0]);
+    assert!(loud.estimated_ber > quiet.estimated_ber);
+
+    // The Gaussian fit of a Monte Carlo read predicts the simulated BER
+    let profile = NoiseProfile::gaussian(0.05, 0.1, 0.15, 10.0);
+    let measured = measure_signal_quality(20_000, &profile, &gray, &mut StdRng::seed_from_u64(103));
+    assert!((measured.sigmas[0] - 0.05).abs() < 0.002, "{:?}", measured);
+    let simulated = run_ber_simulation_with_profile(20_000, 1, &profile, &gray, &mut StdRng::seed_from_u64(103))[1].ber;
+    assert!((measured.estimated_ber / simulated - 1.0).abs() < 0.2, "{} vs {}", measured.estimated_ber, simulated);
+    // ...and extrapolates below what a run of that size observes
+    let faint = measure_signal_quality(20_000, &NoiseProfile::gaussian(0.02, 0.04, 0.06, 4.0), &gray, &mut StdRng::seed_from_u64(103));
+    assert!(faint.estimated_ber > 0.0 && faint.estimated_ber < 1e-8, "{:?}", faint);
+}
+
+#[test]
+fn test_constellation_samples_export_raw_reads() {
+    let config = CodecConfig::default();
+    let samples = record_constellation(64, 4, 0.3, &config, &mut StdRng::seed_from_u64(104));
+    assert_eq!(samples.len(), 5 * 64);
+    // Without noise every read is the written level and decided right
+    for sample in &samples[..64] {
+        assert_eq!((sample.noise_level, sample.received, sample.decided), (0.0, sample.written, sample.symbol));
+        assert_eq!(sample.written, config.modulate(sample.symbol));
+    }
+    // With noise, reads spread around the levels and some cross a boundary
+    let loud = &samples[4 * 64..];
+    assert!(loud.iter().all(|sample| sample.noise_level == 0.3 && (sample.received.intensity - sample.written.intensity).abs() <= 0.3));
+    assert!(loud.iter().any(|sample| sample.decided != sample.symbol));
+    assert!(loud.iter().all(|sample| sample.decided == config.demodulate(&sample.received)));
+    // The same written symbols at every level
+    assert!(samples[..64].iter().zip(loud).all(|(quiet, loud)| quiet.symbol == loud.symbol));
+
+    let mut csv = Vec::new();
+    constellation_to_csv(&mut csv, &samples).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next().unwrap(), photon_core::export::CONSTELLATION_COLUMNS.join(","));
+    let last: Vec<f32> = csv.lines().last().unwrap().split(',').map(|field| field.parse().unwrap()).collect();
+    let sample = samples.last().unwrap();
+    assert_eq!((last.len(), last[0], last[2] as u32, last[10]), (11, 0.3, sample.decided, sample.received.wavelength));
+    assert_eq!(csv.lines().count(), samples.len() + 1);
+
+    let mut json = Vec::new();
+    constellation_to_json(&mut json, &samples).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    let array = value.as_array().unwrap();
+    assert_eq!(array.len(), samples.len());
+    assert_eq!(array[70]["symbol"], samples[70].symbol);
+    assert_eq!(array[70]["received"]["phase"].as_f64().unwrap() as f32, samples[70].received.phase);
+    let mut empty = Vec::new();
+    constellation_to_json(&mut empty, &[]).unwrap();
+    assert_eq!(empty, b"[]\n");
+}
+
+#[test]
+fn test_mutual_information_bounds_usable_bits() {
+    let config = CodecConfig::default();
+    let mut rng = StdRng::seed_from_u64(106);
+
+    let clean = estimate_mutual_information(2000, &NoiseProfile::from(NoiseParams::uniform(0.0)), &config, &mut rng);
+    assert_eq!(clean.dimensions, [2.0, 2.0, 2.0, 2.0]);
+    assert_eq!((clean.bits_per_voxel, clean.stored_bits_per_voxel, clean.efficiency()), (8.0, 8, 1.0));
+
+    // Uniform noise below half the intensity spacing never blurs two levels
+    let bounded = estimate_mutual_information(2000, &NoiseProfile::from(NoiseParams::uniform(0.1)), &config, &mut rng);
+    assert_eq!(bounded.bits_per_voxel, 8.0);
+
+    let noisy: MutualInformation = estimate_mutual_information(20_000, &NoiseProfile::from(NoiseParams::uniform(0.2)), &config, &mut rng);
+    assert!((noisy.dimensions[0] - 1.44).abs() < 0.05, "{:?}", noisy);
+    assert_eq!(noisy.dimensions[1..], [2.0, 2.0, 2.0]);
+    assert!(noisy.efficiency() < 1.0 && noisy.std_error > 0.0);
+
+    let drowned = estimate_mutual_information(5000, &NoiseProfile::gaussian(10.0, 10.0, 10.0, 1000.0), &config, &mut rng);
+    assert!(drowned.bits_per_voxel < 0.2 && drowned.dimensions.iter().all(|&bits| bits >= 0.0), "{:?}", drowned);
+
+    // Quiet channels have room for more levels than the default stores
+    let dense = CodecConfig { intensity_levels: 8, polarization_levels: 8, phase_levels: 8, ..CodecConfig::default() };
+    let capacity = estimate_mutual_information(5000, &NoiseProfile::gaussian(0.02, 0.04, 0.06, 5.0), &dense, &mut rng);
+    assert_eq!(capacity.stored_bits_per_voxel, 11);
+    assert!(capacity.bits_per_voxel > 10.9 && capacity.efficiency() <= 1.0, "{:?}", capacity);
+}
+
+#[test]
+fn test_ber_grid_maps_noise_against_crosstalk() {
+    let shape = LatticeShape { width: 16, height: 16, depth: 4 };
+    let (noise_levels, factors) = ([0.0, 0.1, 0.2], [0.0, 0.05]);
+    let channel = |noise: f32, factor: f32| ChannelPipeline::new().stage(CrosstalkConfig::new(factor)).stage(NoiseProfile::from(NoiseParams::uniform(noise)));
+    let grid: BerGrid = run_ber_grid(shape, &noise_levels, &factors, &CodecConfig::default(), channel, &mut StdRng::seed_from_u64(108));
+
+    assert_eq!(grid.points.len(), 6);
+    assert_eq!((grid.point(2, 1).x, grid.point(2, 1).y), (0.2, 0.05));
+    assert!(grid.points.iter().all(|point| point.total_bits == shape.len() * 8));
+    // Clean up to the half-spacing noise, broken past it or by strong crosstalk
+    assert_eq!((grid.point(0, 0).ber, grid.point(1, 0).ber), (0.0, 0.0));
+    assert!(grid.point(2, 0).ber > 0.0 && grid.point(0, 1).ber > 0.0);
+    assert_eq!(grid.envelope(0.0).count(), 2);
+
+    let matrix = grid.matrix();
+    assert_eq!((matrix.len(), matrix[0].len()), (2, 3));
+    assert_eq!(matrix[1][2], grid.point(2, 1).ber);
+
+    let replay = run_ber_grid(shape, &noise_levels, &factors, &CodecConfig::default(), channel, &mut StdRng::seed_from_u64(108));
+    assert_eq!(replay, grid);
+
+    let mut csv = Vec::new();
+    grid_to_csv(&mut csv, &grid, ["NoiseLevel", "CrosstalkFactor"]).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "NoiseLevel,CrosstalkFactor,TotalBits,ErrorBits,BER");
+    let last: Vec<f64> = lines[6].split(',').map(|field| field.parse().unwrap()).collect();
+    assert_eq!((last[0] as f32, last[1] as f32, last[3] as usize), (0.2, 0.05, grid.point(2, 1).error_bits));
+}
+
+#[test]
+fn test_adaptive_ber_reads_until_target_errors() {
+    let config = AdaptiveConfig { target_errors: 50, batch_size: 500, max_bits: 80_000, confidence: 0.95 };
+    let results: Vec<AdaptiveResult> = run_ber_simulation_adaptive(4, 0.2, &CodecConfig::default(), &config, &mut StdRng::seed_from_u64(109));
+    assert_eq!(results.len(), 5);
+
+    // Below half the intensity spacing no error ever shows: the cap stops the run
+    let clean = &results[0];
+    assert!(!clean.converged);
+    assert_eq!((clean.batches, clean.total_bits, clean.error_bits), (20, 80_000, 0));
+    assert_eq!(clean.ber_low, 0.0);
+    assert!(clean.ber_high > 0.0 && clean.ber_high < 5e-5 && clean.relative_error().is_infinite());
+
+    // Where errors are frequent one batch suffices
+    let noisy = &results[4];
+    assert!(noisy.converged && noisy.error_bits >= 50);
+    assert_eq!((noisy.batches, noisy.total_bits), (1, 4000));
+    assert!(noisy.ber_low < noisy.ber && noisy.ber < noisy.ber_high);
+    assert!(noisy.relative_error() < 0.3);
+    assert_eq!(noisy.dimension_errors.weakest(), Some("intensity"));
+    assert!(results.iter().all(|r| r.converged == (r.error_bits >= 50) && r.total_bits == r.batches * 4000));
+
+    assert!(AdaptiveConfig { max_bits: 1000, ..config }.validate().is_err());
+    assert!(AdaptiveConfig { target_errors: 0, ..config }.validate().is_err());
+    assert!(AdaptiveConfig::default().validate().is_ok());
+}
+
+#[test]
+fn test_importance_sampling_estimates_low_ber_tails() {
+    let mut rng = StdRng::seed_from_u64(110);
+
+    // Where plain simulation sees errors, both agree
+    let profile = NoiseProfile::gaussian(0.08, 0.0, 0.0, 0.0);
+    let estimate: ImportanceResult = estimate_ber_importance(20_000, &profile, &CodecConfig::default(), &mut rng);
+    let simulated = run_ber_simulation_with_profile(20_000, 1, &profile, &CodecConfig::default(), &mut rng)[1].ber;
+    assert!((estimate.ber / simulated - 1.0).abs() < 0.1, "{:?} against {}", estimate, simulated);
+    let (low, high) = estimate.interval(0.95);
+    assert!(low < estimate.ber && estimate.ber < high);
+
+    // Far below, about half the biased reads still fail, so the precision holds
+    let config = CodecConfig { gray_code: true, ..CodecConfig::default() };
+    let profile = NoiseProfile::gaussian(0.0173, 0.0173, 0.0173, 1.73);
+    let estimate = estimate_ber_importance(10_000, &profile, &config, &mut rng);
+    let gaussian = SignalQuality::from_profile(&config, &profile).estimated_ber;
+    assert!(gaussian < 1e-13);
+    assert!((estimate.ber / gaussian - 1.0).abs() < 0.25, "{:?} against {:e}", estimate, gaussian);
+    assert!(estimate.error_events > 3000 && estimate.relative_error() < 0.1);
+    assert_eq!(estimate.total_bits, 80_000);
+
+    let clean = estimate_ber_importance(100, &NoiseProfile::none(), &config, &mut rng);
+    assert_eq!((clean.ber, clean.error_events), (0.0, 0));
+    assert!(clean.relative_error().is_infinite());
+}
@@ -1,5 +1,17 @@
-use photon_core::{encode_data, decode_data};
+use photon_core::{encode_data, decode_data, CodecConfig, ModulationScheme, PhotonicVoxel, Quantizer};
+use photon_core::{add_error_correction_with, recover_error_correction_with, recover_error_correction_with_erasures, EccConfig};
+use photon_core::ecc::{corrupt_burst, corrupt_random_bytes};
 use proptest::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Reed-Solomon layouts in blocks of a few bytes per shard, with data filling
+/// `blocks` whole blocks so that every block has the same shards.
+fn ecc_layouts() -> impl Strategy<Value = (EccConfig, usize)> {
+    (1usize..12, 1usize..9, 1usize..16, 1usize..4).prop_map(|(data_shards, parity_shards, shard_len, blocks)| {
+        let config = EccConfig { block_shard_len: Some(shard_len), ..EccConfig::new(data_shards, parity_shards) };
+        (config, blocks * shard_len * data_shards)
+    })
+}
 
 proptest! {
     // Fuzz test for the codec: Arbitrary byte vectors should round-trip correctly (without noise)
@@ -12,3 +24,77 @@ proptest! {
         prop_assert_eq!(data, decoded, "Round-trip failed");
     }
 }
+
+proptest! {
+    // The lookup-table quantizer must take the same decisions as the
+    // nearest-level search, for noisy and out-of-range values alike
+    #[test]
+    fn test_quantizer_matches_linear_search(
+        bits in (0usize..4, 0usize..4, 0usize..4),
+        palette in proptest::collection::btree_set(300u16..900, 1..12),
+        gray_code in any::<bool>(),
+        values in proptest::collection::vec((-0.5f32..1.5, -4.0f32..8.0, -8.0f32..14.0, 250.0f32..950.0), 1..200),
+    ) {
+        let config = CodecConfig {
+            intensity_levels: 1 << bits.0,
+            polarization_levels: 1 << bits.1,
+            phase_levels: 1 << bits.2,
+            wavelengths: palette.iter().rev().map(|&w| w as f32).collect(),
+            gray_code,
+        };
+        prop_assume!(config.validate().is_ok());
+
+        let quantizer = Quantizer::new(&config);
+        for (intensity, polarization, phase, wavelength) in values {
+            let voxel = PhotonicVoxel::new(intensity, polarization, phase, wavelength);
+            prop_assert_eq!(quantizer.demodulate(&voxel), config.demodulate(&voxel));
+        }
+    }
+}
+
+proptest! {
+    // Up to `correctable_errors()` corrupted bytes anywhere are always repaired
+    #[test]
+    fn test_ecc_repairs_random_byte_errors((config, data_len) in ecc_layouts(), seed in any::<u64>()) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let data: Vec<u8> = (0..data_len).map(|_| rng.random::<u8>()).collect();
+        let mut encoded = add_error_correction_with(&data, &config);
+        let corrupted = corrupt_random_bytes(&mut encoded, config.correctable_errors(), &mut rng);
+        prop_assert_eq!(corrupted.len(), config.correctable_errors());
+        prop_assert_eq!(recover_error_correction_with(&encoded, &config), Ok(data));
+    }
+
+    // A burst of up to `correctable_errors()` shards' worth of bytes puts at
+    // most that many errors in any codeword, wherever it starts
+    #[test]
+    fn test_ecc_repairs_bursts((config, data_len) in ecc_layouts(), start in any::<prop::sample::Index>(), seed in any::<u64>()) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let data: Vec<u8> = (0..data_len).map(|_| rng.random::<u8>()).collect();
+        let mut encoded = add_error_correction_with(&data, &config);
+        let len = config.correctable_errors() * config.block_shard_len.unwrap();
+        let start = start.index(encoded.len());
+        let burst = corrupt_burst(&mut encoded, start, len, &mut rng);
+        prop_assert!(burst.len() <= len);
+        prop_assert_eq!(recover_error_correction_with(&encoded, &config), Ok(data));
+    }
+
+    // Fewer erased shards per block than parity shards are always rebuilt,
+    // on top of nothing else going wrong
+    #[test]
+    fn test_ecc_rebuilds_erased_shards((config, data_len) in ecc_layouts(), seed in any::<u64>()) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let data: Vec<u8> = (0..data_len).map(|_| rng.random::<u8>()).collect();
+        let mut encoded = add_error_correction_with(&data, &config);
+        let shard_len = config.block_shard_len.unwrap();
+        let erased_shards = rand::seq::index::sample(&mut rng, config.total_shards(), config.parity_shards - 1);
+
+        let mut erasures = vec![false; encoded.len()];
+        for block in 0..encoded.len() / (shard_len * config.total_shards()) {
+            for shard in erased_shards.iter() {
+                let burst = corrupt_burst(&mut encoded, (block * config.total_shards() + shard) * shard_len, shard_len, &mut rng);
+                erasures[burst].fill(true);
+            }
+        }
+        prop_assert_eq!(recover_error_correction_with_erasures(&encoded, &erasures, &config), Ok(data));
+    }
+}